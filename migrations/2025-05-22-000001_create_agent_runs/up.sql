@@ -0,0 +1,31 @@
+CREATE TABLE agent_runs (
+    id UUID PRIMARY KEY,
+    agent_id UUID NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    input TEXT NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    output TEXT,
+    error TEXT,
+    usage JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_agent_runs_agent_id ON agent_runs(agent_id, created_at DESC);
+
+CREATE TABLE agent_run_steps (
+    id UUID PRIMARY KEY,
+    run_id UUID NOT NULL REFERENCES agent_runs(id) ON DELETE CASCADE,
+    step INTEGER NOT NULL,
+    kind VARCHAR(50) NOT NULL,
+    tool_name VARCHAR(255),
+    tool_call_id VARCHAR(255),
+    arguments JSONB,
+    result JSONB,
+    error TEXT,
+    latency_ms BIGINT NOT NULL,
+    usage JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_agent_run_steps_run_id ON agent_run_steps(run_id);
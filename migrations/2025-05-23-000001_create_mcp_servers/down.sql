@@ -0,0 +1 @@
+DROP TABLE mcp_servers;
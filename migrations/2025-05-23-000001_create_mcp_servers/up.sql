@@ -0,0 +1,17 @@
+CREATE TABLE mcp_servers (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    transport VARCHAR(50) NOT NULL,
+    command TEXT,
+    args JSONB NOT NULL DEFAULT '[]',
+    env JSONB NOT NULL DEFAULT '{}',
+    url TEXT,
+    headers JSONB NOT NULL DEFAULT '{}',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX idx_mcp_servers_user_id ON mcp_servers(user_id);
@@ -0,0 +1,16 @@
+CREATE TABLE user_tools (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    description TEXT NOT NULL,
+    parameters JSONB NOT NULL DEFAULT '[]',
+    method VARCHAR(10) NOT NULL,
+    url_template TEXT NOT NULL,
+    headers JSONB NOT NULL DEFAULT '{}',
+    response_path TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX idx_user_tools_user_id ON user_tools(user_id);
@@ -0,0 +1,11 @@
+CREATE TABLE tool_permissions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tool_name VARCHAR(255) NOT NULL,
+    dangerous BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, tool_name)
+);
+
+CREATE INDEX idx_tool_permissions_user_id ON tool_permissions(user_id);
@@ -0,0 +1,2 @@
+DROP TABLE tool_approvals;
+ALTER TABLE agent_runs DROP COLUMN state;
@@ -0,0 +1,19 @@
+-- Conversation state of runs paused for approval, so they can be resumed
+ALTER TABLE agent_runs ADD COLUMN state JSONB;
+
+CREATE TABLE tool_approvals (
+    id UUID PRIMARY KEY,
+    run_id UUID NOT NULL REFERENCES agent_runs(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    step INTEGER NOT NULL,
+    tool_call_id VARCHAR(255) NOT NULL,
+    tool_name VARCHAR(255) NOT NULL,
+    arguments JSONB NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    decided_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_tool_approvals_run_id ON tool_approvals(run_id);
+CREATE INDEX idx_tool_approvals_pending ON tool_approvals(user_id) WHERE status = 'pending';
@@ -0,0 +1 @@
+ALTER TABLE user_llm_configs DROP COLUMN fallbacks;
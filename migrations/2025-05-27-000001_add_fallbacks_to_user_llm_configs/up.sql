@@ -0,0 +1,2 @@
+-- Ordered configs to fall over to when the primary provider is unavailable
+ALTER TABLE user_llm_configs ADD COLUMN fallbacks JSONB NOT NULL DEFAULT '[]';
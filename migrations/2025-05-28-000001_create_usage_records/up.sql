@@ -0,0 +1,20 @@
+-- One row per LLM completion, priced when it was made
+CREATE TABLE usage_records (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    conversation_id UUID REFERENCES conversations(id) ON DELETE SET NULL,
+    message_id UUID REFERENCES messages(id) ON DELETE SET NULL,
+    provider_id UUID REFERENCES llm_providers(id) ON DELETE SET NULL,
+    provider_name VARCHAR(255) NOT NULL,
+    provider_type VARCHAR(255) NOT NULL,
+    model VARCHAR(255) NOT NULL,
+    source VARCHAR(50) NOT NULL,
+    prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    completion_tokens BIGINT NOT NULL DEFAULT 0,
+    cached_tokens BIGINT NOT NULL DEFAULT 0,
+    total_tokens BIGINT NOT NULL DEFAULT 0,
+    cost_usd DOUBLE PRECISION,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_usage_records_user_created ON usage_records(user_id, created_at);
@@ -0,0 +1,16 @@
+-- Limits on LLM calls for a user, or for one of the user's API keys when api_key_id is set.
+-- NULL columns fall back to the server defaults.
+CREATE TABLE rate_limits (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE,
+    requests_per_minute INTEGER,
+    concurrent_streams INTEGER,
+    tokens_per_minute BIGINT,
+    tokens_per_day BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_rate_limits_user ON rate_limits(user_id) WHERE api_key_id IS NULL;
+CREATE UNIQUE INDEX idx_rate_limits_api_key ON rate_limits(api_key_id) WHERE api_key_id IS NOT NULL;
@@ -0,0 +1,4 @@
+DROP TABLE conversation_collections;
+DROP TABLE knowledge_chunks;
+DROP TABLE knowledge_documents;
+DROP TABLE knowledge_collections;
@@ -0,0 +1,47 @@
+-- Document collections used for retrieval. Embeddings are made with the collection's LLM config;
+-- embedding_model and dimensions are fixed by the first document ingested.
+CREATE TABLE knowledge_collections (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_llm_config_id UUID NOT NULL REFERENCES user_llm_configs(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    embedding_model VARCHAR(255),
+    dimensions INTEGER,
+    chunk_size INTEGER NOT NULL DEFAULT 1000,
+    chunk_overlap INTEGER NOT NULL DEFAULT 200,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE knowledge_documents (
+    id UUID PRIMARY KEY,
+    collection_id UUID NOT NULL REFERENCES knowledge_collections(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE knowledge_chunks (
+    id UUID PRIMARY KEY,
+    document_id UUID NOT NULL REFERENCES knowledge_documents(id) ON DELETE CASCADE,
+    collection_id UUID NOT NULL REFERENCES knowledge_collections(id) ON DELETE CASCADE,
+    chunk_index INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    embedding REAL[] NOT NULL
+);
+
+-- Collections whose chunks are added to a conversation's context
+CREATE TABLE conversation_collections (
+    conversation_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    collection_id UUID NOT NULL REFERENCES knowledge_collections(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (conversation_id, collection_id)
+);
+
+CREATE INDEX idx_knowledge_collections_user_id ON knowledge_collections(user_id);
+CREATE INDEX idx_knowledge_documents_collection_id ON knowledge_documents(collection_id);
+CREATE INDEX idx_knowledge_chunks_collection_id ON knowledge_chunks(collection_id);
+CREATE INDEX idx_knowledge_chunks_document_id ON knowledge_chunks(document_id);
@@ -0,0 +1,18 @@
+-- One row per execution of a job, so a run no longer overwrites the outcome of the previous one.
+-- The jobs row keeps summarizing the latest run.
+CREATE TABLE job_runs (
+    id UUID PRIMARY KEY,
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    trigger_source VARCHAR(50) NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    exit_code INTEGER,
+    stdout TEXT,
+    stderr TEXT,
+    error TEXT,
+    state_file_content JSONB,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX job_runs_job_id_started_at_idx ON job_runs (job_id, started_at DESC);
@@ -0,0 +1,12 @@
+-- Output lines of job runs, stored as the worker streams them. The serial id orders the lines
+-- and is the cursor for paging and following a run's logs.
+CREATE TABLE job_run_logs (
+    id BIGSERIAL PRIMARY KEY,
+    run_id UUID NOT NULL REFERENCES job_runs(id) ON DELETE CASCADE,
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    stream VARCHAR(10) NOT NULL,
+    line TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX job_run_logs_run_id_id_idx ON job_run_logs (run_id, id);
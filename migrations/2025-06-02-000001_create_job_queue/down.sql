@@ -0,0 +1,2 @@
+ALTER TABLE workers DROP COLUMN last_seen_at;
+DROP TABLE job_queue;
@@ -0,0 +1,29 @@
+-- Runs waiting for, or held by, a worker. Workers claim an entry with FOR UPDATE SKIP LOCKED and
+-- hold it under a lease they keep renewing; an entry whose lease runs out is queued again, until
+-- it has used up its attempts and is dead-lettered.
+CREATE TABLE job_queue (
+    run_id UUID PRIMARY KEY REFERENCES job_runs(id) ON DELETE CASCADE,
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    worker_type UUID NOT NULL,
+    command JSONB NOT NULL,
+    pipeline_name TEXT NOT NULL,
+    pipeline_file TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    available_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    leased_by UUID REFERENCES workers(id) ON DELETE SET NULL,
+    lease_token UUID,
+    lease_expires_at TIMESTAMPTZ,
+    stop_requested BOOLEAN NOT NULL DEFAULT FALSE,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX job_queue_queued_idx ON job_queue (user_id, worker_type, available_at)
+    WHERE status = 'queued';
+CREATE INDEX job_queue_leased_idx ON job_queue (lease_expires_at) WHERE status = 'leased';
+
+ALTER TABLE workers ADD COLUMN last_seen_at TIMESTAMPTZ;
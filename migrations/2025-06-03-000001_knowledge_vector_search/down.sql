@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_knowledge_chunks_content_fts;
+
+ALTER TABLE knowledge_chunks ALTER COLUMN embedding TYPE REAL[] USING embedding::real[];
@@ -0,0 +1,10 @@
+-- Chunks are ranked in Postgres: by distance between pgvector embeddings, and by full-text
+-- match on their content. Collections fix their dimensions with the first document, so the
+-- column isn't sized and the vector half is an exact scan over the searched collections.
+CREATE EXTENSION IF NOT EXISTS vector;
+
+ALTER TABLE knowledge_chunks ALTER COLUMN embedding TYPE vector USING embedding::vector;
+
+-- 'simple' doesn't stem or drop stop words, so any language's words match as written
+CREATE INDEX idx_knowledge_chunks_content_fts ON knowledge_chunks
+    USING GIN (to_tsvector('simple', content));
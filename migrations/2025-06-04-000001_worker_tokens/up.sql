@@ -0,0 +1,3 @@
+-- Each worker authenticates to the job queue with its own secret, which is only stored hashed.
+-- Workers registered before this have none until one is issued with POST /workers/{id}/token.
+ALTER TABLE workers ADD COLUMN token_hash VARCHAR(255);
@@ -26,6 +26,9 @@ pub enum AppError {
     #[error("Unauthorized")]
     Unauthorized,
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Internal server error")]
     InternalServerError,
 
@@ -100,6 +103,9 @@ pub enum AppError {
 
     #[error("Provider not found: {0}")]
     ProviderNotFound(String),
+
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String, retry_after: u64 },
 }
 
 impl From<BlockingError> for AppError {
@@ -114,16 +120,21 @@ impl ResponseError for AppError {
             AppError::NotFound => StatusCode::NOT_FOUND,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::AuthenticationError => StatusCode::UNAUTHORIZED,
             AppError::ExternalServiceError(_) => StatusCode::BAD_GATEWAY,
             AppError::UnsupportedProviderError(_) => StatusCode::BAD_REQUEST,
             AppError::ProviderNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
     fn error_response(&self) -> actix_web::HttpResponse {
-        actix_web::HttpResponse::build(self.status_code())
-            .json(serde_json::json!({ "error": self.to_string() }))
+        let mut response = actix_web::HttpResponse::build(self.status_code());
+        if let AppError::RateLimited { retry_after, .. } = self {
+            response.insert_header(("Retry-After", retry_after.to_string()));
+        }
+        response.json(serde_json::json!({ "error": self.to_string() }))
     }
 }
@@ -1,9 +1,18 @@
 use crate::db::DbPool;
 use crate::error::AppError;
-use crate::models::agent::{CreateAgentRequest, UpdateAgentRequest};
+use crate::handlers::function_calling::user_tool_registry;
+use crate::handlers::stream_chat::create_response_stream;
+use crate::models::agent::{CreateAgentRequest, RunAgentRequest, UpdateAgentRequest};
+use crate::models::agent_run::{DecideToolApprovalRequest, ToolApproval};
+use crate::services::agent_run_service::AgentRunService;
+use crate::services::agent_runtime::{AgentEvent, AgentRunResult, AgentRuntime};
 use crate::services::agent_service::AgentService;
+use crate::services::chat_service::ChatService;
+use crate::services::rate_limit_service::{RateLimitPermit, RateLimitService};
 use crate::utils::extractors::AuthenticatedUser;
 use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 use log::{debug, error, info};
 
@@ -120,6 +129,241 @@ pub async fn delete_agent(
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Admits a run against the user's limits and those of the API key behind `user_llm_config_id`.
+/// One permit covers every step of the run, and each step's tokens are counted against it.
+fn acquire_run_permit(
+    pool: &DbPool,
+    user_id: Uuid,
+    user_llm_config_id: Uuid,
+    stream: bool,
+) -> Result<RateLimitPermit, AppError> {
+    let user_config = ChatService::get_user_llm_config_by_id(pool, user_llm_config_id)?;
+    if user_config.user_id != user_id {
+        return Err(AppError::Unauthorized);
+    }
+    RateLimitService::acquire(pool, user_id, Some(user_config.api_key_id), stream)
+}
+
+pub async fn run_agent(
+    pool: web::Data<DbPool>,
+    agent_id: web::Path<Uuid>,
+    req: web::Json<RunAgentRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let agent_id = agent_id.into_inner();
+    info!("Received run agent request for agent {} from user {}", agent_id, user.0);
+
+    let req = req.into_inner();
+    let permit = acquire_run_permit(&pool, user.0, req.user_llm_config_id, false)?;
+    let registry = user_tool_registry(&pool, user.0).await?;
+    let result = AgentRuntime::run_agent(&pool, registry, user.0, agent_id, req, permit).await?;
+
+    info!("Agent {} finished with status {:?}", agent_id, result.status);
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn stream_agent_run(
+    pool: web::Data<DbPool>,
+    agent_id: web::Path<Uuid>,
+    req: web::Json<RunAgentRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let agent_id = agent_id.into_inner();
+    let req = req.into_inner();
+    info!("Received streaming run request for agent {} from user {}", agent_id, user.0);
+
+    // Resolve everything up front so configuration errors surface as HTTP errors. The permit
+    // holds one of the user's stream slots until the run below finishes.
+    let permit = acquire_run_permit(&pool, user.0, req.user_llm_config_id, true)?;
+    let registry = user_tool_registry(&pool, user.0).await?;
+    let (runtime, agent, tools) =
+        AgentRuntime::load(&pool, registry, user.0, agent_id, &req).await?;
+    let runtime = runtime.with_rate_limit(permit);
+
+    let (tx, rx) = mpsc::channel(100);
+    let (event_tx, mut event_rx) = mpsc::channel::<AgentEvent>(100);
+
+    actix_web::rt::spawn(async move {
+        if let Err(e) = runtime
+            .run_with_events(&agent, &tools, &req.input, Some(&event_tx))
+            .await
+        {
+            error!("Agent {} run failed: {}", agent_id, e);
+        }
+    });
+
+    actix_web::rt::spawn(async move {
+        while let Some(event) = event_rx.recv().await {
+            let data = match serde_json::to_string(&event) {
+                Ok(data) => data,
+                Err(e) => {
+                    error!("Failed to serialize agent event: {}", e);
+                    continue;
+                }
+            };
+            let frame = format!("event: {}\ndata: {}\n\n", event.event_name(), data);
+            if tx.send(Ok(web::Bytes::from(frame))).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(create_response_stream(rx)))
+}
+
+pub async fn list_agent_runs(
+    pool: web::Data<DbPool>,
+    agent_id: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    debug!("Received list runs request for agent {} from user {}", agent_id, user.0);
+
+    let runs = web::block(move || {
+        AgentRunService::list_runs(&pool, agent_id.into_inner(), user.0)
+    })
+    .await
+    .map_err(|e| {
+        error!("Error listing agent runs: {:?}", e);
+        AppError::InternalServerError
+    })?;
+
+    Ok(HttpResponse::Ok().json(runs?))
+}
+
+pub async fn get_agent_run(
+    pool: web::Data<DbPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let (agent_id, run_id) = path.into_inner();
+    debug!("Received get run request for run {} of agent {} from user {}", run_id, agent_id, user.0);
+
+    let run = web::block(move || AgentRunService::get_run(&pool, agent_id, run_id, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error getting agent run: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+    Ok(HttpResponse::Ok().json(run?))
+}
+
+pub async fn list_pending_approvals(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let approvals = web::block(move || AgentRunService::list_pending_approvals(&pool, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error listing pending approvals: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+    Ok(HttpResponse::Ok().json(approvals?))
+}
+
+#[derive(Serialize)]
+struct ApprovalDecisionResponse {
+    approval: ToolApproval,
+    /// Set when this was the run's last pending decision and the run resumed
+    run: Option<AgentRunResult>,
+}
+
+pub async fn decide_tool_approval(
+    pool: web::Data<DbPool>,
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    req: web::Json<DecideToolApprovalRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let (agent_id, run_id, approval_id) = path.into_inner();
+    let req = req.into_inner();
+    info!("Received {:?} for approval {} of run {} from user {}", req.decision, approval_id, run_id, user.0);
+
+    // Make sure the run belongs to this agent and user before touching its approvals
+    let approval = web::block({
+        let pool = pool.clone();
+        move || {
+            AgentRunService::get_run(&pool, agent_id, run_id, user.0)?;
+            AgentRunService::decide_approval(
+                &pool,
+                run_id,
+                approval_id,
+                user.0,
+                req.decision,
+                req.arguments,
+                req.reason,
+            )
+        }
+    })
+    .await
+    .map_err(|e| {
+        error!("Error deciding approval: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    let run = resume_if_decided(&pool, user.0, agent_id, run_id).await?;
+    Ok(HttpResponse::Ok().json(ApprovalDecisionResponse { approval, run }))
+}
+
+/// Resumes a run whose approvals are all decided, e.g. one that was left paused because a
+/// rate limit was reached when its last approval was decided
+pub async fn resume_agent_run(
+    pool: web::Data<DbPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let (agent_id, run_id) = path.into_inner();
+    info!("Received resume request for run {} of agent {} from user {}", run_id, agent_id, user.0);
+
+    let run = resume_if_decided(&pool, user.0, agent_id, run_id)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("Run {} is not waiting to be resumed", run_id))
+        })?;
+    Ok(HttpResponse::Ok().json(run))
+}
+
+/// Resumes the run if no approvals are pending. The run's step only counts against the rate
+/// limits once it's known that it will continue.
+async fn resume_if_decided(
+    pool: &web::Data<DbPool>,
+    user_id: Uuid,
+    agent_id: Uuid,
+    run_id: Uuid,
+) -> Result<Option<AgentRunResult>, AppError> {
+    let permit = web::block({
+        let pool = pool.clone();
+        move || {
+            let Some(run) = AgentRunService::resumable_run(&pool, agent_id, run_id, user_id)? else {
+                return Ok(None);
+            };
+            let permit = match AgentRuntime::paused_config_id(&run) {
+                Some(config_id) => acquire_run_permit(&pool, user_id, config_id, false)?,
+                None => RateLimitService::acquire(&pool, user_id, None, false)?,
+            };
+            Ok::<_, AppError>(Some(permit))
+        }
+    })
+    .await
+    .map_err(|e| {
+        error!("Error checking run {} for resumption: {:?}", run_id, e);
+        AppError::InternalServerError
+    })??;
+    let Some(permit) = permit else {
+        return Ok(None);
+    };
+
+    let registry = user_tool_registry(pool, user_id).await?;
+    let run = AgentRuntime::resume_run(pool, registry, user_id, agent_id, run_id, permit).await?;
+    if let Some(run) = &run {
+        info!("Run {} resumed and finished with status {:?}", run_id, run.status);
+    }
+    Ok(run)
+}
+
 // Handle OPTIONS requests for CORS preflight
 pub async fn options_handler() -> HttpResponse {
     info!("Received OPTIONS request for agent endpoint");
@@ -9,6 +9,8 @@ use crate::db::DbPool;
 use crate::error::AppError;
 use crate::models::attachment::Attachment;
 use crate::schema::attachments;
+use crate::services::attachment_service::AttachmentService;
+use crate::utils::extractors::AuthenticatedUser;
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct CreateAttachmentData {
@@ -70,6 +72,35 @@ pub async fn create_attachment(
     Ok(HttpResponse::Ok().json(attachment))
 }
 
+/// An image or document uploaded for use in multimodal chat messages
+#[derive(serde::Deserialize)]
+pub struct UploadAttachmentData {
+    pub file_name: String,
+    /// Base64-encoded file content
+    pub data: String,
+}
+
+#[post("/messages/{message_id}/attachments/upload")]
+pub async fn upload_attachment(
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    upload: web::Json<UploadAttachmentData>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let message_id = path.into_inner();
+    if AttachmentService::message_owner(&pool, message_id)? != user.0 {
+        return Err(AppError::NotFoundError(format!("Message not found: {}", message_id)));
+    }
+
+    let bytes = base64::decode(&upload.data)
+        .map_err(|e| AppError::BadRequest(format!("Invalid base64 data: {}", e)))?;
+    let attachment =
+        AttachmentService::store_upload(&pool, message_id, &upload.file_name, &bytes).await?;
+    info!("Stored upload {} as attachment {}", upload.file_name, attachment.id);
+
+    Ok(HttpResponse::Created().json(attachment))
+}
+
 #[get("/messages/{message_id}/attachments")]
 pub async fn get_message_attachments(
     pool: web::Data<DbPool>,
@@ -123,6 +154,7 @@ pub async fn delete_attachment(
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(get_attachment)
         .service(create_attachment)
+        .service(upload_attachment)
         .service(get_message_attachments)
         .service(delete_attachment);
 }
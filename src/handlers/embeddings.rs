@@ -0,0 +1,85 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::usage::UsageSource;
+use crate::services::chat_service::ChatService;
+use crate::services::llm_service::{LLMService, LLMServiceError};
+use crate::services::rate_limit_service::RateLimitService;
+use crate::services::usage_service::UsageService;
+use crate::utils::extractors::AuthenticatedUser;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Most inputs accepted in one request; larger batches are split per provider
+const MAX_INPUTS: usize = 2048;
+
+/// A single text or a batch of texts
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+impl EmbeddingInput {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            EmbeddingInput::Single(input) => vec![input],
+            EmbeddingInput::Batch(inputs) => inputs,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingsRequest {
+    pub user_llm_config_id: Uuid,
+    pub input: EmbeddingInput,
+    /// Overrides the provider's `embedding_model`
+    pub model: Option<String>,
+    /// Shortens vectors, for models that support it
+    pub dimensions: Option<u64>,
+}
+
+pub async fn create_embeddings(
+    pool: web::Data<DbPool>,
+    req: web::Json<EmbeddingsRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+    let inputs = req.input.into_vec();
+    if inputs.is_empty() || inputs.len() > MAX_INPUTS {
+        return Err(AppError::BadRequest(format!(
+            "input must have between 1 and {} entries",
+            MAX_INPUTS
+        )));
+    }
+
+    let user_config = ChatService::get_user_llm_config_by_id(&pool, req.user_llm_config_id)?;
+    if user_config.user_id != user.0 {
+        return Err(AppError::NotFoundError("LLM config not found".to_string()));
+    }
+    let mut provider = ChatService::get_llm_provider(&pool, user_config.provider_id)?;
+    if let Some(model) = req.model {
+        provider.configuration["embedding_model"] = json!(model);
+    }
+    if let Some(dimensions) = req.dimensions {
+        provider.configuration["dimensions"] = json!(dimensions);
+    }
+
+    let permit = RateLimitService::acquire(&pool, user.0, Some(user_config.api_key_id), false)?;
+
+    let output = LLMService::embed(&pool, &provider, &user_config, inputs)
+        .await
+        .map_err(|e: LLMServiceError| match e.0 {
+            error @ (AppError::BadRequest(_) | AppError::UnsupportedProviderError(_)) => error,
+            error => AppError::ExternalServiceError(error.to_string()),
+        })?;
+
+    if let Some(stats) = &output.usage_stats {
+        permit.record_tokens(stats.tokens.total_tokens);
+        UsageService::record_quietly(&pool, user.0, stats, UsageSource::Embeddings, None, None);
+    }
+
+    Ok(HttpResponse::Ok().json(output))
+}
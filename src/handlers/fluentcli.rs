@@ -2,6 +2,7 @@ use crate::models::fluentcli::CommandRequest;
 use crate::services::fluentcli_service::FluentCLIService;
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use log::{debug, error, info};
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
 pub async fn execute_command(
@@ -12,7 +13,9 @@ pub async fn execute_command(
     info!("Received execute_command request for user_id: {}", user_id);
     debug!("Command request: {:?}", command_request);
 
-    match FluentCLIService::execute_command(user_id, command_request.into_inner()).await {
+    // The whole result is returned at once, so the output lines aren't needed as they arrive
+    let (lines, _) = mpsc::unbounded_channel();
+    match FluentCLIService::execute_command(user_id, command_request.into_inner(), lines).await {
         Ok(result) => {
             info!("Command executed successfully");
             debug!("Command result: {:?}", result);
@@ -6,50 +6,71 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 use std::sync::Arc;
-use lazy_static::lazy_static;
 use crate::services::function_calling::tool::registry::ToolRegistry;
 use crate::services::function_calling::tool::executor::{ToolExecutor, ToolExecutorBase};
 use crate::services::function_calling::tool::error::ToolError;
 use crate::services::function_calling::examples::weather_tool::WeatherTool;
+use crate::services::knowledge_service::KnowledgeService;
+use crate::services::mcp_server_service::McpServerService;
+use crate::services::rate_limit_service::RateLimitService;
+use crate::services::tool_permission_service::ToolPermissionService;
+use crate::services::user_tool_service::UserToolService;
+use crate::models::tool_permission::SetToolPermissionRequest;
+use crate::models::user_tool::{CreateUserToolRequest, UpdateUserToolRequest, UserToolResponse};
 use async_trait::async_trait;
 
-// Create a global tool registry
-lazy_static! {
-    static ref TOOL_REGISTRY: Arc<ToolRegistry> = {
-        let registry = Arc::new(ToolRegistry::new());
-        registry
-    };
-}
-
-// Initialize the tool registry with available tools
-async fn init_tool_registry() {
-    let registry = TOOL_REGISTRY.clone();
+/// Names of the built-in tools, which user-defined tools may not shadow
+pub const BUILTIN_TOOL_NAMES: &[&str] = &["get_weather", "search_web", "calculate", "retrieve_documents"];
 
-    // Only initialize if the registry is empty
-    if registry.is_empty().await {
-        // Register the weather tool
-        let weather_tool = WeatherTool::new();
-        registry.register(weather_tool).await;
+// Register the built-in tools every user has access to
+async fn register_builtin_tools(registry: &ToolRegistry) {
+    // Register the weather tool
+    let weather_tool = WeatherTool::new();
+    registry.register(weather_tool).await;
 
-        // Register the search tool
-        let search_tool = SearchTool::new();
-        registry.register(search_tool).await;
+    // Register the search tool
+    let search_tool = SearchTool::new();
+    registry.register(search_tool).await;
 
-        // Register the calculator tool
-        let calculator_tool = CalculatorTool::new();
-        registry.register(calculator_tool).await;
+    // Register the calculator tool
+    let calculator_tool = CalculatorTool::new();
+    registry.register(calculator_tool).await;
 
-        // Update the tool parameters
-        if let Some(mut tool) = registry.get_tool("search_web").await {
-            tool.parameters = create_search_tool_parameters();
-            registry.update_tool_parameters(&tool.name, tool.parameters).await;
-        }
+    // Update the tool parameters
+    registry.update_tool_parameters("get_weather", create_weather_tool_parameters()).await;
+    registry.update_tool_parameters("search_web", create_search_tool_parameters()).await;
+    registry.update_tool_parameters("calculate", create_calculator_tool_parameters()).await;
+}
 
-        if let Some(mut tool) = registry.get_tool("calculate").await {
-            tool.parameters = create_calculator_tool_parameters();
-            registry.update_tool_parameters(&tool.name, tool.parameters).await;
+/// Builds a registry with the built-in tools plus the user's own tools, MCP server tools and,
+/// if the user has document collections, `retrieve_documents`.
+/// Each request gets its own registry, so one user's tools are never reachable by another.
+pub async fn user_tool_registry(pool: &DbPool, user_id: Uuid) -> Result<Arc<ToolRegistry>, AppError> {
+    let registry = ToolRegistry::for_user(user_id);
+    register_builtin_tools(&registry).await;
+
+    let (user_tools, servers, collections, dangerous) = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, AppError> {
+            Ok((
+                UserToolService::load_tools(&pool, user_id)?,
+                McpServerService::list_servers(&pool, user_id)?,
+                KnowledgeService::list_collections(&pool, user_id)?,
+                ToolPermissionService::dangerous_tools(&pool, user_id)?,
+            ))
         }
-    }
+    })
+    .await
+    .map_err(|e| {
+        log::error!("Error loading user tools: {:?}", e);
+        AppError::InternalServerError
+    })??;
+    UserToolService::register_tools(user_tools, &registry).await;
+    McpServerService::register_user_tools(&servers, &registry).await;
+    KnowledgeService::register_tool(pool, user_id, &collections, &registry).await;
+    ToolPermissionService::apply(&dangerous, &registry).await;
+
+    Ok(Arc::new(registry))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,6 +79,9 @@ pub struct ApiTool {
     pub name: String,
     pub description: String,
     pub parameters: Value,
+    /// Dangerous tools only run when the call is confirmed
+    #[serde(default)]
+    pub dangerous: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,6 +89,9 @@ pub struct ApiToolCall {
     pub id: String,
     pub tool_id: String,
     pub arguments: Value,
+    /// Must be set to run a tool marked as dangerous
+    #[serde(default)]
+    pub confirm: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -99,14 +126,9 @@ impl ToolExecutorBase for SearchTool {
     }
 
     fn validate_args(&self, args: &Value) -> Result<(), ToolError> {
-        if !args.get("query").is_some() {
-            return Err(ToolError::MissingParameter("query".to_string()));
-        }
-
-        if let Some(query) = args.get("query") {
-            if !query.is_string() || query.as_str().unwrap().trim().is_empty() {
-                return Err(ToolError::InvalidArgument("query must be a non-empty string".to_string()));
-            }
+        // Presence and type are checked against the schema by the registry
+        if args["query"].as_str().map_or(true, |query| query.trim().is_empty()) {
+            return Err(ToolError::InvalidArgument("$.query: must not be empty".to_string()));
         }
 
         Ok(())
@@ -257,14 +279,9 @@ impl ToolExecutorBase for CalculatorTool {
     }
 
     fn validate_args(&self, args: &Value) -> Result<(), ToolError> {
-        if !args.get("expression").is_some() {
-            return Err(ToolError::MissingParameter("expression".to_string()));
-        }
-
-        if let Some(expression) = args.get("expression") {
-            if !expression.is_string() || expression.as_str().unwrap().trim().is_empty() {
-                return Err(ToolError::InvalidArgument("expression must be a non-empty string".to_string()));
-            }
+        // Presence and type are checked against the schema by the registry
+        if args["expression"].as_str().map_or(true, |expression| expression.trim().is_empty()) {
+            return Err(ToolError::InvalidArgument("$.expression: must not be empty".to_string()));
         }
 
         Ok(())
@@ -286,7 +303,7 @@ impl ToolExecutor for CalculatorTool {
 }
 
 // Convert internal Tool to API Tool
-fn convert_to_api_tool(tool: &crate::services::function_calling::types::Tool) -> ApiTool {
+pub(crate) fn convert_to_api_tool(tool: &crate::services::function_calling::types::Tool) -> ApiTool {
     // Create a JSON schema for the tool parameters
     let parameters = tool.to_json_schema();
 
@@ -295,9 +312,29 @@ fn convert_to_api_tool(tool: &crate::services::function_calling::types::Tool) ->
         name: tool.name.clone(),
         description: tool.description.clone(),
         parameters,
+        dangerous: false,
     }
 }
 
+// Create tool parameters for the weather tool
+fn create_weather_tool_parameters() -> Vec<crate::services::function_calling::types::ToolParameter> {
+    use crate::services::function_calling::types::{ToolParameter, ParameterType};
+
+    vec![
+        ToolParameter::new("location", ParameterType::String {
+            format: None,
+            enum_values: None
+        }, true)
+        .with_description("City and country, e.g., 'Paris, France'"),
+
+        ToolParameter::new("units", ParameterType::String {
+            format: None,
+            enum_values: Some(vec!["celsius".to_string(), "fahrenheit".to_string()])
+        }, false)
+        .with_description("Temperature units: 'celsius' or 'fahrenheit'"),
+    ]
+}
+
 // Create tool parameters for the search tool
 fn create_search_tool_parameters() -> Vec<crate::services::function_calling::types::ToolParameter> {
     use crate::services::function_calling::types::{ToolParameter, ParameterType};
@@ -331,62 +368,68 @@ fn create_calculator_tool_parameters() -> Vec<crate::services::function_calling:
 }
 
 pub async fn list_tools(
-    _pool: web::Data<DbPool>,
-    _user: AuthenticatedUser,
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, AppError> {
-    // Initialize the tool registry if needed
-    init_tool_registry().await;
-
-    // Get the list of tools from the registry
-    let registry = TOOL_REGISTRY.clone();
+    // Get the list of tools from the user's registry
+    let registry = user_tool_registry(&pool, user.0).await?;
     let tools = registry.list_tools().await;
 
     // Convert to API tools
-    let api_tools: Vec<ApiTool> = tools.iter()
-        .map(|tool| convert_to_api_tool(tool))
-        .collect();
+    let mut api_tools = Vec::with_capacity(tools.len());
+    for tool in &tools {
+        let mut api_tool = convert_to_api_tool(tool);
+        api_tool.dangerous = registry.is_dangerous(&tool.name).await;
+        api_tools.push(api_tool);
+    }
 
     Ok(HttpResponse::Ok().json(api_tools))
 }
 
 pub async fn get_tool(
-    _pool: web::Data<DbPool>,
+    pool: web::Data<DbPool>,
     tool_id: web::Path<String>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, AppError> {
-    // Initialize the tool registry if needed
-    init_tool_registry().await;
-
-    // Get the tool from the registry
-    let registry = TOOL_REGISTRY.clone();
+    // Get the tool from the user's registry
+    let registry = user_tool_registry(&pool, user.0).await?;
     let tool_id = tool_id.into_inner();
 
     let tool = registry.get_tool(&tool_id).await
         .ok_or_else(|| AppError::NotFoundError(format!("Tool not found: {}", tool_id)))?;
 
     // Convert to API tool
-    let api_tool = convert_to_api_tool(&tool);
+    let mut api_tool = convert_to_api_tool(&tool);
+    api_tool.dangerous = registry.is_dangerous(&tool.name).await;
 
     Ok(HttpResponse::Ok().json(api_tool))
 }
 
 pub async fn execute_tool(
-    _pool: web::Data<DbPool>,
+    pool: web::Data<DbPool>,
     tool_call: web::Json<ApiToolCall>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, AppError> {
-    // Initialize the tool registry if needed
-    init_tool_registry().await;
-
     let tool_call = tool_call.into_inner();
-    let registry = TOOL_REGISTRY.clone();
-
-    // Execute the tool
-    let result = registry.execute(&tool_call.tool_id, tool_call.arguments.clone()).await
-        .map_err(|e| {
+    let _permit = RateLimitService::acquire(&pool, user.0, None, false)?;
+    let registry = user_tool_registry(&pool, user.0).await?;
+
+    // Execute the tool; dangerous tools need the caller's explicit confirmation
+    let result = if tool_call.confirm {
+        registry.execute_confirmed(&tool_call.tool_id, tool_call.arguments.clone()).await
+    } else {
+        registry.execute(&tool_call.tool_id, tool_call.arguments.clone()).await
+    };
+    let result = result.map_err(|e| match e {
+        ToolError::ConfirmationRequired(name) => AppError::Forbidden(format!(
+            "Tool '{}' is marked as dangerous; resend the call with \"confirm\": true",
+            name
+        )),
+        e => {
             log::error!("Tool execution error: {}", e);
             AppError::BadRequest(format!("Tool execution failed: {}", e))
-        })?;
+        }
+    })?;
 
     let tool_result = ApiToolResult {
         id: Uuid::new_v4().to_string(),
@@ -395,4 +438,134 @@ pub async fn execute_tool(
     };
 
     Ok(HttpResponse::Ok().json(tool_result))
-}
\ No newline at end of file
+}
+
+fn check_builtin_name(name: Option<&str>) -> Result<(), AppError> {
+    match name {
+        Some(name) if BUILTIN_TOOL_NAMES.contains(&name) => Err(AppError::BadRequest(format!(
+            "'{}' is the name of a built-in tool",
+            name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub async fn create_user_tool(
+    pool: web::Data<DbPool>,
+    req: web::Json<CreateUserToolRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    log::info!("Received create tool request from user {}", user.0);
+    check_builtin_name(Some(&req.name))?;
+
+    let tool = web::block(move || UserToolService::create_tool(&pool, user.0, req.into_inner()))
+        .await
+        .map_err(|e| {
+            log::error!("Error creating tool: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Created().json(UserToolResponse::from(tool)))
+}
+
+pub async fn get_user_tool_config(
+    pool: web::Data<DbPool>,
+    tool_id: web::Path<String>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let tool = web::block(move || UserToolService::get_tool(&pool, user.0, &tool_id.into_inner()))
+        .await
+        .map_err(|e| {
+            log::error!("Error getting tool: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(UserToolResponse::from(tool)))
+}
+
+pub async fn update_user_tool(
+    pool: web::Data<DbPool>,
+    tool_id: web::Path<String>,
+    req: web::Json<UpdateUserToolRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    log::info!("Received update tool request for {} from user {}", tool_id, user.0);
+    check_builtin_name(req.name.as_deref())?;
+
+    let tool = web::block(move || {
+        UserToolService::update_tool(&pool, user.0, &tool_id.into_inner(), req.into_inner())
+    })
+    .await
+    .map_err(|e| {
+        log::error!("Error updating tool: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    Ok(HttpResponse::Ok().json(UserToolResponse::from(tool)))
+}
+
+pub async fn delete_user_tool(
+    pool: web::Data<DbPool>,
+    tool_id: web::Path<String>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    log::info!("Received delete tool request for {} from user {}", tool_id, user.0);
+
+    web::block(move || UserToolService::delete_tool(&pool, user.0, &tool_id.into_inner()))
+        .await
+        .map_err(|e| {
+            log::error!("Error deleting tool: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn list_tool_permissions(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let permissions = web::block(move || ToolPermissionService::list_permissions(&pool, user.0))
+        .await
+        .map_err(|e| {
+            log::error!("Error listing tool permissions: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+pub async fn set_tool_permission(
+    pool: web::Data<DbPool>,
+    tool_id: web::Path<String>,
+    req: web::Json<SetToolPermissionRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    log::info!("Received tool permission update for {} from user {}", tool_id, user.0);
+
+    let permission = web::block(move || {
+        ToolPermissionService::set_permission(&pool, user.0, &tool_id.into_inner(), req.dangerous)
+    })
+    .await
+    .map_err(|e| {
+        log::error!("Error setting tool permission: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    Ok(HttpResponse::Ok().json(permission))
+}
+
+pub async fn delete_tool_permission(
+    pool: web::Data<DbPool>,
+    tool_id: web::Path<String>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    web::block(move || ToolPermissionService::delete_permission(&pool, user.0, &tool_id.into_inner()))
+        .await
+        .map_err(|e| {
+            log::error!("Error deleting tool permission: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::NoContent().finish())
+}
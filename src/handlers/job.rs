@@ -1,9 +1,19 @@
 use crate::db::DbPool;
+use crate::error::AppError;
+use crate::handlers::stream_chat::create_response_stream;
 use crate::models::job::{NewJob, NewJobPayload, UpdateJob};
+use crate::models::job_run::{
+    is_active_status, JobLogsQuery, JobRunSummary, JobRunsQuery, RunTrigger, MAX_LOGS_PAGE,
+};
 use crate::services::job_service::JobService;
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration};
 use uuid::Uuid;
+
+/// How often a followed run is checked for new log lines
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(1);
 pub async fn create_job(
     pool: web::Data<DbPool>,
     new_job_payload: web::Json<NewJobPayload>,
@@ -42,6 +52,7 @@ pub async fn create_job(
 
     match JobService::create_job(&pool, new_job) {
         Ok(job) => Ok(HttpResponse::Created().json(job)),
+        Err(AppError::TimerError(message)) => Ok(HttpResponse::BadRequest().body(message)),
         Err(e) => {
             log::error!("Error creating job: {:?}", e);
             Ok(HttpResponse::InternalServerError().body("Failed to create job"))
@@ -89,6 +100,7 @@ pub async fn update_job(
         user_id,
     ) {
         Ok(job) => HttpResponse::Ok().json(job),
+        Err(AppError::TimerError(message)) => HttpResponse::BadRequest().body(message),
         Err(e) => {
             log::error!("Error updating job: {:?}", e);
             HttpResponse::InternalServerError().body("Failed to update job")
@@ -117,7 +129,7 @@ pub async fn start_job(
     req: HttpRequest,
 ) -> impl Responder {
     let user_id = req.extensions().get::<Uuid>().cloned().unwrap();
-    match JobService::start_job(&pool, job_id.into_inner(), user_id).await {
+    match JobService::start_job(&pool, job_id.into_inner(), user_id, RunTrigger::Manual).await {
         Ok(job) => HttpResponse::Ok().json(job),
         Err(e) => {
             log::error!("Error starting job: {:?}", e);
@@ -141,6 +153,47 @@ pub async fn stop_job(
     }
 }
 
+pub async fn list_job_runs(
+    pool: web::Data<DbPool>,
+    job_id: web::Path<Uuid>,
+    req: HttpRequest,
+    query: web::Query<JobRunsQuery>,
+) -> impl Responder {
+    let user_id = req.extensions().get::<Uuid>().cloned().unwrap();
+    match JobService::list_runs(
+        &pool,
+        job_id.into_inner(),
+        user_id,
+        query.limit,
+        query.offset,
+    ) {
+        Ok(runs) => HttpResponse::Ok().json(runs),
+        Err(e) => {
+            log::error!("Error listing job runs: {:?}", e);
+            HttpResponse::InternalServerError().body("Failed to list job runs")
+        }
+    }
+}
+
+pub async fn get_job_run(
+    pool: web::Data<DbPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = req.extensions().get::<Uuid>().cloned().unwrap();
+    let (job_id, run_id) = path.into_inner();
+    match JobService::get_run(&pool, job_id, run_id, user_id) {
+        Ok(run) => HttpResponse::Ok().json(run),
+        Err(AppError::DatabaseError(diesel::result::Error::NotFound)) => {
+            HttpResponse::NotFound().body("Job run not found")
+        }
+        Err(e) => {
+            log::error!("Error getting job run: {:?}", e);
+            HttpResponse::InternalServerError().body("Failed to get job run")
+        }
+    }
+}
+
 pub async fn get_job_status(
     pool: web::Data<DbPool>,
     job_id: web::Path<Uuid>,
@@ -175,15 +228,101 @@ pub async fn get_job_logs(
     pool: web::Data<DbPool>,
     job_id: web::Path<Uuid>,
     req: HttpRequest,
+    query: web::Query<JobLogsQuery>,
 ) -> impl Responder {
     let user_id = req.extensions().get::<Uuid>().cloned().unwrap();
-    match JobService::get_job_logs(&pool, job_id.into_inner(), user_id).await {
-        Ok(logs) => HttpResponse::Ok().json(logs),
+    let job_id = job_id.into_inner();
+    let query = query.into_inner();
+
+    if !query.follow.unwrap_or(false) {
+        return match JobService::get_job_logs(&pool, job_id, user_id, &query) {
+            Ok(page) => HttpResponse::Ok().json(page),
+            Err(AppError::DatabaseError(diesel::result::Error::NotFound)) => {
+                HttpResponse::NotFound().body("Job has no runs")
+            }
+            Err(e) => {
+                log::error!("Error getting job logs: {:?}", e);
+                HttpResponse::InternalServerError().body("Failed to get job logs")
+            }
+        };
+    }
+
+    let run = match JobService::find_log_run(&pool, job_id, user_id, query.run_id) {
+        Ok(run) => run,
+        Err(AppError::DatabaseError(diesel::result::Error::NotFound)) => {
+            return HttpResponse::NotFound().body("Job has no runs")
+        }
         Err(e) => {
             log::error!("Error getting job logs: {:?}", e);
-            HttpResponse::InternalServerError().body("Failed to get job logs")
+            return HttpResponse::InternalServerError().body("Failed to get job logs");
         }
-    }
+    };
+    // An EventSource that reconnects resumes after the last line it received
+    let after = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .or(query.after)
+        .unwrap_or(0);
+
+    follow_job_logs(pool.get_ref().clone(), run, after)
+}
+
+/// Streams a run's log lines as server-sent events, ending with an `end` event once the run has
+/// finished and every line has been sent
+fn follow_job_logs(pool: DbPool, run: JobRunSummary, mut after: i64) -> HttpResponse {
+    let (tx, rx) = mpsc::channel(100);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            // Lines are all stored before a run finishes, so reading them after seeing it finished
+            // can't miss any
+            let status = match JobService::run_status(&pool, run.id) {
+                Ok(status) => status,
+                Err(e) => {
+                    log::error!("Error following logs of run {}: {:?}", run.id, e);
+                    return;
+                }
+            };
+
+            loop {
+                let lines = match JobService::run_logs(&pool, run.id, after, MAX_LOGS_PAGE) {
+                    Ok(lines) => lines,
+                    Err(e) => {
+                        log::error!("Error following logs of run {}: {:?}", run.id, e);
+                        return;
+                    }
+                };
+                for line in &lines {
+                    let data = serde_json::to_string(line).unwrap_or_default();
+                    let frame = format!("id: {}\nevent: log\ndata: {}\n\n", line.id, data);
+                    if tx.send(Ok(web::Bytes::from(frame))).await.is_err() {
+                        return;
+                    }
+                    after = line.id;
+                }
+                if (lines.len() as i64) < MAX_LOGS_PAGE {
+                    break;
+                }
+            }
+
+            if !is_active_status(&status) {
+                let frame = format!(
+                    "event: end\ndata: {}\n\n",
+                    serde_json::json!({ "run_id": run.id, "status": status })
+                );
+                let _ = tx.send(Ok(web::Bytes::from(frame))).await;
+                return;
+            }
+            sleep(LOG_POLL_INTERVAL).await;
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(create_response_stream(rx))
 }
 
 pub async fn get_job_data(
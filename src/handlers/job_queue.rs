@@ -0,0 +1,96 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::job_queue::{LeaseLogs, LeaseRenewal, LeaseRequest, LeaseResult};
+use crate::services::job_queue_service::JobQueueService;
+use crate::utils::extractors::QueueWorker;
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use uuid::Uuid;
+
+fn lease_lost(run_id: Uuid) -> HttpResponse {
+    HttpResponse::Conflict().json(json!({
+        "error": format!("This worker no longer holds the lease on run {}", run_id)
+    }))
+}
+
+/// Hands the worker the next run it can take, or `204 No Content` if there is none
+pub async fn lease_job(
+    pool: web::Data<DbPool>,
+    worker: QueueWorker,
+    request: Option<web::Json<LeaseRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let lease_seconds = request.and_then(|request| request.lease_seconds);
+    match JobQueueService::lease(&pool, &worker.0, lease_seconds)? {
+        Some(lease) => Ok(HttpResponse::Ok().json(lease)),
+        None => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+pub async fn renew_lease(
+    pool: web::Data<DbPool>,
+    worker: QueueWorker,
+    run_id: web::Path<Uuid>,
+    renewal: web::Json<LeaseRenewal>,
+) -> Result<HttpResponse, AppError> {
+    let run_id = run_id.into_inner();
+    match JobQueueService::renew_lease(
+        &pool,
+        &worker.0,
+        run_id,
+        renewal.lease_token,
+        renewal.lease_seconds,
+    )? {
+        Some(status) => Ok(HttpResponse::Ok().json(status)),
+        None => Ok(lease_lost(run_id)),
+    }
+}
+
+pub async fn append_logs(
+    pool: web::Data<DbPool>,
+    worker: QueueWorker,
+    run_id: web::Path<Uuid>,
+    logs: web::Json<LeaseLogs>,
+) -> Result<HttpResponse, AppError> {
+    let run_id = run_id.into_inner();
+    let logs = logs.into_inner();
+    if JobQueueService::append_logs(&pool, &worker.0, run_id, logs.lease_token, logs.lines)? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(lease_lost(run_id))
+    }
+}
+
+/// Takes the result of a run and releases it
+pub async fn complete_job(
+    pool: web::Data<DbPool>,
+    worker: QueueWorker,
+    run_id: web::Path<Uuid>,
+    result: web::Json<LeaseResult>,
+) -> Result<HttpResponse, AppError> {
+    let run_id = run_id.into_inner();
+    let LeaseResult {
+        lease_token,
+        state_file_content,
+        result,
+    } = result.into_inner();
+    let entry = match JobQueueService::complete(
+        &pool,
+        &worker.0,
+        run_id,
+        lease_token,
+        &result,
+        state_file_content,
+    )? {
+        Some(entry) => entry,
+        None => return Ok(lease_lost(run_id)),
+    };
+
+    log::info!(
+        "Worker {} finished run {} with exit code {}",
+        worker.0.id,
+        run_id,
+        result.exit_code
+    );
+    let _ = tokio::fs::remove_file(&entry.pipeline_file).await;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,213 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::knowledge::{
+    CreateCollectionRequest, IngestDocumentRequest, SearchRequest,
+    SetConversationCollectionsRequest, UpdateCollectionRequest,
+};
+use crate::models::usage::UsageSource;
+use crate::services::chat_service::ChatService;
+use crate::services::knowledge_service::{KnowledgeService, DEFAULT_RESULTS, MAX_RESULTS};
+use crate::services::rate_limit_service::RateLimitService;
+use crate::services::usage_service::UsageService;
+use crate::utils::extractors::AuthenticatedUser;
+use actix_web::{web, HttpResponse};
+use log::{debug, error, info};
+use serde_json::json;
+use uuid::Uuid;
+
+pub async fn create_collection(
+    pool: web::Data<DbPool>,
+    req: web::Json<CreateCollectionRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    info!("Received create collection request from user {}", user.0);
+
+    let collection = web::block(move || KnowledgeService::create_collection(&pool, user.0, req.into_inner()))
+        .await
+        .map_err(|e| {
+            error!("Error creating collection: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Created().json(collection))
+}
+
+pub async fn list_collections(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    debug!("Received list collections request from user {}", user.0);
+
+    let collections = web::block(move || KnowledgeService::list_collections(&pool, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error listing collections: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(collections))
+}
+
+pub async fn get_collection(
+    pool: web::Data<DbPool>,
+    collection_id: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let collection = web::block(move || KnowledgeService::get_collection(&pool, collection_id.into_inner(), user.0))
+        .await
+        .map_err(|e| {
+            error!("Error getting collection: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(collection))
+}
+
+pub async fn update_collection(
+    pool: web::Data<DbPool>,
+    collection_id: web::Path<Uuid>,
+    req: web::Json<UpdateCollectionRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let collection_id = collection_id.into_inner();
+    info!("Received update collection request for {} from user {}", collection_id, user.0);
+
+    let collection = web::block(move || {
+        KnowledgeService::update_collection(&pool, collection_id, user.0, req.into_inner())
+    })
+    .await
+    .map_err(|e| {
+        error!("Error updating collection: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    Ok(HttpResponse::Ok().json(collection))
+}
+
+pub async fn delete_collection(
+    pool: web::Data<DbPool>,
+    collection_id: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let collection_id = collection_id.into_inner();
+    info!("Received delete collection request for {} from user {}", collection_id, user.0);
+
+    web::block(move || KnowledgeService::delete_collection(&pool, collection_id, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error deleting collection: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn ingest_document(
+    pool: web::Data<DbPool>,
+    collection_id: web::Path<Uuid>,
+    req: web::Json<IngestDocumentRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let collection_id = collection_id.into_inner();
+    info!("Received document for collection {} from user {}", collection_id, user.0);
+
+    let collection = KnowledgeService::get_collection(&pool, collection_id, user.0)?;
+    let user_config = ChatService::get_user_llm_config_by_id(&pool, collection.user_llm_config_id)?;
+    let permit = RateLimitService::acquire(&pool, user.0, Some(user_config.api_key_id), false)?;
+
+    let (document, usage_stats) =
+        KnowledgeService::ingest_document(&pool, user.0, collection_id, req.into_inner()).await?;
+
+    if let Some(stats) = &usage_stats {
+        permit.record_tokens(stats.tokens.total_tokens);
+        UsageService::record_quietly(&pool, user.0, stats, UsageSource::Embeddings, None, None);
+    }
+
+    Ok(HttpResponse::Created().json(document))
+}
+
+pub async fn list_documents(
+    pool: web::Data<DbPool>,
+    collection_id: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let documents = web::block(move || KnowledgeService::list_documents(&pool, collection_id.into_inner(), user.0))
+        .await
+        .map_err(|e| {
+            error!("Error listing documents: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(documents))
+}
+
+pub async fn delete_document(
+    pool: web::Data<DbPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let (collection_id, document_id) = path.into_inner();
+    info!("Received delete document request for {} from user {}", document_id, user.0);
+
+    web::block(move || KnowledgeService::delete_document(&pool, collection_id, document_id, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error deleting document: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn search(
+    pool: web::Data<DbPool>,
+    req: web::Json<SearchRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+    let limit = req.limit.unwrap_or(DEFAULT_RESULTS).clamp(1, MAX_RESULTS);
+
+    let _permit = RateLimitService::acquire(&pool, user.0, None, false)?;
+    let results =
+        KnowledgeService::search(&pool, user.0, &req.collection_ids, &req.query, limit, None).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "query": req.query, "results": results })))
+}
+
+pub async fn get_conversation_collections(
+    pool: web::Data<DbPool>,
+    conversation_id: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let collections = web::block(move || {
+        KnowledgeService::conversation_collections(&pool, user.0, conversation_id.into_inner())
+    })
+    .await
+    .map_err(|e| {
+        error!("Error listing conversation collections: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    Ok(HttpResponse::Ok().json(collections))
+}
+
+pub async fn set_conversation_collections(
+    pool: web::Data<DbPool>,
+    conversation_id: web::Path<Uuid>,
+    req: web::Json<SetConversationCollectionsRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let conversation_id = conversation_id.into_inner();
+    info!("Received collections for conversation {} from user {}", conversation_id, user.0);
+
+    let collections = web::block(move || {
+        KnowledgeService::set_conversation_collections(&pool, user.0, conversation_id, &req.collection_ids)
+    })
+    .await
+    .map_err(|e| {
+        error!("Error setting conversation collections: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    Ok(HttpResponse::Ok().json(collections))
+}
@@ -2,15 +2,17 @@ use crate::db::DbPool;
 use crate::error::AppError;
 use crate::handlers::llm_chat::{llm_chat_handler, LLMChatRequest};
 use crate::handlers::stream_chat::{stream_chat, StreamChatRequest};
+use crate::services::llm_providers::ProviderRegistry;
 use crate::utils::extractors::AuthenticatedUser;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 
 pub async fn llm_chat(
     pool: web::Data<DbPool>,
+    http_req: HttpRequest,
     req: web::Json<LLMChatRequest>,
     user: AuthenticatedUser,
 ) -> Result<HttpResponse, AppError> {
-    llm_chat_handler(pool, req, user).await
+    llm_chat_handler(pool, http_req, req, user).await
 }
 
 pub async fn llm_stream_chat(
@@ -34,3 +36,8 @@ pub async fn get_llm_providers(pool: web::Data<DbPool>) -> Result<HttpResponse,
 
     Ok(HttpResponse::Ok().json(providers))
 }
+
+/// Lists the registered provider types and their capabilities
+pub async fn get_provider_types() -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(ProviderRegistry::global().list()))
+}
@@ -1,9 +1,15 @@
 use crate::db::DbPool;
 use crate::error::AppError;
+use crate::models::usage::UsageSource;
 use crate::services::chat_service::ChatService;
-use crate::services::llm_service::{LLMChatMessage, LLMService, LLMServiceError};
+use crate::services::function_calling::{Tool, ToolCall, ToolChoice};
+use crate::services::knowledge_service::KnowledgeService;
+use crate::services::llm_service::{LLMChatMessage, LLMService, LLMServiceError, LLMToolOptions};
+use crate::services::rate_limit_service::RateLimitService;
+use crate::services::response_cache::CachePolicy;
+use crate::services::usage_service::UsageService;
 use crate::utils::extractors::AuthenticatedUser;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -12,20 +18,36 @@ pub struct LLMChatRequest {
     pub user_llm_config_id: Uuid,
     pub conversation_id: Uuid,
     pub messages: Vec<LLMChatMessage>,
+    #[serde(default)]
+    pub tools: Vec<Tool>,
+    #[serde(default)]
+    pub tool_choice: ToolChoice,
+    /// Cache the response even if the provider isn't configured for caching
+    #[serde(default)]
+    pub cache: bool,
 }
 
 #[derive(Serialize)]
 pub struct LLMChatResponse {
     status: String,
     response: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<ToolCall>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cached: bool,
 }
 
 pub async fn llm_chat_handler(
     pool: web::Data<DbPool>,
+    http_req: HttpRequest,
     req: web::Json<LLMChatRequest>,
     user: AuthenticatedUser,
 ) -> Result<HttpResponse, AppError> {
     let req = req.into_inner();
+    let cache = CachePolicy::from_request(
+        &http_req,
+        if req.cache { CachePolicy::Force } else { CachePolicy::Auto },
+    );
 
     // Get the user LLM config
     let user_config = ChatService::get_user_llm_config_by_id(&pool, req.user_llm_config_id)?;
@@ -33,10 +55,43 @@ pub async fn llm_chat_handler(
     // Get the LLM provider
     let provider = ChatService::get_llm_provider(&pool, user_config.provider_id)?;
 
+    let permit = RateLimitService::acquire(&pool, user.0, Some(user_config.api_key_id), false)?;
+
+    let tools = if req.tools.is_empty() {
+        None
+    } else {
+        Some(LLMToolOptions {
+            tools: req.tools.clone(),
+            choice: req.tool_choice.clone(),
+        })
+    };
+
+    // Add excerpts from the conversation's document collections
+    let messages =
+        KnowledgeService::augment_messages(&pool, user.0, req.conversation_id, req.messages.clone())
+            .await;
+
     // Call the LLM service
-    let response = LLMService::chat(&pool, &provider, &user_config, req.messages.clone())
-        .await
-        .map_err(|e: LLMServiceError| AppError::ExternalServiceError(e.to_string()))?;
+    let output = LLMService::chat_with_cache(
+        &pool,
+        &provider,
+        &user_config,
+        messages,
+        tools.as_ref(),
+        cache,
+    )
+    .await
+    .map_err(|e: LLMServiceError| match e.0 {
+        // Problems with the request itself, such as an image sent to a text-only provider
+        error @ (AppError::BadRequest(_)
+        | AppError::UnsupportedProviderError(_)
+        | AppError::NotFoundError(_)) => error,
+        error => AppError::ExternalServiceError(error.to_string()),
+    })?;
+    let response = output.content;
+    let provider_model = output
+        .provider_model
+        .unwrap_or_else(|| provider.name.clone());
 
     // Save the LLM response to the database with attachment processing
     let message = ChatService::create_message_with_attachments(
@@ -44,14 +99,28 @@ pub async fn llm_chat_handler(
         req.conversation_id,
         "assistant".to_string(),
         response.clone(),
-        provider.name.clone(),
+        provider_model,
         Some(response.clone()), // raw_output
-        None,                   // usage_stats
+        output.usage_stats.as_ref().map(|stats| stats.to_value()),
     )
     .await?;
 
+    if let Some(stats) = &output.usage_stats {
+        permit.record_tokens(stats.tokens.total_tokens);
+        UsageService::record_quietly(
+            &pool,
+            user.0,
+            stats,
+            UsageSource::Chat,
+            Some(req.conversation_id),
+            Some(message.id),
+        );
+    }
+
     Ok(HttpResponse::Ok().json(LLMChatResponse {
         status: "success".to_string(),
         response: message.content,
+        tool_calls: output.tool_calls,
+        cached: output.cached,
     }))
 }
@@ -1,8 +1,9 @@
 use crate::db::DbPool;
 use crate::error::AppError;
 use crate::models::llm_provider::NewLLMProvider;
-use crate::models::user_llm_config::NewUserLLMConfig;
+use crate::models::user_llm_config::{LLMFallback, NewUserLLMConfig};
 use crate::services::llm_provider::LLMProviderService;
+use crate::services::llm_providers::ProviderRegistry;
 use actix_web::{web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -23,12 +24,16 @@ pub struct CreateUserLLMConfigRequest {
     pub provider_id: Uuid,
     pub api_key_id: Uuid,
     pub description: Option<String>,
+    #[serde(default)]
+    pub fallbacks: Option<Vec<LLMFallback>>,
 }
 
 pub async fn create_llm_provider(
     pool: web::Data<DbPool>,
     req: web::Json<CreateLLMProviderRequest>,
 ) -> Result<impl Responder, AppError> {
+    ProviderRegistry::global().get_type(&req.provider_type)?;
+
     let new_provider = NewLLMProvider {
         user_id: req.user_id,
         name: req.name.clone(),
@@ -69,6 +74,8 @@ pub async fn update_llm_provider(
     provider_id: web::Path<Uuid>,
     req: web::Json<CreateLLMProviderRequest>,
 ) -> Result<impl Responder, AppError> {
+    ProviderRegistry::global().get_type(&req.provider_type)?;
+
     let updated_provider = NewLLMProvider {
         user_id: req.user_id,
         name: req.name.clone(),
@@ -132,6 +139,7 @@ pub async fn create_user_llm_config(
         provider_id: req.provider_id,
         api_key_id: req.api_key_id,
         description: req.description.clone(),
+        fallbacks: req.fallbacks.as_ref().map(|fallbacks| serde_json::json!(fallbacks)),
     };
 
     let config = web::block(move || LLMProviderService::create_user_llm_config(&pool, new_config))
@@ -181,6 +189,7 @@ pub async fn update_user_llm_config(
         provider_id: req.provider_id,
         api_key_id: req.api_key_id,
         description: req.description.clone(),
+        fallbacks: req.fallbacks.as_ref().map(|fallbacks| serde_json::json!(fallbacks)),
     };
 
     let config = web::block(move || {
@@ -1,6 +1,8 @@
 use crate::db::DbPool;
 use crate::error::AppError;
-use crate::models::llm_template::{CreateUnifiedLLMConfigRequest, UnifiedLLMConfig};
+use crate::models::llm_template::{
+    CreateUnifiedLLMConfigRequest, UnifiedLLMConfig, UpdateFallbacksRequest,
+};
 use crate::services::llm_template_service::LLMTemplateService;
 use actix_web::{web, HttpResponse, Responder};
 use log::{debug, error, info};
@@ -43,6 +45,7 @@ pub async fn create_unified_config(
         configuration: req.configuration.clone(),
         api_key: req.api_key.clone(),
         api_key_description: req.api_key_description.clone(),
+        fallbacks: req.fallbacks.clone(),
         created_at: None,
         updated_at: None,
     };
@@ -100,3 +103,23 @@ pub async fn delete_unified_config(
         }
     }
 }
+
+/// Replace the fallback chain of a unified LLM configuration
+pub async fn update_unified_config_fallbacks(
+    pool: web::Data<DbPool>,
+    user_id: web::ReqData<Uuid>,
+    path: web::Path<Uuid>,
+    req: web::Json<UpdateFallbacksRequest>,
+) -> Result<impl Responder, AppError> {
+    let user_id = *user_id;
+    let config_id = path.into_inner();
+    info!("Updating fallbacks of unified LLM configuration: {} for user: {}", config_id, user_id);
+
+    match LLMTemplateService::update_fallbacks(&pool, user_id, config_id, req.into_inner().fallbacks) {
+        Ok(response) => Ok(HttpResponse::Ok().json(response)),
+        Err(e) => {
+            error!("Failed to update unified LLM configuration fallbacks: {:?}", e);
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::handlers::function_calling::{convert_to_api_tool, ApiTool};
+use crate::models::mcp_server::{CreateMcpServerRequest, McpServerResponse, UpdateMcpServerRequest};
+use crate::services::function_calling::mcp::{schema_to_parameters, tool::registered_name};
+use crate::services::function_calling::Tool;
+use crate::services::mcp_server_service::McpServerService;
+use crate::utils::extractors::AuthenticatedUser;
+use actix_web::{web, HttpResponse};
+use log::{debug, error, info};
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct ListServerToolsQuery {
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+pub async fn create_mcp_server(
+    pool: web::Data<DbPool>,
+    req: web::Json<CreateMcpServerRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    info!("Received create MCP server request from user {}", user.0);
+
+    let server = web::block(move || McpServerService::create_server(&pool, user.0, req.into_inner()))
+        .await
+        .map_err(|e| {
+            error!("Error creating MCP server: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Created().json(McpServerResponse::from(server)))
+}
+
+pub async fn list_mcp_servers(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    debug!("Received list MCP servers request from user {}", user.0);
+
+    let servers = web::block(move || McpServerService::list_servers(&pool, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error listing MCP servers: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    let servers: Vec<McpServerResponse> = servers.into_iter().map(McpServerResponse::from).collect();
+    Ok(HttpResponse::Ok().json(servers))
+}
+
+pub async fn get_mcp_server(
+    pool: web::Data<DbPool>,
+    server_id: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    debug!("Received get MCP server request for {} from user {}", server_id, user.0);
+
+    let server = web::block(move || McpServerService::get_server(&pool, server_id.into_inner(), user.0))
+        .await
+        .map_err(|e| {
+            error!("Error getting MCP server: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(McpServerResponse::from(server)))
+}
+
+pub async fn update_mcp_server(
+    pool: web::Data<DbPool>,
+    server_id: web::Path<Uuid>,
+    req: web::Json<UpdateMcpServerRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let server_id = server_id.into_inner();
+    info!("Received update MCP server request for {} from user {}", server_id, user.0);
+
+    let server = web::block(move || {
+        McpServerService::update_server(&pool, server_id, user.0, req.into_inner())
+    })
+    .await
+    .map_err(|e| {
+        error!("Error updating MCP server: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    // The next use reconnects with the new configuration
+    McpServerService::disconnect(server_id).await;
+
+    Ok(HttpResponse::Ok().json(McpServerResponse::from(server)))
+}
+
+pub async fn delete_mcp_server(
+    pool: web::Data<DbPool>,
+    server_id: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let server_id = server_id.into_inner();
+    info!("Received delete MCP server request for {} from user {}", server_id, user.0);
+
+    web::block(move || McpServerService::delete_server(&pool, server_id, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error deleting MCP server: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    McpServerService::disconnect(server_id).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn list_mcp_server_tools(
+    pool: web::Data<DbPool>,
+    server_id: web::Path<Uuid>,
+    query: web::Query<ListServerToolsQuery>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    debug!("Received list tools request for MCP server {} from user {}", server_id, user.0);
+
+    let server = web::block(move || McpServerService::get_server(&pool, server_id.into_inner(), user.0))
+        .await
+        .map_err(|e| {
+            error!("Error getting MCP server: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    let (_, definitions) = McpServerService::connect(&server, query.refresh).await?;
+
+    let tools: Vec<ApiTool> = definitions
+        .iter()
+        .map(|definition| {
+            convert_to_api_tool(&Tool::new(
+                &registered_name(&server.name, &definition.name),
+                definition.description.as_deref().unwrap_or_default(),
+                schema_to_parameters(&definition.input_schema),
+            ))
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(tools))
+}
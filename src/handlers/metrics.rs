@@ -12,17 +12,28 @@ lazy_static! {
         "scheduled_jobs_total",
         "Number of scheduled jobs"
     ).expect("create gauge");
+    pub static ref LLM_CACHE_COUNTER: CounterVec = CounterVec::new(
+        opts!("llm_response_cache_total", "LLM response cache lookups"),
+        &["result"]
+    ).expect("create counter");
 }
 
 pub fn init_metrics() {
     let _ = REGISTRY.register(Box::new(HTTP_COUNTER.clone()));
     let _ = REGISTRY.register(Box::new(SCHEDULED_JOBS_GAUGE.clone()));
+    let _ = REGISTRY.register(Box::new(LLM_CACHE_COUNTER.clone()));
 }
 
 pub fn set_scheduled_jobs(count: i64) {
     SCHEDULED_JOBS_GAUGE.set(count);
 }
 
+pub fn record_llm_cache_lookup(hit: bool) {
+    LLM_CACHE_COUNTER
+        .with_label_values(&[if hit { "hit" } else { "miss" }])
+        .inc();
+}
+
 pub async fn metrics() -> impl Responder {
     let encoder = TextEncoder::new();
     let metric_families = REGISTRY.gather();
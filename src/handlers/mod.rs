@@ -6,18 +6,25 @@ pub mod chat;
 pub mod configuration;
 pub mod conversation;
 pub mod docker_file;
+pub mod embeddings;
 pub mod fluentcli;
 pub mod function_calling;
 pub mod job;
+pub mod job_queue;
+pub mod knowledge;
 pub mod llm;
 pub mod llm_chat;
 pub mod llm_provider;
 pub mod llm_template;
+pub mod mcp_server;
+pub mod openai_gateway;
 pub mod message;
 pub mod pipeline;
+pub mod rate_limit;
 pub mod secure_vault;
 pub mod stream_chat;
 pub mod temp_image;
+pub mod usage;
 pub mod user;
 pub mod user_llm_config;
 pub mod worker;
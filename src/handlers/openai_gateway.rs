@@ -0,0 +1,189 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::handlers::stream_chat::create_response_stream;
+use crate::models::usage::UsageSource;
+use crate::services::llm_providers::ProviderRegistry;
+use crate::services::llm_service::{LLMService, LLMServiceError, LLMStreamChunk};
+use crate::services::openai_gateway::{
+    apply_request_options, completion_chunk, completion_response, finish_reason, models_response,
+    to_llm_messages, to_tool_options, ChatCompletionRequest, ChatCompletionToolCall,
+    OpenAIGatewayService,
+};
+use crate::services::rate_limit_service::RateLimitService;
+use crate::services::response_cache::CachePolicy;
+use crate::services::usage_service::{TokenUsage, UsageService, UsageStats};
+use crate::utils::extractors::AuthenticatedUser;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use futures::StreamExt;
+use log::{debug, error, info};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+pub async fn list_models(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let models = web::block(move || OpenAIGatewayService::list_models(&pool, user.0))
+        .await
+        .map_err(|e| {
+            error!("Error listing models: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(models_response(&models)))
+}
+
+pub async fn chat_completions(
+    pool: web::Data<DbPool>,
+    http_req: HttpRequest,
+    req: web::Json<ChatCompletionRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+    info!("Received chat completion for model {} from user {}", req.model, user.0);
+
+    let model = web::block({
+        let pool = pool.clone();
+        let model = req.model.clone();
+        move || OpenAIGatewayService::resolve_model(&pool, user.0, &model)
+    })
+    .await
+    .map_err(|e| {
+        error!("Error resolving model: {:?}", e);
+        AppError::InternalServerError
+    })??;
+
+    let mut provider = model.provider;
+    apply_request_options(&mut provider.configuration, &req);
+    let messages = to_llm_messages(&req.messages)?;
+    let tools = to_tool_options(&req.tools, req.tool_choice.as_ref())?;
+
+    // Providers with a tool adapter build both tool and plain requests through it, which
+    // also reports usage and honours `stream`; the others only support plain chat
+    let has_adapter = ProviderRegistry::global()
+        .get_type(&provider.provider_type)?
+        .capabilities
+        .tools;
+    let tools = match (tools, has_adapter) {
+        (Some(_), false) => {
+            return Err(AppError::BadRequest(format!(
+                "Model '{}' does not support tools",
+                req.model
+            )))
+        }
+        (tools, true) => Some(tools.unwrap_or_default()),
+        (None, false) => None,
+    };
+
+    let permit = RateLimitService::acquire(&pool, user.0, Some(model.user_config.api_key_id), req.stream)?;
+
+    let id = format!("chatcmpl-{}", Uuid::new_v4().simple());
+    let created = Utc::now().timestamp();
+
+    if !req.stream {
+        let cache = CachePolicy::from_request(&http_req, CachePolicy::Auto);
+        let output = LLMService::chat_with_cache(
+            &pool,
+            &provider,
+            &model.user_config,
+            messages,
+            tools.as_ref(),
+            cache,
+        )
+        .await
+        .map_err(|e| e.0)?;
+        if let Some(stats) = &output.usage_stats {
+            permit.record_tokens(stats.tokens.total_tokens);
+            UsageService::record_quietly(&pool, user.0, stats, UsageSource::Gateway, None, None);
+        }
+        let mut response = HttpResponse::Ok();
+        if output.cached {
+            response.insert_header(("X-Cache", "HIT"));
+        }
+        return Ok(response.json(completion_response(&id, &req.model, created, &output)));
+    }
+
+    let (tx, rx) = mpsc::channel(100);
+    let pool = Arc::new(pool.get_ref().clone());
+    let model_name = req.model.clone();
+    let user_id = user.0;
+
+    actix_web::rt::spawn(async move {
+        let stream = LLMService::llm_stream_chat(
+            Arc::clone(&pool),
+            Arc::new(provider),
+            Arc::new(model.user_config),
+            messages,
+            tools,
+        )
+        .await;
+
+        let chunk = |delta: Value, finish: Option<&str>| -> Result<web::Bytes, actix_web::Error> {
+            let chunk = completion_chunk(&id, &model_name, created, delta, finish);
+            Ok(web::Bytes::from(format!("data: {}\n\n", chunk)))
+        };
+
+        let error_frame = |e: LLMServiceError| -> Result<web::Bytes, actix_web::Error> {
+            error!("Gateway stream failed: {}", e);
+            let error = json!({ "error": { "message": e.to_string(), "type": "server_error" } });
+            Ok(web::Bytes::from(format!("data: {}\n\n", error)))
+        };
+
+        let (mut stream, answering_provider) = match stream {
+            Ok(stream) => (stream.chunks, stream.provider),
+            Err(e) => {
+                let _ = tx.send(error_frame(e)).await;
+                return;
+            }
+        };
+
+        if tx.send(chunk(json!({ "role": "assistant", "content": "" }), None)).await.is_err() {
+            return;
+        }
+
+        let mut tool_call_count = 0;
+        let mut usage: Option<TokenUsage> = None;
+        while let Some(item) = stream.next().await {
+            let frame = match item {
+                Ok(LLMStreamChunk::Usage(report)) => {
+                    if let Some(tokens) = TokenUsage::from_provider(&report) {
+                        usage = Some(usage.map_or(tokens, |usage| usage.merge(tokens)));
+                    }
+                    continue;
+                }
+                Ok(LLMStreamChunk::Text(content)) => chunk(json!({ "content": content }), None),
+                Ok(LLMStreamChunk::ToolCall(call)) => {
+                    let mut tool_call = json!(ChatCompletionToolCall::from(&call));
+                    tool_call["index"] = json!(tool_call_count);
+                    tool_call_count += 1;
+                    chunk(json!({ "tool_calls": [tool_call] }), None)
+                }
+                Err(e) => {
+                    let _ = tx.send(error_frame(e)).await;
+                    return;
+                }
+            };
+            if tx.send(frame).await.is_err() {
+                debug!("Client disconnected from gateway stream");
+                return;
+            }
+        }
+
+        if let Some(tokens) = usage {
+            let stats = UsageStats::new(&answering_provider, tokens);
+            permit.record_tokens(stats.tokens.total_tokens);
+            UsageService::record_quietly(&pool, user_id, &stats, UsageSource::Gateway, None, None);
+        }
+
+        let _ = tx.send(chunk(json!({}), Some(finish_reason(tool_call_count > 0)))).await;
+        let _ = tx.send(Ok(web::Bytes::from("data: [DONE]\n\n"))).await;
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(create_response_stream(rx)))
+}
@@ -0,0 +1,76 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::rate_limit::SetRateLimitRequest;
+use crate::services::rate_limit_service::RateLimitService;
+use crate::utils::extractors::AuthenticatedUser;
+use actix_web::{web, HttpResponse};
+use log::error;
+use uuid::Uuid;
+
+fn blocking_error(e: actix_web::error::BlockingError) -> AppError {
+    error!("Error handling rate limits: {:?}", e);
+    AppError::InternalServerError
+}
+
+pub async fn get_rate_limits(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let limits = web::block(move || RateLimitService::get_limits(&pool, user.0))
+        .await
+        .map_err(blocking_error)??;
+
+    Ok(HttpResponse::Ok().json(limits))
+}
+
+pub async fn set_user_rate_limits(
+    pool: web::Data<DbPool>,
+    req: web::Json<SetRateLimitRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let limit = web::block(move || RateLimitService::set_limits(&pool, user.0, None, req.into_inner()))
+        .await
+        .map_err(blocking_error)??;
+
+    Ok(HttpResponse::Ok().json(limit))
+}
+
+pub async fn delete_user_rate_limits(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    web::block(move || RateLimitService::delete_limits(&pool, user.0, None))
+        .await
+        .map_err(blocking_error)??;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn set_api_key_rate_limits(
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    req: web::Json<SetRateLimitRequest>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let api_key_id = path.into_inner();
+    let limit = web::block(move || {
+        RateLimitService::set_limits(&pool, user.0, Some(api_key_id), req.into_inner())
+    })
+    .await
+    .map_err(blocking_error)??;
+
+    Ok(HttpResponse::Ok().json(limit))
+}
+
+pub async fn delete_api_key_rate_limits(
+    pool: web::Data<DbPool>,
+    path: web::Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let api_key_id = path.into_inner();
+    web::block(move || RateLimitService::delete_limits(&pool, user.0, Some(api_key_id)))
+        .await
+        .map_err(blocking_error)??;
+
+    Ok(HttpResponse::NoContent().finish())
+}
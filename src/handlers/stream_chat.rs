@@ -1,14 +1,22 @@
 use crate::db::DbPool;
 use crate::error::AppError;
 use crate::models::llm_provider::LLMProvider;
+use crate::models::usage::UsageSource;
 use crate::models::user_llm_config::UserLLMConfig;
 use crate::services::chat_service::ChatService;
-use crate::services::llm_service::{LLMChatMessage, LLMService, LLMServiceError};
+use crate::services::function_calling::{Tool, ToolChoice};
+use crate::services::knowledge_service::KnowledgeService;
+use crate::services::llm_service::{
+    LLMChatMessage, LLMService, LLMServiceError, LLMStreamChunk, LLMToolOptions,
+};
+use crate::services::rate_limit_service::RateLimitService;
+use crate::services::usage_service::{TokenUsage, UsageService, UsageStats};
 use crate::utils::extractors::AuthenticatedUser;
 use actix_web::{web, HttpResponse};
 use futures::{Stream, StreamExt};
+use log::{debug, error};
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
@@ -20,6 +28,10 @@ pub struct StreamChatRequest {
     pub provider_id: Uuid,
     pub conversation_id: Uuid,
     pub messages: Vec<LLMChatMessage>,
+    #[serde(default)]
+    pub tools: Vec<Tool>,
+    #[serde(default)]
+    pub tool_choice: ToolChoice,
 }
 
 pub async fn stream_chat(
@@ -34,43 +46,60 @@ pub async fn stream_chat(
         req.provider_id,
     )?);
 
+    // The permit holds one of the user's stream slots until the task below finishes
+    let permit = RateLimitService::acquire(&pool, user.0, Some(user_config.api_key_id), true)?;
+
+    // With tools the stream is framed as SSE events so tool calls can be told apart from text
+    let tools = if req.tools.is_empty() {
+        None
+    } else {
+        Some(LLMToolOptions {
+            tools: req.tools.clone(),
+            choice: req.tool_choice.clone(),
+        })
+    };
+
+    // Add excerpts from the conversation's document collections
+    let messages =
+        KnowledgeService::augment_messages(&pool, user.0, req.conversation_id, req.messages.clone())
+            .await;
+
     let (tx, rx) = mpsc::channel(100);
     let full_response = Arc::new(Mutex::new(String::new()));
     let full_response_clone = Arc::clone(&full_response);
 
     let pool_arc = Arc::new(pool.get_ref().clone());
+    let conversation_id = req.conversation_id;
+    let user_id = user.0;
 
     actix_web::rt::spawn({
         let pool_arc = Arc::clone(&pool_arc);
         let provider = Arc::clone(&provider);
         let user_config = Arc::clone(&user_config);
-        let messages = req.messages.clone();
         async move {
-            if let Err(e) = handle_llm_stream(
-                pool_arc,
+            let (provider_model, usage_stats) = match handle_llm_stream(
+                Arc::clone(&pool_arc),
                 provider,
                 user_config,
                 messages,
+                tools,
                 tx,
                 full_response_clone,
             )
             .await
             {
-                eprintln!("Error in handle_llm_stream: {:?}", e);
-            }
-        }
-    });
-
-    let response_stream = create_response_stream(rx);
+                Ok(answer) => answer,
+                Err(e) => {
+                    error!("Error in handle_llm_stream: {:?}", e);
+                    return;
+                }
+            };
 
-    // Use a separate task to save the full response to the database
-    let conversation_id = req.conversation_id;
-    let provider_model = provider.name.clone();
+            if let Some(stats) = &usage_stats {
+                permit.record_tokens(stats.tokens.total_tokens);
+            }
 
-    actix_web::rt::spawn({
-        let pool_arc = Arc::clone(&pool_arc);
-        let full_response = Arc::clone(&full_response);
-        async move {
+            // Save the full response once streaming is done, under the provider that answered
             let full_response = full_response.lock().await.clone();
             if !full_response.trim().is_empty() {
                 match ChatService::create_message(
@@ -81,15 +110,29 @@ pub async fn stream_chat(
                     provider_model,
                     None,                        // attachment_id
                     Some(full_response.clone()), // raw_output
-                    None,                        // usage_stats
+                    usage_stats.as_ref().map(|stats| stats.to_value()),
                 ) {
-                    Ok(_) => println!("Message saved to database successfully"),
-                    Err(e) => eprintln!("Error saving message to database: {:?}", e),
+                    Ok(message) => {
+                        debug!("Message saved to database successfully");
+                        if let Some(stats) = &usage_stats {
+                            UsageService::record_quietly(
+                                &pool_arc,
+                                user_id,
+                                stats,
+                                UsageSource::Chat,
+                                Some(conversation_id),
+                                Some(message.id),
+                            );
+                        }
+                    }
+                    Err(e) => error!("Error saving message to database: {:?}", e),
                 }
             }
         }
     });
 
+    let response_stream = create_response_stream(rx);
+
     Ok(HttpResponse::Ok()
         .content_type("text/event-stream")
         .streaming(response_stream))
@@ -100,25 +143,61 @@ async fn handle_llm_stream(
     provider: Arc<LLMProvider>,
     user_config: Arc<UserLLMConfig>,
     messages: Vec<LLMChatMessage>,
+    tools: Option<LLMToolOptions>,
     tx: mpsc::Sender<Result<web::Bytes, actix_web::Error>>,
     full_response: Arc<Mutex<String>>,
-) -> Result<(), AppError> {
-    let stream = LLMService::llm_stream_chat(pool, provider, user_config, messages).await;
-    process_stream(stream, tx, full_response).await;
-    Ok(())
+) -> Result<(String, Option<UsageStats>), AppError> {
+    let framed = tools.is_some();
+    let stream =
+        match LLMService::llm_stream_chat(pool, provider, user_config, messages, tools).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                let _ = tx
+                    .send(Err(actix_web::error::ErrorInternalServerError(
+                        e.to_string(),
+                    )))
+                    .await;
+                return Err(e.into());
+            }
+        };
+    let usage = process_stream(stream.chunks, tx, full_response, framed).await;
+    let usage_stats = usage.map(|tokens| UsageStats::new(&stream.provider, tokens));
+    Ok((stream.provider_model, usage_stats))
 }
 
 async fn process_stream(
-    mut stream: Pin<Box<dyn Stream<Item = Result<String, LLMServiceError>> + Send>>,
+    mut stream: Pin<Box<dyn Stream<Item = Result<LLMStreamChunk, LLMServiceError>> + Send>>,
     tx: mpsc::Sender<Result<web::Bytes, actix_web::Error>>,
     full_response: Arc<Mutex<String>>,
-) {
+    framed: bool,
+) -> Option<TokenUsage> {
+    let mut usage: Option<TokenUsage> = None;
     while let Some(result) = stream.next().await {
         match result {
-            Ok(chunk) => {
+            Ok(LLMStreamChunk::Usage(report)) => {
+                if let Some(tokens) = TokenUsage::from_provider(&report) {
+                    usage = Some(usage.map_or(tokens, |usage| usage.merge(tokens)));
+                }
+            }
+            Ok(LLMStreamChunk::ToolCall(call)) => {
+                if !framed {
+                    log::warn!("Ignoring tool call '{}' in plain chat stream", call.name);
+                    continue;
+                }
+                let frame = sse_frame("tool_call", &json!(call));
+                if tx.send(Ok(web::Bytes::from(frame))).await.is_err() {
+                    break;
+                }
+            }
+            Ok(LLMStreamChunk::Text(chunk)) => {
                 let mut full = full_response.lock().await;
                 full.push_str(&chunk);
-                if tx.send(Ok(web::Bytes::from(chunk))).await.is_err() {
+                let bytes = if framed {
+                    sse_frame("token", &json!({ "content": chunk }))
+                } else {
+                    chunk
+                };
+                if tx.send(Ok(web::Bytes::from(bytes))).await.is_err() {
                     break;
                 }
             }
@@ -132,9 +211,14 @@ async fn process_stream(
             }
         }
     }
+    usage
+}
+
+fn sse_frame(event: &str, data: &Value) -> String {
+    format!("event: {}\ndata: {}\n\n", event, data)
 }
 
-fn create_response_stream(
+pub(crate) fn create_response_stream(
     mut rx: mpsc::Receiver<Result<web::Bytes, actix_web::Error>>,
 ) -> impl Stream<Item = Result<web::Bytes, actix_web::Error>> {
     futures::stream::poll_fn(move |cx| rx.poll_recv(cx))
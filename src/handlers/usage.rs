@@ -0,0 +1,30 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::usage::UsageReportQuery;
+use crate::services::usage_service::UsageService;
+use crate::utils::extractors::AuthenticatedUser;
+use actix_web::{web, HttpResponse};
+use log::error;
+
+/// `GET /usage?group_by=day|provider|model|conversation&from=YYYY-MM-DD&to=YYYY-MM-DD`
+pub async fn get_usage_report(
+    pool: web::Data<DbPool>,
+    query: web::Query<UsageReportQuery>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(AppError::BadRequest("'from' must not be after 'to'".to_string()));
+        }
+    }
+
+    let report = web::block(move || UsageService::report(&pool, user.0, &query))
+        .await
+        .map_err(|e| {
+            error!("Error building usage report: {:?}", e);
+            AppError::InternalServerError
+        })??;
+
+    Ok(HttpResponse::Ok().json(report))
+}
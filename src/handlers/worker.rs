@@ -2,7 +2,7 @@ use actix_web::{web, HttpResponse, Responder, HttpRequest, HttpMessage};
 use uuid::Uuid;
 use crate::db::DbPool;
 use crate::services::worker_service::WorkerService;
-use crate::models::worker::{NewWorker, UpdateWorker, NewWorkerPayload};
+use crate::models::worker::{NewWorker, UpdateWorker, NewWorkerPayload, WorkerWithToken};
 
 
 pub async fn create_worker(
@@ -14,18 +14,27 @@ pub async fn create_worker(
     log::info!("Creating worker for user_id: {}", user_id);
     log::info!("Received data: {:?}", new_worker_payload);
 
+    // The token is only returned here; the worker container authenticates with it
+    let (token, token_hash) = match WorkerService::generate_token() {
+        Ok(token) => token,
+        Err(e) => {
+            log::error!("Error generating worker token: {:?}", e);
+            return HttpResponse::InternalServerError().body("Failed to create worker");
+        }
+    };
     let new_worker = NewWorker {
         user_id,
         name: new_worker_payload.name.clone(),
         worker_type: new_worker_payload.worker_type,
         active: false,
+        token_hash,
     };
     log::info!("New worker data: {:?}", new_worker);
 
     match WorkerService::create_worker(&pool, new_worker) {
         Ok(worker) => {
             log::info!("Worker created successfully: {:?}", worker);
-            HttpResponse::Created().json(worker)
+            HttpResponse::Created().json(WorkerWithToken { worker, token })
         },
         Err(e) => {
             log::error!("Error creating worker: {:?}", e);
@@ -119,4 +128,19 @@ pub async fn deactivate_worker(
             HttpResponse::InternalServerError().body("Failed to deactivate worker")
         }
     }
-}
\ No newline at end of file
+}
+/// Issues a new token for the worker. The old one stops working right away.
+pub async fn rotate_worker_token(
+    pool: web::Data<DbPool>,
+    worker_id: web::Path<Uuid>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = req.extensions().get::<Uuid>().cloned().unwrap();
+    match WorkerService::rotate_token(&pool, worker_id.into_inner(), user_id) {
+        Ok((worker, token)) => HttpResponse::Ok().json(WorkerWithToken { worker, token }),
+        Err(e) => {
+            log::error!("Error rotating worker token: {:?}", e);
+            HttpResponse::InternalServerError().body("Failed to rotate worker token")
+        }
+    }
+}
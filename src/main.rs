@@ -8,6 +8,7 @@ mod schema;
 mod services;
 mod utils;
 use handlers::metrics;
+use services::job_queue_service::JobQueueService;
 use services::job_scheduler::JobScheduler;
 use crate::config::Config;
 use dotenv::dotenv;
@@ -51,6 +52,7 @@ async fn main() -> std::io::Result<()> {
     println!("Database setup complete");
 
     JobScheduler::start(pool.clone());
+    JobQueueService::start_reaper(pool.clone());
 
     HttpServer::new(move || {
         let allowed_origins = std::env::var("ALLOWED_ORIGINS").unwrap_or_else(|_| "*".into());
@@ -1,4 +1,5 @@
 use crate::schema::agents;
+use crate::services::function_calling::ApprovalMode;
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -88,6 +89,16 @@ where
     Ok(tools_vec.map(|v| json!(v)))
 }
 
+#[derive(Deserialize, Debug)]
+pub struct RunAgentRequest {
+    pub user_llm_config_id: Uuid,
+    pub input: String,
+    pub max_steps: Option<usize>,
+    /// Which tool calls pause the run until a person approves them
+    #[serde(default)]
+    pub approval_mode: ApprovalMode,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AgentResponse {
     pub id: Uuid,
@@ -0,0 +1,121 @@
+use crate::schema::{agent_run_steps, agent_runs, tool_approvals};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = agent_runs)]
+pub struct AgentRun {
+    pub id: Uuid,
+    pub agent_id: Uuid,
+    pub user_id: Uuid,
+    pub input: String,
+    pub status: String, // running, awaiting_approval, completed, max_steps_exceeded, failed
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub usage: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub state: Option<Value>, // PausedRunState while awaiting approval
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = agent_runs)]
+pub struct NewAgentRun {
+    pub id: Uuid,
+    pub agent_id: Uuid,
+    pub user_id: Uuid,
+    pub input: String,
+    pub status: String,
+}
+
+#[derive(Queryable, Identifiable, Associations, Debug, Serialize, Deserialize, Clone)]
+#[diesel(belongs_to(AgentRun, foreign_key = run_id))]
+#[diesel(table_name = agent_run_steps)]
+pub struct AgentRunStepRecord {
+    pub id: Uuid,
+    pub run_id: Uuid,
+    pub step: i32,
+    pub kind: String, // llm_call or tool_call
+    pub tool_name: Option<String>,
+    pub tool_call_id: Option<String>,
+    pub arguments: Option<Value>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub usage: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = agent_run_steps)]
+pub struct NewAgentRunStep {
+    pub id: Uuid,
+    pub run_id: Uuid,
+    pub step: i32,
+    pub kind: String,
+    pub tool_name: Option<String>,
+    pub tool_call_id: Option<String>,
+    pub arguments: Option<Value>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub usage: Option<Value>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AgentRunDetail {
+    #[serde(flatten)]
+    pub run: AgentRun,
+    pub steps: Vec<AgentRunStepRecord>,
+    pub approvals: Vec<ToolApproval>,
+}
+
+#[derive(Queryable, Identifiable, Associations, Debug, Serialize, Deserialize, Clone)]
+#[diesel(belongs_to(AgentRun, foreign_key = run_id))]
+#[diesel(table_name = tool_approvals)]
+pub struct ToolApproval {
+    pub id: Uuid,
+    pub run_id: Uuid,
+    pub user_id: Uuid,
+    pub step: i32,
+    pub tool_call_id: String,
+    pub tool_name: String,
+    pub arguments: Value,
+    pub status: String, // pending, approved or rejected
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = tool_approvals)]
+pub struct NewToolApproval {
+    pub id: Uuid,
+    pub run_id: Uuid,
+    pub user_id: Uuid,
+    pub step: i32,
+    pub tool_call_id: String,
+    pub tool_name: String,
+    pub arguments: Value,
+    pub status: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approve,
+    Reject,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DecideToolApprovalRequest {
+    pub decision: ApprovalDecision,
+    /// Replaces the arguments the model proposed when approving
+    pub arguments: Option<Value>,
+    /// Passed back to the model when rejecting
+    pub reason: Option<String>,
+}
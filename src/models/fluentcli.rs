@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommandRequest {
     pub command: String,
     pub args: Vec<String>,
+    /// Lets the worker stop the command through `/stop`, and names the run a queued command
+    /// belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,4 +16,31 @@ pub struct CommandResult {
     pub output: String,
     pub error: Option<String>,
     pub exit_code: i32,
+    /// Whether the command ended because it was stopped
+    #[serde(default)]
+    pub stopped: bool,
+}
+
+/// One line of the worker's response to `/execute`: output lines as the command produces them,
+/// then its exit status
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerEvent {
+    Log {
+        stream: String,
+        line: String,
+    },
+    Exit {
+        exit_code: i32,
+        error: Option<String>,
+        #[serde(default)]
+        stopped: bool,
+    },
+}
+
+/// A line a command wrote to stdout or stderr
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutputLine {
+    pub stream: String,
+    pub line: String,
 }
@@ -73,3 +73,50 @@ pub struct NewJobPayload {
     pub pipeline_id: Uuid,
     pub results: Option<Value>,
 }
+
+/// What the scheduler does about runs it missed, e.g. while the backend was down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MisfirePolicy {
+    /// Drop the missed runs and wait for the next one
+    Skip,
+    /// Run once for all of the missed runs
+    #[default]
+    RunOnce,
+    /// Run once for every missed run, oldest first
+    CatchUp,
+}
+
+/// The schedule stored in `jobs.timers`. Exactly one of `cron`, `interval_seconds` and `run_at`
+/// says when the job runs; `next_run_at` and `last_run_at` are kept by the scheduler.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobTimers {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    /// IANA timezone the cron expression is read in, UTC by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_seconds: Option<i64>,
+    /// A single run, as set by `JobService::schedule_job`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub misfire_policy: Option<MisfirePolicy>,
+    /// How late a run may start before it counts as missed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub misfire_grace_seconds: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Anything else the client stored alongside the schedule
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
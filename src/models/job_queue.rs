@@ -0,0 +1,103 @@
+use crate::models::fluentcli::{CommandResult, OutputLine};
+use crate::schema::job_queue;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// How many times a run is handed to a worker before it's dead-lettered
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 3;
+pub const DEFAULT_LEASE_SECONDS: i64 = 60;
+pub const MIN_LEASE_SECONDS: i64 = 15;
+pub const MAX_LEASE_SECONDS: i64 = 600;
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = job_queue)]
+pub struct QueueEntry {
+    pub run_id: Uuid,
+    pub job_id: Uuid,
+    pub user_id: Uuid,
+    pub worker_type: Uuid,
+    /// The `CommandRequest` the worker runs
+    pub command: Value,
+    pub pipeline_name: String,
+    /// The pipeline written to the shared path for the worker
+    pub pipeline_file: String,
+    pub status: String, // queued, leased, done, dead
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// Queued entries aren't handed out before this, which spaces out retries
+    pub available_at: DateTime<Utc>,
+    pub leased_by: Option<Uuid>,
+    #[serde(skip_serializing)]
+    pub lease_token: Option<Uuid>,
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    pub stop_requested: bool,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = job_queue)]
+pub struct NewQueueEntry {
+    pub run_id: Uuid,
+    pub job_id: Uuid,
+    pub user_id: Uuid,
+    pub worker_type: Uuid,
+    pub command: Value,
+    pub pipeline_name: String,
+    pub pipeline_file: String,
+    pub max_attempts: i32,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct LeaseRequest {
+    /// How long the worker holds a run without renewing the lease. Defaults to 60 seconds
+    pub lease_seconds: Option<i64>,
+}
+
+/// A run handed to a worker
+#[derive(Serialize, Debug)]
+pub struct JobLease {
+    pub run_id: Uuid,
+    pub job_id: Uuid,
+    /// Proves the worker still holds the run when it renews the lease or reports on it
+    pub lease_token: Uuid,
+    pub lease_expires_at: DateTime<Utc>,
+    pub attempt: i32,
+    pub command: Value,
+    /// The file in `FLUENT_STATE_STORE` the pipeline leaves its state in, which the worker sends
+    /// back with the result
+    pub state_file: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LeaseRenewal {
+    pub lease_token: Uuid,
+    pub lease_seconds: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeaseStatus {
+    pub lease_expires_at: DateTime<Utc>,
+    /// The run should be stopped, and its result reported with `stopped` set
+    pub stop_requested: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LeaseLogs {
+    pub lease_token: Uuid,
+    pub lines: Vec<OutputLine>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LeaseResult {
+    pub lease_token: Uuid,
+    /// The pipeline's state file, if the worker found one
+    #[serde(default)]
+    pub state_file_content: Option<Value>,
+    #[serde(flatten)]
+    pub result: CommandResult,
+}
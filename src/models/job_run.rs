@@ -0,0 +1,135 @@
+use crate::schema::{job_run_logs, job_runs};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+pub const DEFAULT_RUNS_PAGE: i64 = 50;
+pub const MAX_RUNS_PAGE: i64 = 200;
+pub const DEFAULT_LOGS_PAGE: i64 = 500;
+pub const MAX_LOGS_PAGE: i64 = 5000;
+
+/// Statuses of runs, and of jobs, that haven't ended yet
+pub const ACTIVE_RUN_STATUSES: &[&str] = &["queued", "running", "stopping"];
+
+pub fn is_active_status(status: &str) -> bool {
+    ACTIVE_RUN_STATUSES.contains(&status)
+}
+
+/// What started a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunTrigger {
+    /// `POST /jobs/{id}/start`
+    Manual,
+    /// The job's timers
+    Schedule,
+}
+
+impl RunTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunTrigger::Manual => "manual",
+            RunTrigger::Schedule => "schedule",
+        }
+    }
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = job_runs)]
+pub struct JobRun {
+    pub id: Uuid,
+    pub job_id: Uuid,
+    pub user_id: Uuid,
+    pub trigger_source: String,
+    pub status: String, // queued, running, stopping, completed, failed, stopped
+    pub exit_code: Option<i32>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    /// Why the run couldn't finish, e.g. every worker it was handed to went away
+    pub error: Option<String>,
+    pub state_file_content: Option<Value>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// A run without its output, for listing a job's history
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = job_runs)]
+pub struct JobRunSummary {
+    pub id: Uuid,
+    pub job_id: Uuid,
+    pub trigger_source: String,
+    pub status: String,
+    pub exit_code: Option<i32>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = job_runs)]
+pub struct NewJobRun {
+    pub id: Uuid,
+    pub job_id: Uuid,
+    pub user_id: Uuid,
+    pub trigger_source: String,
+    pub status: String,
+}
+
+/// The outcome of a run, written when it finishes
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = job_runs)]
+pub struct FinishedJobRun {
+    pub exit_code: Option<i32>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub error: Option<String>,
+    pub state_file_content: Option<Value>,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Runs are listed newest first
+#[derive(Deserialize, Debug, Default)]
+pub struct JobRunsQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = job_run_logs)]
+pub struct JobRunLog {
+    pub id: i64,
+    pub run_id: Uuid,
+    pub stream: String, // stdout or stderr
+    pub line: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = job_run_logs)]
+pub struct NewJobRunLog {
+    pub run_id: Uuid,
+    pub job_id: Uuid,
+    pub stream: String,
+    pub line: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct JobLogsQuery {
+    /// Defaults to the job's latest run
+    pub run_id: Option<Uuid>,
+    /// Only lines with a greater id
+    pub after: Option<i64>,
+    pub limit: Option<i64>,
+    /// Stream lines as server-sent events until the run ends
+    pub follow: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JobLogsPage {
+    pub run_id: Uuid,
+    pub status: String,
+    pub lines: Vec<JobRunLog>,
+    /// Pass as `after` to read the lines that follow
+    pub next_after: i64,
+}
@@ -0,0 +1,167 @@
+use crate::schema::{
+    conversation_collections, knowledge_chunks, knowledge_collections, knowledge_documents,
+};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Float4, Int4, Text, Uuid as SqlUuid};
+use pgvector::Vector;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const DEFAULT_CHUNK_SIZE: i32 = 1000;
+pub const DEFAULT_CHUNK_OVERLAP: i32 = 200;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = knowledge_collections)]
+pub struct KnowledgeCollection {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_llm_config_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Set by the first ingested document; later documents and queries must use the same model
+    pub embedding_model: Option<String>,
+    pub dimensions: Option<i32>,
+    pub chunk_size: i32,
+    pub chunk_overlap: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = knowledge_collections)]
+pub struct NewKnowledgeCollection {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_llm_config_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub chunk_size: i32,
+    pub chunk_overlap: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateCollectionRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// LLM config whose provider embeds the collection's documents
+    pub user_llm_config_id: Uuid,
+    pub chunk_size: Option<i32>,
+    pub chunk_overlap: Option<i32>,
+}
+
+impl NewKnowledgeCollection {
+    pub fn from_request(user_id: Uuid, req: CreateCollectionRequest) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            user_llm_config_id: req.user_llm_config_id,
+            name: req.name,
+            description: req.description,
+            chunk_size: req.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE),
+            chunk_overlap: req.chunk_overlap.unwrap_or(DEFAULT_CHUNK_OVERLAP),
+        }
+    }
+}
+
+/// Chunking changes only apply to documents ingested afterwards
+#[derive(Deserialize, Debug)]
+pub struct UpdateCollectionRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub chunk_size: Option<i32>,
+    pub chunk_overlap: Option<i32>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = knowledge_documents)]
+pub struct KnowledgeDocument {
+    pub id: Uuid,
+    pub collection_id: Uuid,
+    pub name: String,
+    pub content_type: String,
+    pub chunk_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = knowledge_documents)]
+pub struct NewKnowledgeDocument {
+    pub id: Uuid,
+    pub collection_id: Uuid,
+    pub name: String,
+    pub content_type: String,
+    pub chunk_count: i32,
+}
+
+/// A document sent either as `text` or as base64 `data`, e.g. for PDFs
+#[derive(Deserialize, Debug)]
+pub struct IngestDocumentRequest {
+    pub name: String,
+    /// Guessed from `name` when missing
+    pub content_type: Option<String>,
+    pub text: Option<String>,
+    pub data: Option<String>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, Clone)]
+#[diesel(table_name = knowledge_chunks)]
+pub struct KnowledgeChunk {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    pub collection_id: Uuid,
+    pub chunk_index: i32,
+    pub content: String,
+    pub embedding: Vector,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = knowledge_chunks)]
+pub struct NewKnowledgeChunk {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    pub collection_id: Uuid,
+    pub chunk_index: i32,
+    pub content: String,
+    pub embedding: Vector,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = conversation_collections)]
+pub struct NewConversationCollection {
+    pub conversation_id: Uuid,
+    pub collection_id: Uuid,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SetConversationCollectionsRequest {
+    pub collection_ids: Vec<Uuid>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchRequest {
+    pub query: String,
+    /// Defaults to all of the user's collections
+    #[serde(default)]
+    pub collection_ids: Vec<Uuid>,
+    pub limit: Option<usize>,
+}
+
+/// A chunk returned by retrieval, ranked by its fused keyword and vector score
+#[derive(QueryableByName, Serialize, Debug, Clone)]
+pub struct RetrievedChunk {
+    #[diesel(sql_type = SqlUuid)]
+    pub chunk_id: Uuid,
+    #[diesel(sql_type = SqlUuid)]
+    pub document_id: Uuid,
+    #[diesel(sql_type = Text)]
+    pub document_name: String,
+    #[diesel(sql_type = SqlUuid)]
+    pub collection_id: Uuid,
+    #[diesel(sql_type = Int4)]
+    pub chunk_index: i32,
+    #[diesel(sql_type = Text)]
+    pub content: String,
+    #[diesel(sql_type = Float4)]
+    pub score: f32,
+}
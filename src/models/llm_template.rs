@@ -1,3 +1,4 @@
+use crate::models::user_llm_config::LLMFallback;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
@@ -29,6 +30,7 @@ pub struct UnifiedLLMConfig {
     pub configuration: Value,
     pub api_key: String,
     pub api_key_description: String,
+    pub fallbacks: Vec<LLMFallback>,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
@@ -43,6 +45,8 @@ pub struct CreateUnifiedLLMConfigRequest {
     pub configuration: Value,
     pub api_key: String,
     pub api_key_description: String,
+    #[serde(default)]
+    pub fallbacks: Vec<LLMFallback>,
 }
 
 /// Response for a unified LLM configuration
@@ -56,6 +60,13 @@ pub struct UnifiedLLMConfigResponse {
     pub configuration: Value,
     pub api_key_id: Uuid,
     pub api_key_description: String,
+    pub fallbacks: Vec<LLMFallback>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
+
+/// Request to replace the fallback chain of a unified LLM configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateFallbacksRequest {
+    pub fallbacks: Vec<LLMFallback>,
+}
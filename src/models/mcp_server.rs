@@ -0,0 +1,136 @@
+use crate::schema::mcp_servers;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = mcp_servers)]
+pub struct McpServer {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub transport: String, // stdio or http
+    pub command: Option<String>,
+    pub args: Value, // JSON array of command arguments
+    pub env: Value,  // JSON object of environment variables
+    pub url: Option<String>,
+    pub headers: Value, // JSON object of HTTP headers
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl McpServer {
+    pub fn arg_list(&self) -> Vec<String> {
+        serde_json::from_value(self.args.clone()).unwrap_or_default()
+    }
+
+    pub fn env_map(&self) -> HashMap<String, String> {
+        serde_json::from_value(self.env.clone()).unwrap_or_default()
+    }
+
+    pub fn header_map(&self) -> HashMap<String, String> {
+        serde_json::from_value(self.headers.clone()).unwrap_or_default()
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = mcp_servers)]
+pub struct NewMcpServer {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub transport: String,
+    pub command: Option<String>,
+    pub args: Value,
+    pub env: Value,
+    pub url: Option<String>,
+    pub headers: Value,
+    pub enabled: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateMcpServerRequest {
+    pub name: String,
+    pub transport: String,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub enabled: Option<bool>,
+}
+
+impl NewMcpServer {
+    pub fn from_request(user_id: Uuid, req: CreateMcpServerRequest) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            name: req.name,
+            transport: req.transport,
+            command: req.command,
+            args: json!(req.args),
+            env: json!(req.env),
+            url: req.url,
+            headers: json!(req.headers),
+            enabled: req.enabled.unwrap_or(true),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateMcpServerRequest {
+    pub name: Option<String>,
+    pub transport: Option<String>,
+    pub command: Option<String>,
+    pub args: Option<Vec<String>>,
+    pub env: Option<HashMap<String, String>>,
+    pub url: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub enabled: Option<bool>,
+}
+
+/// Server configuration as returned by the API, with env and header values redacted
+#[derive(Serialize, Debug)]
+pub struct McpServerResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub transport: String,
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub env: Vec<String>,
+    pub url: Option<String>,
+    pub headers: Vec<String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<McpServer> for McpServerResponse {
+    fn from(server: McpServer) -> Self {
+        let mut env: Vec<String> = server.env_map().into_keys().collect();
+        let mut headers: Vec<String> = server.header_map().into_keys().collect();
+        env.sort();
+        headers.sort();
+
+        Self {
+            args: server.arg_list(),
+            env,
+            headers,
+            id: server.id,
+            name: server.name,
+            transport: server.transport,
+            command: server.command,
+            url: server.url,
+            enabled: server.enabled,
+            created_at: server.created_at,
+            updated_at: server.updated_at,
+        }
+    }
+}
@@ -1,14 +1,23 @@
 pub mod active_worker;
 pub mod agent;
+pub mod agent_run;
 pub mod amber_store;
 pub mod api_key;
 pub mod configuration;
 pub mod docker_file;
 pub mod fluentcli;
 pub mod job;
+pub mod job_queue;
+pub mod job_run;
+pub mod knowledge;
+pub mod mcp_server;
 pub mod pipeline;
+pub mod rate_limit;
 pub mod secure_vault;
+pub mod tool_permission;
+pub mod usage;
 pub mod user;
+pub mod user_tool;
 pub mod worker;
 
 // New chat-related models
@@ -0,0 +1,90 @@
+use crate::schema::rate_limits;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = rate_limits)]
+pub struct RateLimit {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub api_key_id: Option<Uuid>,
+    pub requests_per_minute: Option<i32>,
+    pub concurrent_streams: Option<i32>,
+    pub tokens_per_minute: Option<i64>,
+    pub tokens_per_day: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = rate_limits)]
+pub struct NewRateLimit {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub api_key_id: Option<Uuid>,
+    pub requests_per_minute: Option<i32>,
+    pub concurrent_streams: Option<i32>,
+    pub tokens_per_minute: Option<i64>,
+    pub tokens_per_day: Option<i64>,
+}
+
+/// Body of `PUT /rate-limits` and `PUT /rate-limits/api-keys/{id}`; unset fields use the defaults
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct SetRateLimitRequest {
+    pub requests_per_minute: Option<i32>,
+    pub concurrent_streams: Option<i32>,
+    pub tokens_per_minute: Option<i64>,
+    pub tokens_per_day: Option<i64>,
+}
+
+/// Limits in effect for one subject, where `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Limits {
+    pub requests_per_minute: Option<u64>,
+    pub concurrent_streams: Option<u64>,
+    pub tokens_per_minute: Option<u64>,
+    pub tokens_per_day: Option<u64>,
+}
+
+impl Limits {
+    /// The stricter of the two limits for each field
+    pub fn tighten(self, other: Limits) -> Limits {
+        fn min(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+        Limits {
+            requests_per_minute: min(self.requests_per_minute, other.requests_per_minute),
+            concurrent_streams: min(self.concurrent_streams, other.concurrent_streams),
+            tokens_per_minute: min(self.tokens_per_minute, other.tokens_per_minute),
+            tokens_per_day: min(self.tokens_per_day, other.tokens_per_day),
+        }
+    }
+}
+
+impl From<&RateLimit> for Limits {
+    fn from(limit: &RateLimit) -> Self {
+        let limit_of = |value: Option<i64>| value.map(|value| value.max(0) as u64);
+        Limits {
+            requests_per_minute: limit_of(limit.requests_per_minute.map(i64::from)),
+            concurrent_streams: limit_of(limit.concurrent_streams.map(i64::from)),
+            tokens_per_minute: limit_of(limit.tokens_per_minute),
+            tokens_per_day: limit_of(limit.tokens_per_day),
+        }
+    }
+}
+
+/// A user's own limits and those of their API keys
+#[derive(Serialize, Debug)]
+pub struct RateLimitsResponse {
+    /// Server defaults, which stored limits can only tighten
+    pub defaults: Limits,
+    /// Limits applied to the user's calls
+    pub effective: Limits,
+    pub user: Option<RateLimit>,
+    pub api_keys: Vec<RateLimit>,
+}
@@ -0,0 +1,30 @@
+use crate::schema::tool_permissions;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = tool_permissions)]
+pub struct ToolPermission {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub tool_name: String,
+    pub dangerous: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = tool_permissions)]
+pub struct NewToolPermission {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub tool_name: String,
+    pub dangerous: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SetToolPermissionRequest {
+    pub dangerous: bool,
+}
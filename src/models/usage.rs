@@ -0,0 +1,111 @@
+use crate::schema::usage_records;
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = usage_records)]
+pub struct UsageRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub conversation_id: Option<Uuid>,
+    pub message_id: Option<Uuid>,
+    pub provider_id: Option<Uuid>,
+    pub provider_name: String,
+    pub provider_type: String,
+    pub model: String,
+    pub source: String,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cached_tokens: i64,
+    pub total_tokens: i64,
+    pub cost_usd: Option<f64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = usage_records)]
+pub struct NewUsageRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub conversation_id: Option<Uuid>,
+    pub message_id: Option<Uuid>,
+    pub provider_id: Option<Uuid>,
+    pub provider_name: String,
+    pub provider_type: String,
+    pub model: String,
+    pub source: String,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cached_tokens: i64,
+    pub total_tokens: i64,
+    pub cost_usd: Option<f64>,
+}
+
+/// Where a completion was requested from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageSource {
+    Chat,
+    Gateway,
+    Agent,
+    Embeddings,
+}
+
+impl UsageSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageSource::Chat => "chat",
+            UsageSource::Gateway => "gateway",
+            UsageSource::Agent => "agent",
+            UsageSource::Embeddings => "embeddings",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroupBy {
+    #[default]
+    Day,
+    Provider,
+    Model,
+    Conversation,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct UsageReportQuery {
+    #[serde(default)]
+    pub group_by: UsageGroupBy,
+    /// First day included, in UTC
+    pub from: Option<NaiveDate>,
+    /// Last day included, in UTC
+    pub to: Option<NaiveDate>,
+}
+
+/// Summed usage of the records sharing a group key
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct UsageTotals {
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cached_tokens: i64,
+    pub total_tokens: i64,
+    pub cost_usd: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UsageReportRow {
+    pub key: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UsageReport {
+    pub group_by: UsageGroupBy,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub rows: Vec<UsageReportRow>,
+    pub total: UsageTotals,
+}
@@ -2,6 +2,7 @@ use crate::schema::user_llm_configs;
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use uuid::Uuid;
 
 #[derive(
@@ -16,6 +17,17 @@ pub struct UserLLMConfig {
     pub description: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub fallbacks: Value,
+}
+
+impl UserLLMConfig {
+    /// Returns the configs to fall over to, in order
+    pub fn fallback_chain(&self) -> Vec<LLMFallback> {
+        serde_json::from_value(self.fallbacks.clone()).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid fallbacks on config {}: {}", self.id, e);
+            Vec::new()
+        })
+    }
 }
 
 #[derive(Insertable, Deserialize, Debug, AsChangeset, Clone)]
@@ -25,4 +37,15 @@ pub struct NewUserLLMConfig {
     pub provider_id: Uuid,
     pub api_key_id: Uuid,
     pub description: Option<String>,
+    #[serde(default)]
+    pub fallbacks: Option<Value>,
+}
+
+/// A config to use when the ones before it in the chain are unavailable
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LLMFallback {
+    pub user_llm_config_id: Uuid,
+    /// Overrides the model configured on the fallback's provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
 }
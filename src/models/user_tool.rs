@@ -0,0 +1,125 @@
+use crate::schema::user_tools;
+use crate::services::function_calling::{Tool, ToolParameter};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = user_tools)]
+pub struct UserTool {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub parameters: Value, // JSON array of ToolParameter
+    pub method: String,
+    pub url_template: String,
+    pub headers: Value, // JSON object of header templates
+    pub response_path: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl UserTool {
+    pub fn parameter_list(&self) -> Vec<ToolParameter> {
+        serde_json::from_value(self.parameters.clone()).unwrap_or_default()
+    }
+
+    pub fn header_map(&self) -> HashMap<String, String> {
+        serde_json::from_value(self.headers.clone()).unwrap_or_default()
+    }
+
+    pub fn to_tool(&self) -> Tool {
+        Tool::new(&self.name, &self.description, self.parameter_list())
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = user_tools)]
+pub struct NewUserTool {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+    pub method: String,
+    pub url_template: String,
+    pub headers: Value,
+    pub response_path: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateUserToolRequest {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Vec<ToolParameter>,
+    pub method: String,
+    pub url_template: String,
+    /// Header values may reference vault secrets as `{{secret:vault_name}}`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub response_path: Option<String>,
+}
+
+impl NewUserTool {
+    pub fn from_request(user_id: Uuid, req: CreateUserToolRequest) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            name: req.name,
+            description: req.description,
+            parameters: json!(req.parameters),
+            method: req.method.to_uppercase(),
+            url_template: req.url_template,
+            headers: json!(req.headers),
+            response_path: req.response_path,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateUserToolRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub parameters: Option<Vec<ToolParameter>>,
+    pub method: Option<String>,
+    pub url_template: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub response_path: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserToolResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub parameters: Vec<ToolParameter>,
+    pub method: String,
+    pub url_template: String,
+    pub headers: HashMap<String, String>,
+    pub response_path: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<UserTool> for UserToolResponse {
+    fn from(tool: UserTool) -> Self {
+        Self {
+            parameters: tool.parameter_list(),
+            // Header templates only hold secret references, never the secrets themselves
+            headers: tool.header_map(),
+            id: tool.id,
+            name: tool.name,
+            description: tool.description,
+            method: tool.method,
+            url_template: tool.url_template,
+            response_path: tool.response_path,
+            created_at: tool.created_at,
+            updated_at: tool.updated_at,
+        }
+    }
+}
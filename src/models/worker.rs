@@ -14,6 +14,11 @@ pub struct Worker {
     pub active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When the worker last asked for work or renewed a lease
+    pub last_seen_at: Option<DateTime<Utc>>,
+    /// The bcrypt hash of the secret the worker authenticates to the job queue with
+    #[serde(skip)]
+    pub token_hash: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -23,6 +28,15 @@ pub struct NewWorker {
     pub name: String,
     pub worker_type: Uuid,
     pub active: bool,
+    pub token_hash: String,
+}
+
+/// A worker along with its secret, which is only shown when it's issued
+#[derive(Serialize, Debug)]
+pub struct WorkerWithToken {
+    #[serde(flatten)]
+    pub worker: Worker,
+    pub token: String,
 }
 
 #[derive(Deserialize, Debug)]
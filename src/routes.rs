@@ -9,10 +9,11 @@ use crate::handlers::llm_provider::{
 };
 use crate::handlers::llm_template::{
     create_unified_config, delete_unified_config, get_templates, get_template, get_unified_configs,
+    update_unified_config_fallbacks,
 };
 use crate::handlers::{
-    agent, amber_store, api_key, attachment, configuration, docker_file, fluentcli, function_calling,
-    job, llm, pipeline, metrics, secure_vault, stream_chat, temp_image, user, worker,
+    agent, amber_store, api_key, attachment, configuration, docker_file, embeddings, fluentcli, function_calling,
+    job, job_queue, knowledge, llm, mcp_server, openai_gateway, pipeline, metrics, rate_limit, secure_vault, stream_chat, temp_image, usage, user, worker,
 };
 use crate::utils::auth::Auth;
 use actix_web::{web, Scope};
@@ -50,10 +51,20 @@ pub fn configure_routes() -> Scope {
                 .route("/{id}/data", web::get().to(job::get_job_data))
                 .route("/{id}/start", web::post().to(job::start_job))
                 .route("/{id}/stop", web::post().to(job::stop_job))
+                .route("/{id}/runs", web::get().to(job::list_job_runs))
+                .route("/{id}/runs/{run_id}", web::get().to(job::get_job_run))
                 .route("/{id}/status", web::get().to(job::get_job_status))
                 .route("/{id}/output", web::get().to(job::get_job_output))
                 .route("/{id}/logs", web::get().to(job::get_job_logs)),
         )
+        .service(
+            // Called by worker containers, which authenticate as a registered worker
+            web::scope("/queue")
+                .route("/lease", web::post().to(job_queue::lease_job))
+                .route("/runs/{run_id}/heartbeat", web::post().to(job_queue::renew_lease))
+                .route("/runs/{run_id}/logs", web::post().to(job_queue::append_logs))
+                .route("/runs/{run_id}/complete", web::post().to(job_queue::complete_job)),
+        )
         .service(
             web::scope("/amber_stores")
                 .wrap(Auth)
@@ -114,7 +125,8 @@ pub fn configure_routes() -> Scope {
                 .route(
                     "/{id}/deactivate",
                     web::post().to(worker::deactivate_worker),
-                ),
+                )
+                .route("/{id}/token", web::post().to(worker::rotate_worker_token)),
         )
         .service(
             web::scope("/fluentcli")
@@ -134,12 +146,28 @@ pub fn configure_routes() -> Scope {
                     "/conversations/{conversation_id}/messages/{message_id}",
                     web::delete().to(delete_message),
                 )
+                .route(
+                    "/conversations/{id}/collections",
+                    web::get().to(knowledge::get_conversation_collections),
+                )
+                .route(
+                    "/conversations/{id}/collections",
+                    web::put().to(knowledge::set_conversation_collections),
+                )
                 .service(attachment::get_attachment)
                 .service(attachment::create_attachment)
+                .service(attachment::upload_attachment)
                 .service(attachment::get_message_attachments)
                 .service(attachment::delete_attachment)
                 .route("/stream", web::get().to(stream_chat::stream_chat)),
         )
+        .service(
+            // OpenAI-compatible API; `model` resolves to one of the caller's LLM configs
+            web::scope("/v1")
+                .wrap(Auth)
+                .route("/chat/completions", web::post().to(openai_gateway::chat_completions))
+                .route("/models", web::get().to(openai_gateway::list_models)),
+        )
         .service(
             web::scope("/llm")
                 .wrap(Auth)
@@ -148,8 +176,10 @@ pub fn configure_routes() -> Scope {
                 .route("/providers/{id}", web::get().to(get_llm_provider))
                 .route("/providers/{id}", web::put().to(update_llm_provider))
                 .route("/providers/{id}", web::delete().to(delete_llm_provider))
+                .route("/provider-types", web::get().to(llm::get_provider_types))
                 .route("/chat", web::post().to(llm::llm_chat))
                 .route("/stream_chat", web::post().to(llm::llm_stream_chat))
+                .route("/embeddings", web::post().to(embeddings::create_embeddings))
                 .route("/user-configs", web::post().to(create_user_llm_config))
                 .route("/user-configs", web::get().to(list_user_llm_configs))
                 .route("/user-configs/{id}", web::get().to(get_user_llm_config))
@@ -163,7 +193,41 @@ pub fn configure_routes() -> Scope {
                 .route("/templates/{id}", web::get().to(get_template))
                 .route("/unified-configs", web::post().to(create_unified_config))
                 .route("/unified-configs", web::get().to(get_unified_configs))
-                .route("/unified-configs/{id}", web::delete().to(delete_unified_config)),
+                .route("/unified-configs/{id}", web::delete().to(delete_unified_config))
+                .route(
+                    "/unified-configs/{id}/fallbacks",
+                    web::put().to(update_unified_config_fallbacks),
+                ),
+        )
+        .service(
+            web::scope("/knowledge")
+                .wrap(Auth)
+                .route("/collections", web::post().to(knowledge::create_collection))
+                .route("/collections", web::get().to(knowledge::list_collections))
+                .route("/collections/{id}", web::get().to(knowledge::get_collection))
+                .route("/collections/{id}", web::put().to(knowledge::update_collection))
+                .route("/collections/{id}", web::delete().to(knowledge::delete_collection))
+                .route("/collections/{id}/documents", web::post().to(knowledge::ingest_document))
+                .route("/collections/{id}/documents", web::get().to(knowledge::list_documents))
+                .route(
+                    "/collections/{id}/documents/{document_id}",
+                    web::delete().to(knowledge::delete_document),
+                )
+                .route("/search", web::post().to(knowledge::search)),
+        )
+        .service(
+            web::scope("/rate-limits")
+                .wrap(Auth)
+                .route("", web::get().to(rate_limit::get_rate_limits))
+                .route("", web::put().to(rate_limit::set_user_rate_limits))
+                .route("", web::delete().to(rate_limit::delete_user_rate_limits))
+                .route("/api-keys/{id}", web::put().to(rate_limit::set_api_key_rate_limits))
+                .route("/api-keys/{id}", web::delete().to(rate_limit::delete_api_key_rate_limits)),
+        )
+        .service(
+            web::scope("/usage")
+                .wrap(Auth)
+                .route("", web::get().to(usage::get_usage_report)),
         )
         .service(
             web::scope("/agents")
@@ -173,6 +237,16 @@ pub fn configure_routes() -> Scope {
                 .route("/{id}", web::get().to(agent::get_agent))
                 .route("/{id}", web::put().to(agent::update_agent))
                 .route("/{id}", web::delete().to(agent::delete_agent))
+                .route("/{id}/run", web::post().to(agent::run_agent))
+                .route("/{id}/run/stream", web::post().to(agent::stream_agent_run))
+                .route("/{id}/runs", web::get().to(agent::list_agent_runs))
+                .route("/{id}/runs/{run_id}", web::get().to(agent::get_agent_run))
+                .route(
+                    "/{id}/runs/{run_id}/approvals/{approval_id}",
+                    web::post().to(agent::decide_tool_approval),
+                )
+                .route("/{id}/runs/{run_id}/resume", web::post().to(agent::resume_agent_run))
+                .route("/approvals/pending", web::get().to(agent::list_pending_approvals))
                 // Add OPTIONS method for CORS preflight requests
                 .route("", web::method(actix_web::http::Method::OPTIONS).to(agent::options_handler))
                 .route("/{id}", web::method(actix_web::http::Method::OPTIONS).to(agent::options_handler)),
@@ -181,8 +255,21 @@ pub fn configure_routes() -> Scope {
             web::scope("/function-calling")
                 .wrap(Auth)
                 .route("/tools", web::get().to(function_calling::list_tools))
+                .route("/tools", web::post().to(function_calling::create_user_tool))
                 .route("/tools/{id}", web::get().to(function_calling::get_tool))
-                .route("/execute", web::post().to(function_calling::execute_tool)),
+                .route("/tools/{id}", web::put().to(function_calling::update_user_tool))
+                .route("/tools/{id}", web::delete().to(function_calling::delete_user_tool))
+                .route("/tools/{id}/config", web::get().to(function_calling::get_user_tool_config))
+                .route("/tools/{id}/permission", web::put().to(function_calling::set_tool_permission))
+                .route("/tools/{id}/permission", web::delete().to(function_calling::delete_tool_permission))
+                .route("/permissions", web::get().to(function_calling::list_tool_permissions))
+                .route("/execute", web::post().to(function_calling::execute_tool))
+                .route("/servers", web::get().to(mcp_server::list_mcp_servers))
+                .route("/servers", web::post().to(mcp_server::create_mcp_server))
+                .route("/servers/{id}", web::get().to(mcp_server::get_mcp_server))
+                .route("/servers/{id}", web::put().to(mcp_server::update_mcp_server))
+                .route("/servers/{id}", web::delete().to(mcp_server::delete_mcp_server))
+                .route("/servers/{id}/tools", web::get().to(mcp_server::list_mcp_server_tools)),
         )
         .service(temp_image::get_temp_image)
         .route("/metrics", web::get().to(metrics::metrics))
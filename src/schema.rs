@@ -28,6 +28,43 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    agent_run_steps (id) {
+        id -> Uuid,
+        run_id -> Uuid,
+        step -> Int4,
+        #[max_length = 50]
+        kind -> Varchar,
+        #[max_length = 255]
+        tool_name -> Nullable<Varchar>,
+        #[max_length = 255]
+        tool_call_id -> Nullable<Varchar>,
+        arguments -> Nullable<Jsonb>,
+        result -> Nullable<Jsonb>,
+        error -> Nullable<Text>,
+        latency_ms -> Int8,
+        usage -> Nullable<Jsonb>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    agent_runs (id) {
+        id -> Uuid,
+        agent_id -> Uuid,
+        user_id -> Uuid,
+        input -> Text,
+        #[max_length = 50]
+        status -> Varchar,
+        output -> Nullable<Text>,
+        error -> Nullable<Text>,
+        usage -> Nullable<Jsonb>,
+        created_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+        state -> Nullable<Jsonb>,
+    }
+}
+
 diesel::table! {
     amber_store (id) {
         id -> Uuid,
@@ -78,6 +115,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    conversation_collections (conversation_id, collection_id) {
+        conversation_id -> Uuid,
+        collection_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     conversations (id) {
         id -> Uuid,
@@ -102,6 +147,61 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    job_queue (run_id) {
+        run_id -> Uuid,
+        job_id -> Uuid,
+        user_id -> Uuid,
+        worker_type -> Uuid,
+        command -> Jsonb,
+        pipeline_name -> Text,
+        pipeline_file -> Text,
+        #[max_length = 20]
+        status -> Varchar,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        available_at -> Timestamptz,
+        leased_by -> Nullable<Uuid>,
+        lease_token -> Nullable<Uuid>,
+        lease_expires_at -> Nullable<Timestamptz>,
+        stop_requested -> Bool,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    job_run_logs (id) {
+        id -> Int8,
+        run_id -> Uuid,
+        job_id -> Uuid,
+        #[max_length = 10]
+        stream -> Varchar,
+        line -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    job_runs (id) {
+        id -> Uuid,
+        job_id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 50]
+        trigger_source -> Varchar,
+        #[max_length = 50]
+        status -> Varchar,
+        exit_code -> Nullable<Int4>,
+        stdout -> Nullable<Text>,
+        stderr -> Nullable<Text>,
+        error -> Nullable<Text>,
+        state_file_content -> Nullable<Jsonb>,
+        started_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     jobs (id) {
         id -> Uuid,
@@ -125,6 +225,51 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    knowledge_chunks (id) {
+        id -> Uuid,
+        document_id -> Uuid,
+        collection_id -> Uuid,
+        chunk_index -> Int4,
+        content -> Text,
+        embedding -> Vector,
+    }
+}
+
+diesel::table! {
+    knowledge_collections (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        user_llm_config_id -> Uuid,
+        #[max_length = 255]
+        name -> Varchar,
+        description -> Nullable<Text>,
+        #[max_length = 255]
+        embedding_model -> Nullable<Varchar>,
+        dimensions -> Nullable<Int4>,
+        chunk_size -> Int4,
+        chunk_overlap -> Int4,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    knowledge_documents (id) {
+        id -> Uuid,
+        collection_id -> Uuid,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 100]
+        content_type -> Varchar,
+        chunk_count -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     llm_providers (id) {
         id -> Uuid,
@@ -142,6 +287,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    mcp_servers (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 50]
+        transport -> Varchar,
+        command -> Nullable<Text>,
+        args -> Jsonb,
+        env -> Jsonb,
+        url -> Nullable<Text>,
+        headers -> Jsonb,
+        enabled -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     messages (id) {
         id -> Uuid,
@@ -170,6 +334,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    rate_limits (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        api_key_id -> Nullable<Uuid>,
+        requests_per_minute -> Nullable<Int4>,
+        concurrent_streams -> Nullable<Int4>,
+        tokens_per_minute -> Nullable<Int8>,
+        tokens_per_day -> Nullable<Int8>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     secure_vault (id) {
         id -> Uuid,
@@ -192,6 +370,61 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    tool_approvals (id) {
+        id -> Uuid,
+        run_id -> Uuid,
+        user_id -> Uuid,
+        step -> Int4,
+        #[max_length = 255]
+        tool_call_id -> Varchar,
+        #[max_length = 255]
+        tool_name -> Varchar,
+        arguments -> Jsonb,
+        #[max_length = 50]
+        status -> Varchar,
+        reason -> Nullable<Text>,
+        created_at -> Timestamptz,
+        decided_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    tool_permissions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 255]
+        tool_name -> Varchar,
+        dangerous -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    usage_records (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        conversation_id -> Nullable<Uuid>,
+        message_id -> Nullable<Uuid>,
+        provider_id -> Nullable<Uuid>,
+        #[max_length = 255]
+        provider_name -> Varchar,
+        #[max_length = 255]
+        provider_type -> Varchar,
+        #[max_length = 255]
+        model -> Varchar,
+        #[max_length = 50]
+        source -> Varchar,
+        prompt_tokens -> Int8,
+        completion_tokens -> Int8,
+        cached_tokens -> Int8,
+        total_tokens -> Int8,
+        cost_usd -> Nullable<Float8>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     user_llm_configs (id) {
         id -> Uuid,
@@ -201,6 +434,25 @@ diesel::table! {
         description -> Nullable<Text>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        fallbacks -> Jsonb,
+    }
+}
+
+diesel::table! {
+    user_tools (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 255]
+        name -> Varchar,
+        description -> Text,
+        parameters -> Jsonb,
+        #[max_length = 10]
+        method -> Varchar,
+        url_template -> Text,
+        headers -> Jsonb,
+        response_path -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -227,47 +479,94 @@ diesel::table! {
         active -> Bool,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        last_seen_at -> Nullable<Timestamptz>,
+        #[max_length = 255]
+        token_hash -> Nullable<Varchar>,
     }
 }
 
 diesel::joinable!(active_workers -> users (user_id));
+diesel::joinable!(agent_run_steps -> agent_runs (run_id));
+diesel::joinable!(agent_runs -> agents (agent_id));
+diesel::joinable!(agent_runs -> users (user_id));
 diesel::joinable!(agents -> users (user_id));
 diesel::joinable!(amber_store -> users (user_id));
 diesel::joinable!(api_keys -> users (user_id));
 diesel::joinable!(configurations -> users (user_id));
+diesel::joinable!(conversation_collections -> conversations (conversation_id));
+diesel::joinable!(conversation_collections -> knowledge_collections (collection_id));
 diesel::joinable!(conversations -> users (user_id));
 diesel::joinable!(docker_files -> users (user_id));
+diesel::joinable!(job_queue -> job_runs (run_id));
+diesel::joinable!(job_queue -> jobs (job_id));
+diesel::joinable!(job_queue -> users (user_id));
+diesel::joinable!(job_queue -> workers (leased_by));
+diesel::joinable!(job_run_logs -> job_runs (run_id));
+diesel::joinable!(job_run_logs -> jobs (job_id));
+diesel::joinable!(job_runs -> jobs (job_id));
+diesel::joinable!(job_runs -> users (user_id));
 diesel::joinable!(jobs -> amber_store (amber_id));
 diesel::joinable!(jobs -> configurations (config));
 diesel::joinable!(jobs -> docker_files (worker_type));
 diesel::joinable!(jobs -> users (user_id));
+diesel::joinable!(knowledge_chunks -> knowledge_collections (collection_id));
+diesel::joinable!(knowledge_chunks -> knowledge_documents (document_id));
+diesel::joinable!(knowledge_collections -> user_llm_configs (user_llm_config_id));
+diesel::joinable!(knowledge_collections -> users (user_id));
+diesel::joinable!(knowledge_documents -> knowledge_collections (collection_id));
 diesel::joinable!(llm_providers -> users (user_id));
+diesel::joinable!(mcp_servers -> users (user_id));
 diesel::joinable!(messages -> attachments (attachment_id));
 diesel::joinable!(messages -> conversations (conversation_id));
 diesel::joinable!(pipelines -> users (user_id));
+diesel::joinable!(rate_limits -> api_keys (api_key_id));
+diesel::joinable!(rate_limits -> users (user_id));
 diesel::joinable!(secure_vault -> users (user_id));
 diesel::joinable!(secure_vaults -> users (user_id));
+diesel::joinable!(tool_approvals -> agent_runs (run_id));
+diesel::joinable!(tool_approvals -> users (user_id));
+diesel::joinable!(tool_permissions -> users (user_id));
+diesel::joinable!(usage_records -> conversations (conversation_id));
+diesel::joinable!(usage_records -> llm_providers (provider_id));
+diesel::joinable!(usage_records -> messages (message_id));
+diesel::joinable!(usage_records -> users (user_id));
 diesel::joinable!(user_llm_configs -> api_keys (api_key_id));
 diesel::joinable!(user_llm_configs -> llm_providers (provider_id));
 diesel::joinable!(user_llm_configs -> users (user_id));
+diesel::joinable!(user_tools -> users (user_id));
 diesel::joinable!(workers -> docker_files (worker_type));
 
 diesel::allow_tables_to_appear_in_same_query!(
     active_workers,
+    agent_run_steps,
+    agent_runs,
     agents,
     amber_store,
     api_keys,
     attachments,
     configurations,
+    conversation_collections,
     conversations,
     docker_files,
+    job_queue,
+    job_run_logs,
+    job_runs,
     jobs,
+    knowledge_chunks,
+    knowledge_collections,
+    knowledge_documents,
     llm_providers,
+    mcp_servers,
     messages,
     pipelines,
+    rate_limits,
     secure_vault,
     secure_vaults,
+    tool_approvals,
+    tool_permissions,
+    usage_records,
     user_llm_configs,
+    user_tools,
     users,
     workers,
 );
@@ -0,0 +1,302 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::agent_run::{
+    AgentRun, AgentRunDetail, AgentRunStepRecord, ApprovalDecision, NewAgentRun,
+    NewAgentRunStep, NewToolApproval, ToolApproval,
+};
+use crate::schema::{agent_run_steps, agent_runs, tool_approvals};
+use chrono::Utc;
+use diesel::prelude::*;
+use log::{debug, info, warn};
+use serde_json::Value;
+use uuid::Uuid;
+
+pub struct AgentRunService;
+
+impl AgentRunService {
+    pub fn create_run(
+        pool: &DbPool,
+        agent_id: Uuid,
+        user_id: Uuid,
+        input: &str,
+    ) -> Result<AgentRun, AppError> {
+        let conn = &mut pool.get()?;
+
+        let new_run = NewAgentRun {
+            id: Uuid::new_v4(),
+            agent_id,
+            user_id,
+            input: input.to_string(),
+            status: "running".to_string(),
+        };
+
+        let run = diesel::insert_into(agent_runs::table)
+            .values(&new_run)
+            .get_result::<AgentRun>(conn)?;
+
+        info!("Started run {} for agent {}", run.id, agent_id);
+        Ok(run)
+    }
+
+    pub fn add_step(pool: &DbPool, step: NewAgentRunStep) -> Result<AgentRunStepRecord, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Recording {} step {} for run {}", step.kind, step.step, step.run_id);
+
+        diesel::insert_into(agent_run_steps::table)
+            .values(&step)
+            .get_result::<AgentRunStepRecord>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn complete_run(
+        pool: &DbPool,
+        run_id: Uuid,
+        status: &str,
+        output: Option<String>,
+        error: Option<String>,
+        usage: Option<Value>,
+    ) -> Result<AgentRun, AppError> {
+        let conn = &mut pool.get()?;
+
+        let run = diesel::update(agent_runs::table.find(run_id))
+            .set((
+                agent_runs::status.eq(status),
+                agent_runs::output.eq(output),
+                agent_runs::error.eq(error),
+                agent_runs::usage.eq(usage),
+                agent_runs::completed_at.eq(Some(Utc::now())),
+                agent_runs::state.eq(None::<Value>),
+            ))
+            .get_result::<AgentRun>(conn)?;
+
+        info!("Run {} finished with status {}", run_id, status);
+        Ok(run)
+    }
+
+    pub fn list_runs(
+        pool: &DbPool,
+        agent_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<AgentRun>, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Listing runs for agent {} and user {}", agent_id, user_id);
+
+        agent_runs::table
+            .filter(agent_runs::agent_id.eq(agent_id))
+            .filter(agent_runs::user_id.eq(user_id))
+            .order(agent_runs::created_at.desc())
+            .load::<AgentRun>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn get_run(
+        pool: &DbPool,
+        agent_id: Uuid,
+        run_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<AgentRunDetail, AppError> {
+        let conn = &mut pool.get()?;
+
+        let run = agent_runs::table
+            .filter(agent_runs::id.eq(run_id))
+            .filter(agent_runs::agent_id.eq(agent_id))
+            .filter(agent_runs::user_id.eq(user_id))
+            .first::<AgentRun>(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    AppError::NotFoundError(format!("Agent run not found: {}", run_id))
+                } else {
+                    AppError::DatabaseError(e)
+                }
+            })?;
+
+        let steps = AgentRunStepRecord::belonging_to(&run)
+            .order((agent_run_steps::step.asc(), agent_run_steps::created_at.asc()))
+            .load::<AgentRunStepRecord>(conn)?;
+
+        let approvals = ToolApproval::belonging_to(&run)
+            .order(tool_approvals::created_at.asc())
+            .load::<ToolApproval>(conn)?;
+
+        Ok(AgentRunDetail { run, steps, approvals })
+    }
+
+    /// Saves the state of a run that is waiting on tool approvals, along with the approvals
+    pub fn pause_run(
+        pool: &DbPool,
+        run_id: Uuid,
+        state: Value,
+        usage: Option<Value>,
+        approvals: Vec<NewToolApproval>,
+    ) -> Result<Vec<ToolApproval>, AppError> {
+        let conn = &mut pool.get()?;
+
+        let approvals = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::update(agent_runs::table.find(run_id))
+                .set((
+                    agent_runs::status.eq("awaiting_approval"),
+                    agent_runs::state.eq(Some(state)),
+                    agent_runs::usage.eq(usage),
+                ))
+                .execute(conn)?;
+
+            diesel::insert_into(tool_approvals::table)
+                .values(&approvals)
+                .get_results::<ToolApproval>(conn)
+        })?;
+
+        info!("Run {} is waiting on {} approval(s)", run_id, approvals.len());
+        Ok(approvals)
+    }
+
+    pub fn list_pending_approvals(pool: &DbPool, user_id: Uuid) -> Result<Vec<ToolApproval>, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Listing pending approvals for user {}", user_id);
+
+        tool_approvals::table
+            .filter(tool_approvals::user_id.eq(user_id))
+            .filter(tool_approvals::status.eq("pending"))
+            .order(tool_approvals::created_at.asc())
+            .load::<ToolApproval>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    /// Returns the approvals requested at the given step of a run
+    pub fn list_step_approvals(
+        pool: &DbPool,
+        run_id: Uuid,
+        step: i32,
+    ) -> Result<Vec<ToolApproval>, AppError> {
+        let conn = &mut pool.get()?;
+
+        tool_approvals::table
+            .filter(tool_approvals::run_id.eq(run_id))
+            .filter(tool_approvals::step.eq(step))
+            .order(tool_approvals::created_at.asc())
+            .load::<ToolApproval>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    /// Records a decision on a pending approval; `arguments` replaces the proposed ones
+    pub fn decide_approval(
+        pool: &DbPool,
+        run_id: Uuid,
+        approval_id: Uuid,
+        user_id: Uuid,
+        decision: ApprovalDecision,
+        arguments: Option<Value>,
+        reason: Option<String>,
+    ) -> Result<ToolApproval, AppError> {
+        let conn = &mut pool.get()?;
+
+        let existing = tool_approvals::table
+            .filter(tool_approvals::id.eq(approval_id))
+            .filter(tool_approvals::run_id.eq(run_id))
+            .filter(tool_approvals::user_id.eq(user_id))
+            .first::<ToolApproval>(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    AppError::NotFoundError(format!("Approval not found: {}", approval_id))
+                } else {
+                    AppError::DatabaseError(e)
+                }
+            })?;
+
+        let status = match decision {
+            ApprovalDecision::Approve => "approved",
+            ApprovalDecision::Reject => "rejected",
+        };
+        let arguments = match decision {
+            ApprovalDecision::Approve => arguments.unwrap_or(existing.arguments),
+            ApprovalDecision::Reject => existing.arguments,
+        };
+
+        // Only pending approvals can be decided, so concurrent decisions can't both win
+        let approval = diesel::update(tool_approvals::table.find(approval_id))
+            .filter(tool_approvals::status.eq("pending"))
+            .set((
+                tool_approvals::status.eq(status),
+                tool_approvals::arguments.eq(arguments),
+                tool_approvals::reason.eq(reason),
+                tool_approvals::decided_at.eq(Some(Utc::now())),
+            ))
+            .get_result::<ToolApproval>(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    AppError::BadRequest(format!("Approval {} was already decided", approval_id))
+                } else {
+                    AppError::DatabaseError(e)
+                }
+            })?;
+
+        info!("Tool call {} was {}", approval.tool_call_id, approval.status);
+        Ok(approval)
+    }
+
+    /// Returns the paused run if all of its approvals are decided, without claiming it
+    pub fn resumable_run(
+        pool: &DbPool,
+        agent_id: Uuid,
+        run_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<AgentRun>, AppError> {
+        let conn = &mut pool.get()?;
+
+        if Self::count_pending(conn, run_id)? > 0 {
+            return Ok(None);
+        }
+
+        let run = agent_runs::table
+            .filter(agent_runs::id.eq(run_id))
+            .filter(agent_runs::agent_id.eq(agent_id))
+            .filter(agent_runs::user_id.eq(user_id))
+            .filter(agent_runs::status.eq("awaiting_approval"))
+            .first::<AgentRun>(conn)
+            .optional()?;
+        Ok(run)
+    }
+
+    /// Marks a paused run as running again once all of its approvals are decided.
+    /// Returns `None` if approvals are still pending or another request already resumed it.
+    pub fn claim_paused_run(
+        pool: &DbPool,
+        agent_id: Uuid,
+        run_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<AgentRun>, AppError> {
+        let conn = &mut pool.get()?;
+
+        if Self::count_pending(conn, run_id)? > 0 {
+            return Ok(None);
+        }
+
+        let run = diesel::update(agent_runs::table)
+            .filter(agent_runs::id.eq(run_id))
+            .filter(agent_runs::agent_id.eq(agent_id))
+            .filter(agent_runs::user_id.eq(user_id))
+            .filter(agent_runs::status.eq("awaiting_approval"))
+            .set(agent_runs::status.eq("running"))
+            .get_result::<AgentRun>(conn)
+            .optional()?;
+
+        if run.is_none() {
+            warn!("Run {} is not awaiting approval", run_id);
+        }
+        Ok(run)
+    }
+
+    fn count_pending(conn: &mut PgConnection, run_id: Uuid) -> Result<i64, AppError> {
+        let pending: i64 = tool_approvals::table
+            .filter(tool_approvals::run_id.eq(run_id))
+            .filter(tool_approvals::status.eq("pending"))
+            .count()
+            .get_result(conn)?;
+        if pending > 0 {
+            debug!("Run {} still has {} pending approval(s)", run_id, pending);
+        }
+        Ok(pending)
+    }
+}
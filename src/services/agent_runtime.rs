@@ -0,0 +1,993 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::agent::{AgentResponse, RunAgentRequest};
+use crate::models::agent_run::{AgentRun, NewAgentRunStep, NewToolApproval, ToolApproval};
+use crate::models::llm_provider::LLMProvider;
+use crate::models::usage::UsageSource;
+use crate::services::agent_run_service::AgentRunService;
+use crate::services::agent_service::AgentService;
+use crate::services::chat_service::ChatService;
+use crate::services::function_calling::provider::find_provider_adapter;
+use crate::services::function_calling::{
+    ApprovalMode, FunctionCallingManager, Message, Tool, ToolCall, ToolChoice, ToolRegistry,
+    ToolResult,
+};
+use crate::services::llm_service::{
+    LLMChatMessage, LLMChatOutput, LLMService, LLMServiceError, LLMStreamChunk, LLMToolOptions,
+};
+use crate::services::rate_limit_service::RateLimitPermit;
+use crate::services::reasoning_patterns::{PatternComposer, ReasoningPattern};
+use crate::services::usage_service::{TokenUsage, UsageService, UsageStats};
+use futures::{Stream, StreamExt};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Number of model round trips allowed when the request doesn't specify a budget
+pub const DEFAULT_MAX_STEPS: usize = 10;
+/// Upper bound on the step budget a caller may request
+pub const MAX_STEPS_LIMIT: usize = 50;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRunStatus {
+    Completed,
+    MaxStepsExceeded,
+    AwaitingApproval,
+}
+
+impl AgentRunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AgentRunStatus::Completed => "completed",
+            AgentRunStatus::MaxStepsExceeded => "max_steps_exceeded",
+            AgentRunStatus::AwaitingApproval => "awaiting_approval",
+        }
+    }
+}
+
+/// A single model round trip and the tool activity it triggered
+#[derive(Serialize, Debug, Clone)]
+pub struct AgentRunStep {
+    pub step: usize,
+    pub content: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+    pub tool_results: Vec<ToolResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AgentRunResult {
+    pub run_id: Option<Uuid>,
+    pub agent_id: Uuid,
+    pub status: AgentRunStatus,
+    pub output: Option<String>,
+    pub steps: Vec<AgentRunStep>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pending_approvals: Vec<ToolApproval>,
+}
+
+/// Progress event emitted while an agent runs
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    Thought { step: usize, content: String },
+    ToolCall { step: usize, call: ToolCall },
+    ToolResult { step: usize, result: ToolResult },
+    /// Text of the step's completion as it streams in. A step that goes on to call tools also
+    /// reports its whole text as a `Thought`.
+    Token { step: usize, content: String },
+    ApprovalRequired { step: usize, approval: ToolApproval },
+    Final {
+        status: AgentRunStatus,
+        output: Option<String>,
+        steps: usize,
+    },
+    Error { message: String },
+}
+
+impl AgentEvent {
+    /// Returns the Server-Sent Events name for this event
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            AgentEvent::Thought { .. } => "thought",
+            AgentEvent::ToolCall { .. } => "tool_call",
+            AgentEvent::ToolResult { .. } => "tool_result",
+            AgentEvent::Token { .. } => "token",
+            AgentEvent::ApprovalRequired { .. } => "approval_required",
+            AgentEvent::Final { .. } => "final",
+            AgentEvent::Error { .. } => "error",
+        }
+    }
+}
+
+/// What a paused run needs to continue once its tool calls are decided
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PausedRunState {
+    pub user_llm_config_id: Uuid,
+    pub max_steps: usize,
+    pub approval_mode: ApprovalMode,
+    /// The step whose tool calls are waiting for approval
+    pub step: usize,
+    /// The conversation so far, ending with the results of the calls that already ran
+    pub messages: Vec<Message>,
+}
+
+/// Executes an agent against an LLM provider, running tool calls until the
+/// model produces a final answer or the step budget is spent
+pub struct AgentRuntime {
+    manager: FunctionCallingManager,
+    registry: Arc<ToolRegistry>,
+    provider: LLMProvider,
+    api_key: String,
+    max_steps: usize,
+    recorder: Option<RunRecorder>,
+    approval_mode: ApprovalMode,
+    /// The tokens of every step count against this permit's limits
+    rate_limit: Option<RateLimitPermit>,
+}
+
+/// Persists run transcripts; failures are logged rather than aborting the run
+struct RunRecorder {
+    pool: DbPool,
+    user_id: Uuid,
+    /// Needed to rebuild the runtime when a paused run resumes
+    user_llm_config_id: Option<Uuid>,
+}
+
+impl AgentRuntime {
+    pub fn new(
+        provider: LLMProvider,
+        api_key: String,
+        registry: Arc<ToolRegistry>,
+        max_steps: usize,
+    ) -> Result<Self, AppError> {
+        let adapter = find_provider_adapter(&provider.provider_type).ok_or_else(|| {
+            AppError::UnsupportedProviderError(format!(
+                "{} does not support function calling",
+                provider.provider_type
+            ))
+        })?;
+
+        Ok(Self {
+            manager: FunctionCallingManager::new(adapter, registry.clone()),
+            registry,
+            provider,
+            api_key,
+            max_steps: max_steps.clamp(1, MAX_STEPS_LIMIT),
+            recorder: None,
+            approval_mode: ApprovalMode::Off,
+            rate_limit: None,
+        })
+    }
+
+    /// Counts the tokens of every step against the caller's rate limits. The permit is held
+    /// until the runtime is dropped, so a stream slot stays taken for the whole run.
+    pub fn with_rate_limit(mut self, permit: RateLimitPermit) -> Self {
+        self.rate_limit = Some(permit);
+        self
+    }
+
+    /// Records every run made by this runtime in `agent_runs`/`agent_run_steps`
+    pub fn with_recorder(mut self, pool: DbPool, user_id: Uuid) -> Self {
+        self.recorder = Some(RunRecorder {
+            pool,
+            user_id,
+            user_llm_config_id: None,
+        });
+        self
+    }
+
+    /// Pauses runs on tool calls that need approval. Pausing needs a recorder, since the
+    /// run is resumed from its saved state; without one those calls fail instead.
+    pub fn with_approval_mode(mut self, approval_mode: ApprovalMode, user_llm_config_id: Uuid) -> Self {
+        self.manager = self.manager.with_approval_mode(approval_mode);
+        self.approval_mode = approval_mode;
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.user_llm_config_id = Some(user_llm_config_id);
+        }
+        self
+    }
+
+    /// Loads the agent and the caller's LLM config, then runs the agent on the input
+    pub async fn run_agent(
+        pool: &DbPool,
+        registry: Arc<ToolRegistry>,
+        user_id: Uuid,
+        agent_id: Uuid,
+        request: RunAgentRequest,
+        permit: RateLimitPermit,
+    ) -> Result<AgentRunResult, AppError> {
+        let (runtime, agent, tools) = Self::load(pool, registry, user_id, agent_id, &request).await?;
+        runtime.with_rate_limit(permit).run(&agent, &tools, &request.input).await
+    }
+
+    /// The LLM config a paused run continues with
+    pub fn paused_config_id(run: &AgentRun) -> Option<Uuid> {
+        run.state
+            .as_ref()?
+            .get("user_llm_config_id")?
+            .as_str()?
+            .parse()
+            .ok()
+    }
+
+    /// Builds a runtime for the agent along with the tools it may use
+    pub async fn load(
+        pool: &DbPool,
+        registry: Arc<ToolRegistry>,
+        user_id: Uuid,
+        agent_id: Uuid,
+        request: &RunAgentRequest,
+    ) -> Result<(Self, AgentResponse, Vec<Tool>), AppError> {
+        // A registry built for another user must never serve this run
+        if registry.owner().is_some_and(|owner| owner != user_id) {
+            return Err(AppError::Forbidden("Tool registry belongs to another user".to_string()));
+        }
+
+        let agent = AgentService::get_agent(pool, agent_id, user_id)?;
+        let runtime = Self::build(
+            pool,
+            registry,
+            user_id,
+            request.user_llm_config_id,
+            request.max_steps.unwrap_or(DEFAULT_MAX_STEPS),
+            request.approval_mode,
+        )
+        .await?;
+        let tools = runtime.resolve_tools(&agent).await?;
+
+        Ok((runtime, agent, tools))
+    }
+
+    /// Builds a recording runtime from one of the caller's LLM configs
+    async fn build(
+        pool: &DbPool,
+        registry: Arc<ToolRegistry>,
+        user_id: Uuid,
+        user_llm_config_id: Uuid,
+        max_steps: usize,
+        approval_mode: ApprovalMode,
+    ) -> Result<Self, AppError> {
+        let user_config = ChatService::get_user_llm_config_by_id(pool, user_llm_config_id)?;
+        if user_config.user_id != user_id {
+            return Err(AppError::Unauthorized);
+        }
+
+        let provider = ChatService::get_llm_provider(pool, user_config.provider_id)?;
+        let api_key = LLMService::get_api_key(pool, &user_config)
+            .await
+            .map_err(|e| e.0)?;
+
+        Ok(Self::new(provider, api_key, registry, max_steps)?
+            .with_recorder(pool.clone(), user_id)
+            .with_approval_mode(approval_mode, user_llm_config_id))
+    }
+
+    /// Continues a run whose tool approvals have all been decided. Returns `None` if
+    /// approvals are still pending or the run was already resumed by another request.
+    pub async fn resume_run(
+        pool: &DbPool,
+        registry: Arc<ToolRegistry>,
+        user_id: Uuid,
+        agent_id: Uuid,
+        run_id: Uuid,
+        permit: RateLimitPermit,
+    ) -> Result<Option<AgentRunResult>, AppError> {
+        if registry.owner().is_some_and(|owner| owner != user_id) {
+            return Err(AppError::Forbidden("Tool registry belongs to another user".to_string()));
+        }
+
+        let Some(run) = AgentRunService::claim_paused_run(pool, agent_id, run_id, user_id)? else {
+            return Ok(None);
+        };
+        let usage = match &run.usage {
+            Some(Value::Object(usage)) => usage.clone(),
+            _ => Map::new(),
+        };
+
+        let prepared = async {
+            let state: PausedRunState = run
+                .state
+                .ok_or_else(|| AppError::BadRequest(format!("Run {} has no saved state", run_id)))
+                .and_then(|state| {
+                    serde_json::from_value(state).map_err(|e| AppError::SerializationError(e.to_string()))
+                })?;
+            let agent = AgentService::get_agent(pool, agent_id, user_id)?;
+            let runtime = Self::build(
+                pool,
+                registry,
+                user_id,
+                state.user_llm_config_id,
+                state.max_steps,
+                state.approval_mode,
+            )
+            .await?
+            .with_rate_limit(permit);
+            let tools = runtime.resolve_tools(&agent).await?;
+            let approvals = AgentRunService::list_step_approvals(pool, run_id, state.step as i32)?;
+            Ok::<_, AppError>((runtime, agent, tools, state, approvals))
+        }
+        .await;
+
+        // The run was claimed, so it must not be left looking like it's still running
+        let (runtime, agent, tools, state, approvals) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                error!("Failed to resume run {}: {}", run_id, e);
+                AgentRunService::complete_run(pool, run_id, "failed", None, Some(e.to_string()), Some(Value::Object(usage)))?;
+                return Err(e);
+            }
+        };
+
+        info!("Resuming run {} of agent {} at step {}", run_id, agent_id, state.step);
+        let result = runtime
+            .resume(&agent, &tools, run_id, state, approvals, usage)
+            .await;
+        Ok(Some(result?))
+    }
+
+    /// Looks up the agent's tools in the registry
+    pub async fn resolve_tools(&self, agent: &AgentResponse) -> Result<Vec<Tool>, AppError> {
+        let mut tools = Vec::with_capacity(agent.tools.len());
+        for name in &agent.tools {
+            let tool = self.registry.get_tool(name).await.ok_or_else(|| {
+                AppError::BadRequest(format!("Agent references unknown tool: {}", name))
+            })?;
+            tools.push(tool);
+        }
+        Ok(tools)
+    }
+
+    /// Runs the agent loop for a single user input
+    pub async fn run(
+        &self,
+        agent: &AgentResponse,
+        tools: &[Tool],
+        input: &str,
+    ) -> Result<AgentRunResult, AppError> {
+        self.run_with_events(agent, tools, input, None).await
+    }
+
+    /// Runs the agent loop, reporting each step to `events` as it happens
+    pub async fn run_with_events(
+        &self,
+        agent: &AgentResponse,
+        tools: &[Tool],
+        input: &str,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+    ) -> Result<AgentRunResult, AppError> {
+        let run_id = self.start_record(agent, input);
+        let mut usage = Map::new();
+        let messages = vec![
+            Message::system(&Self::system_prompt(agent, tools)),
+            Message::user(input),
+        ];
+        let result = self
+            .run_loop(agent, tools, messages, 1, Vec::new(), run_id, &mut usage, events)
+            .await;
+
+        self.finish(run_id, result, usage, events).await
+    }
+
+    /// Feeds the decided tool calls of a paused run back to the model and continues the loop
+    async fn resume(
+        &self,
+        agent: &AgentResponse,
+        tools: &[Tool],
+        run_id: Uuid,
+        state: PausedRunState,
+        approvals: Vec<ToolApproval>,
+        mut usage: Map<String, Value>,
+    ) -> Result<AgentRunResult, AppError> {
+        let PausedRunState { step, mut messages, .. } = state;
+
+        let mut tool_calls = Vec::with_capacity(approvals.len());
+        let mut tool_results = Vec::with_capacity(approvals.len());
+        for approval in approvals {
+            let call = ToolCall {
+                id: approval.tool_call_id.clone(),
+                name: approval.tool_name.clone(),
+                arguments: approval.arguments.clone(),
+            };
+            let result = if approval.status == "approved" {
+                self.execute_tool_call(agent, Some(run_id), step, &call, true).await
+            } else {
+                let result = json!({
+                    "error": "The user rejected this tool call",
+                    "reason": approval.reason,
+                });
+                self.record_step(Some(run_id), Self::tool_step(step, &call, result.clone(), Some("rejected".to_string()), 0));
+                ToolResult {
+                    tool_call_id: call.id.clone(),
+                    result,
+                }
+            };
+            messages.push(Message::tool_result(&result.tool_call_id, &result.result));
+            tool_calls.push(call);
+            tool_results.push(result);
+        }
+
+        let steps = vec![AgentRunStep {
+            step,
+            content: None,
+            tool_calls,
+            tool_results,
+        }];
+        let result = self
+            .run_loop(agent, tools, messages, step + 1, steps, Some(run_id), &mut usage, None)
+            .await;
+
+        self.finish(Some(run_id), result, usage, None).await
+    }
+
+    /// Records how the run ended and reports it as the final event
+    async fn finish(
+        &self,
+        run_id: Option<Uuid>,
+        result: Result<AgentRunResult, AppError>,
+        usage: Map<String, Value>,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+    ) -> Result<AgentRunResult, AppError> {
+        // Paused runs were saved when they paused
+        let paused = matches!(&result, Ok(r) if r.status == AgentRunStatus::AwaitingApproval);
+        if let (Some(run_id), false) = (run_id, paused) {
+            self.finish_record(run_id, &result, Value::Object(usage));
+        }
+
+        if let Some(events) = events {
+            let event = match &result {
+                Ok(result) => AgentEvent::Final {
+                    status: result.status,
+                    output: result.output.clone(),
+                    steps: result.steps.len(),
+                },
+                Err(e) => AgentEvent::Error {
+                    message: e.to_string(),
+                },
+            };
+            let _ = events.send(event).await;
+        }
+
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn run_loop(
+        &self,
+        agent: &AgentResponse,
+        tools: &[Tool],
+        mut messages: Vec<Message>,
+        first_step: usize,
+        mut steps: Vec<AgentRunStep>,
+        run_id: Option<Uuid>,
+        usage: &mut Map<String, Value>,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+    ) -> Result<AgentRunResult, AppError> {
+        info!(
+            "Running agent {} with {} tools (max {} steps)",
+            agent.id,
+            tools.len(),
+            self.max_steps
+        );
+
+        for step in first_step..=self.max_steps {
+            let started = Instant::now();
+            let response = self.complete(&messages, tools, step, events).await;
+            let latency_ms = started.elapsed().as_millis() as i64;
+
+            let LLMChatOutput {
+                content,
+                tool_calls,
+                usage: call_usage,
+                usage_stats,
+                ..
+            } = match response {
+                Ok(output) => output,
+                Err(e) => {
+                    self.record_step(run_id, Self::llm_step(step, None, Some(e.to_string()), latency_ms, None));
+                    return Err(e);
+                }
+            };
+            let content = Some(content).filter(|c| !c.is_empty());
+
+            if let Some(call_usage) = &call_usage {
+                accumulate_usage(usage, call_usage);
+            }
+            if let (Some(recorder), Some(stats)) = (self.recorder.as_ref(), &usage_stats) {
+                UsageService::record_quietly(&recorder.pool, recorder.user_id, stats, UsageSource::Agent, None, None);
+            }
+            if let (Some(permit), Some(stats)) = (self.rate_limit.as_ref(), &usage_stats) {
+                permit.record_tokens(stats.tokens.total_tokens);
+            }
+            self.record_step(
+                run_id,
+                Self::llm_step(
+                    step,
+                    Some(json!({ "content": content, "tool_calls": tool_calls })),
+                    None,
+                    latency_ms,
+                    call_usage,
+                ),
+            );
+
+            if tool_calls.is_empty() {
+                debug!("Agent {} produced a final answer at step {}", agent.id, step);
+                steps.push(AgentRunStep {
+                    step,
+                    content: content.clone(),
+                    tool_calls,
+                    tool_results: Vec::new(),
+                });
+                return Ok(AgentRunResult {
+                    run_id,
+                    agent_id: agent.id,
+                    status: AgentRunStatus::Completed,
+                    output: content,
+                    steps,
+                    pending_approvals: Vec::new(),
+                });
+            }
+
+            if let Some(content) = content.as_ref().filter(|c| !c.trim().is_empty()) {
+                Self::emit(events, AgentEvent::Thought { step, content: content.clone() }).await;
+            }
+
+            let mut tool_results = Vec::with_capacity(tool_calls.len());
+            let mut pending = Vec::new();
+            for call in &tool_calls {
+                Self::emit(events, AgentEvent::ToolCall { step, call: call.clone() }).await;
+                // Calls outside the agent's tools are refused below rather than held for approval
+                if agent.tools.contains(&call.name) && self.manager.requires_approval(call).await {
+                    if run_id.is_some() {
+                        pending.push(call.clone());
+                        continue;
+                    }
+                    warn!("Can't pause unrecorded run for approval of '{}'", call.name);
+                }
+                let result = self.execute_tool_call(agent, run_id, step, call, false).await;
+                Self::emit(events, AgentEvent::ToolResult { step, result: result.clone() }).await;
+                tool_results.push(result);
+            }
+
+            messages.push(Message::assistant_with_tool_calls(
+                content.as_deref().unwrap_or_default(),
+                tool_calls.clone(),
+            ));
+            for result in &tool_results {
+                messages.push(Message::tool_result(&result.tool_call_id, &result.result));
+            }
+
+            steps.push(AgentRunStep {
+                step,
+                content,
+                tool_calls,
+                tool_results,
+            });
+
+            if let (Some(run_id), false) = (run_id, pending.is_empty()) {
+                let approvals = self.pause(run_id, step, messages, pending, usage)?;
+                for approval in &approvals {
+                    Self::emit(events, AgentEvent::ApprovalRequired { step, approval: approval.clone() }).await;
+                }
+                return Ok(AgentRunResult {
+                    run_id: Some(run_id),
+                    agent_id: agent.id,
+                    status: AgentRunStatus::AwaitingApproval,
+                    output: None,
+                    steps,
+                    pending_approvals: approvals,
+                });
+            }
+        }
+
+        warn!(
+            "Agent {} exhausted its step budget of {}",
+            agent.id, self.max_steps
+        );
+        Ok(AgentRunResult {
+            run_id,
+            agent_id: agent.id,
+            status: AgentRunStatus::MaxStepsExceeded,
+            output: None,
+            steps,
+            pending_approvals: Vec::new(),
+        })
+    }
+
+    /// Builds the system prompt from the agent's reasoning patterns and instructions
+    pub fn system_prompt(agent: &AgentResponse, tools: &[Tool]) -> String {
+        let patterns = agent
+            .reasoning_patterns
+            .iter()
+            .flatten()
+            .filter_map(|name| match name.parse::<ReasoningPattern>() {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    warn!("Ignoring reasoning pattern on agent {}: {}", agent.id, e);
+                    None
+                }
+            })
+            .collect();
+
+        let composed =
+            PatternComposer::new(patterns).system_prompt(&agent.name, &agent.description, tools);
+
+        match agent.system_prompt.as_deref().map(str::trim) {
+            Some(custom) if !custom.is_empty() => format!("{}\n\n{}", custom, composed),
+            _ => composed,
+        }
+    }
+
+    async fn emit(events: Option<&mpsc::Sender<AgentEvent>>, event: AgentEvent) {
+        if let Some(events) = events {
+            // A closed channel means the client went away; the run still completes
+            let _ = events.send(event).await;
+        }
+    }
+
+    async fn execute_tool_call(
+        &self,
+        agent: &AgentResponse,
+        run_id: Option<Uuid>,
+        step: usize,
+        call: &ToolCall,
+        approved: bool,
+    ) -> ToolResult {
+        let started = Instant::now();
+
+        // Only the agent's own tools may run, even if the model asks for others
+        let outcome = if !agent.tools.contains(&call.name) {
+            warn!("Agent {} requested unavailable tool '{}'", agent.id, call.name);
+            Err(format!("Tool not available to this agent: {}", call.name))
+        } else if approved {
+            self.manager.execute_approved_tool_call(call).await.map_err(|e| e.to_string())
+        } else {
+            self.manager.execute_tool_call(call).await.map_err(|e| e.to_string())
+        };
+        let latency_ms = started.elapsed().as_millis() as i64;
+
+        let (result, error) = match outcome {
+            Ok(result) => (result, None),
+            Err(e) => {
+                warn!("Tool '{}' failed: {}", call.name, e);
+                (json!({ "error": e }), Some(e))
+            }
+        };
+
+        self.record_step(run_id, Self::tool_step(step, call, result.clone(), error, latency_ms));
+
+        ToolResult {
+            tool_call_id: call.id.clone(),
+            result,
+        }
+    }
+
+    fn tool_step(
+        step: usize,
+        call: &ToolCall,
+        result: Value,
+        error: Option<String>,
+        latency_ms: i64,
+    ) -> NewAgentRunStep {
+        NewAgentRunStep {
+            id: Uuid::new_v4(),
+            run_id: Uuid::nil(),
+            step: step as i32,
+            kind: "tool_call".to_string(),
+            tool_name: Some(call.name.clone()),
+            tool_call_id: Some(call.id.clone()),
+            arguments: Some(call.arguments.clone()),
+            result: Some(result),
+            error,
+            latency_ms,
+            usage: None,
+        }
+    }
+
+    fn llm_step(
+        step: usize,
+        result: Option<Value>,
+        error: Option<String>,
+        latency_ms: i64,
+        usage: Option<Value>,
+    ) -> NewAgentRunStep {
+        NewAgentRunStep {
+            id: Uuid::new_v4(),
+            run_id: Uuid::nil(),
+            step: step as i32,
+            kind: "llm_call".to_string(),
+            tool_name: None,
+            tool_call_id: None,
+            arguments: None,
+            result,
+            error,
+            latency_ms,
+            usage,
+        }
+    }
+
+    fn start_record(&self, agent: &AgentResponse, input: &str) -> Option<Uuid> {
+        let recorder = self.recorder.as_ref()?;
+        match AgentRunService::create_run(&recorder.pool, agent.id, recorder.user_id, input) {
+            Ok(run) => Some(run.id),
+            Err(e) => {
+                error!("Failed to record run for agent {}: {}", agent.id, e);
+                None
+            }
+        }
+    }
+
+    fn record_step(&self, run_id: Option<Uuid>, mut step: NewAgentRunStep) {
+        let (Some(recorder), Some(run_id)) = (self.recorder.as_ref(), run_id) else {
+            return;
+        };
+        step.run_id = run_id;
+        if let Err(e) = AgentRunService::add_step(&recorder.pool, step) {
+            error!("Failed to record step for run {}: {}", run_id, e);
+        }
+    }
+
+    /// Saves the run's state and creates an approval for each held-back call
+    fn pause(
+        &self,
+        run_id: Uuid,
+        step: usize,
+        messages: Vec<Message>,
+        pending: Vec<ToolCall>,
+        usage: &Map<String, Value>,
+    ) -> Result<Vec<ToolApproval>, AppError> {
+        let recorder = self.recorder.as_ref().ok_or(AppError::InternalServerError)?;
+        let user_llm_config_id = recorder.user_llm_config_id.ok_or_else(|| {
+            AppError::ConfigurationError("Approval mode is set without an LLM config".to_string())
+        })?;
+
+        let state = PausedRunState {
+            user_llm_config_id,
+            max_steps: self.max_steps,
+            approval_mode: self.approval_mode,
+            step,
+            messages,
+        };
+        let state = serde_json::to_value(&state).map_err(|e| AppError::SerializationError(e.to_string()))?;
+
+        let approvals = pending
+            .into_iter()
+            .map(|call| NewToolApproval {
+                id: Uuid::new_v4(),
+                run_id,
+                user_id: recorder.user_id,
+                step: step as i32,
+                tool_call_id: call.id,
+                tool_name: call.name,
+                arguments: call.arguments,
+                status: "pending".to_string(),
+            })
+            .collect();
+
+        AgentRunService::pause_run(&recorder.pool, run_id, state, Some(Value::Object(usage.clone())), approvals)
+    }
+
+    fn finish_record(&self, run_id: Uuid, result: &Result<AgentRunResult, AppError>, usage: Value) {
+        let Some(recorder) = self.recorder.as_ref() else {
+            return;
+        };
+        let (status, output, error) = match result {
+            Ok(result) => (result.status.as_str(), result.output.clone(), None),
+            Err(e) => ("failed", None, Some(e.to_string())),
+        };
+        if let Err(e) =
+            AgentRunService::complete_run(&recorder.pool, run_id, status, output, error, Some(usage))
+        {
+            error!("Failed to finalize run {}: {}", run_id, e);
+        }
+    }
+
+    /// Asks the model for the next step. With an event channel the step's text is sent as
+    /// `token` events, streamed as it arrives where tool calls can be read from the stream.
+    async fn complete(
+        &self,
+        messages: &[Message],
+        tools: &[Tool],
+        step: usize,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+    ) -> Result<LLMChatOutput, AppError> {
+        let messages = messages.iter().map(LLMChatMessage::from).collect();
+        let options = LLMToolOptions {
+            tools: tools.to_vec(),
+            choice: ToolChoice::Auto,
+        };
+
+        if events.is_none() || !self.manager.streams_tool_calls() {
+            let output =
+                LLMService::chat_with_api_key(&self.provider, &self.api_key, messages, Some(&options))
+                    .await
+                    .map_err(|e| e.0)?;
+            if !output.content.is_empty() {
+                Self::emit(events, AgentEvent::Token { step, content: output.content.clone() }).await;
+            }
+            return Ok(output);
+        }
+
+        let chunks = LLMService::stream_with_api_key(&self.provider, &self.api_key, messages, &options)
+            .await
+            .map_err(|e| e.0)?;
+        Self::collect_stream(&self.provider, chunks, step, events).await
+    }
+
+    /// Assembles a streamed completion, emitting each text delta as a `token` event
+    async fn collect_stream(
+        provider: &LLMProvider,
+        mut chunks: Pin<Box<dyn Stream<Item = Result<LLMStreamChunk, LLMServiceError>> + Send>>,
+        step: usize,
+        events: Option<&mpsc::Sender<AgentEvent>>,
+    ) -> Result<LLMChatOutput, AppError> {
+        let mut output = LLMChatOutput {
+            provider_model: Some(provider.name.clone()),
+            ..Default::default()
+        };
+        let mut tokens: Option<TokenUsage> = None;
+        while let Some(chunk) = chunks.next().await {
+            match chunk.map_err(|e| e.0)? {
+                LLMStreamChunk::Text(text) => {
+                    if text.is_empty() {
+                        continue;
+                    }
+                    output.content.push_str(&text);
+                    Self::emit(events, AgentEvent::Token { step, content: text }).await;
+                }
+                LLMStreamChunk::ToolCall(call) => output.tool_calls.push(call),
+                LLMStreamChunk::Usage(report) => {
+                    if let Some(report_tokens) = TokenUsage::from_provider(&report) {
+                        tokens = Some(tokens.map_or(report_tokens, |tokens| tokens.merge(report_tokens)));
+                    }
+                }
+            }
+        }
+        // Some providers split the report across events, so the merged counts are recorded
+        output.usage = tokens.map(|tokens| json!(tokens));
+        output.usage_stats = tokens.map(|tokens| UsageStats::new(provider, tokens));
+        Ok(output)
+    }
+}
+
+/// Adds the numeric fields of a provider usage object to the running totals
+fn accumulate_usage(totals: &mut Map<String, Value>, usage: &Value) {
+    let Some(usage) = usage.as_object() else {
+        return;
+    };
+    for (key, value) in usage {
+        if let Some(count) = value.as_u64() {
+            let total = totals.get(key).and_then(Value::as_u64).unwrap_or(0);
+            totals.insert(key.clone(), json!(total + count));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn agent(patterns: Option<Vec<String>>, system_prompt: Option<String>) -> AgentResponse {
+        AgentResponse {
+            id: Uuid::new_v4(),
+            name: "Tester".to_string(),
+            description: "runs tests".to_string(),
+            tools: vec!["get_weather".to_string()],
+            icon: None,
+            system_prompt,
+            reasoning_patterns: patterns,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn system_prompt_includes_patterns_and_instructions() {
+        let agent = agent(
+            Some(vec!["Planning".to_string(), "bogus".to_string()]),
+            Some("Always answer in French.".to_string()),
+        );
+        let prompt = AgentRuntime::system_prompt(&agent, &[]);
+        assert!(prompt.starts_with("Always answer in French."));
+        assert!(prompt.contains("detailed plan"));
+        assert!(prompt.contains("You are Tester"));
+    }
+
+    #[test]
+    fn events_serialize_with_type_tag() {
+        let event = AgentEvent::Token {
+            step: 2,
+            content: "Hello".to_string(),
+        };
+        assert_eq!(event.event_name(), "token");
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], "token");
+        assert_eq!(value["step"], 2);
+    }
+
+    #[tokio::test]
+    async fn streamed_text_is_sent_as_token_events() {
+        let now = Utc::now().naive_utc();
+        let provider = LLMProvider {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            name: "work".to_string(),
+            provider_type: "gpt".to_string(),
+            api_endpoint: String::new(),
+            supported_modalities: json!(["text"]),
+            configuration: json!({ "model": "gpt-4o" }),
+            created_at: now,
+            updated_at: now,
+        };
+        let chunks: Vec<Result<LLMStreamChunk, LLMServiceError>> = vec![
+            Ok(LLMStreamChunk::Text("It is ".to_string())),
+            Ok(LLMStreamChunk::Text("sunny ".to_string())),
+            Ok(LLMStreamChunk::Text("in Paris.".to_string())),
+            Ok(LLMStreamChunk::Usage(json!({ "prompt_tokens": 12, "completion_tokens": 4 }))),
+        ];
+
+        let (tx, mut rx) = mpsc::channel(10);
+        let output = AgentRuntime::collect_stream(&provider, Box::pin(futures::stream::iter(chunks)), 3, Some(&tx))
+            .await
+            .unwrap();
+        drop(tx);
+
+        let mut tokens = Vec::new();
+        while let Some(event) = rx.recv().await {
+            match event {
+                AgentEvent::Token { step, content } => {
+                    assert_eq!(step, 3);
+                    tokens.push(content);
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        assert_eq!(tokens, ["It is ", "sunny ", "in Paris."]);
+        assert_eq!(output.content, "It is sunny in Paris.");
+        assert!(output.tool_calls.is_empty());
+        assert_eq!(output.usage_stats.unwrap().tokens.total_tokens, 16);
+    }
+
+    #[test]
+    fn paused_state_round_trips() {
+        let state = PausedRunState {
+            user_llm_config_id: Uuid::new_v4(),
+            max_steps: 8,
+            approval_mode: ApprovalMode::All,
+            step: 3,
+            messages: vec![
+                Message::user("Email the report"),
+                Message::assistant_with_tool_calls(
+                    "",
+                    vec![ToolCall {
+                        id: "call_1".to_string(),
+                        name: "send_email".to_string(),
+                        arguments: json!({ "to": "ops@example.com" }),
+                    }],
+                ),
+            ],
+        };
+
+        let value = serde_json::to_value(&state).unwrap();
+        assert_eq!(value["approval_mode"], "all");
+        let restored: PausedRunState = serde_json::from_value(value).unwrap();
+        assert_eq!(restored.step, 3);
+        assert_eq!(restored.messages.len(), 2);
+    }
+
+    #[test]
+    fn usage_is_summed_across_calls() {
+        let mut totals = Map::new();
+        accumulate_usage(&mut totals, &json!({ "prompt_tokens": 10, "completion_tokens": 5 }));
+        accumulate_usage(&mut totals, &json!({ "prompt_tokens": 7, "model": "gpt-4" }));
+        assert_eq!(totals["prompt_tokens"], 17);
+        assert_eq!(totals["completion_tokens"], 5);
+        assert!(!totals.contains_key("model"));
+    }
+}
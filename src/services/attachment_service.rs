@@ -1,7 +1,7 @@
 use crate::db::DbPool;
 use crate::error::AppError;
 use crate::models::attachment::{Attachment, AttachmentType, NewAttachment};
-use crate::schema::attachments;
+use crate::schema::{attachments, conversations, messages};
 use diesel::prelude::*;
 use log::{debug, error, info};
 use regex::Regex;
@@ -11,6 +11,10 @@ use tokio::fs;
 use url::Url;
 use uuid::Uuid;
 
+/// Largest image or document accepted as an upload, which still fits the 10MB JSON body
+/// limit once base64-encoded
+pub const MAX_UPLOAD_BYTES: usize = 7 * 1024 * 1024;
+
 pub struct AttachmentService;
 
 impl AttachmentService {
@@ -130,6 +134,93 @@ impl AttachmentService {
             .map_err(AppError::DatabaseError)
     }
 
+    /// Returns the user owning the conversation a message belongs to
+    pub fn message_owner(pool: &DbPool, message_id: Uuid) -> Result<Uuid, AppError> {
+        let conn = &mut pool.get()?;
+        messages::table
+            .inner_join(conversations::table)
+            .filter(messages::id.eq(message_id))
+            .select(conversations::user_id)
+            .first::<Uuid>(conn)
+            .optional()
+            .map_err(AppError::DatabaseError)?
+            .ok_or_else(|| AppError::NotFoundError(format!("Message not found: {}", message_id)))
+    }
+
+    /// Stores an uploaded image or document so it can be sent to a model by attachment id
+    pub async fn store_upload(
+        pool: &DbPool,
+        message_id: Uuid,
+        file_name: &str,
+        bytes: &[u8],
+    ) -> Result<Attachment, AppError> {
+        if bytes.len() > MAX_UPLOAD_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "Attachment is larger than {} bytes",
+                MAX_UPLOAD_BYTES
+            )));
+        }
+
+        let media_type = mime_guess::from_path(file_name).first_or_octet_stream();
+        let attachment_type = match (media_type.type_().as_str(), media_type.subtype().as_str()) {
+            ("image", _) => AttachmentType::Image,
+            ("application", "pdf") | ("text", _) => AttachmentType::Document,
+            _ => {
+                return Err(AppError::BadRequest(format!(
+                    "Unsupported attachment type: {}",
+                    media_type
+                )))
+            }
+        };
+
+        let extension = file_name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("bin");
+        let attachment_dir = Self::get_attachment_dir();
+        let file_path = attachment_dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+        debug!("Storing {} upload at {:?}", media_type, file_path);
+
+        fs::create_dir_all(&attachment_dir).await.map_err(|e| {
+            error!("Failed to create attachments directory: {:?}", e);
+            AppError::InternalServerError
+        })?;
+        fs::write(&file_path, bytes).await.map_err(|e| {
+            error!("Failed to write attachment file: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+        Self::create_attachment(
+            pool,
+            message_id,
+            attachment_type,
+            file_path.to_str().unwrap().to_string(),
+        )
+    }
+
+    /// Reads an attachment from one of the user's conversations, returning its media type and bytes
+    pub async fn read_for_user(
+        pool: &DbPool,
+        user_id: Uuid,
+        attachment_id: Uuid,
+    ) -> Result<(String, Vec<u8>), AppError> {
+        let attachment = Self::get_attachment(pool, attachment_id)
+            .map_err(|_| AppError::NotFoundError(format!("Attachment not found: {}", attachment_id)))?;
+        if Self::message_owner(pool, attachment.message_id)? != user_id {
+            return Err(AppError::NotFoundError(format!(
+                "Attachment not found: {}",
+                attachment_id
+            )));
+        }
+
+        let bytes = fs::read(&attachment.file_path).await.map_err(|e| {
+            error!("Failed to read attachment file {}: {:?}", attachment.file_path, e);
+            AppError::TempFileError(format!("Failed to read file: {}", e))
+        })?;
+        let media_type = mime_guess::from_path(&attachment.file_path)
+            .first_or_octet_stream()
+            .to_string();
+
+        Ok((media_type, bytes))
+    }
+
     pub async fn delete_attachment(pool: &DbPool, attachment_id: Uuid) -> Result<(), AppError> {
         use crate::schema::attachments::dsl;
 
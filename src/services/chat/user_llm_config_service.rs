@@ -28,6 +28,7 @@ impl UserLLMConfigService {
             provider_id: _provider_id,
             api_key_id: _api_key_id,
             description: _description,
+            fallbacks: None,
         };
 
         let result = diesel::insert_into(user_llm_configs)
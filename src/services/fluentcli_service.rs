@@ -1,7 +1,8 @@
 use crate::error::AppError;
-use crate::models::fluentcli::{CommandRequest, CommandResult};
-use log::{debug, info};
+use crate::models::fluentcli::{CommandRequest, CommandResult, OutputLine, WorkerEvent};
+use log::{debug, info, warn};
 use reqwest;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
 const WORKER_ADDRESS: &str = "http://worker:8080"; // Adjust this to match your Docker setup
@@ -9,9 +10,11 @@ const WORKER_ADDRESS: &str = "http://worker:8080"; // Adjust this to match your
 pub struct FluentCLIService;
 
 impl FluentCLIService {
+    /// Runs a command on the worker, sending each line of its output to `lines` as it's produced
     pub async fn execute_command(
         user_id: Uuid,
         command: CommandRequest,
+        lines: mpsc::UnboundedSender<OutputLine>,
     ) -> Result<CommandResult, AppError> {
         info!("Executing command for user_id: {}", user_id);
         debug!("Command request: {:?}", command);
@@ -24,22 +27,174 @@ impl FluentCLIService {
             .await
             .map_err(|e| AppError::ExternalServiceError(e.to_string()))?;
 
-        let result: CommandResult = response
-            .json()
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::ExternalServiceError(format!(
+                "Worker returned {}: {}",
+                status, body
+            )));
+        }
+
+        // Workers that don't stream answer with the whole result once the command exits
+        let is_json = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .map_or(false, |content_type| {
+                content_type.starts_with("application/json")
+            });
+        if is_json {
+            return response
+                .json::<CommandResult>()
+                .await
+                .map_err(|e| AppError::ExternalServiceError(e.to_string()));
+        }
+
+        let mut response = response;
+        let mut events = WorkerEvents::default();
+        while let Some(chunk) = response
+            .chunk()
             .await
-            .map_err(|e| AppError::ExternalServiceError(e.to_string()))?;
+            .map_err(|e| AppError::ExternalServiceError(e.to_string()))?
+        {
+            if let Some(result) = events.read(&chunk, &lines) {
+                return Ok(result);
+            }
+        }
 
-        Ok(result)
+        Err(AppError::ExternalServiceError(
+            "Worker closed the output stream before the command exited".to_string(),
+        ))
     }
+}
 
-    pub async fn stop_command(run_id: Uuid) -> Result<(), AppError> {
-        let client = reqwest::Client::new();
-        client
-            .post(&format!("{}/stop", WORKER_ADDRESS))
-            .json(&serde_json::json!({"run_id": run_id}))
-            .send()
-            .await
-            .map_err(|e| AppError::ExternalServiceError(e.to_string()))?;
-        Ok(())
+/// Reads the worker's NDJSON events from its response, whose chunks can end mid-line
+#[derive(Default)]
+struct WorkerEvents {
+    buffer: Vec<u8>,
+    stdout: String,
+    stderr: String,
+}
+
+impl WorkerEvents {
+    /// Takes the next chunk, sending each complete output line to `lines`. Returns the result
+    /// once the command's exit event has been read.
+    fn read(
+        &mut self,
+        chunk: &[u8],
+        lines: &mpsc::UnboundedSender<OutputLine>,
+    ) -> Option<CommandResult> {
+        self.buffer.extend_from_slice(chunk);
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let event: Vec<u8> = self.buffer.drain(..=end).collect();
+            match serde_json::from_slice::<WorkerEvent>(&event) {
+                Ok(WorkerEvent::Log { stream, line }) => {
+                    let (stream, output) = if stream == "stderr" {
+                        ("stderr", &mut self.stderr)
+                    } else {
+                        ("stdout", &mut self.stdout)
+                    };
+                    output.push_str(&line);
+                    output.push('\n');
+                    let _ = lines.send(OutputLine {
+                        stream: stream.to_string(),
+                        line,
+                    });
+                }
+                Ok(WorkerEvent::Exit {
+                    exit_code,
+                    error,
+                    stopped,
+                }) => {
+                    let stderr = std::mem::take(&mut self.stderr);
+                    return Some(CommandResult {
+                        output: std::mem::take(&mut self.stdout),
+                        error: error.or_else(|| (exit_code != 0).then_some(stderr)),
+                        exit_code,
+                        stopped,
+                    });
+                }
+                Err(e) => warn!("Ignoring unreadable worker event: {}", e),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received(rx: &mut mpsc::UnboundedReceiver<OutputLine>) -> Vec<(String, String)> {
+        let mut lines = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            lines.push((line.stream, line.line));
+        }
+        lines
+    }
+
+    #[test]
+    fn events_split_across_chunks_are_read_once_complete() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut events = WorkerEvents::default();
+
+        assert!(events
+            .read(br#"{"type":"log","stream":"stdout","li"#, &tx)
+            .is_none());
+        assert!(received(&mut rx).is_empty());
+
+        assert!(events
+            .read(b"ne\":\"one\"}\n{\"type\":\"log\",\"stream\":\"stderr\",\"line\":\"two\"}\n{\"type\":\"ex", &tx)
+            .is_none());
+        assert_eq!(
+            received(&mut rx),
+            vec![
+                ("stdout".to_string(), "one".to_string()),
+                ("stderr".to_string(), "two".to_string()),
+            ]
+        );
+
+        assert!(events
+            .read(br#"it","exit_code":0,"error":null}"#, &tx)
+            .is_none());
+        let result = events.read(b"\n", &tx).unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.output, "one\n");
+        assert_eq!(result.error, None);
+        assert!(!result.stopped);
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut events = WorkerEvents::default();
+        let stream = concat!(
+            "not json\n",
+            "\n",
+            "{\"type\":\"progress\",\"percent\":50}\n",
+            "{\"type\":\"log\",\"stream\":\"stdout\"}\n",
+            "{\"type\":\"log\",\"stream\":\"stderr\",\"line\":\"boom\"}\r\n",
+            "{\"type\":\"exit\",\"exit_code\":2,\"error\":null,\"stopped\":false}\n",
+        );
+
+        let result = events.read(stream.as_bytes(), &tx).unwrap();
+        assert_eq!(
+            received(&mut rx),
+            vec![("stderr".to_string(), "boom".to_string())]
+        );
+        assert_eq!(result.exit_code, 2);
+        assert_eq!(result.output, "");
+        assert_eq!(result.error.as_deref(), Some("boom\n"));
+    }
+
+    #[test]
+    fn a_stream_without_an_exit_event_has_no_result() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut events = WorkerEvents::default();
+        let partial = "{\"type\":\"log\",\"stream\":\"stdout\",\"line\":\"a\"}\n{\"type\":\"exit\",\"exit_code\":0";
+
+        assert!(events.read(partial.as_bytes(), &tx).is_none());
+        assert_eq!(received(&mut rx), vec![("stdout".to_string(), "a".to_string())]);
     }
 }
@@ -1,5 +1,6 @@
 use std::fmt;
 use crate::services::function_calling::tool::error::ToolError;
+use crate::services::function_calling::types::ToolCall;
 
 /// Represents an error that can occur in the function calling system
 #[derive(Debug)]
@@ -14,6 +15,8 @@ pub enum FunctionCallingError {
     ProviderError(String),
     /// Error occurred due to an invalid configuration
     ConfigurationError(String),
+    /// The response contains tool calls that have to be approved before they run
+    ApprovalRequired(Vec<ToolCall>),
 }
 
 impl fmt::Display for FunctionCallingError {
@@ -24,6 +27,10 @@ impl fmt::Display for FunctionCallingError {
             Self::ToolExecutionError(err) => write!(f, "Tool execution error: {}", err),
             Self::ProviderError(msg) => write!(f, "Provider error: {}", msg),
             Self::ConfigurationError(msg) => write!(f, "Configuration error: {}", msg),
+            Self::ApprovalRequired(calls) => {
+                let names: Vec<&str> = calls.iter().map(|call| call.name.as_str()).collect();
+                write!(f, "Approval required for: {}", names.join(", "))
+            }
         }
     }
 }
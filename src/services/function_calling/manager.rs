@@ -1,15 +1,38 @@
 use std::sync::Arc;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use log::{debug, error, info};
-use crate::services::function_calling::types::{Tool, ToolResult};
+use log::{debug, error, info, warn};
+use crate::services::function_calling::types::{Message, Tool, ToolCall, ToolResult};
 use crate::services::function_calling::provider::adapter::{ProviderAdapter, ToolChoice};
 use crate::services::function_calling::tool::registry::ToolRegistry;
 use crate::services::function_calling::error::FunctionCallingError;
+use crate::services::function_calling::tool::error::ToolError;
+
+/// Which tool calls must be approved by a person before they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalMode {
+    /// Every call runs immediately; dangerous tools fail as usual
+    Off,
+    /// Calls to tools marked as dangerous wait for approval
+    #[default]
+    Dangerous,
+    /// Every call waits for approval
+    All,
+}
+
+/// Tool calls from a response, split into executed results and calls awaiting approval
+#[derive(Debug, Default)]
+pub struct HandledToolCalls {
+    pub results: Vec<ToolResult>,
+    pub pending_approval: Vec<ToolCall>,
+}
 
 /// Manager for function calling
 pub struct FunctionCallingManager {
     provider_adapter: Arc<dyn ProviderAdapter>,
     tool_registry: Arc<ToolRegistry>,
+    approval_mode: ApprovalMode,
 }
 
 impl FunctionCallingManager {
@@ -18,6 +41,22 @@ impl FunctionCallingManager {
         Self {
             provider_adapter,
             tool_registry,
+            approval_mode: ApprovalMode::Off,
+        }
+    }
+
+    /// Sets which tool calls are held back for approval instead of being executed
+    pub fn with_approval_mode(mut self, approval_mode: ApprovalMode) -> Self {
+        self.approval_mode = approval_mode;
+        self
+    }
+
+    /// Returns true if the call has to be approved before it runs
+    pub async fn requires_approval(&self, call: &ToolCall) -> bool {
+        match self.approval_mode {
+            ApprovalMode::Off => false,
+            ApprovalMode::Dangerous => self.tool_registry.is_dangerous(&call.name).await,
+            ApprovalMode::All => true,
         }
     }
     
@@ -27,20 +66,37 @@ impl FunctionCallingManager {
         self.provider_adapter.prepare_request(request, tools, choice);
     }
     
-    /// Handles a response from the provider
+    /// Handles a response from the provider, failing with `ApprovalRequired` if any
+    /// call has to be approved first
     pub async fn handle_response(&self, response: &Value) -> Result<Vec<ToolResult>, FunctionCallingError> {
+        let handled = self.handle_response_with_approval(response).await?;
+        if !handled.pending_approval.is_empty() {
+            return Err(FunctionCallingError::ApprovalRequired(handled.pending_approval));
+        }
+        Ok(handled.results)
+    }
+
+    /// Handles a response from the provider, executing the calls that don't need
+    /// approval and returning the rest untouched
+    pub async fn handle_response_with_approval(&self, response: &Value) -> Result<HandledToolCalls, FunctionCallingError> {
         debug!("Handling response from provider");
         let tool_calls = self.provider_adapter.parse_tool_calls(response).await?;
         
         if tool_calls.is_empty() {
             debug!("No tool calls found in response");
-            return Ok(vec![]);
+            return Ok(HandledToolCalls::default());
         }
         
         info!("Found {} tool calls in response", tool_calls.len());
         
-        let mut results = Vec::new();
+        let mut handled = HandledToolCalls::default();
         for call in tool_calls {
+            if self.requires_approval(&call).await {
+                info!("Tool call '{}' is waiting for approval", call.name);
+                handled.pending_approval.push(call);
+                continue;
+            }
+
             debug!("Executing tool: {}", call.name);
             let result = match self.tool_registry.execute(&call.name, call.arguments.clone()).await {
                 Ok(result) => {
@@ -53,15 +109,67 @@ impl FunctionCallingManager {
                 }
             };
             
-            results.push(ToolResult {
+            handled.results.push(ToolResult {
                 tool_call_id: call.id,
                 result,
             });
         }
         
-        Ok(results)
+        Ok(handled)
     }
     
+    /// Writes the conversation into the request in the provider's format
+    pub fn format_messages(&self, request: &mut Value, messages: &[Message]) {
+        self.provider_adapter.format_messages(request, messages);
+    }
+
+    /// Parses tool calls from a provider response without executing them
+    pub async fn parse_tool_calls(&self, response: &Value) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        self.provider_adapter.parse_tool_calls(response).await
+    }
+
+    /// Returns true if tool calls can be read from the provider's streamed responses
+    pub fn streams_tool_calls(&self) -> bool {
+        self.provider_adapter.stream_assembler().is_some()
+    }
+
+    /// Extracts the assistant's text content from a provider response
+    pub fn parse_content(&self, response: &Value) -> Option<String> {
+        self.provider_adapter.parse_content(response)
+    }
+
+    /// Executes a single tool call through the registry
+    pub async fn execute_tool_call(&self, call: &ToolCall) -> Result<Value, ToolError> {
+        debug!("Executing tool: {}", call.name);
+        self.tool_registry.execute(&call.name, call.arguments.clone()).await
+    }
+
+    /// Executes a call a person has approved, including calls to dangerous tools
+    pub async fn execute_approved_tool_call(&self, call: &ToolCall) -> Result<Value, ToolError> {
+        debug!("Executing approved tool: {}", call.name);
+        self.tool_registry.execute_confirmed(&call.name, call.arguments.clone()).await
+    }
+
+    /// Executes tool calls, reporting failures as results so the model can recover
+    pub async fn execute_tool_calls(&self, calls: &[ToolCall]) -> Vec<ToolResult> {
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            let result = match self.execute_tool_call(call).await {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!("Tool '{}' failed: {}", call.name, e);
+                    serde_json::json!({ "error": e.to_string() })
+                }
+            };
+
+            results.push(ToolResult {
+                tool_call_id: call.id.clone(),
+                result,
+            });
+        }
+        results
+    }
+
     /// Returns the name of the provider
     pub fn provider_name(&self) -> &str {
         self.provider_adapter.name()
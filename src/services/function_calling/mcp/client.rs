@@ -0,0 +1,124 @@
+use log::{debug, info};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use crate::services::function_calling::mcp::transport::{HttpTransport, McpTransport, StdioTransport};
+use crate::services::function_calling::tool::error::ToolError;
+
+/// MCP protocol revision this client speaks
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// A tool advertised by an MCP server through `tools/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpToolDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(rename = "inputSchema", default)]
+    pub input_schema: Value,
+}
+
+/// Client for a single MCP server connection
+pub struct McpClient {
+    transport: Box<dyn McpTransport>,
+}
+
+impl McpClient {
+    /// Performs the initialization handshake over an existing transport
+    pub async fn connect(transport: Box<dyn McpTransport>) -> Result<Self, ToolError> {
+        let client = Self { transport };
+
+        let result = client.transport.request("initialize", json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            }
+        })).await?;
+
+        info!(
+            "Connected to MCP server {} (protocol {})",
+            result["serverInfo"]["name"].as_str().unwrap_or("unknown"),
+            result["protocolVersion"].as_str().unwrap_or("unknown")
+        );
+
+        client.transport.notify("notifications/initialized", json!({})).await?;
+        Ok(client)
+    }
+
+    /// Starts a server as a child process and connects to it over stdio
+    pub async fn connect_stdio(
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<Self, ToolError> {
+        Self::connect(Box::new(StdioTransport::spawn(command, args, env)?)).await
+    }
+
+    /// Connects to a server over HTTP, sending every request with `client`
+    pub async fn connect_http(
+        client: Client,
+        url: &str,
+        headers: HashMap<String, String>,
+    ) -> Result<Self, ToolError> {
+        Self::connect(Box::new(HttpTransport::new(client, url, headers))).await
+    }
+
+    /// Lists every tool the server offers, following pagination cursors
+    pub async fn list_tools(&self) -> Result<Vec<McpToolDefinition>, ToolError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.transport.request("tools/list", params).await
+                .map_err(|e| ToolError::DiscoveryError(e.to_string()))?;
+
+            let page: Vec<McpToolDefinition> = serde_json::from_value(result["tools"].clone())
+                .map_err(|e| ToolError::DiscoveryError(format!("Invalid tools/list result: {}", e)))?;
+            tools.extend(page);
+
+            match result["nextCursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => break,
+            }
+        }
+
+        debug!("MCP server offers {} tools", tools.len());
+        Ok(tools)
+    }
+
+    /// Calls a tool and returns its result as JSON
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, ToolError> {
+        let result = self.transport.request("tools/call", json!({
+            "name": name,
+            "arguments": arguments,
+        })).await?;
+
+        let text = result["content"].as_array()
+            .map(|content| content.iter()
+                .filter_map(|item| item["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n"))
+            .unwrap_or_default();
+
+        if result["isError"].as_bool().unwrap_or(false) {
+            return Err(ToolError::RemoteToolError(text));
+        }
+
+        if let Some(structured) = result.get("structuredContent") {
+            return Ok(structured.clone());
+        }
+
+        // Most servers return JSON as text content, so hand it back structured when possible
+        Ok(serde_json::from_str(&text).unwrap_or_else(|_| match result["content"].as_array() {
+            Some(content) if content.iter().all(|item| item["type"] == "text") => Value::String(text),
+            _ => result["content"].clone(),
+        }))
+    }
+}
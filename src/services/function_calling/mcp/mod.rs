@@ -0,0 +1,7 @@
+pub mod client;
+pub mod tool;
+pub mod transport;
+
+pub use client::{McpClient, McpToolDefinition};
+pub use tool::{register_mcp_tools, schema_to_parameters, McpTool};
+pub use transport::{HttpTransport, McpTransport, StdioTransport};
@@ -0,0 +1,124 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use crate::services::function_calling::mcp::client::{McpClient, McpToolDefinition};
+use crate::services::function_calling::tool::error::ToolError;
+use crate::services::function_calling::tool::executor::{ToolExecutor, ToolExecutorBase};
+use crate::services::function_calling::tool::registry::ToolRegistry;
+use crate::services::function_calling::types::{ParameterType, ToolParameter};
+
+/// A tool that proxies calls to an MCP server
+pub struct McpTool {
+    client: Arc<McpClient>,
+    name: String,
+    remote_name: String,
+    description: String,
+}
+
+impl McpTool {
+    /// Wraps a tool discovered on a server, registering it under `{server}__{tool}`
+    pub fn new(client: Arc<McpClient>, server_name: &str, definition: &McpToolDefinition) -> Self {
+        Self {
+            client,
+            name: registered_name(server_name, &definition.name),
+            remote_name: definition.name.clone(),
+            description: definition.description.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl ToolExecutorBase for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for McpTool {
+    async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+        self.client.call_tool(&self.remote_name, args).await
+    }
+}
+
+/// Registers every tool from a server with the registry, returning their registered names
+pub async fn register_mcp_tools(
+    registry: &ToolRegistry,
+    client: Arc<McpClient>,
+    server_name: &str,
+    definitions: &[McpToolDefinition],
+) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for definition in definitions {
+        let tool = McpTool::new(client.clone(), server_name, definition);
+        let name = tool.name.clone();
+
+        registry.register_remote(&name, tool).await;
+        registry.update_tool_parameters(&name, schema_to_parameters(&definition.input_schema)).await;
+        names.push(name);
+    }
+
+    names
+}
+
+/// Builds the name a remote tool is registered under, keeping it valid for every provider
+pub fn registered_name(server_name: &str, tool_name: &str) -> String {
+    format!("{}__{}", server_name, tool_name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// Maps an object JSON schema from `tools/list` into tool parameters
+pub fn schema_to_parameters(schema: &Value) -> Vec<ToolParameter> {
+    let required: Vec<&str> = schema["required"].as_array()
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    schema["properties"].as_object()
+        .map(|properties| properties.iter().map(|(name, property)| {
+            let parameter = ToolParameter::new(name, schema_to_type(property), required.contains(&name.as_str()));
+            match property["description"].as_str() {
+                Some(description) => parameter.with_description(description),
+                None => parameter,
+            }
+        }).collect())
+        .unwrap_or_default()
+}
+
+fn schema_to_type(schema: &Value) -> ParameterType {
+    // Nullable types are written as ["string", "null"]
+    let type_name = match &schema["type"] {
+        Value::String(name) => name.as_str(),
+        Value::Array(names) => names.iter()
+            .filter_map(Value::as_str)
+            .find(|name| *name != "null")
+            .unwrap_or("string"),
+        _ if schema["properties"].is_object() => "object",
+        _ => "string",
+    };
+
+    match type_name {
+        "number" | "integer" => ParameterType::Number {
+            minimum: schema["minimum"].as_f64(),
+            maximum: schema["maximum"].as_f64(),
+        },
+        "boolean" => ParameterType::Boolean,
+        "object" => ParameterType::Object {
+            properties: schema_to_parameters(schema),
+        },
+        "array" => ParameterType::Array {
+            items: Box::new(schema_to_type(&schema["items"])),
+        },
+        _ => ParameterType::String {
+            format: schema["format"].as_str().map(str::to_string),
+            enum_values: schema["enum"].as_array().map(|values| {
+                values.iter().filter_map(Value::as_str).map(str::to_string).collect()
+            }),
+        },
+    }
+}
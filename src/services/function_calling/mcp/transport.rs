@@ -0,0 +1,249 @@
+use async_trait::async_trait;
+use log::{debug, warn};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex};
+use crate::services::function_calling::provider::stream::SseBuffer;
+use crate::services::function_calling::tool::error::ToolError;
+
+/// How long to wait for a server to answer a single request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+type PendingRequests = Arc<StdMutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// Sends JSON-RPC messages to an MCP server
+#[async_trait]
+pub trait McpTransport: Send + Sync {
+    /// Sends a request and waits for its result
+    async fn request(&self, method: &str, params: Value) -> Result<Value, ToolError>;
+
+    /// Sends a notification, which has no response
+    async fn notify(&self, method: &str, params: Value) -> Result<(), ToolError>;
+}
+
+fn request_message(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+/// Unwraps a JSON-RPC response into its result
+fn into_result(response: Value) -> Result<Value, ToolError> {
+    if let Some(error) = response.get("error") {
+        return Err(ToolError::RemoteToolError(format!(
+            "{} (code {})",
+            error["message"].as_str().unwrap_or("Unknown error"),
+            error["code"]
+        )));
+    }
+
+    Ok(response.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// Talks to a server running as a child process over newline-delimited JSON on stdin/stdout
+pub struct StdioTransport {
+    // Held so the process is killed when the transport is dropped
+    _child: Child,
+    stdin: Mutex<ChildStdin>,
+    pending: PendingRequests,
+    next_id: AtomicU64,
+}
+
+impl StdioTransport {
+    /// Starts the server process
+    pub fn spawn(command: &str, args: &[String], env: &HashMap<String, String>) -> Result<Self, ToolError> {
+        let mut child = Command::new(command)
+            .args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ToolError::ExternalServiceError(
+                format!("Failed to start MCP server '{}': {}", command, e)
+            ))?;
+
+        let stdin = child.stdin.take()
+            .ok_or_else(|| ToolError::InternalError("MCP server stdin unavailable".to_string()))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| ToolError::InternalError("MCP server stdout unavailable".to_string()))?;
+
+        let pending: PendingRequests = Arc::new(StdMutex::new(HashMap::new()));
+        tokio::spawn(Self::read_responses(stdout, pending.clone()));
+
+        Ok(Self {
+            _child: child,
+            stdin: Mutex::new(stdin),
+            pending,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Routes responses from the server to the requests waiting on them
+    async fn read_responses(stdout: ChildStdout, pending: PendingRequests) {
+        let mut lines = BufReader::new(stdout).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let message: Value = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(_) => {
+                    debug!("Ignoring non-JSON output from MCP server: {}", line);
+                    continue;
+                }
+            };
+
+            // Server-initiated requests and notifications carry a method
+            match message["id"].as_u64() {
+                Some(id) if message.get("method").is_none() => {
+                    if let Some(sender) = pending.lock().unwrap().remove(&id) {
+                        let _ = sender.send(message);
+                    }
+                },
+                _ => debug!("Ignoring MCP server message: {}", line),
+            }
+        }
+
+        // Dropping the senders fails every request still waiting
+        warn!("MCP server closed its output");
+        pending.lock().unwrap().clear();
+    }
+
+    async fn write(&self, message: &Value) -> Result<(), ToolError> {
+        let mut line = message.to_string();
+        line.push('\n');
+
+        let mut stdin = self.stdin.lock().await;
+        let written = match stdin.write_all(line.as_bytes()).await {
+            Ok(()) => stdin.flush().await,
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| ToolError::NetworkError(format!("Failed to write to MCP server: {}", e)))
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, ToolError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+
+        if let Err(e) = self.write(&request_message(id, method, params)).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, receiver).await {
+            Ok(Ok(response)) => into_result(response),
+            Ok(Err(_)) => Err(ToolError::RemoteToolError("MCP server exited".to_string())),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(ToolError::RemoteToolError(format!("MCP request '{}' timed out", method)))
+            },
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), ToolError> {
+        self.write(&json!({ "jsonrpc": "2.0", "method": method, "params": params })).await
+    }
+}
+
+/// Talks to a server over HTTP, accepting either JSON or SSE-framed responses
+pub struct HttpTransport {
+    client: Client,
+    url: String,
+    headers: HashMap<String, String>,
+    session_id: Mutex<Option<String>>,
+    next_id: AtomicU64,
+}
+
+impl HttpTransport {
+    /// Creates a transport that sends its requests with `client`
+    pub fn new(client: Client, url: &str, headers: HashMap<String, String>) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+            headers,
+            session_id: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response, ToolError> {
+        let mut request = self.client
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .timeout(REQUEST_TIMEOUT)
+            .json(message);
+
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if let Some(session_id) = self.session_id.lock().await.clone() {
+            request = request.header("Mcp-Session-Id", session_id);
+        }
+
+        let response = request.send().await?;
+
+        if let Some(session_id) = response.headers().get("mcp-session-id").and_then(|v| v.to_str().ok()) {
+            *self.session_id.lock().await = Some(session_id.to_string());
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ToolError::RemoteToolError(format!(
+                "MCP server returned {}: {}", status, body
+            )));
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl McpTransport for HttpTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, ToolError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let response = self.post(&request_message(id, method, params)).await?;
+
+        let is_event_stream = response.headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .map_or(false, |content_type| content_type.starts_with("text/event-stream"));
+
+        if !is_event_stream {
+            return into_result(response.json::<Value>().await?);
+        }
+
+        // The response to our request may follow other server messages on the stream
+        let mut sse = SseBuffer::new();
+        let mut response = response;
+        while let Some(chunk) = response.chunk().await? {
+            for payload in sse.push(&chunk) {
+                let message: Value = match serde_json::from_str(&payload) {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                };
+                if message["id"].as_u64() == Some(id) && message.get("method").is_none() {
+                    return into_result(message);
+                }
+            }
+        }
+
+        Err(ToolError::RemoteToolError(format!(
+            "MCP server ended the stream without answering '{}'", method
+        )))
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), ToolError> {
+        self.post(&json!({ "jsonrpc": "2.0", "method": method, "params": params })).await?;
+        Ok(())
+    }
+}
@@ -4,10 +4,11 @@ pub mod manager;
 pub mod provider;
 pub mod tool;
 pub mod examples;
+pub mod mcp;
 
-pub use types::{Tool, ToolParameter, ParameterType, ToolCall, ToolResult, Message};
+pub use types::{Tool, ToolParameter, ParameterType, ToolCall, ToolResult, Message, ContentPart, MediaSource};
 pub use error::FunctionCallingError;
-pub use manager::FunctionCallingManager;
+pub use manager::{ApprovalMode, FunctionCallingManager, HandledToolCalls};
 pub use provider::adapter::{ProviderAdapter, ToolChoice};
 pub use tool::registry::ToolRegistry;
 pub use tool::executor::ToolExecutor;
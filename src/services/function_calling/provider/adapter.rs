@@ -1,12 +1,16 @@
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use crate::services::function_calling::types::{Tool, ToolCall};
+use crate::services::function_calling::types::{Message, Tool, ToolCall};
 use crate::services::function_calling::error::FunctionCallingError;
+use crate::services::function_calling::provider::stream::ToolCallAssembler;
 
 /// Enum for specifying how tools should be chosen
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ToolChoice {
     /// The model can choose which tool to use (if any)
+    #[default]
     Auto,
     /// The model must use a tool
     Required,
@@ -27,6 +31,12 @@ pub trait ProviderAdapter: Send + Sync {
     
     /// Parses tool calls from the provider's response
     async fn parse_tool_calls(&self, response: &Value) -> Result<Vec<ToolCall>, FunctionCallingError>;
+
+    /// Extracts the assistant's text content from the provider's response
+    fn parse_content(&self, response: &Value) -> Option<String>;
+
+    /// Writes the conversation into the request in the provider's message format
+    fn format_messages(&self, request: &mut Value, messages: &[Message]);
     
     /// Prepares a request for the provider
     fn prepare_request(&self, request: &mut Value, tools: &[Tool], choice: ToolChoice) {
@@ -42,6 +52,11 @@ pub trait ProviderAdapter: Send + Sync {
     fn supports_streaming(&self) -> bool {
         false
     }
+
+    /// Creates an assembler for tool calls in the provider's streamed responses
+    fn stream_assembler(&self) -> Option<Box<dyn ToolCallAssembler>> {
+        None
+    }
     
     /// Returns the name of the provider
     fn name(&self) -> &'static str;
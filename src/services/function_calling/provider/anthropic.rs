@@ -1,12 +1,58 @@
 use async_trait::async_trait;
 use serde_json::{json, Value};
-use crate::services::function_calling::types::{Tool, ToolCall};
+use crate::services::function_calling::types::{ContentPart, MediaSource, Message, Tool, ToolCall};
 use crate::services::function_calling::provider::adapter::{ProviderAdapter, ToolChoice};
+use crate::services::function_calling::provider::stream::{AnthropicToolCallAssembler, ToolCallAssembler};
 use crate::services::function_calling::error::FunctionCallingError;
 
 /// Adapter for Anthropic Claude's tool use API
 pub struct AnthropicAdapter;
 
+/// User message content as a string, or as content blocks when images or documents are attached
+pub fn user_content(content: &str, parts: &[ContentPart]) -> Value {
+    if parts.is_empty() {
+        return json!(content);
+    }
+
+    let mut blocks = Vec::with_capacity(parts.len() + 1);
+    for part in parts {
+        match part {
+            ContentPart::Text { text } => blocks.push(json!({ "type": "text", "text": text })),
+            ContentPart::Image { source } => {
+                if let Some(source) = media_source(source) {
+                    blocks.push(json!({ "type": "image", "source": source }));
+                }
+            },
+            ContentPart::Document { source, name } => {
+                if let Some(source) = media_source(source) {
+                    let mut block = json!({ "type": "document", "source": source });
+                    if let Some(name) = name {
+                        block["title"] = json!(name);
+                    }
+                    blocks.push(block);
+                }
+            },
+        }
+    }
+    // Claude answers best with the question after the images and documents it refers to
+    if !content.is_empty() {
+        blocks.push(json!({ "type": "text", "text": content }));
+    }
+    json!(blocks)
+}
+
+fn media_source(source: &MediaSource) -> Option<Value> {
+    match source {
+        MediaSource::Attachment { .. } => None,
+        MediaSource::Url { url } => Some(json!({ "type": "url", "url": url })),
+        MediaSource::Base64 { media_type, data } => Some(json!({
+            "type": "base64",
+            "media_type": media_type,
+            "data": data,
+        })),
+    }
+}
+
 #[async_trait]
 impl ProviderAdapter for AnthropicAdapter {
     fn format_tools(&self, tools: &[Tool]) -> Value {
@@ -63,6 +109,67 @@ impl ProviderAdapter for AnthropicAdapter {
         Ok(result)
     }
     
+    fn parse_content(&self, response: &Value) -> Option<String> {
+        let text = response["content"].as_array()?
+            .iter()
+            .filter(|item| item["type"].as_str() == Some("text"))
+            .filter_map(|item| item["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("");
+
+        if text.is_empty() { None } else { Some(text) }
+    }
+
+    fn format_messages(&self, request: &mut Value, messages: &[Message]) {
+        let mut system = Vec::new();
+        let mut formatted: Vec<Value> = Vec::new();
+
+        for message in messages {
+            match message {
+                Message::System { content } => system.push(content.clone()),
+                Message::User { content, parts } => formatted.push(json!({
+                    "role": "user",
+                    "content": user_content(content, parts),
+                })),
+                Message::Assistant { content, tool_calls } => {
+                    let mut blocks = Vec::new();
+                    if !content.is_empty() {
+                        blocks.push(json!({ "type": "text", "text": content }));
+                    }
+                    for call in tool_calls {
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": call.id,
+                            "name": call.name,
+                            "input": call.arguments,
+                        }));
+                    }
+                    formatted.push(json!({ "role": "assistant", "content": blocks }));
+                },
+                Message::ToolResult { tool_call_id, content } => {
+                    let block = json!({
+                        "type": "tool_result",
+                        "tool_use_id": tool_call_id,
+                        "content": content.to_string(),
+                    });
+
+                    // Claude expects all results for one assistant turn in a single user message
+                    match formatted.last_mut() {
+                        Some(last) if last["role"] == "user" && last["content"].is_array() => {
+                            last["content"].as_array_mut().unwrap().push(block);
+                        },
+                        _ => formatted.push(json!({ "role": "user", "content": [block] })),
+                    }
+                },
+            }
+        }
+
+        request["messages"] = json!(formatted);
+        if !system.is_empty() {
+            request["system"] = json!(system.join("\n\n"));
+        }
+    }
+
     fn prepare_request(&self, request: &mut Value, tools: &[Tool], choice: ToolChoice) {
         let formatted_tools = self.format_tools(tools);
         
@@ -91,6 +198,10 @@ impl ProviderAdapter for AnthropicAdapter {
     fn supports_streaming(&self) -> bool {
         true
     }
+
+    fn stream_assembler(&self) -> Option<Box<dyn ToolCallAssembler>> {
+        Some(Box::new(AnthropicToolCallAssembler::new()))
+    }
     
     fn name(&self) -> &'static str {
         "anthropic"
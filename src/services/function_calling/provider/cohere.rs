@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use crate::services::function_calling::types::{Message, Tool, ToolCall};
+use crate::services::function_calling::provider::adapter::{ProviderAdapter, ToolChoice};
+use crate::services::function_calling::provider::openai::{self, OpenAIAdapter};
+use crate::services::function_calling::provider::stream::{CohereStreamAssembler, ToolCallAssembler};
+use crate::services::function_calling::error::FunctionCallingError;
+
+/// Adapter for Cohere's v2 chat tool use API
+pub struct CohereAdapter;
+
+#[async_trait]
+impl ProviderAdapter for CohereAdapter {
+    fn format_tools(&self, tools: &[Tool]) -> Value {
+        // Cohere v2 accepts the same tool definitions as OpenAI
+        OpenAIAdapter.format_tools(tools)
+    }
+
+    fn format_tool_choice(&self, choice: ToolChoice) -> Value {
+        match choice {
+            ToolChoice::Required | ToolChoice::Specific(_) => json!("REQUIRED"),
+            ToolChoice::None => json!("NONE"),
+            ToolChoice::Auto => Value::Null,
+        }
+    }
+
+    async fn parse_tool_calls(&self, response: &Value) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        let calls = match response["message"]["tool_calls"].as_array() {
+            Some(calls) => calls,
+            None => return Ok(vec![]),
+        };
+
+        let mut result = Vec::new();
+
+        for call in calls {
+            let id = call["id"].as_str()
+                .ok_or_else(|| FunctionCallingError::ResponseParsingError(
+                    "Missing 'id' in tool call".to_string()
+                ))?;
+
+            let name = call["function"]["name"].as_str()
+                .ok_or_else(|| FunctionCallingError::ResponseParsingError(
+                    "Missing 'name' in function call".to_string()
+                ))?;
+
+            let arguments = match &call["function"]["arguments"] {
+                Value::String(arguments) => serde_json::from_str(arguments)
+                    .map_err(|e| FunctionCallingError::ResponseParsingError(
+                        format!("Failed to parse arguments: {}", e)
+                    ))?,
+                other => other.clone(),
+            };
+
+            result.push(ToolCall {
+                id: id.to_string(),
+                name: name.to_string(),
+                arguments,
+            });
+        }
+
+        Ok(result)
+    }
+
+    fn parse_content(&self, response: &Value) -> Option<String> {
+        let text = response["message"]["content"].as_array()?
+            .iter()
+            .filter(|item| item["type"].as_str() == Some("text"))
+            .filter_map(|item| item["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("");
+
+        if text.is_empty() { None } else { Some(text) }
+    }
+
+    fn format_messages(&self, request: &mut Value, messages: &[Message]) {
+        let formatted = messages.iter().map(|message| match message {
+            Message::System { content } => json!({ "role": "system", "content": content }),
+            // Cohere v2 takes the same text and image_url parts as OpenAI
+            Message::User { content, parts } => json!({
+                "role": "user",
+                "content": openai::user_content(content, parts),
+            }),
+            Message::Assistant { content, tool_calls } if !tool_calls.is_empty() => json!({
+                "role": "assistant",
+                "tool_plan": content,
+                "tool_calls": tool_calls.iter().map(|call| json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": {
+                        "name": call.name,
+                        "arguments": call.arguments.to_string(),
+                    }
+                })).collect::<Vec<_>>(),
+            }),
+            Message::Assistant { content, .. } => json!({ "role": "assistant", "content": content }),
+            Message::ToolResult { tool_call_id, content } => json!({
+                "role": "tool",
+                "tool_call_id": tool_call_id,
+                "content": content.to_string(),
+            }),
+        }).collect::<Vec<_>>();
+
+        request["messages"] = json!(formatted);
+    }
+
+    fn prepare_request(&self, request: &mut Value, tools: &[Tool], choice: ToolChoice) {
+        request["tools"] = self.format_tools(tools);
+        let formatted_choice = self.format_tool_choice(choice);
+        if !formatted_choice.is_null() {
+            request["tool_choice"] = formatted_choice;
+        }
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn stream_assembler(&self) -> Option<Box<dyn ToolCallAssembler>> {
+        Some(Box::new(CohereStreamAssembler::new()))
+    }
+
+    fn name(&self) -> &'static str {
+        "cohere"
+    }
+}
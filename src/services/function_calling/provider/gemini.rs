@@ -0,0 +1,177 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use crate::services::function_calling::types::{ContentPart, MediaSource, Message, Tool, ToolCall};
+use crate::services::function_calling::provider::adapter::{ProviderAdapter, ToolChoice};
+use crate::services::function_calling::provider::stream::{GeminiStreamAssembler, ToolCallAssembler};
+use crate::services::function_calling::error::FunctionCallingError;
+
+/// Adapter for Google Gemini's function calling API
+pub struct GeminiAdapter;
+
+/// The parts of a user turn, with images and documents inline or by URI
+pub fn user_parts(content: &str, parts: &[ContentPart]) -> Vec<Value> {
+    let mut formatted = Vec::with_capacity(parts.len() + 1);
+    if !content.is_empty() || parts.is_empty() {
+        formatted.push(json!({ "text": content }));
+    }
+    for part in parts {
+        let (source, default_type) = match part {
+            ContentPart::Text { text } => {
+                formatted.push(json!({ "text": text }));
+                continue;
+            },
+            ContentPart::Image { source } => (source, "image/jpeg"),
+            ContentPart::Document { source, .. } => (source, "application/pdf"),
+        };
+        match source {
+            MediaSource::Attachment { .. } => {},
+            MediaSource::Url { url } => formatted.push(json!({
+                "fileData": {
+                    "mimeType": mime_guess::from_path(url).first_raw().unwrap_or(default_type),
+                    "fileUri": url,
+                }
+            })),
+            MediaSource::Base64 { media_type, data } => formatted.push(json!({
+                "inlineData": { "mimeType": media_type, "data": data }
+            })),
+        }
+    }
+    formatted
+}
+
+#[async_trait]
+impl ProviderAdapter for GeminiAdapter {
+    fn format_tools(&self, tools: &[Tool]) -> Value {
+        json!([{
+            "functionDeclarations": tools.iter().map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.to_json_schema(),
+                })
+            }).collect::<Vec<_>>()
+        }])
+    }
+
+    fn format_tool_choice(&self, choice: ToolChoice) -> Value {
+        match choice {
+            ToolChoice::Auto => json!({ "functionCallingConfig": { "mode": "AUTO" } }),
+            ToolChoice::Required => json!({ "functionCallingConfig": { "mode": "ANY" } }),
+            ToolChoice::None => json!({ "functionCallingConfig": { "mode": "NONE" } }),
+            ToolChoice::Specific(name) => json!({
+                "functionCallingConfig": {
+                    "mode": "ANY",
+                    "allowedFunctionNames": [name]
+                }
+            }),
+        }
+    }
+
+    async fn parse_tool_calls(&self, response: &Value) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        let candidates = response["candidates"].as_array()
+            .ok_or_else(|| FunctionCallingError::ResponseParsingError(
+                "Missing 'candidates' array in response".to_string()
+            ))?;
+
+        let parts = match candidates.first().and_then(|c| c["content"]["parts"].as_array()) {
+            Some(parts) => parts,
+            None => return Ok(vec![]),
+        };
+
+        let mut result = Vec::new();
+
+        for part in parts.iter().filter(|part| part["functionCall"].is_object()) {
+            let call = &part["functionCall"];
+            let name = call["name"].as_str()
+                .ok_or_else(|| FunctionCallingError::ResponseParsingError(
+                    "Missing 'name' in functionCall".to_string()
+                ))?;
+
+            // Gemini doesn't assign call ids, so derive one from the name and position
+            result.push(ToolCall {
+                id: format!("{}-{}", name, result.len()),
+                name: name.to_string(),
+                arguments: call["args"].clone(),
+            });
+        }
+
+        Ok(result)
+    }
+
+    fn parse_content(&self, response: &Value) -> Option<String> {
+        let text = response["candidates"][0]["content"]["parts"].as_array()?
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("");
+
+        if text.is_empty() { None } else { Some(text) }
+    }
+
+    fn format_messages(&self, request: &mut Value, messages: &[Message]) {
+        let mut system = Vec::new();
+        let mut contents: Vec<Value> = Vec::new();
+        // Function responses are matched to calls by name, not id
+        let mut call_names: HashMap<&str, &str> = HashMap::new();
+
+        for message in messages {
+            match message {
+                Message::System { content } => system.push(content.clone()),
+                Message::User { content, parts } => contents.push(json!({
+                    "role": "user",
+                    "parts": user_parts(content, parts),
+                })),
+                Message::Assistant { content, tool_calls } => {
+                    let mut parts = Vec::new();
+                    if !content.is_empty() {
+                        parts.push(json!({ "text": content }));
+                    }
+                    for call in tool_calls {
+                        call_names.insert(&call.id, &call.name);
+                        parts.push(json!({
+                            "functionCall": { "name": call.name, "args": call.arguments }
+                        }));
+                    }
+                    contents.push(json!({ "role": "model", "parts": parts }));
+                },
+                Message::ToolResult { tool_call_id, content } => {
+                    let name = call_names.get(tool_call_id.as_str()).copied().unwrap_or(tool_call_id.as_str());
+                    let response = if content.is_object() { content.clone() } else { json!({ "result": content }) };
+                    let part = json!({
+                        "functionResponse": { "name": name, "response": response }
+                    });
+
+                    match contents.last_mut() {
+                        Some(last) if last["role"] == "function" => {
+                            last["parts"].as_array_mut().unwrap().push(part);
+                        },
+                        _ => contents.push(json!({ "role": "function", "parts": [part] })),
+                    }
+                },
+            }
+        }
+
+        request["contents"] = json!(contents);
+        if !system.is_empty() {
+            request["systemInstruction"] = json!({ "parts": [{ "text": system.join("\n\n") }] });
+        }
+    }
+
+    fn prepare_request(&self, request: &mut Value, tools: &[Tool], choice: ToolChoice) {
+        request["tools"] = self.format_tools(tools);
+        request["toolConfig"] = self.format_tool_choice(choice);
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn stream_assembler(&self) -> Option<Box<dyn ToolCallAssembler>> {
+        Some(Box::new(GeminiStreamAssembler::new()))
+    }
+
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+}
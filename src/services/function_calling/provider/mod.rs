@@ -1,20 +1,38 @@
 pub mod adapter;
 pub mod openai;
 pub mod anthropic;
+pub mod gemini;
+pub mod cohere;
+pub mod stream;
 
 pub use adapter::{ProviderAdapter, ToolChoice};
 pub use openai::OpenAIAdapter;
 pub use anthropic::AnthropicAdapter;
+pub use gemini::GeminiAdapter;
+pub use cohere::CohereAdapter;
+pub use stream::{
+    AnthropicToolCallAssembler, CohereStreamAssembler, GeminiStreamAssembler, OpenAIToolCallAssembler,
+    SseBuffer, StreamDelta, ToolCallAssembler,
+};
 
 use std::sync::Arc;
 use crate::models::llm_provider::LLMProvider;
 
 /// Creates a provider adapter based on the provider type
 pub fn get_provider_adapter(provider: &LLMProvider) -> Arc<dyn ProviderAdapter> {
-    match provider.provider_type.as_str() {
-        "gpt" => Arc::new(OpenAIAdapter),
-        "claude" => Arc::new(AnthropicAdapter),
+    find_provider_adapter(&provider.provider_type)
+        .unwrap_or_else(|| panic!("Unsupported provider type for function calling: {}", provider.provider_type))
+}
+
+/// Returns the provider adapter for the provider type, if function calling is supported
+pub fn find_provider_adapter(provider_type: &str) -> Option<Arc<dyn ProviderAdapter>> {
+    match provider_type {
+        // Mistral and Grok expose OpenAI-compatible tool calling
+        "gpt" | "mistral" | "grok" | "openai_compatible" => Some(Arc::new(OpenAIAdapter)),
+        "claude" => Some(Arc::new(AnthropicAdapter)),
+        "gemini" => Some(Arc::new(GeminiAdapter)),
+        "command" => Some(Arc::new(CohereAdapter)),
         // Add more providers as they are implemented
-        _ => panic!("Unsupported provider type for function calling: {}", provider.provider_type),
+        _ => None,
     }
 }
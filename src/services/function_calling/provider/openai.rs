@@ -1,12 +1,48 @@
 use async_trait::async_trait;
 use serde_json::{json, Value};
-use crate::services::function_calling::types::{Tool, ToolCall};
+use crate::services::function_calling::types::{ContentPart, MediaSource, Message, Tool, ToolCall};
 use crate::services::function_calling::provider::adapter::{ProviderAdapter, ToolChoice};
+use crate::services::function_calling::provider::stream::{OpenAIToolCallAssembler, ToolCallAssembler};
 use crate::services::function_calling::error::FunctionCallingError;
 
 /// Adapter for OpenAI's function calling API
 pub struct OpenAIAdapter;
 
+/// User message content as a string, or as content parts when images or documents are attached
+pub fn user_content(content: &str, parts: &[ContentPart]) -> Value {
+    if parts.is_empty() {
+        return json!(content);
+    }
+
+    let mut formatted = Vec::with_capacity(parts.len() + 1);
+    if !content.is_empty() {
+        formatted.push(json!({ "type": "text", "text": content }));
+    }
+    for part in parts {
+        match part {
+            ContentPart::Text { text } => formatted.push(json!({ "type": "text", "text": text })),
+            ContentPart::Image { source } => {
+                if let Some(url) = source.url() {
+                    formatted.push(json!({ "type": "image_url", "image_url": { "url": url } }));
+                }
+            },
+            // `file_data` only takes inline content; document URLs are refused before sending
+            ContentPart::Document { source, name } => {
+                if let (MediaSource::Base64 { .. }, Some(url)) = (source, source.url()) {
+                    formatted.push(json!({
+                        "type": "file",
+                        "file": {
+                            "filename": name.as_deref().unwrap_or("document.pdf"),
+                            "file_data": url,
+                        }
+                    }));
+                }
+            },
+        }
+    }
+    json!(formatted)
+}
+
 #[async_trait]
 impl ProviderAdapter for OpenAIAdapter {
     fn format_tools(&self, tools: &[Tool]) -> Value {
@@ -88,6 +124,48 @@ impl ProviderAdapter for OpenAIAdapter {
         }
     }
     
+    fn parse_content(&self, response: &Value) -> Option<String> {
+        response["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|content| content.to_string())
+    }
+
+    fn format_messages(&self, request: &mut Value, messages: &[Message]) {
+        let formatted = messages.iter().map(|message| match message {
+            Message::System { content } => json!({
+                "role": "system",
+                "content": content,
+            }),
+            Message::User { content, parts } => json!({
+                "role": "user",
+                "content": user_content(content, parts),
+            }),
+            Message::Assistant { content, tool_calls } if !tool_calls.is_empty() => json!({
+                "role": "assistant",
+                "content": if content.is_empty() { Value::Null } else { json!(content) },
+                "tool_calls": tool_calls.iter().map(|call| json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": {
+                        "name": call.name,
+                        "arguments": call.arguments.to_string(),
+                    }
+                })).collect::<Vec<_>>(),
+            }),
+            Message::Assistant { content, .. } => json!({
+                "role": "assistant",
+                "content": content,
+            }),
+            Message::ToolResult { tool_call_id, content } => json!({
+                "role": "tool",
+                "tool_call_id": tool_call_id,
+                "content": content.to_string(),
+            }),
+        }).collect::<Vec<_>>();
+
+        request["messages"] = json!(formatted);
+    }
+
     fn prepare_request(&self, request: &mut Value, tools: &[Tool], choice: ToolChoice) {
         let formatted_tools = self.format_tools(tools);
         let formatted_choice = self.format_tool_choice(choice);
@@ -99,6 +177,10 @@ impl ProviderAdapter for OpenAIAdapter {
     fn supports_streaming(&self) -> bool {
         true
     }
+
+    fn stream_assembler(&self) -> Option<Box<dyn ToolCallAssembler>> {
+        Some(Box::new(OpenAIToolCallAssembler::new()))
+    }
     
     fn name(&self) -> &'static str {
         "openai"
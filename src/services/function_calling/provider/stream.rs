@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+use serde_json::{json, Value};
+use crate::services::function_calling::types::ToolCall;
+use crate::services::function_calling::error::FunctionCallingError;
+
+/// A piece of a streamed response that is ready to hand to the caller
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamDelta {
+    /// Assistant text
+    Text(String),
+    /// A tool call whose arguments have been fully received
+    ToolCall(ToolCall),
+    /// Token usage in the provider's own format, possibly partial until the last one
+    Usage(Value),
+}
+
+/// Incrementally assembles tool calls from a provider's streamed events
+pub trait ToolCallAssembler: Send {
+    /// Consumes one decoded stream event and returns anything that became complete
+    fn push_event(&mut self, event: &Value) -> Result<Vec<StreamDelta>, FunctionCallingError>;
+
+    /// Flushes tool calls that were still open when the stream ended
+    fn finish(&mut self) -> Result<Vec<ToolCall>, FunctionCallingError>;
+}
+
+/// Splits a server-sent event byte stream into `data:` payloads
+#[derive(Debug, Default)]
+pub struct SseBuffer {
+    buffer: String,
+}
+
+impl SseBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a chunk and returns the payloads of every complete `data:` line
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.push_str(&String::from_utf8_lossy(chunk));
+
+        let mut payloads = Vec::new();
+        while let Some(newline) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=newline).collect();
+            if let Some(data) = line.trim_end_matches(['\r', '\n']).strip_prefix("data:") {
+                payloads.push(data.trim_start().to_string());
+            }
+        }
+        payloads
+    }
+}
+
+/// A tool call whose arguments are still arriving
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+impl PartialToolCall {
+    fn complete(self) -> Result<ToolCall, FunctionCallingError> {
+        if self.name.is_empty() {
+            return Err(FunctionCallingError::ResponseParsingError(
+                format!("Streamed tool call '{}' has no function name", self.id)
+            ));
+        }
+
+        let arguments = if self.arguments.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(&self.arguments)
+                .map_err(|e| FunctionCallingError::ResponseParsingError(
+                    format!("Failed to parse streamed arguments for '{}': {}", self.name, e)
+                ))?
+        };
+
+        Ok(ToolCall {
+            id: self.id,
+            name: self.name,
+            arguments,
+        })
+    }
+}
+
+/// Assembles `tool_calls[].function.arguments` fragments from OpenAI chat completion chunks
+#[derive(Debug, Default)]
+pub struct OpenAIToolCallAssembler {
+    calls: BTreeMap<u64, PartialToolCall>,
+}
+
+impl OpenAIToolCallAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn drain(&mut self) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        std::mem::take(&mut self.calls)
+            .into_values()
+            .map(PartialToolCall::complete)
+            .collect()
+    }
+}
+
+impl ToolCallAssembler for OpenAIToolCallAssembler {
+    fn push_event(&mut self, event: &Value) -> Result<Vec<StreamDelta>, FunctionCallingError> {
+        if let Some(error) = event.get("error") {
+            return Err(FunctionCallingError::ProviderError(
+                error["message"].as_str().unwrap_or("Unknown error").to_string()
+            ));
+        }
+
+        let mut deltas = Vec::new();
+        let choice = &event["choices"][0];
+        let delta = &choice["delta"];
+
+        if let Some(content) = delta["content"].as_str() {
+            if !content.is_empty() {
+                deltas.push(StreamDelta::Text(content.to_string()));
+            }
+        }
+
+        for fragment in delta["tool_calls"].as_array().into_iter().flatten() {
+            // Only the first fragment of a call carries its id and name
+            let index = fragment["index"].as_u64().unwrap_or(self.calls.len() as u64);
+            let call = self.calls.entry(index).or_default();
+
+            if let Some(id) = fragment["id"].as_str() {
+                call.id = id.to_string();
+            }
+            if let Some(name) = fragment["function"]["name"].as_str() {
+                call.name.push_str(name);
+            }
+            if let Some(arguments) = fragment["function"]["arguments"].as_str() {
+                call.arguments.push_str(arguments);
+            }
+        }
+
+        if !choice["finish_reason"].is_null() {
+            deltas.extend(self.drain()?.into_iter().map(StreamDelta::ToolCall));
+        }
+
+        // Sent on the final chunk when `stream_options.include_usage` is set
+        if event["usage"].is_object() {
+            deltas.push(StreamDelta::Usage(event["usage"].clone()));
+        }
+
+        Ok(deltas)
+    }
+
+    fn finish(&mut self) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        self.drain()
+    }
+}
+
+/// Assembles `input_json_delta` fragments from Anthropic message stream events
+#[derive(Debug, Default)]
+pub struct AnthropicToolCallAssembler {
+    blocks: BTreeMap<u64, PartialToolCall>,
+}
+
+impl AnthropicToolCallAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ToolCallAssembler for AnthropicToolCallAssembler {
+    fn push_event(&mut self, event: &Value) -> Result<Vec<StreamDelta>, FunctionCallingError> {
+        let index = event["index"].as_u64().unwrap_or_default();
+
+        match event["type"].as_str() {
+            Some("content_block_start") if event["content_block"]["type"] == "tool_use" => {
+                let block = &event["content_block"];
+                self.blocks.insert(index, PartialToolCall {
+                    id: block["id"].as_str().unwrap_or_default().to_string(),
+                    name: block["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: String::new(),
+                });
+                Ok(vec![])
+            },
+            Some("content_block_delta") => {
+                let delta = &event["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => Ok(delta["text"].as_str()
+                        .filter(|text| !text.is_empty())
+                        .map(|text| vec![StreamDelta::Text(text.to_string())])
+                        .unwrap_or_default()),
+                    Some("input_json_delta") => {
+                        let block = self.blocks.get_mut(&index)
+                            .ok_or_else(|| FunctionCallingError::ResponseParsingError(
+                                format!("Received input_json_delta for unknown content block {}", index)
+                            ))?;
+                        block.arguments.push_str(delta["partial_json"].as_str().unwrap_or_default());
+                        Ok(vec![])
+                    },
+                    _ => Ok(vec![]),
+                }
+            },
+            Some("content_block_stop") => match self.blocks.remove(&index) {
+                Some(block) => Ok(vec![StreamDelta::ToolCall(block.complete()?)]),
+                None => Ok(vec![]),
+            },
+            // Input tokens arrive with the message, output tokens with its final delta
+            Some("message_start") if event["message"]["usage"].is_object() => {
+                Ok(vec![StreamDelta::Usage(event["message"]["usage"].clone())])
+            },
+            Some("message_delta") if event["usage"].is_object() => {
+                Ok(vec![StreamDelta::Usage(event["usage"].clone())])
+            },
+            Some("error") => Err(FunctionCallingError::ProviderError(
+                event["error"]["message"].as_str().unwrap_or("Unknown error").to_string()
+            )),
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        std::mem::take(&mut self.blocks)
+            .into_values()
+            .map(PartialToolCall::complete)
+            .collect()
+    }
+}
+
+/// Reads `streamGenerateContent?alt=sse` events, where function calls always arrive whole
+#[derive(Debug, Default)]
+pub struct GeminiStreamAssembler {
+    calls: usize,
+}
+
+impl GeminiStreamAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ToolCallAssembler for GeminiStreamAssembler {
+    fn push_event(&mut self, event: &Value) -> Result<Vec<StreamDelta>, FunctionCallingError> {
+        if let Some(error) = event.get("error") {
+            return Err(FunctionCallingError::ProviderError(
+                error["message"].as_str().unwrap_or("Unknown error").to_string()
+            ));
+        }
+
+        let mut deltas = Vec::new();
+        for part in event["candidates"][0]["content"]["parts"].as_array().into_iter().flatten() {
+            if let Some(text) = part["text"].as_str().filter(|text| !text.is_empty()) {
+                deltas.push(StreamDelta::Text(text.to_string()));
+            }
+            if let Some(name) = part["functionCall"]["name"].as_str() {
+                // Same ids as GeminiAdapter::parse_tool_calls
+                deltas.push(StreamDelta::ToolCall(ToolCall {
+                    id: format!("{}-{}", name, self.calls),
+                    name: name.to_string(),
+                    arguments: part["functionCall"]["args"].clone(),
+                }));
+                self.calls += 1;
+            }
+        }
+
+        if event["usageMetadata"].is_object() {
+            deltas.push(StreamDelta::Usage(event["usageMetadata"].clone()));
+        }
+
+        Ok(deltas)
+    }
+
+    fn finish(&mut self) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        Ok(vec![])
+    }
+}
+
+/// Assembles tool calls from Cohere v2 chat stream events
+#[derive(Debug, Default)]
+pub struct CohereStreamAssembler {
+    calls: BTreeMap<u64, PartialToolCall>,
+}
+
+impl CohereStreamAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ToolCallAssembler for CohereStreamAssembler {
+    fn push_event(&mut self, event: &Value) -> Result<Vec<StreamDelta>, FunctionCallingError> {
+        let index = event["index"].as_u64().unwrap_or_default();
+        let message = &event["delta"]["message"];
+
+        match event["type"].as_str() {
+            Some("content-delta") => Ok(message["content"]["text"].as_str()
+                .filter(|text| !text.is_empty())
+                .map(|text| vec![StreamDelta::Text(text.to_string())])
+                .unwrap_or_default()),
+            Some("tool-call-start") => {
+                let call = &message["tool_calls"];
+                self.calls.insert(index, PartialToolCall {
+                    id: call["id"].as_str().unwrap_or_default().to_string(),
+                    name: call["function"]["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: call["function"]["arguments"].as_str().unwrap_or_default().to_string(),
+                });
+                Ok(vec![])
+            },
+            Some("tool-call-delta") => {
+                let call = self.calls.get_mut(&index)
+                    .ok_or_else(|| FunctionCallingError::ResponseParsingError(
+                        format!("Received tool-call-delta for unknown tool call {}", index)
+                    ))?;
+                call.arguments.push_str(message["tool_calls"]["function"]["arguments"].as_str().unwrap_or_default());
+                Ok(vec![])
+            },
+            Some("tool-call-end") => match self.calls.remove(&index) {
+                Some(call) => Ok(vec![StreamDelta::ToolCall(call.complete()?)]),
+                None => Ok(vec![]),
+            },
+            Some("message-end") if event["delta"]["usage"].is_object() => {
+                Ok(vec![StreamDelta::Usage(event["delta"]["usage"].clone())])
+            },
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<ToolCall>, FunctionCallingError> {
+        std::mem::take(&mut self.calls)
+            .into_values()
+            .map(PartialToolCall::complete)
+            .collect()
+    }
+}
@@ -21,6 +21,8 @@ pub enum ToolError {
     RateLimitExceeded(String),
     /// An error occurred due to permission issues
     PermissionDenied(String),
+    /// The tool is marked as dangerous and the call wasn't confirmed
+    ConfirmationRequired(String),
     /// An error occurred while communicating over the network
     NetworkError(String),
     /// An error occurred while executing a remote tool
@@ -43,6 +45,7 @@ impl fmt::Display for ToolError {
             Self::AuthenticationError(msg) => write!(f, "Authentication error: {}", msg),
             Self::RateLimitExceeded(msg) => write!(f, "Rate limit exceeded: {}", msg),
             Self::PermissionDenied(msg) => write!(f, "Permission denied: {}", msg),
+            Self::ConfirmationRequired(name) => write!(f, "Tool requires confirmation: {}", name),
             Self::NetworkError(msg) => write!(f, "Network error: {}", msg),
             Self::RemoteToolError(msg) => write!(f, "Remote tool error: {}", msg),
             Self::DiscoveryError(msg) => write!(f, "Discovery error: {}", msg),
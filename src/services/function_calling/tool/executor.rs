@@ -12,9 +12,8 @@ pub trait ToolExecutorBase: Send + Sync {
     /// Returns the description of the tool
     fn description(&self) -> &str;
 
-    /// Validates the arguments against the tool's schema
-    fn validate_args(&self, args: &Value) -> Result<(), ToolError> {
-        // Default implementation does no validation
+    /// Applies checks beyond the tool's schema, which the registry validates before this is called
+    fn validate_args(&self, _args: &Value) -> Result<(), ToolError> {
         Ok(())
     }
 }
@@ -1,7 +1,11 @@
 pub mod error;
 pub mod executor;
 pub mod registry;
+pub mod schema;
+pub mod webhook;
 
 pub use error::ToolError;
 pub use executor::ToolExecutor;
 pub use registry::ToolRegistry;
+pub use schema::{validate_arguments, SchemaViolation};
+pub use webhook::WebhookTool;
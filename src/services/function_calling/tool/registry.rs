@@ -1,16 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use serde_json::Value;
 use tokio::sync::RwLock;
+use uuid::Uuid;
 use crate::services::function_calling::types::{Tool, ToolParameter};
 use crate::services::function_calling::tool::executor::{ToolExecutor, DynToolExecutor};
 use crate::services::function_calling::tool::error::ToolError;
+use crate::services::function_calling::tool::schema::validate_arguments;
 use log::{debug, info, warn};
 
 /// Registry for tools
 pub struct ToolRegistry {
     tools: Arc<RwLock<HashMap<String, Box<dyn DynToolExecutor + Send + Sync>>>>,
     parameters: Arc<RwLock<HashMap<String, Vec<ToolParameter>>>>,
+    dangerous: Arc<RwLock<HashSet<String>>>,
+    owner: Option<Uuid>,
 }
 
 impl ToolRegistry {
@@ -19,9 +23,24 @@ impl ToolRegistry {
         Self {
             tools: Arc::new(RwLock::new(HashMap::new())),
             parameters: Arc::new(RwLock::new(HashMap::new())),
+            dangerous: Arc::new(RwLock::new(HashSet::new())),
+            owner: None,
         }
     }
 
+    /// Creates a registry holding the tools of a single user
+    pub fn for_user(user_id: Uuid) -> Self {
+        Self {
+            owner: Some(user_id),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the user this registry belongs to, if any
+    pub fn owner(&self) -> Option<Uuid> {
+        self.owner
+    }
+
     /// Registers a tool with the registry
     pub async fn register<T: ToolExecutor + 'static>(&self, executor: T) {
         let name = executor.name().to_string();
@@ -35,12 +54,25 @@ impl ToolRegistry {
         tools.insert(name.to_string(), Box::new(executor));
     }
 
-    /// Executes a tool with the given name and arguments
+    /// Executes a tool with the given name and arguments, refusing tools marked as dangerous
     pub async fn execute(&self, name: &str, args: Value) -> Result<Value, ToolError> {
+        if self.is_dangerous(name).await {
+            warn!("Refusing unconfirmed call to dangerous tool '{}'", name);
+            return Err(ToolError::ConfirmationRequired(name.to_string()));
+        }
+        self.execute_confirmed(name, args).await
+    }
+
+    /// Executes a tool the caller has explicitly confirmed, including dangerous ones
+    pub async fn execute_confirmed(&self, name: &str, args: Value) -> Result<Value, ToolError> {
         let tools = self.tools.read().await;
         match tools.get(name) {
             Some(executor) => {
-                // Validate arguments before execution
+                // Check the arguments against the declared schema, then any tool-specific rules
+                if let Some(parameters) = self.parameters.read().await.get(name) {
+                    let schema = Tool::new(name, executor.description(), parameters.clone()).to_json_schema();
+                    validate_arguments(&schema, &args)?;
+                }
                 executor.validate_args(&args)?;
 
                 // Execute the tool
@@ -110,4 +142,19 @@ impl ToolRegistry {
         info!("Updating parameters for tool '{}' with {} parameters", name, parameters.len());
         params_map.insert(name.to_string(), parameters);
     }
+
+    /// Marks a tool as dangerous, so it only runs through `execute_confirmed`
+    pub async fn set_dangerous(&self, name: &str, dangerous: bool) {
+        let mut set = self.dangerous.write().await;
+        if dangerous {
+            set.insert(name.to_string());
+        } else {
+            set.remove(name);
+        }
+    }
+
+    /// Returns true if the tool requires explicit confirmation
+    pub async fn is_dangerous(&self, name: &str) -> bool {
+        self.dangerous.read().await.contains(name)
+    }
 }
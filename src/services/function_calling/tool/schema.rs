@@ -0,0 +1,221 @@
+use std::fmt;
+use serde_json::Value;
+use crate::services::function_calling::tool::error::ToolError;
+
+/// A single place where arguments don't match a tool's JSON schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// JSON path of the offending value, e.g. `$.filters[0].field`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Validates arguments against a tool's JSON schema, reporting every violation
+pub fn validate_arguments(schema: &Value, args: &Value) -> Result<(), ToolError> {
+    let violations = collect_violations(schema, args);
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let details = violations.iter()
+        .map(|violation| violation.to_string())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    Err(ToolError::InvalidArgument(format!(
+        "{} schema violation(s): {}", violations.len(), details
+    )))
+}
+
+/// Returns every violation of the schema found in the value
+pub fn collect_violations(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    check(schema, value, "$", &mut violations);
+    violations
+}
+
+fn check(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let mut violation = |message: String| violations.push(SchemaViolation {
+        path: path.to_string(),
+        message,
+    });
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+
+        if !types.is_empty() && !types.iter().any(|name| matches_type(name, value)) {
+            violation(format!("expected {}, got {}", types.join(" or "), type_name(value)));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>().join(", ");
+            violation(format!("must be one of [{}], got {}", allowed, value));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                violation(format!("must be >= {}, got {}", minimum, number));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                violation(format!("must be <= {}, got {}", maximum, number));
+            }
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let required = schema.get("required").and_then(Value::as_array);
+            for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    violations.push(SchemaViolation {
+                        path: format!("{}.{}", path, name),
+                        message: "is required".to_string(),
+                    });
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+
+            for (name, property) in object {
+                let property_path = format!("{}.{}", path, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property_schema) => check(property_schema, property, &property_path, violations),
+                    None if closed => violations.push(SchemaViolation {
+                        path: property_path,
+                        message: "is not an allowed property".to_string(),
+                    }),
+                    None => {},
+                }
+            }
+        },
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, index), violations);
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+fn matches_type(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64()
+            || value.as_f64().map_or(false, |number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::services::function_calling::types::{ParameterType, Tool, ToolParameter};
+
+    fn filter_tool() -> Tool {
+        Tool::new("search", "Search records", vec![
+            ToolParameter::new("query", ParameterType::String { format: None, enum_values: None }, true),
+            ToolParameter::new("sort", ParameterType::String {
+                format: None,
+                enum_values: Some(vec!["asc".to_string(), "desc".to_string()]),
+            }, false),
+            ToolParameter::new("limit", ParameterType::Number { minimum: Some(1.0), maximum: Some(50.0) }, false),
+            ToolParameter::new("filters", ParameterType::Array {
+                items: Box::new(ParameterType::Object {
+                    properties: vec![
+                        ToolParameter::new("field", ParameterType::String { format: None, enum_values: None }, true),
+                        ToolParameter::new("exact", ParameterType::Boolean, false),
+                    ],
+                }),
+            }, false),
+        ])
+    }
+
+    #[test]
+    fn accepts_valid_arguments() {
+        let args = json!({
+            "query": "rust",
+            "sort": "desc",
+            "limit": 10,
+            "filters": [{ "field": "title", "exact": true }]
+        });
+
+        assert!(validate_arguments(&filter_tool().to_json_schema(), &args).is_ok());
+    }
+
+    #[test]
+    fn reports_every_violation_with_its_path() {
+        let args = json!({
+            "sort": "sideways",
+            "limit": 500,
+            "filters": [{ "exact": "yes" }]
+        });
+
+        let mut paths = collect_violations(&filter_tool().to_json_schema(), &args)
+            .into_iter()
+            .map(|violation| violation.path)
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        assert_eq!(paths, vec![
+            "$.filters[0].exact",
+            "$.filters[0].field",
+            "$.limit",
+            "$.query",
+            "$.sort",
+        ]);
+
+        match validate_arguments(&filter_tool().to_json_schema(), &args) {
+            Err(ToolError::InvalidArgument(message)) => {
+                assert!(message.starts_with("5 schema violation(s)"));
+                assert!(message.contains("$.query: is required"));
+            },
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_root_type() {
+        let violations = collect_violations(&filter_tool().to_json_schema(), &json!("rust"));
+        assert_eq!(violations, vec![SchemaViolation {
+            path: "$".to_string(),
+            message: "expected object, got string".to_string(),
+        }]);
+    }
+}
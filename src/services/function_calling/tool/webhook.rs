@@ -0,0 +1,211 @@
+use async_trait::async_trait;
+use log::debug;
+use reqwest::redirect::Policy;
+use reqwest::{Client, Method};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::services::function_calling::tool::error::ToolError;
+use crate::services::function_calling::tool::executor::{ToolExecutor, ToolExecutorBase};
+use crate::utils::{json_path, url_guard};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A tool that calls an HTTP endpoint configured by a user. Only public http(s) addresses are
+/// called, and redirects aren't followed, so a tool can't reach internal services.
+pub struct WebhookTool {
+    name: String,
+    description: String,
+    method: Method,
+    url_template: String,
+    headers: HashMap<String, String>,
+    response_path: Option<String>,
+}
+
+impl WebhookTool {
+    /// Creates a webhook tool; `headers` must already have their secrets resolved
+    pub fn new(
+        name: &str,
+        description: &str,
+        method: &str,
+        url_template: &str,
+        headers: HashMap<String, String>,
+        response_path: Option<String>,
+    ) -> Result<Self, ToolError> {
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|_| ToolError::InvalidArgument(format!("Invalid HTTP method: {}", method)))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            method,
+            url_template: url_template.to_string(),
+            headers,
+            response_path,
+        })
+    }
+
+    /// Fills `{{name}}` placeholders from the arguments, returning the URL and the unused arguments
+    fn render_url(&self, args: &Value) -> Result<(String, Map<String, Value>), ToolError> {
+        let mut remaining = args.as_object().cloned().unwrap_or_default();
+        let mut url = String::new();
+        let mut rest = self.url_template.as_str();
+
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..].find("}}")
+                .map(|offset| start + offset)
+                .ok_or_else(|| ToolError::InternalError(format!("Unclosed placeholder in URL template of '{}'", self.name)))?;
+            let key = rest[start + 2..end].trim();
+
+            let value = remaining.remove(key)
+                .ok_or_else(|| ToolError::MissingParameter(key.to_string()))?;
+            let value = match value {
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+
+            url.push_str(&rest[..start]);
+            url.push_str(&percent_encode(&value));
+            rest = &rest[end + 2..];
+        }
+        url.push_str(rest);
+
+        Ok((url, remaining))
+    }
+}
+
+impl ToolExecutorBase for WebhookTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for WebhookTool {
+    async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+        let (url, remaining) = self.render_url(&args)?;
+        debug!("Calling webhook tool '{}': {} {}", self.name, self.method, url);
+
+        // Checked on every call since arguments fill the template, and the addresses the host
+        // resolves to are pinned so it can't resolve to another one when the request is made
+        let url = url_guard::parse_public_url(&url).map_err(ToolError::PermissionDenied)?;
+        let addrs = url_guard::resolve_public(&url).await.map_err(ToolError::PermissionDenied)?;
+        let host = url.host_str().unwrap_or_default().to_string();
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .resolve_to_addrs(&host, &addrs)
+            .build()?;
+
+        let mut request = client
+            .request(self.method.clone(), url)
+            .timeout(WEBHOOK_TIMEOUT);
+
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        // Arguments not used in the URL go in the query string or the JSON body
+        if !remaining.is_empty() {
+            request = if self.method == Method::GET || self.method == Method::DELETE {
+                let query: Vec<(String, String)> = remaining.into_iter()
+                    .map(|(key, value)| match value {
+                        Value::String(s) => (key, s),
+                        other => (key, other.to_string()),
+                    })
+                    .collect();
+                request.query(&query)
+            } else {
+                request.json(&Value::Object(remaining))
+            };
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            let snippet: String = body.chars().take(500).collect();
+            return Err(ToolError::ExternalServiceError(format!(
+                "Webhook returned {}: {}", status, snippet
+            )));
+        }
+
+        let body = match serde_json::from_str::<Value>(&body) {
+            Ok(json) => json,
+            Err(_) => return Ok(Value::String(body)),
+        };
+
+        match &self.response_path {
+            Some(path) => {
+                let mut matches = json_path::select(&body, path)
+                    .map_err(ToolError::InternalError)?;
+                Ok(match matches.len() {
+                    0 => Value::Null,
+                    1 => matches.remove(0).clone(),
+                    _ => Value::Array(matches.into_iter().cloned().collect()),
+                })
+            },
+            None => Ok(body),
+        }
+    }
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters
+fn percent_encode(value: &str) -> String {
+    value.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn render_url_fills_placeholders_and_keeps_the_rest() {
+        let tool = WebhookTool::new(
+            "lookup_order",
+            "Look up an order",
+            "get",
+            "https://shop.example.com/orders/{{order_id}}?region={{ region }}",
+            HashMap::new(),
+            None,
+        ).unwrap();
+
+        let (url, remaining) = tool.render_url(&json!({
+            "order_id": "A 1/2",
+            "region": "eu",
+            "verbose": true
+        })).unwrap();
+
+        assert_eq!(url, "https://shop.example.com/orders/A%201%2F2?region=eu");
+        assert_eq!(Value::Object(remaining), json!({ "verbose": true }));
+
+        match tool.render_url(&json!({ "region": "eu" })) {
+            Err(ToolError::MissingParameter(name)) => assert_eq!(name, "order_id"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_refuses_internal_addresses() {
+        for url in [
+            "http://169.254.169.254/latest/meta-data/{{path}}",
+            "http://localhost:8000/{{path}}",
+            "http://{{path}}/",
+            "file:///etc/{{path}}",
+        ] {
+            let tool = WebhookTool::new("fetch", "Fetch", "get", url, HashMap::new(), None).unwrap();
+            match tool.execute(json!({ "path": "127.0.0.1" })).await {
+                Err(ToolError::PermissionDenied(_)) => {}
+                other => panic!("{} wasn't refused: {:?}", url, other),
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use serde_json::Value;
+use uuid::Uuid;
 
 /// Represents a tool that can be called by an LLM
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,7 +52,7 @@ pub enum ParameterType {
 }
 
 /// Represents a tool call from an LLM
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub name: String,
@@ -70,15 +71,72 @@ pub struct ToolResult {
 #[serde(tag = "role")]
 pub enum Message {
     #[serde(rename = "user")]
-    User { content: String },
+    User {
+        content: String,
+        /// Images and documents sent after `content`
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        parts: Vec<ContentPart>,
+    },
     #[serde(rename = "assistant")]
-    Assistant { content: String },
+    Assistant {
+        content: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tool_calls: Vec<ToolCall>,
+    },
     #[serde(rename = "system")]
     System { content: String },
     #[serde(rename = "tool")]
     ToolResult { tool_call_id: String, content: Value },
 }
 
+/// A piece of multimodal message content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    Image { source: MediaSource },
+    Document {
+        source: MediaSource,
+        /// File name shown to the model, where the provider supports one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+/// Where the bytes of an image or document come from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MediaSource {
+    /// A stored attachment, replaced with `Base64` before the request is sent
+    Attachment { attachment_id: Uuid },
+    Url { url: String },
+    Base64 { media_type: String, data: String },
+}
+
+impl ContentPart {
+    /// The input modality a provider needs to accept this part
+    pub fn modality(&self) -> &'static str {
+        match self {
+            ContentPart::Text { .. } => "text",
+            ContentPart::Image { .. } => "vision",
+            ContentPart::Document { .. } => "document",
+        }
+    }
+}
+
+impl MediaSource {
+    /// A URL a provider can fetch, using a `data:` URL for inline content
+    pub fn url(&self) -> Option<String> {
+        match self {
+            MediaSource::Attachment { .. } => None,
+            MediaSource::Url { url } => Some(url.clone()),
+            MediaSource::Base64 { media_type, data } => {
+                Some(format!("data:{};base64,{}", media_type, data))
+            }
+        }
+    }
+}
+
 impl Tool {
     /// Creates a new tool
     pub fn new(name: &str, description: &str, parameters: Vec<ToolParameter>) -> Self {
@@ -204,12 +262,34 @@ impl ToolParameter {
 impl Message {
     /// Creates a new user message
     pub fn user(content: &str) -> Self {
-        Self::User { content: content.to_string() }
+        Self::User {
+            content: content.to_string(),
+            parts: Vec::new(),
+        }
+    }
+
+    /// Creates a new user message with images or documents
+    pub fn user_with_parts(content: &str, parts: Vec<ContentPart>) -> Self {
+        Self::User {
+            content: content.to_string(),
+            parts,
+        }
     }
     
     /// Creates a new assistant message
     pub fn assistant(content: &str) -> Self {
-        Self::Assistant { content: content.to_string() }
+        Self::Assistant {
+            content: content.to_string(),
+            tool_calls: Vec::new(),
+        }
+    }
+
+    /// Creates a new assistant message that requested tool calls
+    pub fn assistant_with_tool_calls(content: &str, tool_calls: Vec<ToolCall>) -> Self {
+        Self::Assistant {
+            content: content.to_string(),
+            tool_calls,
+        }
     }
     
     /// Creates a new system message
@@ -0,0 +1,344 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::fluentcli::{CommandResult, OutputLine};
+use crate::models::job_queue::{
+    JobLease, LeaseStatus, NewQueueEntry, QueueEntry, DEFAULT_LEASE_SECONDS, MAX_LEASE_SECONDS,
+    MIN_LEASE_SECONDS,
+};
+use crate::models::job_run::NewJobRunLog;
+use crate::models::worker::Worker;
+use crate::services::job_service::JobService;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde_json::Value;
+use tokio::time::sleep;
+use uuid::Uuid;
+
+/// How often expired leases are looked for
+const REAPER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+/// The wait before a run whose worker went away is handed out again, doubled for each attempt
+const RETRY_BASE_SECONDS: i64 = 30;
+const MAX_RETRY_DELAY_SECONDS: i64 = 600;
+
+/// Runs wait in the `job_queue` table until a registered worker of the job's `worker_type` claims
+/// one. The worker holds it under a lease it renews while the pipeline runs; if the lease runs
+/// out the run is queued again, and after `max_attempts` it's dead-lettered.
+pub struct JobQueueService;
+
+impl JobQueueService {
+    /// Periodically takes back runs whose worker stopped renewing its lease
+    pub fn start_reaper(pool: DbPool) {
+        tokio::spawn(async move {
+            loop {
+                match Self::expire_leases(&pool) {
+                    Ok(0) => {}
+                    Ok(expired) => log::info!("Took back {} expired job leases", expired),
+                    Err(e) => log::error!("Error expiring job leases: {:?}", e),
+                }
+                sleep(REAPER_INTERVAL).await;
+            }
+        });
+    }
+
+    pub fn enqueue(conn: &mut PgConnection, entry: &NewQueueEntry) -> Result<(), AppError> {
+        diesel::insert_into(crate::schema::job_queue::table)
+            .values(entry)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Claims the oldest available run `worker` can take, if any. Workers run the jobs of the
+    /// user they're registered to with the same `worker_type`. Entries locked by a concurrent
+    /// claim are skipped, so each run goes to exactly one worker.
+    pub fn lease(
+        pool: &DbPool,
+        worker: &Worker,
+        lease_seconds: Option<i64>,
+    ) -> Result<Option<JobLease>, AppError> {
+        use crate::schema::job_queue::dsl::*;
+        let conn = &mut pool.get()?;
+        let expires_at = Utc::now() + lease_duration(lease_seconds);
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let entry = job_queue
+                .filter(status.eq("queued"))
+                .filter(user_id.eq(worker.user_id))
+                .filter(worker_type.eq(worker.worker_type))
+                .filter(available_at.le(diesel::dsl::now))
+                .order(available_at.asc())
+                .for_update()
+                .skip_locked()
+                .select(QueueEntry::as_select())
+                .first(conn)
+                .optional()?;
+            let entry = match entry {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+
+            let token = Uuid::new_v4();
+            diesel::update(job_queue.find(entry.run_id))
+                .set((
+                    status.eq("leased"),
+                    attempts.eq(attempts + 1),
+                    leased_by.eq(worker.id),
+                    lease_token.eq(token),
+                    lease_expires_at.eq(expires_at),
+                    updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(conn)?;
+            JobService::start_queued_run(conn, entry.job_id, entry.run_id)?;
+
+            log::info!(
+                "Worker {} leased run {} of job {} (attempt {} of {})",
+                worker.id,
+                entry.run_id,
+                entry.job_id,
+                entry.attempts + 1,
+                entry.max_attempts
+            );
+            Ok(Some(JobLease {
+                run_id: entry.run_id,
+                job_id: entry.job_id,
+                lease_token: token,
+                lease_expires_at: expires_at,
+                attempt: entry.attempts + 1,
+                command: entry.command,
+                state_file: format!("{}-{}.json", entry.pipeline_name, entry.run_id),
+            }))
+        })
+    }
+
+    /// Extends a lease `worker` still holds, and tells it whether the run should be stopped.
+    /// `None` means the lease was lost, e.g. because it expired and the run was handed out again.
+    pub fn renew_lease(
+        pool: &DbPool,
+        worker: &Worker,
+        run_id: Uuid,
+        token: Uuid,
+        lease_seconds: Option<i64>,
+    ) -> Result<Option<LeaseStatus>, AppError> {
+        use crate::schema::job_queue::{dsl, table as job_queue};
+        let conn = &mut pool.get()?;
+        let expires_at = Utc::now() + lease_duration(lease_seconds);
+        diesel::update(
+            job_queue.filter(
+                dsl::run_id
+                    .eq(run_id)
+                    .and(dsl::status.eq("leased"))
+                    .and(dsl::lease_token.eq(token))
+                    .and(dsl::leased_by.eq(worker.id)),
+            ),
+        )
+        .set((
+            dsl::lease_expires_at.eq(expires_at),
+            dsl::updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(dsl::stop_requested)
+        .get_result::<bool>(conn)
+        .optional()
+        .map(|stop_requested| {
+            stop_requested.map(|stop_requested| LeaseStatus {
+                lease_expires_at: expires_at,
+                stop_requested,
+            })
+        })
+        .map_err(AppError::DatabaseError)
+    }
+
+    /// Stores output lines of a leased run. Returns false if the lease was lost.
+    pub fn append_logs(
+        pool: &DbPool,
+        worker: &Worker,
+        run_id: Uuid,
+        token: Uuid,
+        lines: Vec<OutputLine>,
+    ) -> Result<bool, AppError> {
+        use crate::schema::job_queue::{dsl, table as job_queue};
+        use crate::schema::job_run_logs;
+        let conn = &mut pool.get()?;
+        let job_id = job_queue
+            .filter(
+                dsl::run_id
+                    .eq(run_id)
+                    .and(dsl::status.eq("leased"))
+                    .and(dsl::lease_token.eq(token))
+                    .and(dsl::leased_by.eq(worker.id)),
+            )
+            .select(dsl::job_id)
+            .first::<Uuid>(conn)
+            .optional()?;
+        let job_id = match job_id {
+            Some(job_id) => job_id,
+            None => return Ok(false),
+        };
+
+        let rows: Vec<NewJobRunLog> = lines
+            .into_iter()
+            .map(|output| NewJobRunLog {
+                run_id,
+                job_id,
+                stream: output.stream,
+                line: output.line,
+            })
+            .collect();
+        if !rows.is_empty() {
+            diesel::insert_into(job_run_logs::table)
+                .values(&rows)
+                .execute(conn)?;
+        }
+        Ok(true)
+    }
+
+    /// Releases a leased run once its command has exited, and records its result on the run
+    /// and job in the same transaction. Returns the entry, or `None` if the lease was lost.
+    pub fn complete(
+        pool: &DbPool,
+        worker: &Worker,
+        run_id: Uuid,
+        token: Uuid,
+        result: &CommandResult,
+        state_file: Option<Value>,
+    ) -> Result<Option<QueueEntry>, AppError> {
+        use crate::schema::job_queue::{dsl, table as job_queue};
+        let conn = &mut pool.get()?;
+        conn.transaction::<_, AppError, _>(|conn| {
+            let entry = diesel::update(
+                job_queue.filter(
+                    dsl::run_id
+                        .eq(run_id)
+                        .and(dsl::status.eq("leased"))
+                        .and(dsl::lease_token.eq(token))
+                        .and(dsl::leased_by.eq(worker.id)),
+                ),
+            )
+            .set((
+                dsl::status.eq("done"),
+                dsl::lease_token.eq(None::<Uuid>),
+                dsl::lease_expires_at.eq(None::<chrono::DateTime<Utc>>),
+                dsl::last_error.eq(&result.error),
+                dsl::updated_at.eq(diesel::dsl::now),
+            ))
+            .returning(QueueEntry::as_returning())
+            .get_result(conn)
+            .optional()?;
+            if let Some(entry) = &entry {
+                JobService::record_result(conn, entry, result, state_file)?;
+            }
+            Ok(entry)
+        })
+    }
+
+    /// Takes back every run whose lease has expired. A run that was being stopped ends as
+    /// stopped, one that has used up its attempts is dead-lettered and fails, and any other is
+    /// queued again after a delay. Returns how many leases expired.
+    pub fn expire_leases(pool: &DbPool) -> Result<usize, AppError> {
+        use crate::schema::job_queue::dsl::*;
+        let conn = &mut pool.get()?;
+
+        let ended = conn.transaction::<_, AppError, _>(|conn| {
+            let expired = job_queue
+                .filter(status.eq("leased"))
+                .filter(lease_expires_at.lt(diesel::dsl::now))
+                .for_update()
+                .skip_locked()
+                .select(QueueEntry::as_select())
+                .load(conn)?;
+
+            let mut ended = Vec::new();
+            for entry in expired {
+                let reason = format!(
+                    "Lease expired on worker {} during attempt {} of {}",
+                    entry
+                        .leased_by
+                        .map_or("unknown".to_string(), |w| w.to_string()),
+                    entry.attempts,
+                    entry.max_attempts
+                );
+                log::warn!("Run {} of job {}: {}", entry.run_id, entry.job_id, reason);
+                let entry_status = if entry.stop_requested {
+                    JobService::set_run_status(
+                        conn,
+                        entry.job_id,
+                        Some(entry.run_id),
+                        &["stopping"],
+                        "stopped",
+                    )?;
+                    "done"
+                } else if entry.attempts >= entry.max_attempts {
+                    JobService::fail_run(conn, entry.job_id, entry.run_id, &reason)?;
+                    "dead"
+                } else {
+                    JobService::set_run_status(
+                        conn,
+                        entry.job_id,
+                        Some(entry.run_id),
+                        &["running"],
+                        "queued",
+                    )?;
+                    "queued"
+                };
+
+                diesel::update(job_queue.find(entry.run_id))
+                    .set((
+                        status.eq(entry_status),
+                        available_at.eq(Utc::now() + retry_delay(entry.attempts)),
+                        leased_by.eq(None::<Uuid>),
+                        lease_token.eq(None::<Uuid>),
+                        lease_expires_at.eq(None::<chrono::DateTime<Utc>>),
+                        last_error.eq(&reason),
+                        updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(conn)?;
+                ended.push((entry, entry_status));
+            }
+            Ok(ended)
+        })?;
+
+        for (entry, entry_status) in &ended {
+            if *entry_status != "queued" {
+                let _ = std::fs::remove_file(&entry.pipeline_file);
+            }
+        }
+        Ok(ended.len())
+    }
+}
+
+fn lease_duration(seconds: Option<i64>) -> Duration {
+    Duration::seconds(
+        seconds
+            .unwrap_or(DEFAULT_LEASE_SECONDS)
+            .clamp(MIN_LEASE_SECONDS, MAX_LEASE_SECONDS),
+    )
+}
+
+/// How long a run waits before its next attempt, after `attempts` have been made
+fn retry_delay(attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    Duration::seconds((RETRY_BASE_SECONDS << doublings).min(MAX_RETRY_DELAY_SECONDS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lease_duration_is_clamped() {
+        assert_eq!(lease_duration(None), Duration::seconds(60));
+        assert_eq!(lease_duration(Some(120)), Duration::seconds(120));
+        assert_eq!(lease_duration(Some(1)), Duration::seconds(15));
+        assert_eq!(lease_duration(Some(-5)), Duration::seconds(15));
+        assert_eq!(lease_duration(Some(86_400)), Duration::seconds(600));
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_a_limit() {
+        assert_eq!(retry_delay(0), Duration::seconds(30));
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::seconds(60));
+        assert_eq!(retry_delay(3), Duration::seconds(120));
+        assert_eq!(retry_delay(5), Duration::seconds(480));
+        assert_eq!(retry_delay(6), Duration::seconds(600));
+        assert_eq!(retry_delay(i32::MAX), Duration::seconds(600));
+    }
+}
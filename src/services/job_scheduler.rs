@@ -1,10 +1,23 @@
 use crate::db::DbPool;
-use crate::models::job::Job;
-use crate::services::job_service::JobService;
+use crate::error::AppError;
 use crate::handlers::metrics::set_scheduled_jobs;
-use chrono::{DateTime, Utc};
+use crate::models::job::{Job, JobTimers, MisfirePolicy};
+use crate::models::job_run::{is_active_status, RunTrigger};
+use crate::services::job_service::JobService;
+use crate::utils::cron::CronExpression;
+use chrono::{DateTime, Duration, LocalResult, TimeZone, Utc};
+use chrono_tz::Tz;
 use serde_json::Value;
-use tokio::time::{sleep, Duration};
+use std::collections::VecDeque;
+use tokio::time::sleep;
+
+/// How often the scheduler looks for due jobs
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Jobs are only checked once a minute, so shorter intervals can't be kept
+const MIN_INTERVAL_SECONDS: i64 = 60;
+const DEFAULT_MISFIRE_GRACE_SECONDS: i64 = 300;
+/// The most missed runs `catch_up` replays; older ones are dropped
+const MAX_CATCH_UP_RUNS: usize = 100;
 
 pub struct JobScheduler;
 
@@ -12,29 +25,514 @@ impl JobScheduler {
     pub fn start(pool: DbPool) {
         tokio::spawn(async move {
             loop {
-                if let Ok(jobs) = JobService::fetch_scheduled_jobs(&pool) {
-                    set_scheduled_jobs(jobs.len() as i64);
-                    for job in jobs {
-                        if is_due(&job) {
-                            let _ = JobService::start_job(&pool, job.id, job.user_id).await;
+                match JobService::fetch_scheduled_jobs(&pool) {
+                    Ok(jobs) => {
+                        let mut scheduled = 0;
+                        for job in jobs {
+                            if Self::run_if_due(&pool, &job, Utc::now()).await {
+                                scheduled += 1;
+                            }
                         }
+                        set_scheduled_jobs(scheduled);
+                    }
+                    Err(e) => {
+                        log::error!("Error fetching scheduled jobs: {:?}", e);
+                        set_scheduled_jobs(0);
                     }
-                } else {
-                    set_scheduled_jobs(0);
                 }
-                sleep(Duration::from_secs(60)).await;
+                sleep(POLL_INTERVAL).await;
             }
         });
     }
+
+    /// Validates timers sent by a user and clears `next_run_at`, so the scheduler works it out
+    /// again from the new schedule
+    pub fn prepare_timers(timers: Option<Value>) -> Result<Option<Value>, AppError> {
+        let mut parsed = match parse_timers(&timers)? {
+            Some(parsed) => parsed,
+            None => return Ok(timers),
+        };
+        Schedule::from_timers(&parsed)?;
+        parsed.next_run_at = None;
+
+        serde_json::to_value(parsed)
+            .map(Some)
+            .map_err(|e| AppError::TimerError(e.to_string()))
+    }
+
+    /// Starts `job` if its schedule is due and stores when it runs next. Returns whether the job
+    /// has an active schedule.
+    async fn run_if_due(pool: &DbPool, job: &Job, now: DateTime<Utc>) -> bool {
+        let timers = match parse_timers(&job.timers) {
+            Ok(Some(timers)) => timers,
+            Ok(None) => return false,
+            Err(e) => {
+                log::warn!("Ignoring the timers of job {}: {}", job.id, e);
+                return false;
+            }
+        };
+        let schedule = match Schedule::from_timers(&timers) {
+            Ok(Some(schedule)) => schedule,
+            Ok(None) => return false,
+            Err(e) => {
+                log::warn!("Ignoring the timers of job {}: {}", job.id, e);
+                return false;
+            }
+        };
+        // A one-off run belongs to the `scheduled` status it was set with
+        if timers.enabled == Some(false) || (schedule.is_once() && job.status != "scheduled") {
+            return false;
+        }
+
+        let (next_run_at, fire) = match tick(&timers, &schedule, now) {
+            Tick::Wait => return true,
+            Tick::Reschedule(next_run_at) => (next_run_at, false),
+            Tick::Fire(_) if is_active_status(&job.status) => {
+                log::info!("Job {} is due but still running", job.id);
+                return true;
+            }
+            Tick::Fire(next_run_at) => (next_run_at, true),
+        };
+
+        let mut updated = timers.clone();
+        updated.next_run_at = next_run_at;
+        if fire {
+            updated.last_run_at = Some(now);
+        }
+        if updated != timers {
+            let current = job.timers.clone().unwrap_or(Value::Null);
+            let updated = match serde_json::to_value(&updated) {
+                Ok(updated) => updated,
+                Err(e) => {
+                    log::error!("Error serializing the timers of job {}: {}", job.id, e);
+                    return true;
+                }
+            };
+            // Claiming the run this way keeps it from starting twice, and leaves the job alone if
+            // its timers were edited in the meantime
+            match JobService::replace_timers(pool, job.id, current, updated) {
+                Ok(true) => {}
+                Ok(false) => {
+                    log::debug!(
+                        "Timers of job {} changed, leaving it for the next tick",
+                        job.id
+                    );
+                    return true;
+                }
+                Err(e) => {
+                    log::error!("Error updating the timers of job {}: {:?}", job.id, e);
+                    return true;
+                }
+            }
+        }
+
+        if fire {
+            log::info!("Starting scheduled job {}", job.id);
+            if let Err(e) =
+                JobService::start_job(pool, job.id, job.user_id, RunTrigger::Schedule).await
+            {
+                log::error!("Error starting scheduled job {}: {:?}", job.id, e);
+            }
+        }
+        true
+    }
+}
+
+fn parse_timers(timers: &Option<Value>) -> Result<Option<JobTimers>, AppError> {
+    match timers {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|e| AppError::TimerError(format!("Invalid timers: {}", e))),
+    }
+}
+
+/// When a job runs, read from its timers
+#[derive(Debug)]
+struct Schedule {
+    kind: ScheduleKind,
+    start_at: Option<DateTime<Utc>>,
+    end_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+enum ScheduleKind {
+    Cron(CronExpression, Tz),
+    /// Runs are counted from the anchor when there is one, so they don't drift
+    Interval(Duration, Option<DateTime<Utc>>),
+    Once(DateTime<Utc>),
+}
+
+impl Schedule {
+    fn from_timers(timers: &JobTimers) -> Result<Option<Self>, AppError> {
+        let kind = match (&timers.cron, timers.interval_seconds, timers.run_at) {
+            (None, None, None) => return Ok(None),
+            (Some(cron), None, None) => {
+                let expression = cron.parse::<CronExpression>().map_err(|e| {
+                    AppError::TimerError(format!("Invalid cron expression '{}': {}", cron, e))
+                })?;
+                let timezone = match &timers.timezone {
+                    Some(name) => name.parse::<Tz>().map_err(|_| {
+                        AppError::TimerError(format!("Unknown timezone '{}'", name))
+                    })?,
+                    None => Tz::UTC,
+                };
+                ScheduleKind::Cron(expression, timezone)
+            }
+            (None, Some(seconds), None) => {
+                if seconds < MIN_INTERVAL_SECONDS {
+                    return Err(AppError::TimerError(format!(
+                        "interval_seconds must be at least {}",
+                        MIN_INTERVAL_SECONDS
+                    )));
+                }
+                ScheduleKind::Interval(
+                    Duration::seconds(seconds),
+                    timers.start_at.or(timers.next_run_at),
+                )
+            }
+            (None, None, Some(run_at)) => ScheduleKind::Once(run_at),
+            _ => {
+                return Err(AppError::TimerError(
+                    "Only one of cron, interval_seconds and run_at can be set".to_string(),
+                ))
+            }
+        };
+
+        if timers.timezone.is_some() && !matches!(kind, ScheduleKind::Cron(..)) {
+            return Err(AppError::TimerError(
+                "timezone only applies to cron schedules".to_string(),
+            ));
+        }
+        if let (Some(start_at), Some(end_at)) = (timers.start_at, timers.end_at) {
+            if start_at >= end_at {
+                return Err(AppError::TimerError(
+                    "start_at must be before end_at".to_string(),
+                ));
+            }
+        }
+        if timers
+            .misfire_grace_seconds
+            .map_or(false, |grace| grace < 0)
+        {
+            return Err(AppError::TimerError(
+                "misfire_grace_seconds must not be negative".to_string(),
+            ));
+        }
+
+        Ok(Some(Self {
+            kind,
+            start_at: timers.start_at,
+            end_at: timers.end_at,
+        }))
+    }
+
+    fn is_once(&self) -> bool {
+        matches!(self.kind, ScheduleKind::Once(_))
+    }
+
+    /// The first run of a schedule that has no `next_run_at` yet
+    fn first(
+        &self,
+        now: DateTime<Utc>,
+        last_run_at: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        match self.kind {
+            // A one-off run that is already late still runs, as long as it never has
+            ScheduleKind::Once(run_at) => last_run_at.is_none().then_some(run_at),
+            _ => self.next_after(now),
+        }
+    }
+
+    /// The first run strictly after `after` that falls inside the schedule's window
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // Search from just before the window opens, so a run right at `start_at` counts
+        let after = match self.start_at {
+            Some(start_at) if after < start_at => start_at - Duration::nanoseconds(1),
+            _ => after,
+        };
+
+        let next = match &self.kind {
+            ScheduleKind::Cron(expression, timezone) => next_cron(expression, timezone, after),
+            ScheduleKind::Interval(_, Some(anchor)) if after < *anchor => Some(*anchor),
+            ScheduleKind::Interval(every, Some(anchor)) => {
+                let periods = (after - *anchor).num_seconds() / every.num_seconds() + 1;
+                Some(*anchor + *every * periods as i32)
+            }
+            ScheduleKind::Interval(every, None) => Some(after + *every),
+            ScheduleKind::Once(run_at) => Some(*run_at).filter(|run_at| *run_at > after),
+        };
+        next.filter(|next| self.end_at.map_or(true, |end_at| *next <= end_at))
+    }
+}
+
+/// Cron fields are matched in the schedule's timezone. A time skipped by a daylight saving change
+/// never runs, and one repeated by it runs the first time.
+fn next_cron(
+    expression: &CronExpression,
+    timezone: &Tz,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let mut local = after.with_timezone(timezone).naive_local();
+    loop {
+        local = expression.next_after(local)?;
+        let candidates = match timezone.from_local_datetime(&local) {
+            LocalResult::Single(time) => vec![time],
+            LocalResult::Ambiguous(earliest, latest) => vec![earliest, latest],
+            LocalResult::None => vec![],
+        };
+        if let Some(time) = candidates
+            .into_iter()
+            .map(|time| time.with_timezone(&Utc))
+            .find(|time| *time > after)
+        {
+            return Some(time);
+        }
+    }
 }
 
-fn is_due(job: &Job) -> bool {
-    if let Some(Value::Object(map)) = &job.timers {
-        if let Some(Value::String(ts)) = map.get("run_at") {
-            if let Ok(run_at) = DateTime::parse_from_rfc3339(ts) {
-                return run_at <= Utc::now();
+/// What the scheduler should do with a job on this tick
+#[derive(Debug, PartialEq)]
+enum Tick {
+    Wait,
+    /// Store a new `next_run_at` without running the job
+    Reschedule(Option<DateTime<Utc>>),
+    /// Run the job and store the `next_run_at` after this run
+    Fire(Option<DateTime<Utc>>),
+}
+
+fn tick(timers: &JobTimers, schedule: &Schedule, now: DateTime<Utc>) -> Tick {
+    let due = match timers
+        .next_run_at
+        .or_else(|| schedule.first(now, timers.last_run_at))
+    {
+        Some(due) => due,
+        None => return Tick::Wait,
+    };
+    if due > now {
+        return if timers.next_run_at == Some(due) {
+            Tick::Wait
+        } else {
+            Tick::Reschedule(Some(due))
+        };
+    }
+
+    let grace = Duration::seconds(
+        timers
+            .misfire_grace_seconds
+            .unwrap_or(DEFAULT_MISFIRE_GRACE_SECONDS),
+    );
+    if now - due <= grace || schedule.is_once() {
+        return Tick::Fire(schedule.next_after(due));
+    }
+
+    match timers.misfire_policy.unwrap_or_default() {
+        MisfirePolicy::Skip => Tick::Reschedule(schedule.next_after(now)),
+        MisfirePolicy::RunOnce => Tick::Fire(schedule.next_after(now)),
+        MisfirePolicy::CatchUp => {
+            let mut missed = VecDeque::new();
+            let mut occurrence = Some(due);
+            while let Some(time) = occurrence.filter(|time| *time <= now) {
+                if missed.len() == MAX_CATCH_UP_RUNS {
+                    missed.pop_front();
+                }
+                missed.push_back(time);
+                occurrence = schedule.next_after(time);
             }
+            // This tick replays the oldest run kept, and later ticks the rest
+            missed.pop_front();
+            Tick::Fire(missed.pop_front().or(occurrence))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn plan(timers: Value, now: &str) -> Tick {
+        let timers: JobTimers = serde_json::from_value(timers).unwrap();
+        let schedule = Schedule::from_timers(&timers).unwrap().unwrap();
+        tick(&timers, &schedule, at(now))
+    }
+
+    #[test]
+    fn first_tick_stores_the_next_run() {
+        assert_eq!(
+            plan(json!({ "cron": "0 2 * * *" }), "2025-06-01T01:30:00Z"),
+            Tick::Reschedule(Some(at("2025-06-01T02:00:00Z")))
+        );
+        assert_eq!(
+            plan(
+                json!({ "cron": "0 2 * * *", "next_run_at": "2025-06-01T02:00:00Z" }),
+                "2025-06-01T01:31:00Z"
+            ),
+            Tick::Wait
+        );
+    }
+
+    #[test]
+    fn due_runs_fire_and_advance() {
+        assert_eq!(
+            plan(
+                json!({ "cron": "0 2 * * *", "next_run_at": "2025-06-01T02:00:00Z" }),
+                "2025-06-01T02:00:40Z"
+            ),
+            Tick::Fire(Some(at("2025-06-02T02:00:00Z")))
+        );
+        assert_eq!(
+            plan(
+                json!({ "interval_seconds": 3600, "next_run_at": "2025-06-01T02:15:00Z" }),
+                "2025-06-01T02:16:00Z"
+            ),
+            Tick::Fire(Some(at("2025-06-01T03:15:00Z")))
+        );
+    }
+
+    #[test]
+    fn missed_runs_follow_the_misfire_policy() {
+        let timers = |policy: &str| {
+            json!({
+                "cron": "0 * * * *",
+                "next_run_at": "2025-06-01T10:00:00Z",
+                "misfire_policy": policy,
+            })
+        };
+        let now = "2025-06-01T13:30:00Z";
+
+        assert_eq!(
+            plan(timers("skip"), now),
+            Tick::Reschedule(Some(at("2025-06-01T14:00:00Z")))
+        );
+        assert_eq!(
+            plan(timers("run_once"), now),
+            Tick::Fire(Some(at("2025-06-01T14:00:00Z")))
+        );
+        assert_eq!(
+            plan(timers("catch_up"), now),
+            Tick::Fire(Some(at("2025-06-01T11:00:00Z")))
+        );
+    }
+
+    #[test]
+    fn catch_up_is_capped() {
+        let result = plan(
+            json!({
+                "interval_seconds": 60,
+                "next_run_at": "2025-06-01T00:00:00Z",
+                "misfire_policy": "catch_up",
+            }),
+            "2025-06-02T00:00:00Z",
+        );
+        // Only the last 100 minutes are replayed
+        assert_eq!(result, Tick::Fire(Some(at("2025-06-01T22:22:00Z"))));
+    }
+
+    #[test]
+    fn intervals_start_at_the_window() {
+        let timers = json!({ "interval_seconds": 900, "start_at": "2025-06-01T12:00:00Z" });
+        assert_eq!(
+            plan(timers.clone(), "2025-06-01T09:00:00Z"),
+            Tick::Reschedule(Some(at("2025-06-01T12:00:00Z")))
+        );
+        assert_eq!(
+            plan(timers, "2025-06-01T12:20:00Z"),
+            Tick::Reschedule(Some(at("2025-06-01T12:30:00Z")))
+        );
+    }
+
+    #[test]
+    fn nothing_runs_after_the_window_closes() {
+        assert_eq!(
+            plan(
+                json!({
+                    "cron": "0 2 * * *",
+                    "end_at": "2025-06-02T00:00:00Z",
+                    "next_run_at": "2025-06-01T02:00:00Z",
+                }),
+                "2025-06-01T02:00:10Z"
+            ),
+            Tick::Fire(None)
+        );
+        assert_eq!(
+            plan(
+                json!({ "cron": "0 2 * * *", "end_at": "2025-06-02T00:00:00Z" }),
+                "2025-06-03T00:00:00Z"
+            ),
+            Tick::Wait
+        );
+    }
+
+    #[test]
+    fn cron_follows_the_timezone_across_dst() {
+        let timers = |cron: &str| {
+            let timers: JobTimers =
+                serde_json::from_value(json!({ "cron": cron, "timezone": "America/New_York" }))
+                    .unwrap();
+            Schedule::from_timers(&timers).unwrap().unwrap()
+        };
+
+        // 02:30 doesn't exist on 9 March 2025
+        assert_eq!(
+            timers("30 2 * * *").next_after(at("2025-03-08T12:00:00Z")),
+            Some(at("2025-03-10T06:30:00Z"))
+        );
+        // 01:30 happens twice on 2 November 2025 and runs the first time only
+        let schedule = timers("30 1 * * *");
+        let first = schedule.next_after(at("2025-11-01T12:00:00Z")).unwrap();
+        assert_eq!(first, at("2025-11-02T05:30:00Z"));
+        assert_eq!(schedule.next_after(first), Some(at("2025-11-03T06:30:00Z")));
+    }
+
+    #[test]
+    fn one_off_runs_fire_once_however_late() {
+        let timers = json!({ "run_at": "2025-06-01T10:00:00Z" });
+        assert_eq!(plan(timers, "2025-06-01T18:00:00Z"), Tick::Fire(None));
+        assert_eq!(
+            plan(
+                json!({ "run_at": "2025-06-01T10:00:00Z", "last_run_at": "2025-06-01T10:00:30Z" }),
+                "2025-06-01T18:00:00Z"
+            ),
+            Tick::Wait
+        );
+    }
+
+    #[test]
+    fn prepare_timers_validates_and_resets_the_next_run() {
+        let prepared = JobScheduler::prepare_timers(Some(json!({
+            "cron": "@daily",
+            "next_run_at": "2025-06-01T00:00:00Z",
+            "label": "nightly",
+        })))
+        .unwrap()
+        .unwrap();
+        assert_eq!(prepared, json!({ "cron": "@daily", "label": "nightly" }));
+
+        for timers in [
+            json!({ "cron": "0 25 * * *" }),
+            json!({ "cron": "@daily", "timezone": "Mars/Olympus" }),
+            json!({ "cron": "@daily", "interval_seconds": 3600 }),
+            json!({ "interval_seconds": 5 }),
+            json!({ "interval_seconds": 3600, "timezone": "UTC" }),
+            json!({ "cron": "@daily", "misfire_policy": "sometimes" }),
+            json!({
+                "cron": "@daily",
+                "start_at": "2025-06-02T00:00:00Z",
+                "end_at": "2025-06-01T00:00:00Z",
+            }),
+        ] {
+            assert!(
+                JobScheduler::prepare_timers(Some(timers.clone())).is_err(),
+                "{} was accepted",
+                timers
+            );
         }
     }
-    false
 }
@@ -1,11 +1,19 @@
 use crate::db::DbPool;
 use crate::error::AppError;
 use crate::handlers::user;
-use crate::models::fluentcli::CommandRequest;
+use crate::models::fluentcli::{CommandRequest, CommandResult};
 use crate::models::job::{Job, NewJob, UpdateJob};
-
-use crate::services::fluentcli_service::FluentCLIService;
+use crate::models::job_queue::{NewQueueEntry, QueueEntry, DEFAULT_MAX_ATTEMPTS};
+use crate::models::job_run::{
+    is_active_status, FinishedJobRun, JobLogsPage, JobLogsQuery, JobRun, JobRunLog, JobRunSummary,
+    NewJobRun, RunTrigger, ACTIVE_RUN_STATUSES, DEFAULT_LOGS_PAGE, DEFAULT_RUNS_PAGE,
+    MAX_LOGS_PAGE, MAX_RUNS_PAGE,
+};
+
+use crate::services::job_queue_service::JobQueueService;
+use crate::services::job_scheduler::JobScheduler;
 use crate::services::pipeline_service::PipelineService;
+use chrono::Utc;
 use diesel::prelude::*;
 use serde_json::json;
 use std::fmt::Debug;
@@ -13,13 +21,14 @@ use tempfile::NamedTempFile;
 use tokio;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
-use tokio::time::{sleep, Duration};
 use uuid::Uuid;
+
 pub struct JobService;
 
 impl JobService {
-    pub fn create_job(pool: &DbPool, new_job: NewJob) -> Result<Job, AppError> {
+    pub fn create_job(pool: &DbPool, mut new_job: NewJob) -> Result<Job, AppError> {
         use crate::schema::jobs::dsl::*;
+        new_job.timers = JobScheduler::prepare_timers(new_job.timers)?;
         let conn = &mut pool.get()?;
         log::info!("Inserting new job into database: {:?}", new_job);
         match diesel::insert_into(jobs).values(&new_job).get_result(conn) {
@@ -43,14 +52,30 @@ impl JobService {
             .map_err(AppError::DatabaseError)
     }
 
+    /// Jobs the scheduler has to look at: those with timers, and those scheduled to run once
     pub fn fetch_scheduled_jobs(pool: &DbPool) -> Result<Vec<Job>, AppError> {
         use crate::schema::jobs::dsl::*;
         let conn = &mut pool.get()?;
-        jobs.filter(status.eq("scheduled"))
+        jobs.filter(status.eq("scheduled").or(timers.is_not_null()))
             .load::<Job>(conn)
             .map_err(AppError::DatabaseError)
     }
 
+    /// Replaces a job's timers only if they still equal `current`, and returns whether they did
+    pub fn replace_timers(
+        pool: &DbPool,
+        job_id: Uuid,
+        current: serde_json::Value,
+        updated: serde_json::Value,
+    ) -> Result<bool, AppError> {
+        use crate::schema::jobs::dsl::*;
+        let conn = &mut pool.get()?;
+        let count = diesel::update(jobs.filter(id.eq(job_id).and(timers.eq(Some(current)))))
+            .set((timers.eq(Some(updated)), updated_at.eq(diesel::dsl::now)))
+            .execute(conn)?;
+        Ok(count == 1)
+    }
+
     pub fn get_job(pool: &DbPool, job_id: Uuid, user_id: Uuid) -> Result<Job, AppError> {
         use crate::schema::jobs::dsl::*;
         let conn = &mut pool.get()?;
@@ -67,10 +92,13 @@ impl JobService {
     pub fn update_job(
         pool: &DbPool,
         job_id: Uuid,
-        update_data: UpdateJob,
+        mut update_data: UpdateJob,
         user_id: Uuid,
     ) -> Result<Job, AppError> {
         use crate::schema::jobs::dsl::*;
+        if update_data.timers.is_some() {
+            update_data.timers = JobScheduler::prepare_timers(update_data.timers)?;
+        }
         let conn = &mut pool.get()?;
         log::info!(
             "Updating job with id: {:?} for user_id: {:?}",
@@ -128,7 +156,12 @@ impl JobService {
         Ok(updated_job)
     }
 
-    pub async fn start_job(pool: &DbPool, job_id: Uuid, user_id: Uuid) -> Result<Job, AppError> {
+    pub async fn start_job(
+        pool: &DbPool,
+        job_id: Uuid,
+        user_id: Uuid,
+        trigger: RunTrigger,
+    ) -> Result<Job, AppError> {
         use crate::schema::jobs::dsl::*;
         let conn = &mut pool.get()?;
 
@@ -137,8 +170,8 @@ impl JobService {
             .filter(id.eq(job_id).and(user_id.eq(user_id)))
             .first::<Job>(conn)?;
 
-        // Check if the job is already running
-        if job.status == "running" {
+        // Check if the job is already queued or running
+        if is_active_status(&job.status) {
             return Err(AppError::BadRequest("Job is already running".to_string()));
         }
 
@@ -158,12 +191,11 @@ impl JobService {
         let shared_tmp_path =
             std::env::var("SHARED_TMP_PATH").map_err(|e| AppError::EnvVarError(e))?;
 
-        // Get the Fluent state store path from environment variable
-        let fluent_state_store =
-            std::env::var("FLUENT_STATE_STORE").map_err(|e| AppError::EnvVarError(e))?;
+        // Each run gets its own id, which the worker and the state file are keyed by
+        let run_id = Uuid::new_v4();
 
         // Create a temporary file with the pipeline content in the shared path
-        let temp_file_path = format!("{}/pipeline_{}.yaml", shared_tmp_path, job_id);
+        let temp_file_path = format!("{}/pipeline_{}.yaml", shared_tmp_path, run_id);
         log::debug!("Creating temp file at: {}", temp_file_path);
         tokio::fs::write(
             &temp_file_path,
@@ -182,12 +214,7 @@ impl JobService {
             ));
         }
 
-        // Update job status to "running"
-        let updated_job = diesel::update(jobs.find(job_id))
-            .set((status.eq("running"), started_at.eq(diesel::dsl::now)))
-            .get_result::<Job>(conn)?;
-
-        // Execute the job using FluentCLIService
+        // Execute the job on whichever worker claims it from the queue
         let command_request = CommandRequest {
             command: "openai".to_string(),
             args: vec![
@@ -197,136 +224,114 @@ impl JobService {
                 "--input".to_string(),
                 job.data_path.unwrap_or_default(),
                 "--run-id".to_string(),
-                job.id.to_string(),
+                run_id.to_string(),
                 "--json-output".to_string(),
             ],
+            run_id: Some(run_id),
         };
-
-        // Clone the pool for use in the spawned task
-        let pool_clone = pool.clone();
-        let job_id_clone = job.id;
-        let fluent_state_store_clone = fluent_state_store.clone();
-        let temp_file_path_clone = temp_file_path.clone();
-
-        tokio::spawn(async move {
-            let result = FluentCLIService::execute_command(job.user_id, command_request).await;
-
-            if let Ok(mut conn) = pool_clone.get() {
-                let state_file_pattern = format!("{}-{}.json", pipeline_name, job_id_clone);
-                log::debug!(
-                    "Looking for state file with pattern: {} in directory: {}",
-                    state_file_pattern,
-                    fluent_state_store_clone
-                );
-
-                // Add a delay before reading the state file
-                sleep(Duration::from_secs(5)).await;
-
-                let mut attempts = 0;
-                const MAX_ATTEMPTS: u32 = 10;
-                const RETRY_DELAY: Duration = Duration::from_secs(5);
-
-                let state_file_path = loop {
-                    attempts += 1;
-                    let mut found_path = None;
-                    match tokio::fs::read_dir(&fluent_state_store).await {
-                        Ok(mut entries) => {
-                            while let Ok(Some(entry)) = entries.next_entry().await {
-                                let file_name = entry.file_name();
-                                if file_name.to_string_lossy() == state_file_pattern {
-                                    found_path = Some(entry.path());
-                                    break;
-                                }
-                            }
-                            if let Some(path) = found_path {
-                                log::debug!("Found matching state file: {:?}", path);
-                                break Some(path);
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("Error reading directory: {:?}", e);
-                        }
-                    }
-
-                    if attempts >= MAX_ATTEMPTS {
-                        log::warn!(
-                            "No state file found for job {} in directory {} after {} attempts",
-                            job_id_clone,
-                            fluent_state_store,
-                            MAX_ATTEMPTS
-                        );
-                        break None;
-                    }
-
-                    log::debug!(
-                        "State file not found, attempt {} of {}",
-                        attempts,
-                        MAX_ATTEMPTS
-                    );
-                    sleep(RETRY_DELAY).await;
-                };
-
-                let state_file_content_str = if let Some(path) = state_file_path.as_ref() {
-                    tokio::fs::read_to_string(path).await.unwrap_or_else(|e| {
-                        log::warn!("Failed to read state file {:?}: {}", path, e);
-                        "{}".to_string()
-                    })
-                } else {
-                    log::warn!(
-                        "No state file found for job {} in directory {}",
-                        job_id_clone,
-                        fluent_state_store
-                    );
-                    "{}".to_string()
-                };
-
-                let state_file_json =
-                    serde_json::from_str::<serde_json::Value>(&state_file_content_str)
-                        .unwrap_or_else(|e| {
-                            log::warn!("Failed to parse state file as JSON: {}", e);
-                            serde_json::Value::Null
-                        });
-
-                match result {
-                    Ok(command_result) => {
-                        let _ = diesel::update(jobs.find(job_id_clone))
-                            .set((
-                                status.eq("completed"),
-                                completed_at.eq(diesel::dsl::now),
-                                results.eq(serde_json::to_value(command_result)
-                                    .unwrap_or(serde_json::Value::Null)),
-                                state_file_content.eq(state_file_json),
-                            ))
-                            .execute(&mut conn);
-                    }
-                    Err(e) => {
-                        let _ = diesel::update(jobs.find(job_id_clone))
-                            .set((
-                                status.eq("failed"),
-                                completed_at.eq(diesel::dsl::now),
-                                results.eq(serde_json::to_value(e.to_string())
-                                    .unwrap_or(serde_json::Value::Null)),
-                                state_file_content.eq(state_file_json),
-                            ))
-                            .execute(&mut conn);
-                    }
-                }
-
-                // Add a delay before file deletion (e.g., 10 seconds)
-                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-
-                // Clean up the temporary files
-                let _ = tokio::fs::remove_file(&temp_file_path).await;
-                if let Some(path) = state_file_path {
-                    let _ = tokio::fs::remove_file(path).await;
-                }
-            }
+        let command = serde_json::to_value(&command_request)
+            .map_err(|e| AppError::SerializationError(e.to_string()))?;
+
+        // Record the run and queue it, and update job status to "queued"
+        let updated_job = conn.transaction::<_, AppError, _>(|conn| {
+            diesel::insert_into(crate::schema::job_runs::table)
+                .values(&NewJobRun {
+                    id: run_id,
+                    job_id,
+                    user_id: job.user_id,
+                    trigger_source: trigger.as_str().to_string(),
+                    status: "queued".to_string(),
+                })
+                .execute(conn)?;
+            JobQueueService::enqueue(
+                conn,
+                &NewQueueEntry {
+                    run_id,
+                    job_id,
+                    user_id: job.user_id,
+                    worker_type: job.worker_type,
+                    command,
+                    pipeline_name,
+                    pipeline_file: temp_file_path.clone(),
+                    max_attempts: DEFAULT_MAX_ATTEMPTS,
+                },
+            )?;
+            diesel::update(jobs.find(job_id))
+                .set(status.eq("queued"))
+                .get_result::<Job>(conn)
+                .map_err(AppError::DatabaseError)
         });
+        if updated_job.is_err() {
+            let _ = tokio::fs::remove_file(&temp_file_path).await;
+        }
+        updated_job
+    }
 
-        Ok(updated_job)
+    /// Marks a queued run, and its job, as running once a worker has claimed it
+    pub fn start_queued_run(
+        conn: &mut PgConnection,
+        job_id: Uuid,
+        run_id: Uuid,
+    ) -> Result<(), AppError> {
+        use crate::schema::{job_runs, jobs};
+        diesel::update(
+            job_runs::table.filter(job_runs::id.eq(run_id).and(job_runs::status.eq("queued"))),
+        )
+        .set((
+            job_runs::status.eq("running"),
+            job_runs::started_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)?;
+        diesel::update(jobs::table.filter(jobs::id.eq(job_id).and(jobs::status.eq("queued"))))
+            .set((
+                jobs::status.eq("running"),
+                jobs::started_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Stores the result a worker reported for a run, along with the pipeline's state file, on
+    /// the run and on its job
+    pub fn record_result(
+        conn: &mut PgConnection,
+        entry: &QueueEntry,
+        command_result: &CommandResult,
+        state_file: Option<serde_json::Value>,
+    ) -> Result<(), AppError> {
+        use crate::schema::jobs::dsl::*;
+        let run_status = if command_result.stopped {
+            "stopped"
+        } else if command_result.exit_code == 0 {
+            "completed"
+        } else {
+            "failed"
+        };
+        let finished = FinishedJobRun {
+            exit_code: Some(command_result.exit_code),
+            stdout: Some(command_result.output.clone()),
+            stderr: command_result.error.clone(),
+            error: None,
+            state_file_content: state_file.clone(),
+            completed_at: Utc::now(),
+        };
+        let run_status = JobService::finish_run(conn, entry.run_id, run_status, &finished)?;
+
+        // The job itself keeps the outcome of its latest run
+        let job_results = serde_json::to_value(command_result).unwrap_or(serde_json::Value::Null);
+        diesel::update(jobs.find(entry.job_id))
+            .set((
+                status.eq(run_status),
+                completed_at.eq(diesel::dsl::now),
+                results.eq(job_results),
+                state_file_content.eq(state_file),
+            ))
+            .execute(conn)?;
+        Ok(())
     }
 
     pub async fn stop_job(pool: &DbPool, job_id: Uuid, user_id: Uuid) -> Result<Job, AppError> {
+        use crate::schema::job_queue;
         use crate::schema::jobs::dsl::*;
         let conn = &mut pool.get()?;
 
@@ -335,20 +340,241 @@ impl JobService {
             .filter(id.eq(job_id).and(user_id.eq(user_id)))
             .first::<Job>(conn)?;
 
-        // Check if the job is running
-        if job.status != "running" {
+        // Check if the job is queued or running; stopping it again repeats the stop request
+        if !is_active_status(&job.status) {
             return Err(AppError::BadRequest("Job is not running".to_string()));
         }
 
-        // Update job status to "stopped"
-        let updated_job = diesel::update(jobs.find(job_id))
-            .set((status.eq("stopped"), completed_at.eq(diesel::dsl::now)))
-            .get_result::<Job>(conn)?;
+        let run_id = {
+            use crate::schema::job_runs;
+            job_runs::table
+                .filter(job_runs::job_id.eq(job_id))
+                .filter(job_runs::status.eq_any(ACTIVE_RUN_STATUSES.to_vec()))
+                .order(job_runs::started_at.desc())
+                .select(job_runs::id)
+                .first::<Uuid>(conn)
+                .optional()?
+        };
+        let run_id = match run_id {
+            Some(run_id) => run_id,
+            None => {
+                Self::set_run_status(conn, job_id, None, ACTIVE_RUN_STATUSES, "stopped")?;
+                return jobs
+                    .find(job_id)
+                    .first::<Job>(conn)
+                    .map_err(AppError::DatabaseError);
+            }
+        };
 
-        // Send stop signal to worker
-        let _ = FluentCLIService::stop_command(job.id).await;
+        // A run no worker has claimed yet is simply taken off the queue
+        let pipeline_file = diesel::update(
+            job_queue::table.filter(
+                job_queue::run_id
+                    .eq(run_id)
+                    .and(job_queue::status.eq("queued")),
+            ),
+        )
+        .set((
+            job_queue::status.eq("done"),
+            job_queue::stop_requested.eq(true),
+            job_queue::updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(job_queue::pipeline_file)
+        .get_result::<String>(conn)
+        .optional()?;
+        if let Some(pipeline_file) = pipeline_file {
+            Self::set_run_status(conn, job_id, Some(run_id), &["queued"], "stopped")?;
+            let _ = tokio::fs::remove_file(pipeline_file).await;
+            return jobs
+                .find(job_id)
+                .first::<Job>(conn)
+                .map_err(AppError::DatabaseError);
+        }
 
-        Ok(updated_job)
+        // Marked before the worker hears about it, so the final status it reports can't be
+        // overwritten. The worker sees the request when it next renews its lease.
+        Self::set_run_status(
+            conn,
+            job_id,
+            Some(run_id),
+            &["running", "stopping"],
+            "stopping",
+        )?;
+        let requested = diesel::update(
+            job_queue::table.filter(
+                job_queue::run_id
+                    .eq(run_id)
+                    .and(job_queue::status.eq("leased")),
+            ),
+        )
+        .set((
+            job_queue::stop_requested.eq(true),
+            job_queue::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)?;
+        if requested == 0 {
+            // Either the worker has just reported the result, which sets the final status, or
+            // nothing will: the run was started before the queue existed
+            let queued = job_queue::table
+                .find(run_id)
+                .select(job_queue::run_id)
+                .first::<Uuid>(conn)
+                .optional()?;
+            if queued.is_none() {
+                Self::set_run_status(conn, job_id, Some(run_id), &["stopping"], "stopped")?;
+            }
+        }
+
+        jobs.find(job_id)
+            .first::<Job>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    /// Moves a job and its current run from one of the `from` statuses to `to`, and records when
+    /// they ended if `to` is final. Rows that have moved on, e.g. because the run finished
+    /// meanwhile, are left alone.
+    pub fn set_run_status(
+        conn: &mut PgConnection,
+        job_id: Uuid,
+        run_id: Option<Uuid>,
+        from: &[&str],
+        to: &str,
+    ) -> Result<(), AppError> {
+        use crate::schema::{job_runs, jobs};
+        let ended = !is_active_status(to);
+
+        if let Some(run_id) = run_id {
+            let run = job_runs::table
+                .filter(job_runs::id.eq(run_id))
+                .filter(job_runs::status.eq_any(from.to_vec()));
+            if ended {
+                diesel::update(run)
+                    .set((
+                        job_runs::status.eq(to),
+                        job_runs::completed_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(conn)?;
+            } else {
+                diesel::update(run)
+                    .set(job_runs::status.eq(to))
+                    .execute(conn)?;
+            }
+        }
+
+        let job = jobs::table
+            .filter(jobs::id.eq(job_id))
+            .filter(jobs::status.eq_any(from.to_vec()));
+        if ended {
+            diesel::update(job)
+                .set((jobs::status.eq(to), jobs::completed_at.eq(diesel::dsl::now)))
+                .execute(conn)?;
+        } else {
+            diesel::update(job).set(jobs::status.eq(to)).execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Fails a run that no worker could finish, e.g. because it was dead-lettered
+    pub fn fail_run(
+        conn: &mut PgConnection,
+        job_id: Uuid,
+        run_id: Uuid,
+        error: &str,
+    ) -> Result<(), AppError> {
+        use crate::schema::{job_runs, jobs};
+        diesel::update(
+            job_runs::table.filter(
+                job_runs::id
+                    .eq(run_id)
+                    .and(job_runs::status.eq_any(ACTIVE_RUN_STATUSES.to_vec())),
+            ),
+        )
+        .set((
+            job_runs::status.eq("failed"),
+            job_runs::error.eq(error),
+            job_runs::completed_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)?;
+        diesel::update(
+            jobs::table.filter(
+                jobs::id
+                    .eq(job_id)
+                    .and(jobs::status.eq_any(ACTIVE_RUN_STATUSES.to_vec())),
+            ),
+        )
+        .set((
+            jobs::status.eq("failed"),
+            jobs::completed_at.eq(diesel::dsl::now),
+            jobs::results.eq(serde_json::to_value(error).unwrap_or(serde_json::Value::Null)),
+        ))
+        .execute(conn)?;
+        Ok(())
+    }
+
+    /// Stores how a run ended and returns its final status. A run that was already marked as
+    /// stopped keeps that status.
+    fn finish_run(
+        conn: &mut PgConnection,
+        run_id: Uuid,
+        run_status: &str,
+        finished: &FinishedJobRun,
+    ) -> Result<String, AppError> {
+        use crate::schema::job_runs::dsl::*;
+        let updated = diesel::update(
+            job_runs.filter(
+                id.eq(run_id)
+                    .and(status.eq_any(vec!["running", "stopping"])),
+            ),
+        )
+        .set((status.eq(run_status), finished))
+        .execute(conn)?;
+        if updated == 0 {
+            diesel::update(job_runs.find(run_id))
+                .set(finished)
+                .execute(conn)?;
+        }
+        job_runs
+            .find(run_id)
+            .select(status)
+            .first::<String>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn list_runs(
+        pool: &DbPool,
+        job_id: Uuid,
+        user_id: Uuid,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<JobRunSummary>, AppError> {
+        use crate::schema::job_runs;
+        let conn = &mut pool.get()?;
+        job_runs::table
+            .filter(job_runs::job_id.eq(job_id))
+            .filter(job_runs::user_id.eq(user_id))
+            .order(job_runs::started_at.desc())
+            .limit(limit.unwrap_or(DEFAULT_RUNS_PAGE).clamp(1, MAX_RUNS_PAGE))
+            .offset(offset.unwrap_or(0).max(0))
+            .select(JobRunSummary::as_select())
+            .load(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn get_run(
+        pool: &DbPool,
+        job_id: Uuid,
+        run_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<JobRun, AppError> {
+        use crate::schema::job_runs;
+        let conn = &mut pool.get()?;
+        job_runs::table
+            .filter(job_runs::id.eq(run_id))
+            .filter(job_runs::job_id.eq(job_id))
+            .filter(job_runs::user_id.eq(user_id))
+            .select(JobRun::as_select())
+            .first(conn)
+            .map_err(AppError::DatabaseError)
     }
 
     pub async fn get_job_status(
@@ -383,20 +609,77 @@ impl JobService {
         Ok(job_results)
     }
 
-    pub async fn get_job_logs(
+    /// A page of a run's output, from `query.run_id` or the job's latest run
+    pub fn get_job_logs(
         pool: &DbPool,
         job_id: Uuid,
         user_id: Uuid,
-    ) -> Result<Option<serde_json::Value>, AppError> {
-        use crate::schema::jobs::dsl::*;
+        query: &JobLogsQuery,
+    ) -> Result<JobLogsPage, AppError> {
+        let run = Self::find_log_run(pool, job_id, user_id, query.run_id)?;
+        let after = query.after.unwrap_or(0);
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_LOGS_PAGE)
+            .clamp(1, MAX_LOGS_PAGE);
+        let lines = Self::run_logs(pool, run.id, after, limit)?;
+
+        Ok(JobLogsPage {
+            run_id: run.id,
+            status: run.status,
+            next_after: lines.last().map_or(after, |line| line.id),
+            lines,
+        })
+    }
+
+    pub fn find_log_run(
+        pool: &DbPool,
+        job_id: Uuid,
+        user_id: Uuid,
+        run_id: Option<Uuid>,
+    ) -> Result<JobRunSummary, AppError> {
+        use crate::schema::job_runs;
         let conn = &mut pool.get()?;
+        let mut query = job_runs::table
+            .filter(job_runs::job_id.eq(job_id))
+            .filter(job_runs::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(run_id) = run_id {
+            query = query.filter(job_runs::id.eq(run_id));
+        }
+        query
+            .order(job_runs::started_at.desc())
+            .select(JobRunSummary::as_select())
+            .first(conn)
+            .map_err(AppError::DatabaseError)
+    }
 
-        let job_results = jobs
-            .filter(id.eq(job_id).and(user_id.eq(user_id)))
-            .select(results)
-            .first::<Option<serde_json::Value>>(conn)?;
+    pub fn run_logs(
+        pool: &DbPool,
+        run_id: Uuid,
+        after: i64,
+        limit: i64,
+    ) -> Result<Vec<JobRunLog>, AppError> {
+        use crate::schema::job_run_logs;
+        let conn = &mut pool.get()?;
+        job_run_logs::table
+            .filter(job_run_logs::run_id.eq(run_id))
+            .filter(job_run_logs::id.gt(after))
+            .order(job_run_logs::id.asc())
+            .limit(limit)
+            .select(JobRunLog::as_select())
+            .load(conn)
+            .map_err(AppError::DatabaseError)
+    }
 
-        Ok(job_results)
+    pub fn run_status(pool: &DbPool, run_id: Uuid) -> Result<String, AppError> {
+        use crate::schema::job_runs;
+        let conn = &mut pool.get()?;
+        job_runs::table
+            .find(run_id)
+            .select(job_runs::status)
+            .first(conn)
+            .map_err(AppError::DatabaseError)
     }
 
     pub async fn get_job_data(
@@ -0,0 +1,170 @@
+/// Splits text into chunks of at most `size` characters. Paragraphs are kept whole where they
+/// fit, and each chunk after the first starts with up to `overlap` characters from the end of
+/// the previous one so a passage cut at a boundary can still be found.
+pub fn chunk_text(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    let size = size.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for piece in paragraphs(text)
+        .iter()
+        .flat_map(|paragraph| split_long(paragraph, size))
+    {
+        let piece_len = piece.chars().count();
+        if current.is_empty() {
+            current = piece;
+            current_len = piece_len;
+            continue;
+        }
+        if current_len + 2 + piece_len <= size {
+            current.push_str("\n\n");
+            current.push_str(&piece);
+            current_len += 2 + piece_len;
+            continue;
+        }
+
+        // Carry the end of the finished chunk over, as far as the next piece leaves room for
+        let carried = tail(&current, overlap.min(size.saturating_sub(piece_len + 1))).to_string();
+        chunks.push(std::mem::take(&mut current));
+        if carried.is_empty() {
+            current = piece;
+        } else {
+            current = format!("{} {}", carried, piece);
+        }
+        current_len = current.chars().count();
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Paragraphs separated by blank lines, with line endings normalized
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs
+}
+
+/// Breaks a paragraph longer than `size` at sentence ends, or else at whitespace
+fn split_long(paragraph: &str, size: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = paragraph.trim();
+
+    while rest.chars().count() > size {
+        let limit = rest
+            .char_indices()
+            .nth(size)
+            .map(|(index, _)| index)
+            .unwrap_or(rest.len());
+        let head = &rest[..limit];
+        let cut = head
+            .rfind(". ")
+            .map(|index| index + 1)
+            .filter(|&index| index > limit / 2)
+            .or_else(|| head.rfind(char::is_whitespace).filter(|&index| index > 0))
+            .unwrap_or(limit);
+
+        pieces.push(rest[..cut].trim_end().to_string());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces
+}
+
+/// The last `max` characters of `text`, starting at a word boundary where there is one
+fn tail(text: &str, max: usize) -> &str {
+    if max == 0 {
+        return "";
+    }
+    let count = text.chars().count();
+    if count <= max {
+        return text;
+    }
+
+    let start = text
+        .char_indices()
+        .nth(count - max)
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+    let tail = &text[start..];
+    match tail.find(char::is_whitespace) {
+        Some(index) => tail[index..].trim_start(),
+        None => tail,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_paragraphs_share_a_chunk() {
+        let chunks = chunk_text(
+            "First paragraph.\n\nSecond paragraph.\r\n\r\nThird.",
+            100,
+            20,
+        );
+        assert_eq!(
+            chunks,
+            vec!["First paragraph.\n\nSecond paragraph.\n\nThird."]
+        );
+    }
+
+    #[test]
+    fn chunks_respect_the_size_and_overlap() {
+        let text = (0..40)
+            .map(|i| format!("Sentence number {} is here.", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let chunks = chunk_text(&text, 120, 30);
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 120));
+        for pair in chunks.windows(2) {
+            let carried = pair[1].split(' ').take(2).collect::<Vec<_>>().join(" ");
+            assert!(
+                pair[0].contains(&carried),
+                "{:?} does not overlap {:?}",
+                pair[1],
+                pair[0]
+            );
+        }
+    }
+
+    #[test]
+    fn words_longer_than_a_chunk_are_cut() {
+        let chunks = chunk_text(&"x".repeat(25), 10, 0);
+        assert_eq!(chunks, vec!["x".repeat(10), "x".repeat(10), "x".repeat(5)]);
+    }
+
+    #[test]
+    fn multibyte_text_is_cut_on_char_boundaries() {
+        let chunks = chunk_text(&"é ".repeat(50), 15, 4);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 15));
+    }
+
+    #[test]
+    fn blank_text_has_no_chunks() {
+        assert!(chunk_text(" \n\n  \n", 100, 10).is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::error::AppError;
+use log::error;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// PDFs are converted by poppler's `pdftotext`, which must be on the PATH
+const PDFTOTEXT: &str = "pdftotext";
+const PDF_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Document formats that can be ingested into a collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Text,
+    Markdown,
+    Html,
+    Pdf,
+}
+
+impl DocumentFormat {
+    /// Picks the format from a content type, or from the file name when there is none
+    pub fn detect(content_type: Option<&str>, name: &str) -> Option<Self> {
+        let content_type = match content_type {
+            Some(content_type) => content_type.to_string(),
+            None => mime_guess::from_path(name)
+                .first()?
+                .essence_str()
+                .to_string(),
+        };
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        match essence.as_str() {
+            "application/pdf" => Some(DocumentFormat::Pdf),
+            "text/html" | "application/xhtml+xml" => Some(DocumentFormat::Html),
+            "text/markdown" | "text/x-markdown" => Some(DocumentFormat::Markdown),
+            essence if essence.starts_with("text/") => Some(DocumentFormat::Text),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DocumentFormat::Text => "text/plain",
+            DocumentFormat::Markdown => "text/markdown",
+            DocumentFormat::Html => "text/html",
+            DocumentFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// Extracts the plain text of a document. Markdown is kept as is, since models read it well
+pub async fn extract_text(format: DocumentFormat, bytes: &[u8]) -> Result<String, AppError> {
+    match format {
+        DocumentFormat::Text | DocumentFormat::Markdown => {
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        }
+        DocumentFormat::Html => Ok(html_to_text(&String::from_utf8_lossy(bytes))),
+        DocumentFormat::Pdf => pdf_to_text(bytes).await,
+    }
+}
+
+async fn pdf_to_text(bytes: &[u8]) -> Result<String, AppError> {
+    let mut child = Command::new(PDFTOTEXT)
+        .args(["-enc", "UTF-8", "-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            error!("Failed to start {}: {:?}", PDFTOTEXT, e);
+            AppError::ConfigurationError(format!("{} is not available: {}", PDFTOTEXT, e))
+        })?;
+
+    // Write on a separate task so a full stdout pipe can't block the input
+    let mut stdin = child.stdin.take().ok_or(AppError::InternalServerError)?;
+    let input = bytes.to_vec();
+    let writer = tokio::spawn(async move { stdin.write_all(&input).await });
+
+    let output = timeout(PDF_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| AppError::BadRequest("Timed out reading PDF".to_string()))?
+        .map_err(|e| AppError::BadRequest(format!("Failed to read PDF: {}", e)))?;
+    let _ = writer.await;
+
+    if !output.status.success() {
+        return Err(AppError::BadRequest(format!(
+            "Failed to read PDF: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // Pages are separated by form feeds
+    Ok(String::from_utf8_lossy(&output.stdout).replace('\u{c}', "\n\n"))
+}
+
+/// Strips tags, scripts and styles from HTML, keeping block elements as paragraph breaks
+pub fn html_to_text(html: &str) -> String {
+    // ASCII lowercasing keeps byte offsets identical to `html`
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        push_text(&mut text, &html[pos..start]);
+
+        if lower[start..].starts_with("<!--") {
+            pos = lower[start..]
+                .find("-->")
+                .map_or(html.len(), |end| start + end + 3);
+            continue;
+        }
+        let Some(close) = html[start..].find('>') else {
+            push_text(&mut text, &html[start..]);
+            pos = html.len();
+            break;
+        };
+        let end = start + close + 1;
+        let closing = lower[start + 1..].starts_with('/');
+        let name: String = lower[start + 1..end]
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        pos = end;
+
+        match name.as_str() {
+            "script" | "style" | "head" | "noscript" if !closing => {
+                let end_tag = format!("</{}", name);
+                pos = lower[end..]
+                    .find(&end_tag)
+                    .map_or(html.len(), |index| end + index);
+            }
+            "br" => text.push('\n'),
+            "li" | "tr" if !closing => text.push('\n'),
+            "td" | "th" if !closing => text.push(' '),
+            "p" | "div" | "section" | "article" | "header" | "footer" | "blockquote" | "pre"
+            | "ul" | "ol" | "table" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                text.push_str("\n\n")
+            }
+            _ => {}
+        }
+    }
+    push_text(&mut text, &html[pos..]);
+
+    normalize_whitespace(&text)
+}
+
+/// Appends text between tags; whitespace in HTML source is insignificant, so newlines become spaces
+fn push_text(text: &mut String, raw: &str) {
+    text.push_str(&decode_entities(raw).replace(char::is_whitespace, " "));
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(|c| c == 'x' || c == 'X') {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Collapses spaces within lines and runs of blank lines into single paragraph breaks
+fn normalize_whitespace(text: &str) -> String {
+    let mut paragraphs = Vec::new();
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            if !lines.is_empty() {
+                paragraphs.push(lines.join("\n"));
+                lines.clear();
+            }
+        } else {
+            lines.push(line);
+        }
+    }
+    if !lines.is_empty() {
+        paragraphs.push(lines.join("\n"));
+    }
+    paragraphs.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_come_from_the_content_type_or_name() {
+        assert_eq!(
+            DocumentFormat::detect(Some("text/html; charset=utf-8"), "page"),
+            Some(DocumentFormat::Html)
+        );
+        assert_eq!(
+            DocumentFormat::detect(None, "guide.pdf"),
+            Some(DocumentFormat::Pdf)
+        );
+        assert_eq!(
+            DocumentFormat::detect(None, "notes.txt"),
+            Some(DocumentFormat::Text)
+        );
+        assert_eq!(DocumentFormat::detect(Some("image/png"), "chart.png"), None);
+    }
+
+    #[test]
+    fn html_keeps_text_and_paragraphs() {
+        let html = r#"<html><head><title>Ignored</title><style>p { color: red; }</style></head>
+            <body><h1>Rotating   keys</h1>
+            <p>Open <b>Settings</b> &amp; choose
+            &quot;API keys&quot;.</p><!-- a comment --><script>alert("x")</script>
+            <ul><li>Create a key</li><li>Revoke the old one&#33;</li></ul></body></html>"#;
+
+        assert_eq!(
+            html_to_text(html),
+            "Rotating keys\n\nOpen Settings & choose \"API keys\".\n\nCreate a key\nRevoke the old one!"
+        );
+    }
+
+    #[test]
+    fn stray_markup_is_kept_as_text() {
+        assert_eq!(html_to_text("1 &lt; 2 & 3 < 4"), "1 < 2 & 3 < 4");
+    }
+}
@@ -0,0 +1,196 @@
+use super::search::{tokenize, RRF_K};
+use std::collections::HashMap;
+
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// In-memory index over chunk texts and embeddings. Chunks are ranked separately by BM25
+/// keyword score and by cosine similarity, and the two rankings are merged with reciprocal
+/// rank fusion, so a chunk that does well on both comes first. Searches run in Postgres (see
+/// `search::HYBRID_SEARCH_SQL`); this is the same ranking without a database, for tests.
+#[derive(Default)]
+pub struct HybridIndex {
+    term_counts: Vec<HashMap<String, usize>>,
+    lengths: Vec<usize>,
+    document_frequency: HashMap<String, usize>,
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl HybridIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk, returning its position in the index
+    pub fn add(&mut self, text: &str, embedding: Vec<f32>) -> usize {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let tokens = tokenize(text);
+        for token in &tokens {
+            *counts.entry(token.clone()).or_insert(0) += 1;
+        }
+        for term in counts.keys() {
+            *self.document_frequency.entry(term.clone()).or_insert(0) += 1;
+        }
+
+        self.lengths.push(tokens.len());
+        self.term_counts.push(counts);
+        self.embeddings.push(embedding);
+        self.embeddings.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.embeddings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.embeddings.is_empty()
+    }
+
+    /// Returns up to `limit` positions with their fused scores, best first
+    pub fn search(&self, query: &str, query_embedding: &[f32], limit: usize) -> Vec<(usize, f32)> {
+        let mut fused = vec![0.0f32; self.len()];
+        for ranking in [
+            self.keyword_ranking(query),
+            self.vector_ranking(query_embedding),
+        ] {
+            for (rank, (position, _)) in ranking.into_iter().enumerate() {
+                fused[position] += 1.0 / (RRF_K + rank as f32 + 1.0);
+            }
+        }
+
+        let mut results: Vec<(usize, f32)> = fused
+            .into_iter()
+            .enumerate()
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        results.truncate(limit);
+        results
+    }
+
+    /// Chunks containing at least one query term, by BM25 score
+    fn keyword_ranking(&self, query: &str) -> Vec<(usize, f32)> {
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() || self.is_empty() {
+            return Vec::new();
+        }
+
+        let count = self.len() as f32;
+        let average_length = (self.lengths.iter().sum::<usize>() as f32 / count).max(1.0);
+        let mut scores: Vec<(usize, f32)> = self
+            .term_counts
+            .iter()
+            .enumerate()
+            .map(|(position, counts)| {
+                let length_norm =
+                    1.0 - BM25_B + BM25_B * self.lengths[position] as f32 / average_length;
+                let score = terms
+                    .iter()
+                    .filter_map(|term| {
+                        let frequency = *counts.get(term)? as f32;
+                        let documents = self.document_frequency[term] as f32;
+                        let idf = ((count - documents + 0.5) / (documents + 0.5) + 1.0).ln();
+                        Some(
+                            idf * frequency * (BM25_K1 + 1.0) / (frequency + BM25_K1 * length_norm),
+                        )
+                    })
+                    .sum::<f32>();
+                (position, score)
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores
+    }
+
+    /// Chunks whose embedding has the query's dimensions, by cosine similarity
+    fn vector_ranking(&self, query_embedding: &[f32]) -> Vec<(usize, f32)> {
+        let mut scores: Vec<(usize, f32)> = self
+            .embeddings
+            .iter()
+            .enumerate()
+            .filter(|(_, embedding)| embedding.len() == query_embedding.len())
+            .map(|(position, embedding)| (position, cosine_similarity(embedding, query_embedding)))
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> HybridIndex {
+        let mut index = HybridIndex::new();
+        index.add(
+            "Rotate an API key from the settings page",
+            vec![1.0, 0.0, 0.0],
+        );
+        index.add(
+            "Invoices are sent on the first of the month",
+            vec![0.0, 1.0, 0.0],
+        );
+        index.add(
+            "Revoking a key stops requests that use it",
+            vec![0.8, 0.2, 0.0],
+        );
+        index
+    }
+
+    #[test]
+    fn cosine_similarity_ignores_magnitude() {
+        assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn chunks_matching_both_rankings_come_first() {
+        let results = index().search("how do I rotate a key", &[0.9, 0.1, 0.0], 3);
+
+        let order: Vec<usize> = results.iter().map(|(position, _)| *position).collect();
+        assert_eq!(order, vec![0, 2, 1]);
+        assert!(results[0].1 > results[1].1);
+    }
+
+    #[test]
+    fn keywords_rank_chunks_when_vectors_tie() {
+        let mut index = HybridIndex::new();
+        index.add("billing questions", vec![1.0, 0.0]);
+        index.add("webhook retries and billing", vec![1.0, 0.0]);
+
+        let results = index.search("webhook", &[1.0, 0.0], 2);
+        assert_eq!(results[0].0, 1);
+    }
+
+    #[test]
+    fn vectors_of_other_dimensions_are_ignored() {
+        let mut index = HybridIndex::new();
+        index.add("first", vec![1.0, 0.0, 0.0]);
+        index.add("second", vec![1.0, 0.0]);
+
+        let results = index.search("unrelated", &[1.0, 0.0], 5);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, 1);
+    }
+
+    #[test]
+    fn results_are_limited() {
+        assert_eq!(index().search("key", &[1.0, 0.0, 0.0], 1).len(), 1);
+        assert!(HybridIndex::new().search("key", &[1.0], 5).is_empty());
+    }
+}
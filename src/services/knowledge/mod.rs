@@ -0,0 +1,12 @@
+pub mod chunker;
+pub mod extract;
+#[cfg(test)]
+pub mod index;
+pub mod retrieve_tool;
+pub mod search;
+
+pub use chunker::chunk_text;
+pub use extract::{extract_text, DocumentFormat};
+#[cfg(test)]
+pub use index::HybridIndex;
+pub use retrieve_tool::RetrieveDocumentsTool;
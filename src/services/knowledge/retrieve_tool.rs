@@ -0,0 +1,115 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::knowledge::KnowledgeCollection;
+use crate::services::function_calling::tool::error::ToolError;
+use crate::services::function_calling::tool::executor::{ToolExecutor, ToolExecutorBase};
+use crate::services::function_calling::types::{ParameterType, ToolParameter};
+use crate::services::knowledge_service::{KnowledgeService, DEFAULT_RESULTS, MAX_RESULTS};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Searches the user's document collections, so a model can look things up on its own
+pub struct RetrieveDocumentsTool {
+    pool: DbPool,
+    user_id: Uuid,
+    description: String,
+    /// Collection names offered to the model, with their ids
+    collections: Vec<(String, Uuid)>,
+}
+
+impl RetrieveDocumentsTool {
+    pub const NAME: &'static str = "retrieve_documents";
+
+    pub fn new(pool: DbPool, user_id: Uuid, collections: &[KnowledgeCollection]) -> Self {
+        Self {
+            pool,
+            user_id,
+            description: "Search the user's documents and return the passages most relevant to the query"
+                .to_string(),
+            collections: collections
+                .iter()
+                .map(|collection| (collection.name.clone(), collection.id))
+                .collect(),
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::new("query", ParameterType::String {
+                format: None,
+                enum_values: None,
+            }, true)
+            .with_description("What to look for, in natural language"),
+
+            ToolParameter::new("collection", ParameterType::String {
+                format: None,
+                enum_values: Some(self.collections.iter().map(|(name, _)| name.clone()).collect()),
+            }, false)
+            .with_description("Only search this collection (default: all collections)"),
+
+            ToolParameter::new("limit", ParameterType::Number {
+                minimum: Some(1.0),
+                maximum: Some(MAX_RESULTS as f64),
+            }, false)
+            .with_description(&format!("The number of passages to return (default: {})", DEFAULT_RESULTS)),
+        ]
+    }
+}
+
+impl ToolExecutorBase for RetrieveDocumentsTool {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn validate_args(&self, args: &Value) -> Result<(), ToolError> {
+        if args["query"].as_str().map_or(true, |query| query.trim().is_empty()) {
+            return Err(ToolError::InvalidArgument("$.query: must not be empty".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for RetrieveDocumentsTool {
+    async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+        let query = args["query"].as_str().unwrap_or_default();
+        let limit = args["limit"]
+            .as_f64()
+            .map_or(DEFAULT_RESULTS, |limit| limit as usize)
+            .clamp(1, MAX_RESULTS);
+        let collection_ids: Vec<Uuid> = match args["collection"].as_str() {
+            Some(name) => {
+                let id = self
+                    .collections
+                    .iter()
+                    .find(|(collection, _)| collection == name)
+                    .map(|(_, id)| *id)
+                    .ok_or_else(|| ToolError::InvalidArgument(format!("$.collection: unknown collection '{}'", name)))?;
+                vec![id]
+            }
+            None => self.collections.iter().map(|(_, id)| *id).collect(),
+        };
+
+        let chunks = KnowledgeService::search(&self.pool, self.user_id, &collection_ids, query, limit, None)
+            .await
+            .map_err(|e| match e {
+                AppError::ExternalServiceError(message) => ToolError::ExternalServiceError(message),
+                e => ToolError::ExecutionError(e.to_string()),
+            })?;
+
+        Ok(json!({
+            "query": query,
+            "results": chunks.iter().map(|chunk| json!({
+                "document": chunk.document_name,
+                "content": chunk.content,
+                "score": chunk.score,
+            })).collect::<Vec<_>>(),
+        }))
+    }
+}
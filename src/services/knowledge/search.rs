@@ -0,0 +1,74 @@
+/// Reciprocal rank fusion constant; larger values flatten the difference between ranks
+pub const RRF_K: f32 = 60.0;
+/// How many of the best chunks of each ranking are fused, more than any result limit so a chunk
+/// a little further down one ranking can still come first overall
+pub const FUSION_CANDIDATES: i64 = 100;
+
+/// Ranks the chunks of the collections in `$2` twice, by cosine distance between their
+/// embedding and `$1` and by full-text match with the `$4` tsquery. The best `$3` of each
+/// ranking are merged with reciprocal rank fusion using the constant `$5`, and the best `$6`
+/// are returned with their document's name.
+pub const HYBRID_SEARCH_SQL: &str = r#"
+WITH vector_ranked AS (
+    SELECT id, ROW_NUMBER() OVER (ORDER BY embedding <=> $1) AS rank
+    FROM knowledge_chunks
+    WHERE collection_id = ANY($2)
+    ORDER BY embedding <=> $1
+    LIMIT $3
+), keyword_ranked AS (
+    SELECT id, ROW_NUMBER() OVER (
+        ORDER BY ts_rank_cd(to_tsvector('simple', content), query) DESC
+    ) AS rank
+    FROM knowledge_chunks, to_tsquery('simple', $4) AS query
+    WHERE collection_id = ANY($2) AND to_tsvector('simple', content) @@ query
+    ORDER BY rank
+    LIMIT $3
+), fused AS (
+    SELECT id, SUM(1.0 / ($5 + rank)) AS score
+    FROM (
+        SELECT id, rank FROM vector_ranked
+        UNION ALL
+        SELECT id, rank FROM keyword_ranked
+    ) ranked
+    GROUP BY id
+)
+SELECT chunks.id AS chunk_id, chunks.document_id, documents.name AS document_name,
+    chunks.collection_id, chunks.chunk_index, chunks.content, fused.score::real AS score
+FROM fused
+JOIN knowledge_chunks chunks ON chunks.id = fused.id
+JOIN knowledge_documents documents ON documents.id = chunks.document_id
+ORDER BY fused.score DESC, chunks.id
+LIMIT $6
+"#;
+
+/// Lowercased alphanumeric words of at least two characters
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// A tsquery matching chunks that contain any of the query's words. Words are only letters and
+/// digits, so they can't carry tsquery operators.
+pub fn keyword_query(query: &str) -> String {
+    let mut terms = tokenize(query);
+    terms.sort();
+    terms.dedup();
+    terms.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_query_matches_any_word() {
+        assert_eq!(
+            keyword_query("How do I rotate an API key? Rotate!"),
+            "an | api | do | how | key | rotate"
+        );
+        assert_eq!(keyword_query("a & !b | (c)"), "");
+        assert_eq!(keyword_query("key:* <-> 'x'"), "key");
+    }
+}
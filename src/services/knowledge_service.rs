@@ -0,0 +1,647 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::knowledge::{
+    CreateCollectionRequest, IngestDocumentRequest, KnowledgeCollection,
+    KnowledgeDocument, NewConversationCollection, NewKnowledgeChunk, NewKnowledgeCollection,
+    NewKnowledgeDocument, RetrievedChunk, UpdateCollectionRequest,
+};
+use crate::models::llm_provider::LLMProvider;
+use crate::models::usage::UsageSource;
+use crate::models::user_llm_config::UserLLMConfig;
+use crate::schema::{
+    conversation_collections, knowledge_chunks, knowledge_collections, knowledge_documents,
+};
+use crate::services::chat_service::ChatService;
+use crate::services::function_calling::ToolRegistry;
+use crate::services::knowledge::search::{
+    keyword_query, FUSION_CANDIDATES, HYBRID_SEARCH_SQL, RRF_K,
+};
+use crate::services::knowledge::{chunk_text, extract_text, DocumentFormat, RetrieveDocumentsTool};
+use crate::services::llm_providers;
+use crate::services::llm_service::{
+    LLMChatMessage, LLMEmbeddingOutput, LLMService, LLMServiceError,
+};
+use crate::services::usage_service::{UsageService, UsageStats};
+use actix_web::web;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Float4, Text, Uuid as SqlUuid};
+use log::{debug, info, warn};
+use pgvector::sql_types::Vector as VectorType;
+use pgvector::Vector;
+use serde_json::json;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Same limit as attachment uploads, which keeps base64 bodies under the JSON size limit
+const MAX_DOCUMENT_BYTES: usize = 7 * 1024 * 1024;
+const MIN_CHUNK_SIZE: i32 = 100;
+const MAX_CHUNK_SIZE: i32 = 8000;
+pub const DEFAULT_RESULTS: usize = 5;
+pub const MAX_RESULTS: usize = 20;
+/// Chunks are inserted in batches to stay under Postgres' bind parameter limit
+const INSERT_BATCH: usize = 500;
+
+pub struct KnowledgeService;
+
+impl KnowledgeService {
+    pub fn create_collection(
+        pool: &DbPool,
+        user_id: Uuid,
+        request: CreateCollectionRequest,
+    ) -> Result<KnowledgeCollection, AppError> {
+        let new_collection = NewKnowledgeCollection::from_request(user_id, request);
+        Self::validate(
+            &new_collection.name,
+            new_collection.chunk_size,
+            new_collection.chunk_overlap,
+        )?;
+
+        // Documents are embedded with this config, so its provider must support embeddings
+        let user_config =
+            ChatService::get_user_llm_config_by_id(pool, new_collection.user_llm_config_id)?;
+        if user_config.user_id != user_id {
+            return Err(AppError::NotFoundError("LLM config not found".to_string()));
+        }
+        let provider = ChatService::get_llm_provider(pool, user_config.provider_id)?;
+        let supports_embeddings = llm_providers::get_provider(&provider)
+            .map(|llm_provider| {
+                llm_provider
+                    .embedding_model(&provider.configuration)
+                    .is_some()
+            })
+            .unwrap_or(false);
+        if !supports_embeddings {
+            return Err(AppError::BadRequest(format!(
+                "Provider {} does not support embeddings",
+                provider.name
+            )));
+        }
+
+        let conn = &mut pool.get()?;
+        let collection = diesel::insert_into(knowledge_collections::table)
+            .values(&new_collection)
+            .get_result::<KnowledgeCollection>(conn)?;
+
+        info!(
+            "Created knowledge collection {} for user {}",
+            collection.id, user_id
+        );
+        Ok(collection)
+    }
+
+    pub fn get_collection(
+        pool: &DbPool,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<KnowledgeCollection, AppError> {
+        let conn = &mut pool.get()?;
+
+        knowledge_collections::table
+            .filter(knowledge_collections::id.eq(id))
+            .filter(knowledge_collections::user_id.eq(user_id))
+            .first::<KnowledgeCollection>(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    AppError::NotFoundError(format!("Collection not found: {}", id))
+                } else {
+                    AppError::DatabaseError(e)
+                }
+            })
+    }
+
+    pub fn list_collections(
+        pool: &DbPool,
+        user_id: Uuid,
+    ) -> Result<Vec<KnowledgeCollection>, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Listing knowledge collections for user {}", user_id);
+
+        knowledge_collections::table
+            .filter(knowledge_collections::user_id.eq(user_id))
+            .order(knowledge_collections::name.asc())
+            .load::<KnowledgeCollection>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn update_collection(
+        pool: &DbPool,
+        id: Uuid,
+        user_id: Uuid,
+        request: UpdateCollectionRequest,
+    ) -> Result<KnowledgeCollection, AppError> {
+        let existing = Self::get_collection(pool, id, user_id)?;
+
+        let name = request.name.unwrap_or(existing.name);
+        let chunk_size = request.chunk_size.unwrap_or(existing.chunk_size);
+        let chunk_overlap = request.chunk_overlap.unwrap_or(existing.chunk_overlap);
+        Self::validate(&name, chunk_size, chunk_overlap)?;
+
+        let conn = &mut pool.get()?;
+        let collection = diesel::update(knowledge_collections::table.find(id))
+            .set((
+                knowledge_collections::name.eq(name),
+                knowledge_collections::description.eq(request.description.or(existing.description)),
+                knowledge_collections::chunk_size.eq(chunk_size),
+                knowledge_collections::chunk_overlap.eq(chunk_overlap),
+                knowledge_collections::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<KnowledgeCollection>(conn)?;
+
+        info!("Updated knowledge collection {}", id);
+        Ok(collection)
+    }
+
+    pub fn delete_collection(pool: &DbPool, id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+        let conn = &mut pool.get()?;
+
+        let count = diesel::delete(knowledge_collections::table)
+            .filter(knowledge_collections::id.eq(id))
+            .filter(knowledge_collections::user_id.eq(user_id))
+            .execute(conn)?;
+
+        if count == 0 {
+            return Err(AppError::NotFoundError(format!(
+                "Collection not found: {}",
+                id
+            )));
+        }
+
+        info!("Deleted knowledge collection {}", id);
+        Ok(())
+    }
+
+    pub fn list_documents(
+        pool: &DbPool,
+        collection_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<KnowledgeDocument>, AppError> {
+        Self::get_collection(pool, collection_id, user_id)?;
+        let conn = &mut pool.get()?;
+
+        knowledge_documents::table
+            .filter(knowledge_documents::collection_id.eq(collection_id))
+            .order(knowledge_documents::created_at.desc())
+            .load::<KnowledgeDocument>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn delete_document(
+        pool: &DbPool,
+        collection_id: Uuid,
+        document_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(), AppError> {
+        Self::get_collection(pool, collection_id, user_id)?;
+        let conn = &mut pool.get()?;
+
+        let count = diesel::delete(knowledge_documents::table)
+            .filter(knowledge_documents::id.eq(document_id))
+            .filter(knowledge_documents::collection_id.eq(collection_id))
+            .execute(conn)?;
+
+        if count == 0 {
+            return Err(AppError::NotFoundError(format!(
+                "Document not found: {}",
+                document_id
+            )));
+        }
+
+        info!(
+            "Deleted document {} from collection {}",
+            document_id, collection_id
+        );
+        Ok(())
+    }
+
+    /// Extracts, chunks and embeds a document, then stores its chunks. Returns the embedding
+    /// usage so the caller can count it against the user's limits
+    pub async fn ingest_document(
+        pool: &DbPool,
+        user_id: Uuid,
+        collection_id: Uuid,
+        request: IngestDocumentRequest,
+    ) -> Result<(KnowledgeDocument, Option<UsageStats>), AppError> {
+        let collection = Self::get_collection(pool, collection_id, user_id)?;
+
+        let (bytes, format) = match (request.text, request.data) {
+            (Some(text), None) => {
+                // Text is never binary, so an unknown type is read as plain text
+                let format = DocumentFormat::detect(request.content_type.as_deref(), &request.name)
+                    .unwrap_or(DocumentFormat::Text);
+                (text.into_bytes(), format)
+            }
+            (None, Some(data)) => {
+                let bytes = base64::decode(&data)
+                    .map_err(|e| AppError::BadRequest(format!("Invalid base64 data: {}", e)))?;
+                let format = DocumentFormat::detect(request.content_type.as_deref(), &request.name)
+                    .ok_or_else(|| {
+                        AppError::BadRequest(
+                            "Documents must be text, Markdown, HTML or PDF".to_string(),
+                        )
+                    })?;
+                (bytes, format)
+            }
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Exactly one of text or data is required".to_string(),
+                ))
+            }
+        };
+        if bytes.len() > MAX_DOCUMENT_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "Document is larger than {} bytes",
+                MAX_DOCUMENT_BYTES
+            )));
+        }
+
+        let text = extract_text(format, &bytes).await?;
+        let chunks = chunk_text(
+            &text,
+            collection.chunk_size as usize,
+            collection.chunk_overlap as usize,
+        );
+        if chunks.is_empty() {
+            return Err(AppError::BadRequest("Document has no text".to_string()));
+        }
+
+        let (provider, user_config) = Self::embedding_provider(pool, &collection)?;
+        let output = LLMService::embed(pool, &provider, &user_config, chunks.clone())
+            .await
+            .map_err(Self::embedding_error)?;
+        Self::check_embeddings(&collection, &output)?;
+
+        let conn = &mut pool.get()?;
+        let document = conn.transaction::<_, AppError, _>(|conn| {
+            let document = diesel::insert_into(knowledge_documents::table)
+                .values(&NewKnowledgeDocument {
+                    id: Uuid::new_v4(),
+                    collection_id,
+                    name: request.name.clone(),
+                    content_type: format.content_type().to_string(),
+                    chunk_count: chunks.len() as i32,
+                })
+                .get_result::<KnowledgeDocument>(conn)?;
+
+            let new_chunks: Vec<NewKnowledgeChunk> = chunks
+                .into_iter()
+                .zip(output.embeddings)
+                .enumerate()
+                .map(|(index, (content, embedding))| NewKnowledgeChunk {
+                    id: Uuid::new_v4(),
+                    document_id: document.id,
+                    collection_id,
+                    chunk_index: index as i32,
+                    content,
+                    embedding: Vector::from(embedding),
+                })
+                .collect();
+            for batch in new_chunks.chunks(INSERT_BATCH) {
+                diesel::insert_into(knowledge_chunks::table)
+                    .values(batch)
+                    .execute(conn)?;
+            }
+
+            // The first document fixes the model later documents and queries must match
+            if collection.embedding_model.is_none() {
+                diesel::update(knowledge_collections::table.find(collection_id))
+                    .set((
+                        knowledge_collections::embedding_model.eq(&output.model),
+                        knowledge_collections::dimensions.eq(output.dimensions as i32),
+                        knowledge_collections::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)?;
+            }
+
+            Ok(document)
+        })?;
+
+        info!(
+            "Ingested document {} into collection {} as {} chunks",
+            document.id, collection_id, document.chunk_count
+        );
+        Ok((document, output.usage_stats))
+    }
+
+    /// Ranks chunks from the user's collections, or from all of them when `collection_ids` is
+    /// empty. Query embeddings are recorded as usage against the conversation, if there is one
+    pub async fn search(
+        pool: &DbPool,
+        user_id: Uuid,
+        collection_ids: &[Uuid],
+        query: &str,
+        limit: usize,
+        conversation_id: Option<Uuid>,
+    ) -> Result<Vec<RetrievedChunk>, AppError> {
+        if query.trim().is_empty() {
+            return Err(AppError::BadRequest("query must not be empty".to_string()));
+        }
+        let collections = Self::owned_collections(pool, user_id, collection_ids)?;
+
+        // Collections embedded the same way share one query embedding
+        let mut groups: BTreeMap<(Uuid, String), Vec<KnowledgeCollection>> = BTreeMap::new();
+        for collection in collections {
+            if let Some(model) = collection.embedding_model.clone() {
+                groups
+                    .entry((collection.user_llm_config_id, model))
+                    .or_default()
+                    .push(collection);
+            }
+        }
+
+        let mut results: Vec<RetrievedChunk> = Vec::new();
+        for collections in groups.values() {
+            let (mut provider, user_config) = Self::embedding_provider(pool, &collections[0])?;
+            provider.configuration["input_type"] = json!("search_query");
+            let output = LLMService::embed(pool, &provider, &user_config, vec![query.to_string()])
+                .await
+                .map_err(Self::embedding_error)?;
+            for collection in collections {
+                Self::check_embeddings(collection, &output)?;
+            }
+            if let Some(stats) = &output.usage_stats {
+                UsageService::record_quietly(
+                    pool,
+                    user_id,
+                    stats,
+                    UsageSource::Embeddings,
+                    conversation_id,
+                    None,
+                );
+            }
+
+            let ids: Vec<Uuid> = collections.iter().map(|collection| collection.id).collect();
+            let embedding = Vector::from(output.embeddings.into_iter().next().unwrap_or_default());
+            let keywords = keyword_query(query);
+            let pool = pool.clone();
+            let ranked = web::block(move || {
+                let conn = &mut pool.get()?;
+                diesel::sql_query(HYBRID_SEARCH_SQL)
+                    .bind::<VectorType, _>(embedding)
+                    .bind::<Array<SqlUuid>, _>(ids)
+                    .bind::<BigInt, _>(FUSION_CANDIDATES)
+                    .bind::<Text, _>(keywords)
+                    .bind::<Float4, _>(RRF_K)
+                    .bind::<BigInt, _>(limit as i64)
+                    .load::<RetrievedChunk>(conn)
+                    .map_err(AppError::DatabaseError)
+            })
+            .await??;
+            results.extend(ranked);
+        }
+
+        // Each group was ranked on its own, so only their fused scores can be compared
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    pub fn conversation_collections(
+        pool: &DbPool,
+        user_id: Uuid,
+        conversation_id: Uuid,
+    ) -> Result<Vec<KnowledgeCollection>, AppError> {
+        let conn = &mut pool.get()?;
+
+        conversation_collections::table
+            .inner_join(knowledge_collections::table)
+            .filter(conversation_collections::conversation_id.eq(conversation_id))
+            .filter(knowledge_collections::user_id.eq(user_id))
+            .order(knowledge_collections::name.asc())
+            .select(KnowledgeCollection::as_select())
+            .load::<KnowledgeCollection>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    /// Replaces the collections attached to one of the user's conversations
+    pub fn set_conversation_collections(
+        pool: &DbPool,
+        user_id: Uuid,
+        conversation_id: Uuid,
+        collection_ids: &[Uuid],
+    ) -> Result<Vec<KnowledgeCollection>, AppError> {
+        let conversation = ChatService::get_conversation(pool, conversation_id)?;
+        if conversation.user_id != user_id {
+            return Err(AppError::NotFoundError(format!(
+                "Conversation not found: {}",
+                conversation_id
+            )));
+        }
+        let collections = Self::owned_collections(pool, user_id, collection_ids)?;
+
+        let conn = &mut pool.get()?;
+        conn.transaction::<_, AppError, _>(|conn| {
+            diesel::delete(conversation_collections::table)
+                .filter(conversation_collections::conversation_id.eq(conversation_id))
+                .execute(conn)?;
+            let rows: Vec<NewConversationCollection> = collections
+                .iter()
+                .map(|collection| NewConversationCollection {
+                    conversation_id,
+                    collection_id: collection.id,
+                })
+                .collect();
+            if !rows.is_empty() {
+                diesel::insert_into(conversation_collections::table)
+                    .values(&rows)
+                    .execute(conn)?;
+            }
+            Ok(())
+        })?;
+
+        info!(
+            "Attached {} collections to conversation {}",
+            collections.len(),
+            conversation_id
+        );
+        Ok(collections)
+    }
+
+    /// Adds the chunks most relevant to the last user message as a system message, when the
+    /// conversation has collections attached. Retrieval problems are logged and the messages
+    /// are sent unchanged, so a broken collection doesn't stop the chat
+    pub async fn augment_messages(
+        pool: &DbPool,
+        user_id: Uuid,
+        conversation_id: Uuid,
+        mut messages: Vec<LLMChatMessage>,
+    ) -> Vec<LLMChatMessage> {
+        let collection_ids: Vec<Uuid> =
+            match Self::conversation_collections(pool, user_id, conversation_id) {
+                Ok(collections) => collections.iter().map(|collection| collection.id).collect(),
+                Err(e) => {
+                    warn!(
+                        "Failed to load collections of conversation {}: {}",
+                        conversation_id, e
+                    );
+                    return messages;
+                }
+            };
+        let query = messages
+            .iter()
+            .rev()
+            .find(|message| message.role == "user")
+            .map(|message| message.content.clone())
+            .unwrap_or_default();
+        if collection_ids.is_empty() || query.trim().is_empty() {
+            return messages;
+        }
+
+        let chunks = match Self::search(
+            pool,
+            user_id,
+            &collection_ids,
+            &query,
+            DEFAULT_RESULTS,
+            Some(conversation_id),
+        )
+        .await
+        {
+            Ok(chunks) if !chunks.is_empty() => chunks,
+            Ok(_) => return messages,
+            Err(e) => {
+                warn!(
+                    "Retrieval for conversation {} failed: {}",
+                    conversation_id, e
+                );
+                return messages;
+            }
+        };
+
+        debug!(
+            "Adding {} retrieved chunks to conversation {}",
+            chunks.len(),
+            conversation_id
+        );
+        let position = messages
+            .iter()
+            .take_while(|message| message.role == "system")
+            .count();
+        messages.insert(
+            position,
+            LLMChatMessage {
+                role: "system".to_string(),
+                content: Self::context_message(&chunks),
+                parts: Vec::new(),
+                tool_calls: Vec::new(),
+                tool_call_id: None,
+            },
+        );
+        messages
+    }
+
+    /// Formats retrieved chunks as numbered excerpts for the model
+    pub fn context_message(chunks: &[RetrievedChunk]) -> String {
+        let excerpts: Vec<String> = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| format!("[{}] {}\n{}", i + 1, chunk.document_name, chunk.content))
+            .collect();
+        format!(
+            "The following excerpts from the user's documents may help answer the next message. \
+             Use them when they are relevant and mention the document you used.\n\n{}",
+            excerpts.join("\n\n")
+        )
+    }
+
+    /// Registers `retrieve_documents` when the user has at least one collection
+    pub async fn register_tool(
+        pool: &DbPool,
+        user_id: Uuid,
+        collections: &[KnowledgeCollection],
+        registry: &ToolRegistry,
+    ) {
+        if collections.is_empty() {
+            return;
+        }
+        let tool = RetrieveDocumentsTool::new(pool.clone(), user_id, collections);
+        let parameters = tool.parameters();
+        registry.register(tool).await;
+        registry
+            .update_tool_parameters(RetrieveDocumentsTool::NAME, parameters)
+            .await;
+    }
+
+    /// Loads the listed collections, all of which must belong to the user; no ids means all
+    fn owned_collections(
+        pool: &DbPool,
+        user_id: Uuid,
+        collection_ids: &[Uuid],
+    ) -> Result<Vec<KnowledgeCollection>, AppError> {
+        if collection_ids.is_empty() {
+            return Self::list_collections(pool, user_id);
+        }
+
+        let conn = &mut pool.get()?;
+        let collections = knowledge_collections::table
+            .filter(knowledge_collections::user_id.eq(user_id))
+            .filter(knowledge_collections::id.eq_any(collection_ids))
+            .load::<KnowledgeCollection>(conn)?;
+        if let Some(missing) = collection_ids
+            .iter()
+            .find(|id| !collections.iter().any(|collection| collection.id == **id))
+        {
+            return Err(AppError::NotFoundError(format!(
+                "Collection not found: {}",
+                missing
+            )));
+        }
+        Ok(collections)
+    }
+
+    /// The collection's provider, pinned to the model its chunks were embedded with
+    fn embedding_provider(
+        pool: &DbPool,
+        collection: &KnowledgeCollection,
+    ) -> Result<(LLMProvider, UserLLMConfig), AppError> {
+        let user_config =
+            ChatService::get_user_llm_config_by_id(pool, collection.user_llm_config_id)?;
+        let mut provider = ChatService::get_llm_provider(pool, user_config.provider_id)?;
+        if let Some(model) = &collection.embedding_model {
+            provider.configuration["embedding_model"] = json!(model);
+        }
+        Ok((provider, user_config))
+    }
+
+    fn check_embeddings(
+        collection: &KnowledgeCollection,
+        output: &LLMEmbeddingOutput,
+    ) -> Result<(), AppError> {
+        match collection.dimensions {
+            Some(dimensions) if dimensions as usize != output.dimensions => {
+                Err(AppError::BadRequest(format!(
+                    "Collection {} has {}-dimensional embeddings but {} returned {}; \
+                     create a new collection to change the embedding settings",
+                    collection.name, dimensions, output.model, output.dimensions
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn embedding_error(error: LLMServiceError) -> AppError {
+        match error.0 {
+            error @ (AppError::BadRequest(_) | AppError::UnsupportedProviderError(_)) => error,
+            error => AppError::ExternalServiceError(error.to_string()),
+        }
+    }
+
+    fn validate(name: &str, chunk_size: i32, chunk_overlap: i32) -> Result<(), AppError> {
+        if name.trim().is_empty() {
+            return Err(AppError::BadRequest("name must not be empty".to_string()));
+        }
+        if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&chunk_size) {
+            return Err(AppError::BadRequest(format!(
+                "chunk_size must be between {} and {}",
+                MIN_CHUNK_SIZE, MAX_CHUNK_SIZE
+            )));
+        }
+        if chunk_overlap < 0 || chunk_overlap > chunk_size / 2 {
+            return Err(AppError::BadRequest(
+                "chunk_overlap must be between 0 and half of chunk_size".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
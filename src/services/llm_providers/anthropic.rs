@@ -1,10 +1,25 @@
+use super::forward_options;
 use crate::error::AppError;
-use crate::services::llm_service::{LLMChatMessage, LLMProviderTrait, LLMServiceError};
+use crate::services::function_calling::provider::anthropic::user_content;
+use crate::services::function_calling::provider::AnthropicAdapter;
+use crate::services::function_calling::ProviderAdapter;
+use crate::services::llm_service::{
+    tool_request_body, LLMChatMessage, LLMProviderTrait, LLMServiceError, LLMToolOptions,
+};
 use futures::stream::{self, Stream, StreamExt};
 use log::{debug, error};
 use reqwest::Client;
 use serde_json::Value;
 use std::pin::Pin;
+use std::sync::Arc;
+
+/// Sampling options taken from the provider configuration
+/// `max_tokens` replaces the default the body starts with, since Anthropic requires one
+const SAMPLING_OPTIONS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("max_tokens", "max_tokens"),
+];
 
 pub struct AnthropicProvider;
 
@@ -24,21 +39,22 @@ impl LLMProviderTrait for AnthropicProvider {
 
         let filtered_messages: Vec<Value> = messages
             .iter()
-            .filter(|msg| !msg.content.trim().is_empty())
+            .filter(|msg| !msg.content.trim().is_empty() || !msg.parts.is_empty())
             .map(|msg| {
                 serde_json::json!({
                     "role": if msg.role == "user" { "user" } else { "assistant" },
-                    "content": msg.content.trim()
+                    "content": user_content(msg.content.trim(), &msg.parts)
                 })
             })
             .collect();
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": model,
             "messages": filtered_messages,
             "max_tokens": 300,
             "stream": true
         });
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
 
         debug!("Anthropic request body: {:?}", request_body);
 
@@ -110,4 +126,44 @@ impl LLMProviderTrait for AnthropicProvider {
             .filter(|result| futures::future::ready(!matches!(result, Ok(ref s) if s.is_empty()))),
         )
     }
+
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        Some(Arc::new(AnthropicAdapter))
+    }
+
+    fn prepare_tool_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+        tools: &LLMToolOptions,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = config["model"].as_str().ok_or_else(|| {
+            LLMServiceError(AppError::BadRequest(
+                "Model not specified for Anthropic provider".to_string(),
+            ))
+        })?;
+
+        let mut request_body = tool_request_body(
+            &AnthropicAdapter,
+            serde_json::json!({
+                "model": model,
+                "max_tokens": config["max_tokens"].as_u64().unwrap_or(1024),
+                "stream": stream,
+            }),
+            messages,
+            tools,
+        );
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
+
+        debug!("Anthropic tool request body: {:?}", request_body);
+
+        Ok(Client::new()
+            .post("https://api.anthropic.com/v1/messages")
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
 }
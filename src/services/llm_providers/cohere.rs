@@ -1,10 +1,23 @@
+use super::{embedding_model, forward_options, parse_vector};
 use crate::error::AppError;
-use crate::services::llm_service::{LLMChatMessage, LLMProviderTrait, LLMServiceError};
+use crate::services::function_calling::provider::CohereAdapter;
+use crate::services::function_calling::ProviderAdapter;
+use crate::services::llm_service::{
+    tool_request_body, LLMChatMessage, LLMProviderTrait, LLMServiceError, LLMToolOptions,
+};
 use futures::stream::{self, Stream, StreamExt};
 use log::{debug, error, warn};
 use reqwest::Client;
 use serde_json::Value;
 use std::pin::Pin;
+use std::sync::Arc;
+
+/// Sampling options taken from the provider configuration
+const SAMPLING_OPTIONS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "p"),
+    ("max_tokens", "max_tokens"),
+];
 
 pub struct CohereProvider;
 
@@ -45,12 +58,13 @@ impl LLMProviderTrait for CohereProvider {
             .map(|msg| msg.content.trim().to_string())
             .unwrap_or_default();
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": model,
             "chat_history": chat_history,
             "message": last_message,
             "stream": true
         });
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
 
         debug!("Cohere request body: {:?}", request_body);
 
@@ -116,4 +130,94 @@ impl LLMProviderTrait for CohereProvider {
             .filter(|result| futures::future::ready(!matches!(result, Ok(ref s) if s.is_empty()))),
         )
     }
+
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        Some(Arc::new(CohereAdapter))
+    }
+
+    fn prepare_tool_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+        tools: &LLMToolOptions,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = config["model"].as_str().ok_or_else(|| {
+            LLMServiceError(AppError::BadRequest(
+                "Model not specified for Cohere provider".to_string(),
+            ))
+        })?;
+
+        // Tool use goes through the v2 chat API, which takes a plain message list
+        let mut request_body = tool_request_body(
+            &CohereAdapter,
+            serde_json::json!({
+                "model": model,
+                "stream": stream,
+            }),
+            messages,
+            tools,
+        );
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
+
+        debug!("Cohere tool request body: {:?}", request_body);
+
+        Ok(Client::new()
+            .post("https://api.cohere.com/v2/chat")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .json(&request_body))
+    }
+
+    fn embedding_model(&self, config: &Value) -> Option<String> {
+        Some(embedding_model(config, "embed-english-v3.0"))
+    }
+
+    fn prepare_embedding_request(
+        &self,
+        inputs: &[String],
+        config: &Value,
+        api_key: &str,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = embedding_model(config, "embed-english-v3.0");
+        // v3 models embed documents and queries differently; stored text is the common case
+        let mut request_body = serde_json::json!({
+            "model": model,
+            "texts": inputs,
+            "input_type": config["input_type"].as_str().unwrap_or("search_document"),
+            "embedding_types": ["float"],
+        });
+        if let Some(dimensions) = config["dimensions"].as_u64() {
+            request_body["output_dimension"] = serde_json::json!(dimensions);
+        }
+
+        Ok(Client::new()
+            .post("https://api.cohere.com/v2/embed")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .json(&request_body))
+    }
+
+    fn parse_embeddings(&self, response_text: &str) -> Result<Vec<Vec<f32>>, LLMServiceError> {
+        let response: Value = serde_json::from_str(response_text).map_err(|e| {
+            LLMServiceError(AppError::ExternalServiceError(format!(
+                "Invalid Cohere embeddings response: {}",
+                e
+            )))
+        })?;
+        response["embeddings"]["float"]
+            .as_array()
+            .ok_or_else(|| {
+                LLMServiceError(AppError::ExternalServiceError(format!(
+                    "Cohere embeddings response has no float embeddings: {}",
+                    response_text
+                )))
+            })?
+            .iter()
+            .map(parse_vector)
+            .collect()
+    }
 }
@@ -1,6 +1,12 @@
+use super::{embedding_model, parse_vector};
 use crate::error::AppError;
 use crate::services::llm_providers::ProviderConfig;
-use crate::services::llm_service::{LLMChatMessage, LLMProviderTrait, LLMServiceError};
+use crate::services::function_calling::provider::gemini::user_parts;
+use crate::services::function_calling::provider::GeminiAdapter;
+use crate::services::function_calling::ProviderAdapter;
+use crate::services::llm_service::{
+    tool_request_body, LLMChatMessage, LLMProviderTrait, LLMServiceError, LLMToolOptions,
+};
 use futures::stream::{self, Stream, StreamExt};
 use log::{debug, error, warn};
 use reqwest::Client;
@@ -18,6 +24,16 @@ impl ProviderConfig for GeminiProvider {
     }
 }
 
+impl GeminiProvider {
+    /// An option from the provider configuration, falling back to the defaults
+    fn option<'a>(&'a self, config: &'a Value, key: &str) -> &'a Value {
+        match config.get(key) {
+            Some(value) if !value.is_null() => value,
+            _ => &self.config[key],
+        }
+    }
+}
+
 impl LLMProviderTrait for GeminiProvider {
     fn prepare_request(
         &self,
@@ -38,7 +54,7 @@ impl LLMProviderTrait for GeminiProvider {
                         "system" => "system",
                         _ => "user",
                     },
-                    "parts": [{"text": msg.content}]
+                    "parts": user_parts(&msg.content, &msg.parts)
                 })
             })
             .collect();
@@ -46,10 +62,10 @@ impl LLMProviderTrait for GeminiProvider {
         let request_body = serde_json::json!({
             "contents": formatted_messages,
             "generationConfig": {
-                "temperature": self.config["temperature"].as_f64().unwrap_or(0.7),
+                "temperature": self.option(config, "temperature").as_f64().unwrap_or(0.7),
                 "topK": self.config["top_k"].as_u64().unwrap_or(40),
-                "topP": self.config["top_p"].as_f64().unwrap_or(0.95),
-                "maxOutputTokens": self.config["max_tokens"].as_u64().unwrap_or(1024),
+                "topP": self.option(config, "top_p").as_f64().unwrap_or(0.95),
+                "maxOutputTokens": self.option(config, "max_tokens").as_u64().unwrap_or(1024),
             },
             "safetySettings": [
                 {
@@ -193,6 +209,109 @@ impl LLMProviderTrait for GeminiProvider {
             .filter(|result| futures::future::ready(!matches!(result, Ok(ref s) if s.is_empty()))),
         )
     }
+
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        Some(Arc::new(GeminiAdapter))
+    }
+
+    fn prepare_tool_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+        tools: &LLMToolOptions,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = config["model"]
+            .as_str()
+            .or_else(|| self.config["model"].as_str())
+            .unwrap_or("gemini-pro");
+        // Streams are requested as SSE so they can be read like the other providers' streams
+        let method = if stream { "streamGenerateContent?alt=sse" } else { "generateContent" };
+
+        let request_body = tool_request_body(
+            &GeminiAdapter,
+            serde_json::json!({
+                "generationConfig": {
+                    "temperature": self.option(config, "temperature").as_f64().unwrap_or(0.7),
+                    "topP": self.option(config, "top_p").as_f64().unwrap_or(0.95),
+                    "maxOutputTokens": self.option(config, "max_tokens").as_u64().unwrap_or(1024),
+                },
+            }),
+            messages,
+            tools,
+        );
+
+        debug!("Gemini tool request body: {:?}", request_body);
+
+        Ok(Client::new()
+            .post(format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:{}",
+                model, method
+            ))
+            .header("Content-Type", "application/json")
+            .header("x-goog-api-key", api_key)
+            .json(&request_body))
+    }
+
+    fn embedding_model(&self, config: &Value) -> Option<String> {
+        Some(embedding_model(config, "text-embedding-004"))
+    }
+
+    fn embedding_batch_size(&self) -> usize {
+        100
+    }
+
+    fn prepare_embedding_request(
+        &self,
+        inputs: &[String],
+        config: &Value,
+        api_key: &str,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = embedding_model(config, "text-embedding-004");
+        let requests: Vec<Value> = inputs
+            .iter()
+            .map(|input| {
+                let mut request = serde_json::json!({
+                    "model": format!("models/{}", model),
+                    "content": { "parts": [{ "text": input }] },
+                });
+                if let Some(dimensions) = config["dimensions"].as_u64() {
+                    request["outputDimensionality"] = serde_json::json!(dimensions);
+                }
+                request
+            })
+            .collect();
+
+        Ok(Client::new()
+            .post(format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:batchEmbedContents",
+                model
+            ))
+            .header("Content-Type", "application/json")
+            .header("x-goog-api-key", api_key)
+            .json(&serde_json::json!({ "requests": requests })))
+    }
+
+    fn parse_embeddings(&self, response_text: &str) -> Result<Vec<Vec<f32>>, LLMServiceError> {
+        let response: Value = serde_json::from_str(response_text).map_err(|e| {
+            LLMServiceError(AppError::ExternalServiceError(format!(
+                "Invalid Gemini embeddings response: {}",
+                e
+            )))
+        })?;
+        response["embeddings"]
+            .as_array()
+            .ok_or_else(|| {
+                LLMServiceError(AppError::ExternalServiceError(format!(
+                    "Gemini embeddings response has no embeddings: {}",
+                    response_text
+                )))
+            })?
+            .iter()
+            .map(|embedding| parse_vector(&embedding["values"]))
+            .collect()
+    }
 }
 
 impl Clone for GeminiProvider {
@@ -1,10 +1,16 @@
+use super::openai_messages;
 use crate::error::AppError;
-use crate::services::llm_service::{LLMChatMessage, LLMProviderTrait, LLMServiceError};
+use crate::services::function_calling::provider::OpenAIAdapter;
+use crate::services::function_calling::ProviderAdapter;
+use crate::services::llm_service::{
+    tool_request_body, LLMChatMessage, LLMProviderTrait, LLMServiceError, LLMToolOptions,
+};
 use futures::stream::{self, Stream, StreamExt};
 use log::{debug, error, warn};
 use reqwest::Client;
 use serde_json::Value;
 use std::pin::Pin;
+use std::sync::Arc;
 
 pub struct GrokProvider;
 
@@ -24,7 +30,7 @@ impl LLMProviderTrait for GrokProvider {
 
         let request_body = serde_json::json!({
             "model": model,
-            "messages": messages,
+            "messages": openai_messages(messages),
             "stream": true,
             "temperature": config["temperature"].as_f64().unwrap_or(0.7),
             "max_tokens": config["max_tokens"].as_u64().unwrap_or(1024),
@@ -115,4 +121,48 @@ impl LLMProviderTrait for GrokProvider {
             .filter(|result| futures::future::ready(!matches!(result, Ok(ref s) if s.is_empty()))),
         )
     }
+
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        Some(Arc::new(OpenAIAdapter))
+    }
+
+    fn prepare_tool_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+        tools: &LLMToolOptions,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = config["model"].as_str().ok_or_else(|| {
+            LLMServiceError(AppError::BadRequest(
+                "Model not specified for Grok provider".to_string(),
+            ))
+        })?;
+
+        let mut request_body = tool_request_body(
+            &OpenAIAdapter,
+            serde_json::json!({
+                "model": model,
+                "stream": stream,
+                "temperature": config["temperature"].as_f64().unwrap_or(0.7),
+                "max_tokens": config["max_tokens"].as_u64().unwrap_or(1024),
+                "top_p": config["top_p"].as_f64().unwrap_or(0.9),
+            }),
+            messages,
+            tools,
+        );
+        if stream {
+            // Without this the stream carries no token usage
+            request_body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+
+        debug!("Grok tool request body: {:?}", request_body);
+
+        Ok(Client::new()
+            .post("https://api.x.ai/v1/chat/completions")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
 }
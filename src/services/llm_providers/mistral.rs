@@ -1,10 +1,23 @@
+use super::{embedding_model, forward_options, openai_messages, parse_openai_embeddings};
 use crate::error::AppError;
-use crate::services::llm_service::{LLMChatMessage, LLMProviderTrait, LLMServiceError};
+use crate::services::function_calling::provider::OpenAIAdapter;
+use crate::services::function_calling::ProviderAdapter;
+use crate::services::llm_service::{
+    tool_request_body, LLMChatMessage, LLMProviderTrait, LLMServiceError, LLMToolOptions,
+};
 use futures::stream::{self, Stream, StreamExt};
 use log::{debug, error, warn};
 use reqwest::Client;
 use serde_json::Value;
 use std::pin::Pin;
+use std::sync::Arc;
+
+/// Sampling options taken from the provider configuration
+const SAMPLING_OPTIONS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("max_tokens", "max_tokens"),
+];
 
 pub struct MistralProvider;
 
@@ -22,11 +35,12 @@ impl LLMProviderTrait for MistralProvider {
             ))
         })?;
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": model,
-            "messages": messages,
+            "messages": openai_messages(messages),
             "stream": true
         });
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
 
         debug!("Mistral request body: {:?}", request_body);
         debug!("Using API key: {}", api_key);
@@ -95,4 +109,71 @@ impl LLMProviderTrait for MistralProvider {
             .filter(|result| futures::future::ready(!matches!(result, Ok(ref s) if s.is_empty()))),
         )
     }
+
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        Some(Arc::new(OpenAIAdapter))
+    }
+
+    fn prepare_tool_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+        tools: &LLMToolOptions,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = config["model"].as_str().ok_or_else(|| {
+            LLMServiceError(AppError::BadRequest(
+                "Model not specified for Mistral provider".to_string(),
+            ))
+        })?;
+
+        let mut request_body = tool_request_body(
+            &OpenAIAdapter,
+            serde_json::json!({
+                "model": model,
+                "stream": stream,
+            }),
+            messages,
+            tools,
+        );
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
+
+        debug!("Mistral tool request body: {:?}", request_body);
+
+        Ok(Client::new()
+            .post("https://api.mistral.ai/v1/chat/completions")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn embedding_model(&self, config: &Value) -> Option<String> {
+        Some(embedding_model(config, "mistral-embed"))
+    }
+
+    fn prepare_embedding_request(
+        &self,
+        inputs: &[String],
+        config: &Value,
+        api_key: &str,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = embedding_model(config, "mistral-embed");
+        // Mistral rejects the OpenAI-only `dimensions` option
+        let request_body = serde_json::json!({
+            "model": model,
+            "input": inputs,
+            "encoding_format": "float",
+        });
+
+        Ok(Client::new()
+            .post("https://api.mistral.ai/v1/embeddings")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn parse_embeddings(&self, response_text: &str) -> Result<Vec<Vec<f32>>, LLMServiceError> {
+        parse_openai_embeddings(response_text)
+    }
 }
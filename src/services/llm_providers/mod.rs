@@ -5,7 +5,9 @@ pub mod gemini;
 pub mod grok;
 pub mod leonardo;
 pub mod openai;
+pub mod openai_compatible;
 pub mod perplexity;
+pub mod registry;
 pub mod stability;
 pub mod mistral;
 
@@ -16,32 +18,173 @@ pub use gemini::{default_config as gemini_default_config, GeminiProvider};
 pub use grok::GrokProvider;
 pub use leonardo::LeonardoProvider;
 pub use openai::OpenAIProvider;
+pub use openai_compatible::OpenAICompatibleProvider;
 pub use perplexity::PerplexityProvider;
+pub use registry::{ProviderCapabilities, ProviderRegistry, ProviderType};
 pub use stability::StabilityProvider;
 pub use mistral::MistralProvider;
 
+use crate::error::AppError;
+use crate::models::llm_provider::LLMProvider;
+use crate::services::function_calling::provider::openai::user_content;
 use crate::services::llm_service::{LLMChatMessage, LLMProviderTrait, LLMServiceError};
-use serde_json::Value;
-
-pub fn get_provider(provider_type: &str) -> Box<dyn LLMProviderTrait> {
-    match provider_type {
-        "claude" => Box::new(AnthropicProvider),
-        "command" => Box::new(CohereProvider),
-        "dalle" => Box::new(DalleProvider),
-        "gemini" => {
-            let config = gemini_default_config();
-            Box::new(GeminiProvider::new(config))
+use serde_json::{json, Value};
+
+/// Creates the implementation registered for the provider's type
+pub fn get_provider(provider: &LLMProvider) -> Result<Box<dyn LLMProviderTrait>, AppError> {
+    ProviderRegistry::global().create(&provider.provider_type, &provider.api_endpoint)
+}
+
+/// Messages for OpenAI-style chat APIs, with attached images and documents as content parts
+pub(crate) fn openai_messages(messages: &[LLMChatMessage]) -> Vec<Value> {
+    messages
+        .iter()
+        .map(|message| {
+            let mut formatted = json!(message);
+            if let Some(formatted) = formatted.as_object_mut() {
+                if formatted.remove("parts").is_some() {
+                    formatted.insert(
+                        "content".to_string(),
+                        user_content(&message.content, &message.parts),
+                    );
+                }
+            }
+            formatted
+        })
+        .collect()
+}
+
+/// Copies the options set in the provider configuration into a request body. Given as
+/// `(configuration key, body key)` pairs, since providers name some of them differently.
+pub(crate) fn forward_options(body: &mut Value, config: &Value, options: &[(&str, &str)]) {
+    for (from, to) in options {
+        if let Some(value) = config.get(*from).filter(|value| !value.is_null()) {
+            body[*to] = value.clone();
         }
-        "gpt" => Box::new(OpenAIProvider),
-        "leonardo" => Box::new(LeonardoProvider),
-        "perplexity" => Box::new(PerplexityProvider),
-        "grok" => Box::new(GrokProvider),
-        "stability" => Box::new(StabilityProvider),
-        "mistral" => Box::new(MistralProvider),
-        _ => panic!("Unknown provider type: {}", provider_type),
     }
 }
 
+/// The configured `embedding_model`, else the provider's default
+pub(crate) fn embedding_model(config: &Value, default: &str) -> String {
+    config["embedding_model"]
+        .as_str()
+        .unwrap_or(default)
+        .to_string()
+}
+
+/// Embeddings request body for OpenAI-style APIs
+pub(crate) fn openai_embedding_body(model: &str, inputs: &[String], config: &Value) -> Value {
+    let mut body = json!({
+        "model": model,
+        "input": inputs,
+        "encoding_format": "float",
+    });
+    if let Some(dimensions) = config["dimensions"].as_u64() {
+        body["dimensions"] = json!(dimensions);
+    }
+    body
+}
+
+/// Vectors from an OpenAI-style embeddings response, ordered by their `index`
+pub(crate) fn parse_openai_embeddings(response_text: &str) -> Result<Vec<Vec<f32>>, LLMServiceError> {
+    let response: Value = serde_json::from_str(response_text).map_err(|e| {
+        LLMServiceError(AppError::ExternalServiceError(format!(
+            "Invalid embeddings response: {}",
+            e
+        )))
+    })?;
+    let mut data: Vec<&Value> = response["data"]
+        .as_array()
+        .ok_or_else(|| {
+            LLMServiceError(AppError::ExternalServiceError(format!(
+                "Embeddings response has no data: {}",
+                response_text
+            )))
+        })?
+        .iter()
+        .collect();
+    data.sort_by_key(|item| item["index"].as_u64().unwrap_or(0));
+
+    data.into_iter().map(|item| parse_vector(&item["embedding"])).collect()
+}
+
+/// A JSON array of numbers as a vector
+pub(crate) fn parse_vector(value: &Value) -> Result<Vec<f32>, LLMServiceError> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_f64().map(|value| value as f32))
+                .collect::<Option<Vec<f32>>>()
+        })
+        .ok_or_else(|| {
+            LLMServiceError(AppError::ExternalServiceError(
+                "Embedding is not an array of numbers".to_string(),
+            ))
+        })
+}
+
 pub trait ProviderConfig {
     fn new(config: Value) -> Self;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::function_calling::ToolChoice;
+    use crate::services::llm_service::LLMToolOptions;
+
+    fn body(request: reqwest::RequestBuilder) -> Value {
+        let request = request.build().unwrap();
+        serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn temperature_is_sent_to_every_chat_provider() {
+        let messages = vec![LLMChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            parts: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }];
+        let tools = LLMToolOptions {
+            tools: Vec::new(),
+            choice: ToolChoice::Auto,
+        };
+        let config = json!({ "model": "some-model", "temperature": 0.0 });
+
+        let providers: Vec<(&str, Box<dyn LLMProviderTrait>)> = vec![
+            ("openai", Box::new(OpenAIProvider)),
+            ("anthropic", Box::new(AnthropicProvider)),
+            ("mistral", Box::new(MistralProvider)),
+            ("cohere", Box::new(CohereProvider)),
+            ("gemini", Box::new(GeminiProvider::new(gemini_default_config()))),
+        ];
+        for (name, provider) in providers {
+            let temperature = |body: &Value| {
+                if name == "gemini" {
+                    body["generationConfig"]["temperature"].clone()
+                } else {
+                    body["temperature"].clone()
+                }
+            };
+            let chat = body(provider.prepare_request(&messages, &config, "key").unwrap());
+            assert_eq!(temperature(&chat), json!(0.0), "{} chat request", name);
+            for stream in [false, true] {
+                let request = provider
+                    .prepare_tool_request(&messages, &config, "key", &tools, stream)
+                    .unwrap();
+                assert_eq!(temperature(&body(request)), json!(0.0), "{} tool request", name);
+            }
+        }
+    }
+
+    #[test]
+    fn unset_options_are_left_to_the_provider() {
+        let mut body = json!({ "model": "some-model" });
+        forward_options(&mut body, &json!({ "temperature": null }), &[("temperature", "temperature")]);
+        assert_eq!(body, json!({ "model": "some-model" }));
+    }
+}
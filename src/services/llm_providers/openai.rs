@@ -1,10 +1,26 @@
+use super::{
+    embedding_model, forward_options, openai_embedding_body, openai_messages,
+    parse_openai_embeddings,
+};
 use crate::error::AppError;
-use crate::services::llm_service::{LLMChatMessage, LLMProviderTrait, LLMServiceError};
+use crate::services::function_calling::provider::OpenAIAdapter;
+use crate::services::function_calling::ProviderAdapter;
+use crate::services::llm_service::{
+    tool_request_body, LLMChatMessage, LLMProviderTrait, LLMServiceError, LLMToolOptions,
+};
 use futures::stream::{self, Stream, StreamExt};
 use log::{debug, error, warn};
 use reqwest::Client;
 use serde_json::Value;
 use std::pin::Pin;
+use std::sync::Arc;
+
+/// Sampling options taken from the provider configuration
+const SAMPLING_OPTIONS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("max_tokens", "max_completion_tokens"),
+];
 
 pub struct OpenAIProvider;
 
@@ -22,11 +38,12 @@ impl LLMProviderTrait for OpenAIProvider {
             ))
         })?;
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": model,
-            "messages": messages,
+            "messages": openai_messages(messages),
             "stream": true
         });
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
 
         debug!("OpenAI request body: {:?}", request_body);
         debug!("Using API key: {}", api_key);
@@ -112,4 +129,72 @@ impl LLMProviderTrait for OpenAIProvider {
             .filter(|result| futures::future::ready(!matches!(result, Ok(ref s) if s.is_empty()))),
         )
     }
+
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        Some(Arc::new(OpenAIAdapter))
+    }
+
+    fn prepare_tool_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+        tools: &LLMToolOptions,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = config["model"].as_str().ok_or_else(|| {
+            LLMServiceError(AppError::BadRequest(
+                "Model not specified for OpenAI provider".to_string(),
+            ))
+        })?;
+
+        let mut request_body = tool_request_body(
+            &OpenAIAdapter,
+            serde_json::json!({
+                "model": model,
+                "stream": stream,
+            }),
+            messages,
+            tools,
+        );
+        if stream {
+            // Without this the stream carries no token usage
+            request_body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        forward_options(&mut request_body, config, SAMPLING_OPTIONS);
+
+        debug!("OpenAI tool request body: {:?}", request_body);
+
+        Ok(Client::new()
+            .post("https://api.openai.com/v1/chat/completions")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn embedding_model(&self, config: &Value) -> Option<String> {
+        Some(embedding_model(config, "text-embedding-3-small"))
+    }
+
+    fn embedding_batch_size(&self) -> usize {
+        2048
+    }
+
+    fn prepare_embedding_request(
+        &self,
+        inputs: &[String],
+        config: &Value,
+        api_key: &str,
+    ) -> Result<reqwest::RequestBuilder, LLMServiceError> {
+        let model = embedding_model(config, "text-embedding-3-small");
+        Ok(Client::new()
+            .post("https://api.openai.com/v1/embeddings")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&openai_embedding_body(&model, inputs, config)))
+    }
+
+    fn parse_embeddings(&self, response_text: &str) -> Result<Vec<Vec<f32>>, LLMServiceError> {
+        parse_openai_embeddings(response_text)
+    }
 }
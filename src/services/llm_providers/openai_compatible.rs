@@ -0,0 +1,351 @@
+use super::{embedding_model, openai_embedding_body, openai_messages, parse_openai_embeddings};
+use crate::error::AppError;
+use crate::services::function_calling::provider::{
+    OpenAIAdapter, OpenAIToolCallAssembler, SseBuffer, StreamDelta, ToolCallAssembler,
+};
+use crate::services::function_calling::ProviderAdapter;
+use crate::services::llm_service::{
+    assemble_tool_stream, tool_request_body, LLMChatMessage, LLMProviderTrait, LLMServiceError,
+    LLMStreamChunk, LLMToolOptions,
+};
+use futures::stream::{Stream, StreamExt};
+use log::debug;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Request options forwarded as-is when present in the provider configuration
+const FORWARDED_OPTIONS: &[&str] = &[
+    "temperature",
+    "top_p",
+    "max_tokens",
+    "frequency_penalty",
+    "presence_penalty",
+    "stop",
+    "seed",
+];
+
+/// Any server speaking the OpenAI chat completions protocol at the provider's `api_endpoint`,
+/// e.g. Ollama, vLLM, LM Studio, llama.cpp server or OpenRouter
+pub struct OpenAICompatibleProvider {
+    url: String,
+    embeddings_url: String,
+}
+
+impl OpenAICompatibleProvider {
+    /// Accepts either a base URL such as `http://localhost:11434/v1` or the full
+    /// `/chat/completions` URL
+    pub fn new(api_endpoint: &str) -> Self {
+        let endpoint = api_endpoint.trim().trim_end_matches('/');
+        let base = endpoint.strip_suffix("/chat/completions").unwrap_or(endpoint);
+        OpenAICompatibleProvider {
+            url: format!("{}/chat/completions", base),
+            embeddings_url: format!("{}/embeddings", base),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn embeddings_url(&self) -> &str {
+        &self.embeddings_url
+    }
+
+    fn request_body(config: &Value, stream: bool) -> Result<Value, LLMServiceError> {
+        let model = config["model"].as_str().ok_or_else(|| {
+            LLMServiceError(AppError::BadRequest(
+                "Model not specified for OpenAI-compatible provider".to_string(),
+            ))
+        })?;
+
+        let mut body = json!({
+            "model": model,
+            "stream": stream,
+        });
+        if stream {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+        for option in FORWARDED_OPTIONS {
+            if let Some(value) = config.get(*option).filter(|value| !value.is_null()) {
+                body[*option] = value.clone();
+            }
+        }
+        Ok(body)
+    }
+
+    /// Authentication is optional since local servers usually don't need any
+    fn post(&self, url: &str, config: &Value, api_key: &str, body: &Value) -> RequestBuilder {
+        let mut request = Client::new()
+            .post(url)
+            .header("Content-Type", "application/json");
+        if !api_key.trim().is_empty() {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        // Extra headers, e.g. OpenRouter's HTTP-Referer and X-Title
+        if let Some(headers) = config["headers"].as_object() {
+            for (name, value) in headers {
+                if let Some(value) = value.as_str() {
+                    request = request.header(name.as_str(), value);
+                }
+            }
+        }
+        request.json(body)
+    }
+}
+
+impl LLMProviderTrait for OpenAICompatibleProvider {
+    fn prepare_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+    ) -> Result<RequestBuilder, LLMServiceError> {
+        let mut body = Self::request_body(config, true)?;
+        body["messages"] = json!(openai_messages(messages));
+
+        debug!("OpenAI-compatible request to {}: {:?}", self.url, body);
+        Ok(self.post(&self.url, config, api_key, &body))
+    }
+
+    fn parse_response(&self, response_text: &str) -> Result<String, LLMServiceError> {
+        if let Ok(response) = serde_json::from_str::<Value>(response_text) {
+            return Ok(OpenAIAdapter.parse_content(&response).unwrap_or_default());
+        }
+
+        // Plain requests always stream, so a buffered response is a series of SSE events
+        let mut assembler = OpenAIToolCallAssembler::new();
+        let mut content = String::new();
+        for payload in SseBuffer::new().push(format!("{}\n\n", response_text).as_bytes()) {
+            let Ok(event) = serde_json::from_str::<Value>(&payload) else {
+                continue;
+            };
+            let deltas = assembler
+                .push_event(&event)
+                .map_err(|e| LLMServiceError(AppError::LLMError(e.to_string())))?;
+            for delta in deltas {
+                if let StreamDelta::Text(text) = delta {
+                    content.push_str(&text);
+                }
+            }
+        }
+        Ok(content)
+    }
+
+    fn stream_response(
+        &self,
+        response: reqwest::Response,
+    ) -> Pin<Box<dyn Stream<Item = Result<String, LLMServiceError>> + Send + 'static>> {
+        // Reuse the SSE parsing of tool streams, which copes with events split across chunks
+        let assembler = Box::new(OpenAIToolCallAssembler::new());
+        Box::pin(assemble_tool_stream(response, assembler).filter_map(|chunk| async move {
+            match chunk {
+                Ok(LLMStreamChunk::Text(text)) => Some(Ok(text)),
+                Ok(LLMStreamChunk::ToolCall(_)) | Ok(LLMStreamChunk::Usage(_)) => None,
+                Err(e) => Some(Err(e)),
+            }
+        }))
+    }
+
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        Some(Arc::new(OpenAIAdapter))
+    }
+
+    fn prepare_tool_request(
+        &self,
+        messages: &[LLMChatMessage],
+        config: &Value,
+        api_key: &str,
+        tools: &LLMToolOptions,
+        stream: bool,
+    ) -> Result<RequestBuilder, LLMServiceError> {
+        let body = tool_request_body(
+            &OpenAIAdapter,
+            Self::request_body(config, stream)?,
+            messages,
+            tools,
+        );
+
+        debug!("OpenAI-compatible tool request to {}: {:?}", self.url, body);
+        Ok(self.post(&self.url, config, api_key, &body))
+    }
+
+    fn embedding_model(&self, config: &Value) -> Option<String> {
+        // Ollama's default embedding model, matching the default endpoint
+        Some(embedding_model(config, "nomic-embed-text"))
+    }
+
+    fn prepare_embedding_request(
+        &self,
+        inputs: &[String],
+        config: &Value,
+        api_key: &str,
+    ) -> Result<RequestBuilder, LLMServiceError> {
+        let model = embedding_model(config, "nomic-embed-text");
+        let body = openai_embedding_body(&model, inputs, config);
+
+        debug!("OpenAI-compatible embeddings request to {}", self.embeddings_url);
+        Ok(self.post(&self.embeddings_url, config, api_key, &body))
+    }
+
+    fn parse_embeddings(&self, response_text: &str) -> Result<Vec<Vec<f32>>, LLMServiceError> {
+        parse_openai_embeddings(response_text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::llm_provider::LLMProvider;
+    use crate::services::llm_service::LLMService;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    /// Serves one request with `body` and returns the raw request it received
+    async fn mock_server(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..header_end]
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn endpoint_accepts_base_or_full_url() {
+        assert_eq!(
+            OpenAICompatibleProvider::new("http://localhost:11434/v1/").url(),
+            "http://localhost:11434/v1/chat/completions"
+        );
+        assert_eq!(
+            OpenAICompatibleProvider::new("https://openrouter.ai/api/v1/chat/completions").url(),
+            "https://openrouter.ai/api/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn embeddings_url_shares_the_base() {
+        assert_eq!(
+            OpenAICompatibleProvider::new("https://openrouter.ai/api/v1/chat/completions")
+                .embeddings_url(),
+            "https://openrouter.ai/api/v1/embeddings"
+        );
+    }
+
+    #[test]
+    fn buffered_stream_is_parsed() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n\
+                    data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n\
+                    data: [DONE]\n\n";
+        let provider = OpenAICompatibleProvider::new("http://localhost:8000/v1");
+        assert_eq!(provider.parse_response(body).unwrap(), "Hello");
+    }
+
+    #[tokio::test]
+    async fn embeddings_are_ordered_by_index() {
+        let (endpoint, server) = mock_server(
+            r#"{"data":[{"index":1,"embedding":[0.0,1.0]},{"index":0,"embedding":[1.0,0.0]}],"model":"nomic-embed-text","usage":{"prompt_tokens":4,"total_tokens":4}}"#,
+        )
+        .await;
+        let now = chrono::Utc::now().naive_utc();
+        let provider = LLMProvider {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            name: "local".to_string(),
+            provider_type: "openai_compatible".to_string(),
+            api_endpoint: endpoint,
+            supported_modalities: json!(["text"]),
+            configuration: json!({ "model": "llama3.1" }),
+            created_at: now,
+            updated_at: now,
+        };
+
+        let output = LLMService::embed_with_api_key(
+            &provider,
+            "",
+            vec!["first".to_string(), "second".to_string()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.model, "nomic-embed-text");
+        assert_eq!(output.dimensions, 2);
+        assert_eq!(output.embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert_eq!(output.usage_stats.unwrap().tokens.prompt_tokens, 4);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/embeddings "));
+        assert!(request.contains("\"input\":[\"first\",\"second\"]"));
+    }
+
+    #[tokio::test]
+    async fn chat_uses_stored_endpoint_without_auth() {
+        let (endpoint, server) = mock_server(
+            r#"{"choices":[{"message":{"role":"assistant","content":"Hi there"}}],"usage":{"prompt_tokens":3,"completion_tokens":2,"total_tokens":5}}"#,
+        )
+        .await;
+        let now = chrono::Utc::now().naive_utc();
+        let provider = LLMProvider {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            name: "local".to_string(),
+            provider_type: "openai_compatible".to_string(),
+            api_endpoint: endpoint,
+            supported_modalities: json!(["text"]),
+            configuration: json!({ "model": "llama3.1", "temperature": 0.2 }),
+            created_at: now,
+            updated_at: now,
+        };
+        let messages = vec![LLMChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            parts: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }];
+
+        let output =
+            LLMService::chat_with_api_key(&provider, "", messages, Some(&LLMToolOptions::default()))
+                .await
+                .unwrap();
+        assert_eq!(output.content, "Hi there");
+        assert_eq!(output.usage.unwrap()["total_tokens"], 5);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions "));
+        assert!(!request.to_lowercase().contains("authorization:"));
+        assert!(request.contains("\"model\":\"llama3.1\""));
+        assert!(request.contains("\"temperature\":0.2"));
+    }
+}
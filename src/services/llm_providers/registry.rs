@@ -0,0 +1,310 @@
+use super::{
+    gemini_default_config, AnthropicProvider, CohereProvider, DalleProvider, GeminiProvider,
+    GrokProvider, LeonardoProvider, MistralProvider, OpenAICompatibleProvider, OpenAIProvider,
+    PerplexityProvider, StabilityProvider,
+};
+use crate::error::AppError;
+use crate::services::llm_service::LLMProviderTrait;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+lazy_static! {
+    static ref GLOBAL_REGISTRY: ProviderRegistry = ProviderRegistry::with_builtin();
+}
+
+/// Builds a provider implementation for the `api_endpoint` stored on the provider
+type ProviderFactory = Arc<dyn Fn(&str) -> Box<dyn LLMProviderTrait> + Send + Sync>;
+
+/// What a provider type can do, used to drive the UI and templates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ProviderCapabilities {
+    pub text: bool,
+    pub image: bool,
+    pub streaming: bool,
+    pub tools: bool,
+    pub vision: bool,
+    pub documents: bool,
+    pub embeddings: bool,
+}
+
+impl ProviderCapabilities {
+    /// Chat model that streams text and calls tools
+    pub fn chat() -> Self {
+        ProviderCapabilities {
+            text: true,
+            streaming: true,
+            tools: true,
+            ..Default::default()
+        }
+    }
+
+    /// Image generation model
+    pub fn image() -> Self {
+        ProviderCapabilities {
+            image: true,
+            ..Default::default()
+        }
+    }
+
+    /// Chat model that also reads images and, with `documents`, PDFs
+    pub fn multimodal_chat(documents: bool) -> Self {
+        ProviderCapabilities {
+            vision: true,
+            documents,
+            ..Self::chat()
+        }
+    }
+
+    /// The same capabilities, plus creating embeddings
+    pub fn with_embeddings(self) -> Self {
+        ProviderCapabilities {
+            embeddings: true,
+            ..self
+        }
+    }
+
+    /// Modalities in the form stored on `llm_providers.supported_modalities`: `text` and
+    /// `image` outputs, plus `vision` and `document` inputs
+    pub fn modalities(&self) -> Vec<String> {
+        let mut modalities = Vec::new();
+        if self.text {
+            modalities.push("text".to_string());
+        }
+        if self.image {
+            modalities.push("image".to_string());
+        }
+        if self.vision {
+            modalities.push("vision".to_string());
+        }
+        if self.documents {
+            modalities.push("document".to_string());
+        }
+        modalities
+    }
+}
+
+/// A provider type that `llm_providers.provider_type` can refer to
+#[derive(Clone, Serialize)]
+pub struct ProviderType {
+    pub name: String,
+    pub display_name: String,
+    pub default_endpoint: String,
+    pub capabilities: ProviderCapabilities,
+    #[serde(skip)]
+    factory: ProviderFactory,
+}
+
+impl ProviderType {
+    pub fn new<F>(name: &str, display_name: &str, default_endpoint: &str, factory: F) -> Self
+    where
+        F: Fn(&str) -> Box<dyn LLMProviderTrait> + Send + Sync + 'static,
+    {
+        ProviderType {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            default_endpoint: default_endpoint.to_string(),
+            capabilities: ProviderCapabilities::default(),
+            factory: Arc::new(factory),
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Creates a provider implementation for a request
+    pub fn create(&self, api_endpoint: &str) -> Box<dyn LLMProviderTrait> {
+        (self.factory)(api_endpoint)
+    }
+}
+
+/// Provider types known to the server, registered by type name
+#[derive(Default)]
+pub struct ProviderRegistry {
+    types: RwLock<HashMap<String, ProviderType>>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The registry the services resolve providers from
+    pub fn global() -> &'static ProviderRegistry {
+        &GLOBAL_REGISTRY
+    }
+
+    /// Creates a registry with every built-in provider type
+    pub fn with_builtin() -> Self {
+        let registry = Self::new();
+        let chat = ProviderCapabilities::chat();
+
+        registry.register(
+            ProviderType::new("gpt", "OpenAI", "https://api.openai.com/v1/chat/completions", |_| {
+                Box::new(OpenAIProvider)
+            })
+            .with_capabilities(ProviderCapabilities::multimodal_chat(true).with_embeddings()),
+        );
+        registry.register(
+            ProviderType::new("claude", "Anthropic", "https://api.anthropic.com/v1/messages", |_| {
+                Box::new(AnthropicProvider)
+            })
+            .with_capabilities(ProviderCapabilities::multimodal_chat(true)),
+        );
+        registry.register(
+            ProviderType::new(
+                "gemini",
+                "Google Gemini",
+                "https://generativelanguage.googleapis.com/v1beta/models",
+                |_| Box::new(GeminiProvider::new(gemini_default_config())),
+            )
+            .with_capabilities(ProviderCapabilities::multimodal_chat(true).with_embeddings()),
+        );
+        registry.register(
+            ProviderType::new("command", "Cohere", "https://api.cohere.com/v2/chat", |_| {
+                Box::new(CohereProvider)
+            })
+            .with_capabilities(chat.with_embeddings()),
+        );
+        registry.register(
+            ProviderType::new("grok", "xAI Grok", "https://api.x.ai/v1/chat/completions", |_| {
+                Box::new(GrokProvider)
+            })
+            .with_capabilities(ProviderCapabilities::multimodal_chat(false)),
+        );
+        registry.register(
+            ProviderType::new(
+                "mistral",
+                "Mistral AI",
+                "https://api.mistral.ai/v1/chat/completions",
+                |_| Box::new(MistralProvider),
+            )
+            .with_capabilities(ProviderCapabilities::multimodal_chat(false).with_embeddings()),
+        );
+        registry.register(
+            ProviderType::new(
+                "perplexity",
+                "Perplexity",
+                "https://api.perplexity.ai/chat/completions",
+                |_| Box::new(PerplexityProvider),
+            )
+            .with_capabilities(ProviderCapabilities {
+                tools: false,
+                ..chat
+            }),
+        );
+        registry.register(
+            ProviderType::new(
+                "dalle",
+                "OpenAI DALL-E",
+                "https://api.openai.com/v1/images/generations",
+                |_| Box::new(DalleProvider),
+            )
+            .with_capabilities(ProviderCapabilities::image()),
+        );
+        registry.register(
+            ProviderType::new(
+                "leonardo",
+                "Leonardo AI",
+                "https://cloud.leonardo.ai/api/rest/v1/generations",
+                |_| Box::new(LeonardoProvider),
+            )
+            .with_capabilities(ProviderCapabilities::image()),
+        );
+        registry.register(
+            ProviderType::new(
+                "stability",
+                "Stability AI",
+                "https://api.stability.ai/v2beta/stable-image/generate/ultra",
+                |_| Box::new(StabilityProvider),
+            )
+            .with_capabilities(ProviderCapabilities::image()),
+        );
+        registry.register(
+            ProviderType::new(
+                "openai_compatible",
+                "OpenAI-Compatible",
+                "http://localhost:11434/v1",
+                |api_endpoint| Box::new(OpenAICompatibleProvider::new(api_endpoint)),
+            )
+            .with_capabilities(ProviderCapabilities::multimodal_chat(false).with_embeddings()),
+        );
+
+        registry
+    }
+
+    /// Registers a provider type, replacing any previous one with the same name
+    pub fn register(&self, provider_type: ProviderType) {
+        self.types
+            .write()
+            .unwrap()
+            .insert(provider_type.name.clone(), provider_type);
+    }
+
+    pub fn get_type(&self, name: &str) -> Result<ProviderType, AppError> {
+        self.types
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| {
+                AppError::UnsupportedProviderError(format!("Unknown provider type: {}", name))
+            })
+    }
+
+    /// Creates the provider implementation for a provider type
+    pub fn create(
+        &self,
+        name: &str,
+        api_endpoint: &str,
+    ) -> Result<Box<dyn LLMProviderTrait>, AppError> {
+        Ok(self.get_type(name)?.create(api_endpoint))
+    }
+
+    pub fn list(&self) -> Vec<ProviderType> {
+        let mut types: Vec<ProviderType> = self.types.read().unwrap().values().cloned().collect();
+        types.sort_by(|a, b| a.name.cmp(&b.name));
+        types
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_provider_type_is_an_error() {
+        let registry = ProviderRegistry::with_builtin();
+        assert!(matches!(
+            registry.create("gtp", ""),
+            Err(AppError::UnsupportedProviderError(_))
+        ));
+    }
+
+    #[test]
+    fn builtin_capabilities_match_implementations() {
+        let registry = ProviderRegistry::with_builtin();
+        for provider_type in registry.list() {
+            let provider = provider_type.create(&provider_type.default_endpoint);
+            assert_eq!(
+                provider.tool_adapter().is_some(),
+                provider_type.capabilities.tools,
+                "tools capability of {}",
+                provider_type.name
+            );
+            assert_eq!(
+                provider.embedding_model(&serde_json::json!({})).is_some(),
+                provider_type.capabilities.embeddings,
+                "embeddings capability of {}",
+                provider_type.name
+            );
+        }
+        assert_eq!(
+            registry.get_type("dalle").unwrap().capabilities.modalities(),
+            vec!["image".to_string()]
+        );
+    }
+}
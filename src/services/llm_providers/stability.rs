@@ -151,6 +151,9 @@ mod tests {
         let messages = vec![LLMChatMessage {
             role: "user".to_string(),
             content: "Generate an image of a cat".to_string(),
+            parts: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
         }];
 
         let result = provider.prepare_request(&messages, &config, "test_key");
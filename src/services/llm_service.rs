@@ -3,15 +3,27 @@ use crate::error::AppError;
 use crate::models::llm_provider::LLMProvider;
 use crate::models::user_llm_config::UserLLMConfig;
 use crate::services::api_key_service::ApiKeyService;
+use crate::services::attachment_service::AttachmentService;
+use crate::services::function_calling::provider::{SseBuffer, StreamDelta, ToolCallAssembler};
+use crate::services::function_calling::{
+    ContentPart, MediaSource, Message, ProviderAdapter, Tool, ToolCall, ToolChoice,
+};
+use crate::services::llm_provider::LLMProviderService;
 use crate::services::llm_providers;
-use futures::stream::{Stream, StreamExt};
-use log::{debug, error, info};
-use reqwest::{Client, RequestBuilder};
+use crate::services::response_cache::{CachePolicy, ResponseCache};
+use crate::services::usage_service::{TokenUsage, UsageStats};
+use futures::stream::{self, Stream, StreamExt};
+use log::{debug, error, info, warn};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::VecDeque;
 use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
 
 #[derive(Debug)]
 pub struct LLMServiceError(pub AppError);
@@ -34,6 +46,113 @@ impl From<AppError> for LLMServiceError {
 pub struct LLMChatMessage {
     pub role: String,
     pub content: String,
+    /// Images and documents sent after `content`; only user messages may carry them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<ContentPart>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl From<&Message> for LLMChatMessage {
+    fn from(message: &Message) -> Self {
+        let (role, content, tool_calls, tool_call_id) = match message {
+            Message::System { content } => ("system", content.clone(), Vec::new(), None),
+            Message::User { content, .. } => ("user", content.clone(), Vec::new(), None),
+            Message::Assistant { content, tool_calls } => {
+                ("assistant", content.clone(), tool_calls.clone(), None)
+            }
+            Message::ToolResult {
+                tool_call_id,
+                content,
+            } => ("tool", content.to_string(), Vec::new(), Some(tool_call_id.clone())),
+        };
+        let parts = match message {
+            Message::User { parts, .. } => parts.clone(),
+            _ => Vec::new(),
+        };
+
+        LLMChatMessage {
+            role: role.to_string(),
+            content,
+            parts,
+            tool_calls,
+            tool_call_id,
+        }
+    }
+}
+
+impl From<&LLMChatMessage> for Message {
+    fn from(message: &LLMChatMessage) -> Self {
+        match (message.role.as_str(), &message.tool_call_id) {
+            ("tool", Some(tool_call_id)) => Message::ToolResult {
+                tool_call_id: tool_call_id.clone(),
+                content: serde_json::from_str(&message.content)
+                    .unwrap_or_else(|_| Value::String(message.content.clone())),
+            },
+            ("system", _) => Message::system(&message.content),
+            ("assistant", _) => {
+                Message::assistant_with_tool_calls(&message.content, message.tool_calls.clone())
+            }
+            _ => Message::user_with_parts(&message.content, message.parts.clone()),
+        }
+    }
+}
+
+/// Tools offered to the model along with how it may choose among them
+#[derive(Debug, Clone, Default)]
+pub struct LLMToolOptions {
+    pub tools: Vec<Tool>,
+    pub choice: ToolChoice,
+}
+
+/// Assistant output from a chat completion
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LLMChatOutput {
+    pub content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Value>,
+    /// Normalized token counts and cost of `usage`, priced for the provider that answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_stats: Option<UsageStats>,
+    /// Name of the provider that answered, which differs from the requested one after failover
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_model: Option<String>,
+    /// Answered from the response cache, without calling the provider
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+/// Embedding vectors for a batch of inputs, in input order
+#[derive(Debug, Clone, Serialize)]
+pub struct LLMEmbeddingOutput {
+    pub model: String,
+    pub dimensions: usize,
+    pub embeddings: Vec<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_stats: Option<UsageStats>,
+}
+
+/// A piece of a streamed chat completion
+#[derive(Debug, Clone)]
+pub enum LLMStreamChunk {
+    Text(String),
+    ToolCall(ToolCall),
+    /// Token usage in the provider's own format, see `TokenUsage::from_provider`
+    Usage(Value),
+}
+
+impl From<StreamDelta> for LLMStreamChunk {
+    fn from(delta: StreamDelta) -> Self {
+        match delta {
+            StreamDelta::Text(text) => LLMStreamChunk::Text(text),
+            StreamDelta::ToolCall(call) => LLMStreamChunk::ToolCall(call),
+            StreamDelta::Usage(usage) => LLMStreamChunk::Usage(usage),
+        }
+    }
 }
 
 pub trait LLMProviderTrait: Send + Sync {
@@ -44,10 +163,240 @@ pub trait LLMProviderTrait: Send + Sync {
         api_key: &str,
     ) -> Result<RequestBuilder, LLMServiceError>;
     fn parse_response(&self, response_text: &str) -> Result<String, LLMServiceError>;
+
+    /// Extracts token usage from a complete response in the provider's own format
+    fn parse_usage(&self, response_text: &str) -> Option<Value> {
+        let response: Value = serde_json::from_str(response_text).ok()?;
+        ["usage", "usageMetadata", "meta"]
+            .iter()
+            .find_map(|key| response.get(*key).filter(|usage| usage.is_object()).cloned())
+    }
     fn stream_response(
         &self,
         response: reqwest::Response,
     ) -> Pin<Box<dyn Stream<Item = Result<String, LLMServiceError>> + Send + 'static>>;
+
+    /// Returns the function calling adapter if the provider supports native tool use
+    fn tool_adapter(&self) -> Option<Arc<dyn ProviderAdapter>> {
+        None
+    }
+
+    /// Prepares a request that offers tools to the model
+    fn prepare_tool_request(
+        &self,
+        _messages: &[LLMChatMessage],
+        _config: &Value,
+        _api_key: &str,
+        _tools: &LLMToolOptions,
+        _stream: bool,
+    ) -> Result<RequestBuilder, LLMServiceError> {
+        Err(LLMServiceError(AppError::UnsupportedProviderError(
+            "Provider does not support tool calling".to_string(),
+        )))
+    }
+
+    /// Embedding model used for `config`, if the provider can create embeddings
+    fn embedding_model(&self, _config: &Value) -> Option<String> {
+        None
+    }
+
+    /// Largest number of inputs sent in one embeddings request
+    fn embedding_batch_size(&self) -> usize {
+        96
+    }
+
+    /// Prepares a request for the embeddings of one batch of inputs
+    fn prepare_embedding_request(
+        &self,
+        _inputs: &[String],
+        _config: &Value,
+        _api_key: &str,
+    ) -> Result<RequestBuilder, LLMServiceError> {
+        Err(LLMServiceError(AppError::UnsupportedProviderError(
+            "Provider does not support embeddings".to_string(),
+        )))
+    }
+
+    /// Extracts one vector per input, in input order, from an embeddings response
+    fn parse_embeddings(&self, _response_text: &str) -> Result<Vec<Vec<f32>>, LLMServiceError> {
+        Err(LLMServiceError(AppError::UnsupportedProviderError(
+            "Provider does not support embeddings".to_string(),
+        )))
+    }
+
+    /// Streams a response to a tool request as text and tool call chunks
+    fn stream_tool_response(
+        &self,
+        response: reqwest::Response,
+    ) -> Pin<Box<dyn Stream<Item = Result<LLMStreamChunk, LLMServiceError>> + Send + 'static>> {
+        match self.tool_adapter().and_then(|adapter| adapter.stream_assembler()) {
+            Some(assembler) => assemble_tool_stream(response, assembler),
+            None => Box::pin(
+                self.stream_response(response)
+                    .map(|chunk| chunk.map(LLMStreamChunk::Text)),
+            ),
+        }
+    }
+}
+
+struct ToolStreamState {
+    response: Option<reqwest::Response>,
+    sse: SseBuffer,
+    assembler: Box<dyn ToolCallAssembler>,
+    pending: VecDeque<Result<LLMStreamChunk, LLMServiceError>>,
+}
+
+/// Streams text as it arrives and tool calls once their arguments are complete
+pub(crate) fn assemble_tool_stream(
+    response: reqwest::Response,
+    assembler: Box<dyn ToolCallAssembler>,
+) -> Pin<Box<dyn Stream<Item = Result<LLMStreamChunk, LLMServiceError>> + Send + 'static>> {
+    let state = ToolStreamState {
+        response: Some(response),
+        sse: SseBuffer::new(),
+        assembler,
+        pending: VecDeque::new(),
+    };
+
+    Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.pending.pop_front() {
+                return Some((item, state));
+            }
+
+            let response = state.response.as_mut()?;
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    for payload in state.sse.push(&chunk) {
+                        if payload == "[DONE]" {
+                            continue;
+                        }
+                        let event: Value = match serde_json::from_str(&payload) {
+                            Ok(event) => event,
+                            Err(e) => {
+                                debug!("Skipping unparseable stream event {}: {}", payload, e);
+                                continue;
+                            }
+                        };
+                        match state.assembler.push_event(&event) {
+                            Ok(deltas) => state
+                                .pending
+                                .extend(deltas.into_iter().map(|delta| Ok(delta.into()))),
+                            Err(e) => {
+                                error!("Failed to assemble streamed tool call: {}", e);
+                                state
+                                    .pending
+                                    .push_back(Err(LLMServiceError(AppError::LLMError(e.to_string()))));
+                                state.response = None;
+                                break;
+                            }
+                        }
+                    }
+                }
+                Ok(None) => {
+                    state.response = None;
+                    match state.assembler.finish() {
+                        Ok(calls) => state
+                            .pending
+                            .extend(calls.into_iter().map(|call| Ok(LLMStreamChunk::ToolCall(call)))),
+                        Err(e) => state
+                            .pending
+                            .push_back(Err(LLMServiceError(AppError::LLMError(e.to_string())))),
+                    }
+                }
+                Err(e) => {
+                    error!("Error in tool stream: {:?}", e);
+                    state.response = None;
+                    state
+                        .pending
+                        .push_back(Err(LLMServiceError(AppError::ExternalServiceError(e.to_string()))));
+                }
+            }
+        }
+    }))
+}
+
+/// Builds a tool request body with the provider's adapter
+pub(crate) fn tool_request_body(
+    adapter: &dyn ProviderAdapter,
+    mut body: Value,
+    messages: &[LLMChatMessage],
+    tools: &LLMToolOptions,
+) -> Value {
+    let messages: Vec<Message> = messages.iter().map(Message::from).collect();
+    adapter.format_messages(&mut body, &messages);
+    if !tools.tools.is_empty() {
+        adapter.prepare_request(&mut body, &tools.tools, tools.choice.clone());
+    }
+    body
+}
+
+/// Provider types that fetch documents given by URL themselves. The OpenAI format only takes
+/// document content inline.
+const DOCUMENT_URL_PROVIDERS: &[&str] = &["claude", "gemini"];
+
+/// Attempts per provider before falling over to the next one in the chain
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled on each following one
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound on retry delays, including ones a provider asks for with Retry-After
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// A failed provider request and whether retrying or falling over may help
+#[derive(Debug)]
+struct ProviderFailure {
+    error: LLMServiceError,
+    retryable: bool,
+    retry_after: Option<Duration>,
+}
+
+impl ProviderFailure {
+    fn transient(error: AppError) -> Self {
+        ProviderFailure {
+            error: LLMServiceError(error),
+            retryable: true,
+            retry_after: None,
+        }
+    }
+}
+
+impl From<LLMServiceError> for ProviderFailure {
+    fn from(error: LLMServiceError) -> Self {
+        ProviderFailure {
+            error,
+            retryable: false,
+            retry_after: None,
+        }
+    }
+}
+
+/// A provider in a fallback chain along with the name recorded for its answers
+struct ChainTarget {
+    provider: LLMProvider,
+    user_config: UserLLMConfig,
+    provider_model: String,
+}
+
+/// A streamed chat completion and the provider answering it
+pub struct LLMChatStream {
+    pub provider_model: String,
+    /// The provider that answered, with any fallback model override applied
+    pub provider: LLMProvider,
+    pub chunks: Pin<Box<dyn Stream<Item = Result<LLMStreamChunk, LLMServiceError>> + Send>>,
+}
+
+fn retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    retry_after
+        .unwrap_or(RETRY_BASE_DELAY * 2u32.pow(attempt.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+fn chain_exhausted(last_error: Option<LLMServiceError>) -> LLMServiceError {
+    last_error.unwrap_or_else(|| {
+        LLMServiceError(AppError::ExternalServiceError(
+            "No LLM provider available".to_string(),
+        ))
+    })
 }
 
 pub struct LLMService;
@@ -58,46 +407,264 @@ impl LLMService {
         provider: &LLMProvider,
         user_config: &UserLLMConfig,
         messages: Vec<LLMChatMessage>,
-    ) -> Result<String, LLMServiceError> {
+        tools: Option<&LLMToolOptions>,
+    ) -> Result<LLMChatOutput, LLMServiceError> {
+        Self::chat_with_cache(pool, provider, user_config, messages, tools, CachePolicy::Auto).await
+    }
+
+    /// Like `chat`, answering repeated requests from the response cache when `cache` allows it
+    pub async fn chat_with_cache(
+        pool: &DbPool,
+        provider: &LLMProvider,
+        user_config: &UserLLMConfig,
+        messages: Vec<LLMChatMessage>,
+        tools: Option<&LLMToolOptions>,
+        cache: CachePolicy,
+    ) -> Result<LLMChatOutput, LLMServiceError> {
         info!(
             "Starting chat function with provider: {:?}",
             provider.provider_type
         );
 
-        let api_key = Self::get_api_key(pool, user_config).await?;
+        let cache_key = cache
+            .applies_to(provider)
+            .then(|| ResponseCache::key(user_config.user_id, provider, &messages, tools));
+        if let Some(key) = &cache_key {
+            if let Some(output) = ResponseCache::global().get(key) {
+                debug!("Answering from the response cache");
+                // Nothing was sent to the provider, so there is no usage to account for
+                return Ok(LLMChatOutput {
+                    usage: None,
+                    usage_stats: None,
+                    cached: true,
+                    ..output
+                });
+            }
+        }
 
-        let llm_provider = llm_providers::get_provider(&provider.provider_type);
-        let client = Client::new();
-        let request = llm_provider.prepare_request(&messages, &provider.configuration, &api_key)?;
+        let messages = Self::resolve_attachments(pool, user_config.user_id, messages).await?;
+        let mut last_error = None;
+        for target in Self::fallback_chain(pool, provider, user_config) {
+            if let Err(e) = Self::check_modalities(&target.provider, &messages) {
+                warn!("Skipping provider {}: {}", target.provider_model, e);
+                last_error = Some(e);
+                continue;
+            }
+            let api_key = Self::get_api_key(pool, &target.user_config).await?;
+            match Self::try_chat(&target.provider, &api_key, &messages, tools).await {
+                Ok(mut output) => {
+                    output.provider_model = Some(target.provider_model);
+                    if let Some(key) = cache_key {
+                        ResponseCache::global().insert(key, &output);
+                    }
+                    return Ok(output);
+                }
+                Err(failure) if failure.retryable => {
+                    warn!(
+                        "Provider {} unavailable, falling over: {}",
+                        target.provider_model, failure.error
+                    );
+                    last_error = Some(failure.error);
+                }
+                Err(failure) => return Err(failure.error),
+            }
+        }
 
-        let response = request.send().await.map_err(|e| {
-            error!("Failed to send request: {:?}", e);
-            LLMServiceError(AppError::ExternalServiceError(e.to_string()))
+        Err(chain_exhausted(last_error))
+    }
+
+    /// Sends a chat completion with an already resolved API key; attachment sources in the
+    /// messages must already be resolved with `resolve_attachments`
+    pub async fn chat_with_api_key(
+        provider: &LLMProvider,
+        api_key: &str,
+        messages: Vec<LLMChatMessage>,
+        tools: Option<&LLMToolOptions>,
+    ) -> Result<LLMChatOutput, LLMServiceError> {
+        Self::check_modalities(provider, &messages)?;
+        Self::try_chat(provider, api_key, &messages, tools)
+            .await
+            .map_err(|failure| failure.error)
+    }
+
+    async fn try_chat(
+        provider: &LLMProvider,
+        api_key: &str,
+        messages: &[LLMChatMessage],
+        tools: Option<&LLMToolOptions>,
+    ) -> Result<LLMChatOutput, ProviderFailure> {
+        let llm_provider =
+            llm_providers::get_provider(provider).map_err(LLMServiceError)?;
+        // The adapter path reports usage consistently, so plain requests use it when available
+        let no_tools = LLMToolOptions::default();
+        let tools = tools.or_else(|| llm_provider.tool_adapter().map(|_| &no_tools));
+
+        let response = Self::send_with_retries(|| match tools {
+            Some(tools) => llm_provider.prepare_tool_request(
+                messages,
+                &provider.configuration,
+                api_key,
+                tools,
+                false,
+            ),
+            None => llm_provider.prepare_request(messages, &provider.configuration, api_key),
+        })
+        .await?;
+
+        let response_text = response.text().await.map_err(|e| {
+            error!("Failed to get response text: {:?}", e);
+            ProviderFailure::transient(AppError::ExternalServiceError(format!(
+                "Failed to get response text: {}",
+                e
+            )))
         })?;
 
-        if !response.status().is_success() {
-            let error_message = response.text().await.map_err(|e| {
-                error!("Failed to get error message: {:?}", e);
+        let adapter = match (tools, llm_provider.tool_adapter()) {
+            (Some(_), Some(adapter)) => adapter,
+            _ => {
+                let usage = llm_provider.parse_usage(&response_text);
+                return Ok(LLMChatOutput {
+                    content: llm_provider.parse_response(&response_text)?,
+                    usage_stats: Self::usage_stats(provider, usage.as_ref()),
+                    usage,
+                    provider_model: Some(provider.name.clone()),
+                    ..Default::default()
+                });
+            }
+        };
+
+        let response_json: Value = serde_json::from_str(&response_text).map_err(|e| {
+            LLMServiceError(AppError::ExternalServiceError(format!(
+                "Invalid LLM response: {}",
+                e
+            )))
+        })?;
+        let tool_calls = adapter
+            .parse_tool_calls(&response_json)
+            .await
+            .map_err(|e| LLMServiceError(AppError::LLMError(e.to_string())))?;
+
+        let usage = llm_provider.parse_usage(&response_text);
+        Ok(LLMChatOutput {
+            content: adapter.parse_content(&response_json).unwrap_or_default(),
+            tool_calls,
+            usage_stats: Self::usage_stats(provider, usage.as_ref()),
+            usage,
+            provider_model: Some(provider.name.clone()),
+            cached: false,
+        })
+    }
+
+    /// Streams a tool request with an already resolved API key, as text and tool call
+    /// chunks. Transient failures are retried, but there is no failover.
+    pub async fn stream_with_api_key(
+        provider: &LLMProvider,
+        api_key: &str,
+        messages: Vec<LLMChatMessage>,
+        tools: &LLMToolOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<LLMStreamChunk, LLMServiceError>> + Send>>, LLMServiceError>
+    {
+        Self::check_modalities(provider, &messages)?;
+        let llm_provider = llm_providers::get_provider(provider)?;
+        let response = Self::send_with_retries(|| {
+            llm_provider.prepare_tool_request(&messages, &provider.configuration, api_key, tools, true)
+        })
+        .await
+        .map_err(|failure| failure.error)?;
+        Ok(llm_provider.stream_tool_response(response))
+    }
+
+    /// Embeds `inputs` with the config's provider. There is no failover, since vectors from
+    /// different models can't be compared
+    pub async fn embed(
+        pool: &DbPool,
+        provider: &LLMProvider,
+        user_config: &UserLLMConfig,
+        inputs: Vec<String>,
+    ) -> Result<LLMEmbeddingOutput, LLMServiceError> {
+        let api_key = Self::get_api_key(pool, user_config).await?;
+        Self::embed_with_api_key(provider, &api_key, inputs).await
+    }
+
+    /// Embeds `inputs` with an already resolved API key, in batches the provider accepts
+    pub async fn embed_with_api_key(
+        provider: &LLMProvider,
+        api_key: &str,
+        inputs: Vec<String>,
+    ) -> Result<LLMEmbeddingOutput, LLMServiceError> {
+        let llm_provider = llm_providers::get_provider(provider)?;
+        let model = llm_provider
+            .embedding_model(&provider.configuration)
+            .ok_or_else(|| {
+                LLMServiceError(AppError::UnsupportedProviderError(format!(
+                    "Provider {} does not support embeddings",
+                    provider.name
+                )))
+            })?;
+        info!(
+            "Embedding {} inputs with {} model {}",
+            inputs.len(),
+            provider.provider_type,
+            model
+        );
+
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        let mut usage: Option<TokenUsage> = None;
+        for batch in inputs.chunks(llm_provider.embedding_batch_size().max(1)) {
+            let response = Self::send_with_retries(|| {
+                llm_provider.prepare_embedding_request(batch, &provider.configuration, api_key)
+            })
+            .await
+            .map_err(|failure| failure.error)?;
+            let response_text = response.text().await.map_err(|e| {
                 LLMServiceError(AppError::ExternalServiceError(format!(
-                    "Failed to get error message: {}",
+                    "Failed to get response text: {}",
                     e
                 )))
             })?;
-            error!("LLM provider returned an error: {}", error_message);
+
+            let vectors = llm_provider.parse_embeddings(&response_text)?;
+            if vectors.len() != batch.len() {
+                return Err(LLMServiceError(AppError::ExternalServiceError(format!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    vectors.len()
+                ))));
+            }
+            embeddings.extend(vectors);
+
+            if let Some(tokens) = llm_provider
+                .parse_usage(&response_text)
+                .as_ref()
+                .and_then(TokenUsage::from_provider)
+            {
+                usage = Some(usage.map_or(tokens, |usage| usage.add(tokens)));
+            }
+        }
+
+        let dimensions = embeddings.first().map(Vec::len).unwrap_or(0);
+        if embeddings.iter().any(|vector| vector.len() != dimensions) {
             return Err(LLMServiceError(AppError::ExternalServiceError(
-                error_message,
+                "Provider returned embeddings of different dimensions".to_string(),
             )));
         }
 
-        let response_text = response.text().await.map_err(|e| {
-            error!("Failed to get response text: {:?}", e);
-            LLMServiceError(AppError::ExternalServiceError(format!(
-                "Failed to get response text: {}",
-                e
-            )))
-        })?;
+        // Price the embedding model rather than the provider's chat model
+        let mut priced = provider.clone();
+        priced.configuration["model"] = Value::String(model.clone());
+        Ok(LLMEmbeddingOutput {
+            usage_stats: usage.map(|tokens| UsageStats::new(&priced, tokens)),
+            model,
+            dimensions,
+            embeddings,
+        })
+    }
 
-        llm_provider.parse_response(&response_text)
+    /// Normalizes and prices a provider's usage report
+    pub fn usage_stats(provider: &LLMProvider, usage: Option<&Value>) -> Option<UsageStats> {
+        usage
+            .and_then(TokenUsage::from_provider)
+            .map(|tokens| UsageStats::new(provider, tokens))
     }
 
     pub async fn llm_stream_chat(
@@ -105,79 +672,267 @@ impl LLMService {
         provider: Arc<LLMProvider>,
         user_config: Arc<UserLLMConfig>,
         messages: Vec<LLMChatMessage>,
-    ) -> Pin<Box<dyn Stream<Item = Result<String, LLMServiceError>> + Send>> {
+        tools: Option<LLMToolOptions>,
+    ) -> Result<LLMChatStream, LLMServiceError> {
         info!(
             "Starting llm_stream_chat function with provider: {:?}",
             provider.provider_type
         );
 
-        let api_key = match Self::get_api_key(&pool, &user_config).await {
-            Ok(key) => key,
-            Err(e) => return Box::pin(futures::stream::once(async move { Err(e) })),
-        };
+        let messages = Self::resolve_attachments(&pool, user_config.user_id, messages).await?;
+        // Failover is only possible until the first chunk has been handed to the caller
+        let mut last_error = None;
+        for target in Self::fallback_chain(&pool, &provider, &user_config) {
+            if let Err(e) = Self::check_modalities(&target.provider, &messages) {
+                warn!("Skipping provider {}: {}", target.provider_model, e);
+                last_error = Some(e);
+                continue;
+            }
+            let api_key = Self::get_api_key(&pool, &target.user_config).await?;
+            let llm_provider = llm_providers::get_provider(&target.provider)?;
+            let configuration = &target.provider.configuration;
+            let no_tools = LLMToolOptions::default();
+            let tools = tools
+                .as_ref()
+                .or_else(|| llm_provider.tool_adapter().map(|_| &no_tools));
 
-        let llm_provider = llm_providers::get_provider(&provider.provider_type);
+            info!("Sending request to LLM provider {}", target.provider_model);
+            let response = Self::send_with_retries(|| match tools {
+                Some(tools) => llm_provider.prepare_tool_request(
+                    &messages,
+                    configuration,
+                    &api_key,
+                    tools,
+                    true,
+                ),
+                None => llm_provider.prepare_request(&messages, configuration, &api_key),
+            })
+            .await;
 
-        let client = Client::new();
-        let request =
-            match llm_provider.prepare_request(&messages, &provider.configuration, &api_key) {
-                Ok(req) => req,
-                Err(e) => {
-                    error!("Failed to prepare request: {:?}", e);
-                    return Box::pin(futures::stream::once(async move { Err(e) }));
+            match response {
+                Ok(response) => {
+                    let chunks = if tools.is_some() {
+                        info!("Streaming tool response from LLM provider");
+                        llm_provider.stream_tool_response(response)
+                    } else {
+                        info!("Streaming response from LLM provider");
+                        Box::pin(
+                            llm_provider
+                                .stream_response(response)
+                                .map(|chunk| chunk.map(LLMStreamChunk::Text)),
+                        )
+                    };
+                    return Ok(LLMChatStream {
+                        provider_model: target.provider_model,
+                        provider: target.provider,
+                        chunks,
+                    });
                 }
+                Err(failure) if failure.retryable => {
+                    warn!(
+                        "Provider {} unavailable, falling over: {}",
+                        target.provider_model, failure.error
+                    );
+                    last_error = Some(failure.error);
+                }
+                Err(failure) => return Err(failure.error),
+            }
+        }
+
+        Err(chain_exhausted(last_error))
+    }
+
+    /// Replaces attachment sources with their base64 content, checking the user owns them
+    pub async fn resolve_attachments(
+        pool: &DbPool,
+        user_id: Uuid,
+        mut messages: Vec<LLMChatMessage>,
+    ) -> Result<Vec<LLMChatMessage>, LLMServiceError> {
+        for part in messages.iter_mut().flat_map(|message| message.parts.iter_mut()) {
+            let source = match part {
+                ContentPart::Image { source } | ContentPart::Document { source, .. } => source,
+                ContentPart::Text { .. } => continue,
             };
+            if let MediaSource::Attachment { attachment_id } = source {
+                let (media_type, bytes) =
+                    AttachmentService::read_for_user(pool, user_id, *attachment_id).await?;
+                debug!("Inlining attachment {} ({}, {} bytes)", attachment_id, media_type, bytes.len());
+                *source = MediaSource::Base64 {
+                    media_type,
+                    data: base64::encode(&bytes),
+                };
+            }
+        }
+        Ok(messages)
+    }
 
-        info!("Sending request to LLM provider");
-        Box::pin(
-            futures::stream::once(async move { request.send().await })
-                .map(move |result| match result {
-                    Ok(response) => {
-                        if !response.status().is_success() {
-                            let status = response.status();
-                            let error_future = async move {
-                                let error_body = response
-                                    .text()
-                                    .await
-                                    .unwrap_or_else(|e| format!("Failed to get error body: {}", e));
-                                error!(
-                                    "LLM API error: Status {} {}, Body: {}",
-                                    status.as_u16(),
-                                    status.as_str(),
-                                    error_body
-                                );
-                                Err(LLMServiceError(AppError::ExternalServiceError(format!(
-                                    "LLM API error: Status {} {}, Body: {}",
-                                    status.as_u16(),
-                                    status.as_str(),
-                                    error_body
-                                ))))
-                            };
-                            Box::pin(futures::stream::once(error_future))
-                                as Pin<
-                                    Box<dyn Stream<Item = Result<String, LLMServiceError>> + Send>,
-                                >
-                        } else {
-                            info!("Streaming response from LLM provider");
-                            llm_provider.stream_response(response)
-                        }
+    /// Rejects images and documents the provider's `supported_modalities` don't include
+    pub fn check_modalities(
+        provider: &LLMProvider,
+        messages: &[LLMChatMessage],
+    ) -> Result<(), LLMServiceError> {
+        let supported: Vec<&str> = provider
+            .supported_modalities
+            .as_array()
+            .map(|modalities| modalities.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        for message in messages.iter().filter(|message| !message.parts.is_empty()) {
+            if message.role != "user" {
+                return Err(LLMServiceError(AppError::BadRequest(format!(
+                    "Only user messages can have content parts, not {} messages",
+                    message.role
+                ))));
+            }
+            for part in &message.parts {
+                let modality = part.modality();
+                if modality != "text" && !supported.contains(&modality) {
+                    return Err(LLMServiceError(AppError::UnsupportedProviderError(format!(
+                        "Provider {} does not accept {} input; add \"{}\" to its supported_modalities if the model does",
+                        provider.name,
+                        if modality == "vision" { "image" } else { "document" },
+                        modality
+                    ))));
+                }
+                if let ContentPart::Document {
+                    source: MediaSource::Url { .. },
+                    ..
+                } = part
+                {
+                    if !DOCUMENT_URL_PROVIDERS.contains(&provider.provider_type.as_str()) {
+                        return Err(LLMServiceError(AppError::BadRequest(format!(
+                            "Provider {} can't fetch documents from a URL; upload the document as an attachment or send it as base64",
+                            provider.name
+                        ))));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The config's own provider followed by the fallbacks that can still be loaded
+    fn fallback_chain(
+        pool: &DbPool,
+        provider: &LLMProvider,
+        user_config: &UserLLMConfig,
+    ) -> Vec<ChainTarget> {
+        let mut chain = vec![ChainTarget {
+            provider: provider.clone(),
+            user_config: user_config.clone(),
+            provider_model: provider.name.clone(),
+        }];
+
+        for fallback in user_config.fallback_chain() {
+            let fallback_config =
+                match LLMProviderService::get_user_llm_config(pool, fallback.user_llm_config_id) {
+                    Ok(Some(config)) if config.user_id == user_config.user_id => config,
+                    Ok(_) => {
+                        warn!("Skipping missing fallback config {}", fallback.user_llm_config_id);
+                        continue;
                     }
                     Err(e) => {
-                        error!("Request error: {:?}", e);
-                        Box::pin(futures::stream::once(async move {
-                            Err(LLMServiceError(AppError::ExternalServiceError(format!(
-                                "Request error: {}",
-                                e
-                            ))))
-                        }))
-                            as Pin<Box<dyn Stream<Item = Result<String, LLMServiceError>> + Send>>
+                        warn!("Failed to load fallback config {}: {}", fallback.user_llm_config_id, e);
+                        continue;
                     }
-                })
-                .flatten(),
-        )
+                };
+            let mut fallback_provider =
+                match LLMProviderService::get_llm_provider(pool, fallback_config.provider_id) {
+                    Ok(provider) => provider,
+                    Err(e) => {
+                        warn!("Failed to load fallback provider {}: {}", fallback_config.provider_id, e);
+                        continue;
+                    }
+                };
+
+            let provider_model = match (&fallback.model, fallback_provider.configuration.as_object_mut()) {
+                (Some(model), Some(configuration)) => {
+                    configuration.insert("model".to_string(), Value::String(model.clone()));
+                    format!("{}/{}", fallback_provider.name, model)
+                }
+                _ => fallback_provider.name.clone(),
+            };
+
+            chain.push(ChainTarget {
+                provider: fallback_provider,
+                user_config: fallback_config,
+                provider_model,
+            });
+        }
+
+        chain
+    }
+
+    /// Sends the request built by `prepare`, retrying transient failures with exponential backoff
+    async fn send_with_retries<F>(prepare: F) -> Result<reqwest::Response, ProviderFailure>
+    where
+        F: Fn() -> Result<RequestBuilder, LLMServiceError>,
+    {
+        let mut attempt = 1;
+        loop {
+            match Self::send(prepare()?).await {
+                Err(failure) if failure.retryable && attempt < MAX_ATTEMPTS => {
+                    let delay = retry_delay(attempt, failure.retry_after);
+                    warn!(
+                        "LLM request failed (attempt {}/{}), retrying in {:?}: {}",
+                        attempt, MAX_ATTEMPTS, delay, failure.error
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send(request: RequestBuilder) -> Result<reqwest::Response, ProviderFailure> {
+        let response = request.send().await.map_err(|e| {
+            error!("Failed to send request: {:?}", e);
+            ProviderFailure {
+                retryable: e.is_timeout() || e.is_connect(),
+                retry_after: None,
+                error: LLMServiceError(AppError::ExternalServiceError(format!(
+                    "Request error: {}",
+                    e
+                ))),
+            }
+        })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let error_body = response
+            .text()
+            .await
+            .unwrap_or_else(|e| format!("Failed to get error body: {}", e));
+        error!(
+            "LLM API error: Status {} {}, Body: {}",
+            status.as_u16(),
+            status.as_str(),
+            error_body
+        );
+
+        Err(ProviderFailure {
+            retryable: status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+            retry_after,
+            error: LLMServiceError(AppError::ExternalServiceError(format!(
+                "LLM API error: Status {} {}, Body: {}",
+                status.as_u16(),
+                status.as_str(),
+                error_body
+            ))),
+        })
     }
 
-    async fn get_api_key(
+    pub async fn get_api_key(
         pool: &DbPool,
         user_config: &UserLLMConfig,
     ) -> Result<String, LLMServiceError> {
@@ -199,5 +954,103 @@ impl LLMService {
 
         Ok(api_key.key_value)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(modalities: Value) -> LLMProvider {
+        let now = chrono::Utc::now().naive_utc();
+        LLMProvider {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            name: "work".to_string(),
+            provider_type: "gpt".to_string(),
+            api_endpoint: String::new(),
+            supported_modalities: modalities,
+            configuration: serde_json::json!({ "model": "gpt-4o" }),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn image_message(role: &str) -> LLMChatMessage {
+        LLMChatMessage {
+            role: role.to_string(),
+            content: "What is in this picture?".to_string(),
+            parts: vec![ContentPart::Image {
+                source: MediaSource::Url {
+                    url: "https://example.com/cat.png".to_string(),
+                },
+            }],
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
 
+    #[test]
+    fn content_parts_need_a_supported_modality() {
+        let messages = vec![image_message("user")];
+        assert!(LLMService::check_modalities(&provider(serde_json::json!(["text", "vision"])), &messages).is_ok());
+
+        let error = LLMService::check_modalities(&provider(serde_json::json!(["text"])), &messages).unwrap_err();
+        assert!(matches!(error.0, AppError::UnsupportedProviderError(ref message) if message.contains("image input")));
+
+        let error = LLMService::check_modalities(
+            &provider(serde_json::json!(["text", "vision"])),
+            &[image_message("assistant")],
+        )
+        .unwrap_err();
+        assert!(matches!(error.0, AppError::BadRequest(_)));
+    }
+
+    #[test]
+    fn document_urls_need_a_provider_that_fetches_them() {
+        let messages = vec![LLMChatMessage {
+            role: "user".to_string(),
+            content: "Summarize this report".to_string(),
+            parts: vec![ContentPart::Document {
+                source: MediaSource::Url {
+                    url: "https://example.com/report.pdf".to_string(),
+                },
+                name: Some("report.pdf".to_string()),
+            }],
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }];
+
+        let openai = provider(serde_json::json!(["text", "document"]));
+        let error = LLMService::check_modalities(&openai, &messages).unwrap_err();
+        assert!(matches!(error.0, AppError::BadRequest(ref message) if message.contains("URL")));
+
+        let mut claude = provider(serde_json::json!(["text", "document"]));
+        claude.provider_type = "claude".to_string();
+        assert!(LLMService::check_modalities(&claude, &messages).is_ok());
+
+        let mut inline = messages;
+        inline[0].parts[0] = ContentPart::Document {
+            source: MediaSource::Base64 {
+                media_type: "application/pdf".to_string(),
+                data: "JVBERi0=".to_string(),
+            },
+            name: None,
+        };
+        assert!(LLMService::check_modalities(&openai, &inline).is_ok());
+    }
+
+    #[test]
+    fn content_parts_survive_conversion_to_adapter_messages() {
+        let message = image_message("user");
+        let converted = LLMChatMessage::from(&Message::from(&message));
+        assert_eq!(converted.parts, message.parts);
+    }
+
+    #[test]
+    fn retry_delay_backs_off_and_honours_retry_after() {
+        assert_eq!(retry_delay(1, None), Duration::from_millis(500));
+        assert_eq!(retry_delay(3, None), Duration::from_secs(2));
+        assert_eq!(retry_delay(1, Some(Duration::from_secs(3))), Duration::from_secs(3));
+        assert_eq!(retry_delay(1, Some(Duration::from_secs(120))), MAX_RETRY_DELAY);
+    }
 }
@@ -3,9 +3,10 @@ use crate::error::AppError;
 use crate::models::api_key::{ApiKey, NewApiKey};
 use crate::models::llm_provider::{LLMProvider, NewLLMProvider};
 use crate::models::llm_template::{LLMTemplate, UnifiedLLMConfig, UnifiedLLMConfigResponse};
-use crate::models::user_llm_config::{NewUserLLMConfig, UserLLMConfig};
+use crate::models::user_llm_config::{LLMFallback, NewUserLLMConfig, UserLLMConfig};
 use crate::services::api_key_service::ApiKeyService;
 use crate::services::llm_provider::LLMProviderService;
+use crate::services::llm_providers::ProviderRegistry;
 use log::{debug, error, info};
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -17,15 +18,12 @@ impl LLMTemplateService {
     /// Get all available LLM templates
     pub fn get_templates() -> Vec<LLMTemplate> {
         vec![
-            // OpenAI GPT-4
-            LLMTemplate {
-                id: "openai-gpt4".to_string(),
-                name: "OpenAI GPT-4".to_string(),
-                description: "OpenAI's most advanced model for text generation and understanding".to_string(),
-                provider_type: "gpt".to_string(),
-                api_endpoint: "https://api.openai.com/v1/chat/completions".to_string(),
-                supported_modalities: vec!["text".to_string()],
-                configuration: json!({
+            Self::template(
+                "openai-gpt4",
+                "OpenAI GPT-4",
+                "OpenAI's most advanced model for text generation and understanding",
+                "gpt",
+                json!({
                     "model": "gpt-4o",
                     "temperature": 0.7,
                     "max_tokens": 1024,
@@ -33,108 +31,111 @@ impl LLMTemplateService {
                     "frequency_penalty": 0,
                     "presence_penalty": 0
                 }),
-                logo_url: "https://upload.wikimedia.org/wikipedia/commons/thumb/0/04/ChatGPT_logo.svg/1024px-ChatGPT_logo.svg.png".to_string(),
-                key_instructions: vec![
-                    "Go to https://platform.openai.com/account/api-keys".to_string(),
-                    "Sign in or create an account".to_string(),
-                    "Click \"Create new secret key\"".to_string(),
-                    "Copy the key (you won't be able to see it again)".to_string(),
+                "https://upload.wikimedia.org/wikipedia/commons/thumb/0/04/ChatGPT_logo.svg/1024px-ChatGPT_logo.svg.png",
+                &[
+                    "Go to https://platform.openai.com/account/api-keys",
+                    "Sign in or create an account",
+                    "Click \"Create new secret key\"",
+                    "Copy the key (you won't be able to see it again)",
                 ],
-                is_default: true,
-            },
-            // Anthropic Claude
-            LLMTemplate {
-                id: "anthropic-claude".to_string(),
-                name: "Anthropic Claude".to_string(),
-                description: "Claude is a family of AI assistants created by Anthropic".to_string(),
-                provider_type: "claude".to_string(),
-                api_endpoint: "https://api.anthropic.com/v1/messages".to_string(),
-                supported_modalities: vec!["text".to_string()],
-                configuration: json!({
+            ),
+            Self::template(
+                "anthropic-claude",
+                "Anthropic Claude",
+                "Claude is a family of AI assistants created by Anthropic",
+                "claude",
+                json!({
                     "model": "claude-3-opus-20240229",
                     "temperature": 0.7,
                     "max_tokens": 1024,
                     "top_p": 1
                 }),
-                logo_url: "https://upload.wikimedia.org/wikipedia/commons/thumb/1/1b/Anthropic_logo.svg/1200px-Anthropic_logo.svg.png".to_string(),
-                key_instructions: vec![
-                    "Go to https://console.anthropic.com/".to_string(),
-                    "Sign in or create an account".to_string(),
-                    "Navigate to API Keys".to_string(),
-                    "Click \"Create Key\"".to_string(),
+                "https://upload.wikimedia.org/wikipedia/commons/thumb/1/1b/Anthropic_logo.svg/1200px-Anthropic_logo.svg.png",
+                &[
+                    "Go to https://console.anthropic.com/",
+                    "Sign in or create an account",
+                    "Navigate to API Keys",
+                    "Click \"Create Key\"",
                 ],
-                is_default: true,
-            },
-            // Perplexity
-            LLMTemplate {
-                id: "perplexity".to_string(),
-                name: "Perplexity".to_string(),
-                description: "Perplexity AI offers online LLMs with real-time information access".to_string(),
-                provider_type: "perplexity".to_string(),
-                api_endpoint: "https://api.perplexity.ai/chat/completions".to_string(),
-                supported_modalities: vec!["text".to_string()],
-                configuration: json!({
+            ),
+            Self::template(
+                "perplexity",
+                "Perplexity",
+                "Perplexity AI offers online LLMs with real-time information access",
+                "perplexity",
+                json!({
                     "model": "llama-3.1-sonar-huge-128k-online",
                     "max_tokens": 1024,
                     "temperature": 0.7
                 }),
-                logo_url: "https://cdn.icon-icons.com/icons2/3914/PNG/512/perplexity_logo_icon_248863.png".to_string(),
-                key_instructions: vec![
-                    "Go to https://www.perplexity.ai/settings/api".to_string(),
-                    "Sign in or create an account".to_string(),
-                    "Generate a new API key".to_string(),
-                    "Copy the key".to_string(),
+                "https://cdn.icon-icons.com/icons2/3914/PNG/512/perplexity_logo_icon_248863.png",
+                &[
+                    "Go to https://www.perplexity.ai/settings/api",
+                    "Sign in or create an account",
+                    "Generate a new API key",
+                    "Copy the key",
                 ],
-                is_default: true,
-            },
-            // Gemini
-            LLMTemplate {
-                id: "gemini".to_string(),
-                name: "Google Gemini".to_string(),
-                description: "Google's most capable AI model for text, code, and multimodal tasks".to_string(),
-                provider_type: "gemini".to_string(),
-                api_endpoint: "https://generativelanguage.googleapis.com/v1beta/models".to_string(),
-                supported_modalities: vec!["text".to_string()],
-                configuration: json!({
+            ),
+            Self::template(
+                "gemini",
+                "Google Gemini",
+                "Google's most capable AI model for text, code, and multimodal tasks",
+                "gemini",
+                json!({
                     "model": "gemini-1.5-pro",
                     "temperature": 0.7,
                     "top_k": 40,
                     "top_p": 0.95,
                     "max_tokens": 1024
                 }),
-                logo_url: "https://storage.googleapis.com/gweb-uniblog-publish-prod/images/gemini_1.max-1000x1000.png".to_string(),
-                key_instructions: vec![
-                    "Go to https://ai.google.dev/".to_string(),
-                    "Sign in with your Google account".to_string(),
-                    "Navigate to API Keys".to_string(),
-                    "Create a new API key".to_string(),
+                "https://storage.googleapis.com/gweb-uniblog-publish-prod/images/gemini_1.max-1000x1000.png",
+                &[
+                    "Go to https://ai.google.dev/",
+                    "Sign in with your Google account",
+                    "Navigate to API Keys",
+                    "Create a new API key",
                 ],
-                is_default: true,
-            },
-            // Mistral
-            LLMTemplate {
-                id: "mistral".to_string(),
-                name: "Mistral AI".to_string(),
-                description: "Mistral AI offers powerful and efficient language models".to_string(),
-                provider_type: "mistral".to_string(),
-                api_endpoint: "https://api.mistral.ai/v1/chat/completions".to_string(),
-                supported_modalities: vec!["text".to_string()],
-                configuration: json!({
+            ),
+            Self::template(
+                "mistral",
+                "Mistral AI",
+                "Mistral AI offers powerful and efficient language models",
+                "mistral",
+                json!({
                     "model": "mistral-large-latest",
                     "temperature": 0.7,
                     "max_tokens": 1024,
                     "top_p": 0.95
                 }),
-                logo_url: "https://mistral.ai/images/logo.svg".to_string(),
-                key_instructions: vec![
-                    "Go to https://console.mistral.ai/".to_string(),
-                    "Sign in or create an account".to_string(),
-                    "Navigate to API Keys".to_string(),
-                    "Create a new API key".to_string(),
+                "https://mistral.ai/images/logo.svg",
+                &[
+                    "Go to https://console.mistral.ai/",
+                    "Sign in or create an account",
+                    "Navigate to API Keys",
+                    "Create a new API key",
                 ],
-                is_default: true,
-            },
+            ),
+            Self::template(
+                "openai-compatible",
+                "OpenAI-Compatible Server",
+                "Self-hosted or third-party servers speaking the OpenAI API, such as Ollama, vLLM, LM Studio, llama.cpp or OpenRouter",
+                "openai_compatible",
+                json!({
+                    "model": "llama3.1",
+                    "temperature": 0.7,
+                    "max_tokens": 1024
+                }),
+                "",
+                &[
+                    "Set the API endpoint to the server's base URL, e.g. http://localhost:11434/v1",
+                    "Set the model to one the server serves",
+                    "Enter the server's API key, or leave it blank if it doesn't require one",
+                ],
+            ),
         ]
+        .into_iter()
+        .flatten()
+        .collect()
     }
 
     /// Get a template by ID
@@ -151,6 +152,9 @@ impl LLMTemplateService {
         config: UnifiedLLMConfig,
     ) -> Result<UnifiedLLMConfigResponse, AppError> {
         info!("Creating unified LLM configuration for user: {}", user_id);
+
+        ProviderRegistry::global().get_type(&config.provider_type)?;
+        Self::validate_fallbacks(pool, user_id, None, &config.fallbacks)?;
         
         // Create API key
         let api_key = Self::create_api_key(pool, user_id, &config)?;
@@ -167,6 +171,7 @@ impl LLMTemplateService {
             provider.id,
             api_key.id,
             config.name.clone(),
+            &config.fallbacks,
         )?;
         debug!("Created user LLM config with ID: {}", user_config.id);
         
@@ -180,6 +185,7 @@ impl LLMTemplateService {
             configuration: config.configuration,
             api_key_id: api_key.id,
             api_key_description: api_key.description.unwrap_or_default(),
+            fallbacks: config.fallbacks,
             created_at: user_config.created_at.and_utc(),
             updated_at: user_config.updated_at.and_utc(),
         })
@@ -200,36 +206,46 @@ impl LLMTemplateService {
         let mut result = Vec::new();
         
         for user_config in user_configs {
-            // Get provider
-            let provider = LLMProviderService::get_llm_provider(pool, user_config.provider_id)?;
-            
-            // Get API key
-            let api_key = match ApiKeyService::get_api_key_by_id(pool, user_config.api_key_id)? {
-                Some(key) => key,
-                None => {
-                    error!("API key not found for user config: {}", user_config.id);
-                    continue;
-                }
-            };
-            
-            // Create unified response
-            result.push(UnifiedLLMConfigResponse {
-                id: user_config.id,
-                name: user_config.description.unwrap_or_else(|| provider.name.clone()),
-                provider_type: provider.provider_type,
-                api_endpoint: provider.api_endpoint,
-                supported_modalities: Self::json_to_vec_string(&provider.supported_modalities),
-                configuration: provider.configuration,
-                api_key_id: api_key.id,
-                api_key_description: api_key.description.unwrap_or_default(),
-                created_at: user_config.created_at.and_utc(),
-                updated_at: user_config.updated_at.and_utc(),
-            });
+            if let Some(response) = Self::to_response(pool, user_config)? {
+                result.push(response);
+            }
         }
         
         Ok(result)
     }
 
+    /// Replace the fallback chain of a unified LLM configuration
+    pub fn update_fallbacks(
+        pool: &DbPool,
+        user_id: Uuid,
+        config_id: Uuid,
+        fallbacks: Vec<LLMFallback>,
+    ) -> Result<UnifiedLLMConfigResponse, AppError> {
+        info!("Updating fallbacks of unified LLM configuration: {}", config_id);
+
+        let user_config = match LLMProviderService::get_user_llm_config(pool, config_id)? {
+            Some(config) if config.user_id == user_id => config,
+            _ => return Err(AppError::NotFoundError("User LLM config not found".to_string())),
+        };
+
+        Self::validate_fallbacks(pool, user_id, Some(config_id), &fallbacks)?;
+
+        let user_config = LLMProviderService::update_user_llm_config(
+            pool,
+            config_id,
+            NewUserLLMConfig {
+                user_id,
+                provider_id: user_config.provider_id,
+                api_key_id: user_config.api_key_id,
+                description: user_config.description,
+                fallbacks: Some(json!(fallbacks)),
+            },
+        )?;
+
+        Self::to_response(pool, user_config)?
+            .ok_or_else(|| AppError::NotFoundError("API key not found".to_string()))
+    }
+
     /// Delete a unified LLM configuration
     pub fn delete_unified_config(
         pool: &DbPool,
@@ -268,6 +284,96 @@ impl LLMTemplateService {
     }
 
     // Helper methods
+    /// Builds a template whose endpoint and modalities come from the provider registry
+    fn template(
+        id: &str,
+        name: &str,
+        description: &str,
+        provider_type: &str,
+        configuration: Value,
+        logo_url: &str,
+        key_instructions: &[&str],
+    ) -> Option<LLMTemplate> {
+        let registered = match ProviderRegistry::global().get_type(provider_type) {
+            Ok(registered) => registered,
+            Err(e) => {
+                error!("Skipping template {}: {}", id, e);
+                return None;
+            }
+        };
+
+        Some(LLMTemplate {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            provider_type: provider_type.to_string(),
+            api_endpoint: registered.default_endpoint,
+            supported_modalities: registered.capabilities.modalities(),
+            configuration,
+            logo_url: logo_url.to_string(),
+            key_instructions: key_instructions.iter().map(|s| s.to_string()).collect(),
+            is_default: true,
+        })
+    }
+
+    fn to_response(
+        pool: &DbPool,
+        user_config: UserLLMConfig,
+    ) -> Result<Option<UnifiedLLMConfigResponse>, AppError> {
+        // Get provider
+        let provider = LLMProviderService::get_llm_provider(pool, user_config.provider_id)?;
+
+        // Get API key
+        let api_key = match ApiKeyService::get_api_key_by_id(pool, user_config.api_key_id)? {
+            Some(key) => key,
+            None => {
+                error!("API key not found for user config: {}", user_config.id);
+                return Ok(None);
+            }
+        };
+
+        let fallbacks = user_config.fallback_chain();
+        Ok(Some(UnifiedLLMConfigResponse {
+            id: user_config.id,
+            name: user_config.description.unwrap_or_else(|| provider.name.clone()),
+            provider_type: provider.provider_type,
+            api_endpoint: provider.api_endpoint,
+            supported_modalities: Self::json_to_vec_string(&provider.supported_modalities),
+            configuration: provider.configuration,
+            api_key_id: api_key.id,
+            api_key_description: api_key.description.unwrap_or_default(),
+            fallbacks,
+            created_at: user_config.created_at.and_utc(),
+            updated_at: user_config.updated_at.and_utc(),
+        }))
+    }
+
+    /// Fallbacks must be other configs owned by the same user
+    fn validate_fallbacks(
+        pool: &DbPool,
+        user_id: Uuid,
+        config_id: Option<Uuid>,
+        fallbacks: &[LLMFallback],
+    ) -> Result<(), AppError> {
+        for fallback in fallbacks {
+            if Some(fallback.user_llm_config_id) == config_id {
+                return Err(AppError::BadRequest(
+                    "A configuration cannot fall back to itself".to_string(),
+                ));
+            }
+            match LLMProviderService::get_user_llm_config(pool, fallback.user_llm_config_id)? {
+                Some(config) if config.user_id == user_id => {}
+                _ => {
+                    return Err(AppError::BadRequest(format!(
+                        "Fallback configuration {} not found",
+                        fallback.user_llm_config_id
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn create_api_key(
         pool: &DbPool,
         user_id: Uuid,
@@ -305,12 +411,14 @@ impl LLMTemplateService {
         provider_id: Uuid,
         api_key_id: Uuid,
         description: String,
+        fallbacks: &[LLMFallback],
     ) -> Result<UserLLMConfig, AppError> {
         let new_config = NewUserLLMConfig {
             user_id,
             provider_id,
             api_key_id,
             description: Some(description),
+            fallbacks: Some(json!(fallbacks)),
         };
         
         LLMProviderService::create_user_llm_config(pool, new_config)
@@ -0,0 +1,481 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::mcp_server::{
+    CreateMcpServerRequest, McpServer, NewMcpServer, UpdateMcpServerRequest,
+};
+use crate::schema::mcp_servers;
+use crate::services::function_calling::mcp::{register_mcp_tools, McpClient, McpToolDefinition};
+use crate::services::function_calling::ToolRegistry;
+use crate::utils::url_guard;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use lazy_static::lazy_static;
+use log::{debug, error, info, warn};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// A live connection to a server along with the tools it advertised
+struct McpConnection {
+    updated_at: DateTime<Utc>,
+    client: Arc<McpClient>,
+    tools: Vec<McpToolDefinition>,
+}
+
+lazy_static! {
+    // Connections are reused across requests so stdio servers aren't respawned every time
+    static ref MCP_CONNECTIONS: Mutex<HashMap<Uuid, McpConnection>> = Mutex::new(HashMap::new());
+}
+
+/// JSON object mapping the name of a stdio server users may pick to how it's started
+const STDIO_SERVERS_ENV: &str = "MCP_STDIO_SERVERS";
+
+/// A stdio server the operator allows users to run. Users only choose one by name; the command,
+/// arguments and environment always come from here, never from the request.
+#[derive(Deserialize, Debug, Clone)]
+pub struct StdioServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+/// The stdio servers configured in `MCP_STDIO_SERVERS`. Unset means users can't add any.
+pub fn stdio_servers() -> HashMap<String, StdioServerConfig> {
+    match std::env::var(STDIO_SERVERS_ENV) {
+        Ok(value) => parse_stdio_servers(&value),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn parse_stdio_servers(value: &str) -> HashMap<String, StdioServerConfig> {
+    serde_json::from_str(value).unwrap_or_else(|e| {
+        warn!("Ignoring invalid {}: {}", STDIO_SERVERS_ENV, e);
+        HashMap::new()
+    })
+}
+
+/// Comma-separated URLs of internal http servers users may add
+const INTERNAL_HTTP_SERVERS_ENV: &str = "MCP_INTERNAL_HTTP_SERVERS";
+
+/// The http servers the operator allows in `MCP_INTERNAL_HTTP_SERVERS`, normalized. Users can
+/// add these even though they aren't public; any other URL has to be.
+pub fn internal_http_servers() -> HashSet<String> {
+    match std::env::var(INTERNAL_HTTP_SERVERS_ENV) {
+        Ok(value) => parse_http_servers(&value),
+        Err(_) => HashSet::new(),
+    }
+}
+
+fn parse_http_servers(value: &str) -> HashSet<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .filter_map(|url| match Url::parse(url) {
+            Ok(url) => Some(url.to_string()),
+            Err(e) => {
+                warn!("Ignoring invalid URL '{}' in {}: {}", url, INTERNAL_HTTP_SERVERS_ENV, e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn is_internal_server(url: &str, internal_servers: &HashSet<String>) -> bool {
+    Url::parse(url.trim()).map_or(false, |url| internal_servers.contains(url.as_str()))
+}
+
+pub struct McpServerService;
+
+impl McpServerService {
+    pub fn create_server(
+        pool: &DbPool,
+        user_id: Uuid,
+        request: CreateMcpServerRequest,
+    ) -> Result<McpServer, AppError> {
+        Self::validate(
+            &request.name,
+            &request.transport,
+            request.command.as_deref(),
+            !request.args.is_empty() || !request.env.is_empty(),
+            request.url.as_deref(),
+            &stdio_servers(),
+            &internal_http_servers(),
+        )?;
+
+        let conn = &mut pool.get()?;
+        let new_server = NewMcpServer::from_request(user_id, request);
+
+        let server = diesel::insert_into(mcp_servers::table)
+            .values(&new_server)
+            .get_result::<McpServer>(conn)?;
+
+        info!("Created MCP server {} for user {}", server.id, user_id);
+        Ok(server)
+    }
+
+    pub fn get_server(pool: &DbPool, id: Uuid, user_id: Uuid) -> Result<McpServer, AppError> {
+        let conn = &mut pool.get()?;
+
+        mcp_servers::table
+            .filter(mcp_servers::id.eq(id))
+            .filter(mcp_servers::user_id.eq(user_id))
+            .first::<McpServer>(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    AppError::NotFoundError(format!("MCP server not found: {}", id))
+                } else {
+                    AppError::DatabaseError(e)
+                }
+            })
+    }
+
+    pub fn list_servers(pool: &DbPool, user_id: Uuid) -> Result<Vec<McpServer>, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Listing MCP servers for user {}", user_id);
+
+        mcp_servers::table
+            .filter(mcp_servers::user_id.eq(user_id))
+            .order(mcp_servers::name.asc())
+            .load::<McpServer>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn update_server(
+        pool: &DbPool,
+        id: Uuid,
+        user_id: Uuid,
+        request: UpdateMcpServerRequest,
+    ) -> Result<McpServer, AppError> {
+        let existing = Self::get_server(pool, id, user_id)?;
+
+        let name = request.name.unwrap_or(existing.name);
+        let transport = request.transport.unwrap_or(existing.transport);
+        let command = request.command.or(existing.command);
+        let url = request.url.or(existing.url);
+        let args = request.args.map(|args| json!(args)).unwrap_or(existing.args);
+        let env = request.env.map(|env| json!(env)).unwrap_or(existing.env);
+        let has_process_options = !args.as_array().map_or(true, |a| a.is_empty())
+            || !env.as_object().map_or(true, |e| e.is_empty());
+        Self::validate(
+            &name,
+            &transport,
+            command.as_deref(),
+            has_process_options,
+            url.as_deref(),
+            &stdio_servers(),
+            &internal_http_servers(),
+        )?;
+
+        let conn = &mut pool.get()?;
+        let server = diesel::update(mcp_servers::table.find(id))
+            .set((
+                mcp_servers::name.eq(name),
+                mcp_servers::transport.eq(transport),
+                mcp_servers::command.eq(command),
+                mcp_servers::args.eq(args),
+                mcp_servers::env.eq(env),
+                mcp_servers::url.eq(url),
+                mcp_servers::headers.eq(request.headers.map(|headers| json!(headers)).unwrap_or(existing.headers)),
+                mcp_servers::enabled.eq(request.enabled.unwrap_or(existing.enabled)),
+                mcp_servers::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<McpServer>(conn)?;
+
+        info!("Updated MCP server {}", id);
+        Ok(server)
+    }
+
+    pub fn delete_server(pool: &DbPool, id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+        let conn = &mut pool.get()?;
+
+        let count = diesel::delete(mcp_servers::table)
+            .filter(mcp_servers::id.eq(id))
+            .filter(mcp_servers::user_id.eq(user_id))
+            .execute(conn)?;
+
+        if count == 0 {
+            error!("MCP server not found for deletion: {}", id);
+            return Err(AppError::NotFoundError(format!("MCP server not found: {}", id)));
+        }
+
+        info!("Deleted MCP server {}", id);
+        Ok(())
+    }
+
+    /// An http server's URL has to be public unless it's one of the operator's
+    /// `internal_servers`. A stdio server runs a process on this host, so its `command` has to
+    /// name one of the operator's `stdio_servers` and it can't bring its own `args` or `env`.
+    fn validate(
+        name: &str,
+        transport: &str,
+        command: Option<&str>,
+        has_process_options: bool,
+        url: Option<&str>,
+        stdio_servers: &HashMap<String, StdioServerConfig>,
+        internal_servers: &HashSet<String>,
+    ) -> Result<(), AppError> {
+        // The name prefixes every tool from the server, so it must be valid in a tool name
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(AppError::BadRequest(
+                "MCP server name may only contain letters, digits, '_' and '-'".to_string(),
+            ));
+        }
+
+        match transport {
+            "stdio" => {
+                let command = command.map(str::trim).unwrap_or_default();
+                if !stdio_servers.contains_key(command) {
+                    let mut available: Vec<&str> = stdio_servers.keys().map(String::as_str).collect();
+                    available.sort_unstable();
+                    return Err(AppError::BadRequest(format!(
+                        "stdio MCP servers must name a server configured by the operator (available: {})",
+                        if available.is_empty() { "none".to_string() } else { available.join(", ") }
+                    )));
+                }
+                if has_process_options {
+                    return Err(AppError::BadRequest(
+                        "stdio MCP servers can't set args or env; they come from the operator's configuration".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+            "http" if url.map_or(true, |u| u.trim().is_empty()) => Err(AppError::BadRequest(
+                "http MCP servers require a url".to_string(),
+            )),
+            "http" => {
+                let url = url.unwrap_or_default().trim();
+                if is_internal_server(url, internal_servers) {
+                    return Ok(());
+                }
+                let parsed = url_guard::parse_public_url(url).map_err(|e| {
+                    AppError::BadRequest(format!(
+                        "{}; internal MCP servers must be allowed by the operator",
+                        e
+                    ))
+                })?;
+                url_guard::check_resolved(&parsed).map_err(AppError::BadRequest)
+            }
+            other => Err(AppError::BadRequest(format!(
+                "Unsupported MCP transport: {}",
+                other
+            ))),
+        }
+    }
+
+    /// Returns a connection to the server and its tools, reconnecting if the server was edited
+    pub async fn connect(
+        server: &McpServer,
+        refresh: bool,
+    ) -> Result<(Arc<McpClient>, Vec<McpToolDefinition>), AppError> {
+        if !refresh {
+            let connections = MCP_CONNECTIONS.lock().await;
+            if let Some(connection) = connections.get(&server.id) {
+                if connection.updated_at == server.updated_at {
+                    return Ok((connection.client.clone(), connection.tools.clone()));
+                }
+            }
+        }
+
+        info!("Connecting to MCP server {} over {}", server.name, server.transport);
+        let to_app_error = |e: crate::services::function_calling::ToolError| {
+            AppError::ExternalServiceError(format!("MCP server '{}': {}", server.name, e))
+        };
+
+        let client = match server.transport.as_str() {
+            // Looked up again on every connect, so a server the operator has since removed from
+            // the allow-list, or a row stored before it existed, can't start a process
+            "stdio" => {
+                let name = server.command.as_deref().unwrap_or_default().trim();
+                let config = stdio_servers().remove(name).ok_or_else(|| {
+                    AppError::Forbidden(format!(
+                        "MCP server '{}': stdio server '{}' isn't configured by the operator",
+                        server.name, name
+                    ))
+                })?;
+                McpClient::connect_stdio(&config.command, &config.args, &config.env).await
+            }
+            // Checked again on every connect too, since the host may resolve differently by now
+            _ => {
+                let url = server.url.as_deref().unwrap_or_default();
+                let client = Self::http_client(url).await.map_err(|e| {
+                    AppError::Forbidden(format!("MCP server '{}': {}", server.name, e))
+                })?;
+                McpClient::connect_http(client, url, server.header_map()).await
+            }
+        }
+        .map_err(to_app_error)?;
+
+        let client = Arc::new(client);
+        let tools = client.list_tools().await.map_err(to_app_error)?;
+
+        MCP_CONNECTIONS.lock().await.insert(
+            server.id,
+            McpConnection {
+                updated_at: server.updated_at,
+                client: client.clone(),
+                tools: tools.clone(),
+            },
+        );
+
+        Ok((client, tools))
+    }
+
+    /// A client for an http server that doesn't follow redirects. Unless the operator allows the
+    /// URL as an internal server it has to be public, and the addresses its host resolves to are
+    /// pinned so it can't resolve to another one for later requests.
+    async fn http_client(url: &str) -> Result<Client, String> {
+        let builder = Client::builder().redirect(Policy::none());
+        let builder = if is_internal_server(url, &internal_http_servers()) {
+            builder
+        } else {
+            let url = url_guard::parse_public_url(url.trim())?;
+            let addrs = url_guard::resolve_public(&url).await?;
+            builder.resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs)
+        };
+        builder.build().map_err(|e| e.to_string())
+    }
+
+    /// Drops the cached connection, which stops a stdio server process
+    pub async fn disconnect(server_id: Uuid) {
+        if MCP_CONNECTIONS.lock().await.remove(&server_id).is_some() {
+            debug!("Closed MCP connection {}", server_id);
+        }
+    }
+
+    /// Registers the tools of every enabled server the user has configured
+    pub async fn register_user_tools(
+        servers: &[McpServer],
+        registry: &ToolRegistry,
+    ) -> Vec<String> {
+        let mut names = Vec::new();
+
+        for server in servers.iter().filter(|server| server.enabled) {
+            // One unreachable server shouldn't take every other tool down with it
+            match Self::connect(server, false).await {
+                Ok((client, tools)) => {
+                    names.extend(register_mcp_tools(registry, client, &server.name, &tools).await);
+                }
+                Err(e) => warn!("Skipping MCP server {}: {}", server.name, e),
+            }
+        }
+
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow_list() -> HashMap<String, StdioServerConfig> {
+        parse_stdio_servers(
+            r#"{ "files": { "command": "npx", "args": ["-y", "@modelcontextprotocol/server-filesystem", "/srv/shared"] } }"#,
+        )
+    }
+
+    fn validate_stdio(command: Option<&str>, has_process_options: bool) -> Result<(), AppError> {
+        McpServerService::validate(
+            "files",
+            "stdio",
+            command,
+            has_process_options,
+            None,
+            &allow_list(),
+            &HashSet::new(),
+        )
+    }
+
+    fn validate_http(url: &str, internal_servers: &HashSet<String>) -> Result<(), AppError> {
+        McpServerService::validate(
+            "api",
+            "http",
+            None,
+            false,
+            Some(url),
+            &HashMap::new(),
+            internal_servers,
+        )
+    }
+
+    #[test]
+    fn stdio_server_must_be_on_the_allow_list() {
+        assert!(validate_stdio(Some("files"), false).is_ok());
+
+        for command in ["/bin/sh", "npx", "", "FILES"] {
+            let result = validate_stdio(Some(command), false);
+            assert!(
+                matches!(result, Err(AppError::BadRequest(_))),
+                "{} was accepted",
+                command
+            );
+        }
+        assert!(validate_stdio(None, false).is_err());
+        assert!(McpServerService::validate(
+            "x",
+            "stdio",
+            Some("files"),
+            false,
+            None,
+            &HashMap::new(),
+            &HashSet::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn stdio_server_cant_bring_its_own_args_or_env() {
+        let result = validate_stdio(Some("files"), true);
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn http_server_requires_a_url() {
+        assert!(validate_http("https://93.184.215.14/mcp", &HashSet::new()).is_ok());
+        assert!(validate_http(" ", &HashSet::new()).is_err());
+        assert!(McpServerService::validate(
+            "api",
+            "ws",
+            None,
+            false,
+            Some("wss://x"),
+            &HashMap::new(),
+            &HashSet::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn http_server_must_be_public_unless_allowed() {
+        for url in [
+            "http://localhost:8080/mcp",
+            "http://127.0.0.1/mcp",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.5:3000/mcp",
+            "file:///etc/passwd",
+        ] {
+            let result = validate_http(url, &HashSet::new());
+            assert!(matches!(result, Err(AppError::BadRequest(_))), "{} was accepted", url);
+        }
+
+        let internal = parse_http_servers("http://10.0.0.5:3000/mcp, not a url,");
+        assert_eq!(internal.len(), 1);
+        assert!(validate_http("http://10.0.0.5:3000/mcp", &internal).is_ok());
+        assert!(validate_http("http://10.0.0.5:3000/other", &internal).is_err());
+    }
+
+    #[test]
+    fn invalid_allow_list_is_ignored() {
+        assert!(parse_stdio_servers("not json").is_empty());
+        assert_eq!(allow_list()["files"].command, "npx");
+        assert!(allow_list()["files"].env.is_empty());
+    }
+}
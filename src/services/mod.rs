@@ -1,3 +1,5 @@
+pub mod agent_run_service;
+pub mod agent_runtime;
 pub mod agent_service;
 pub mod amber_store_service;
 pub mod api_key_service;
@@ -9,17 +11,29 @@ pub mod docker_file_service;
 pub mod fluentcli_service;
 pub mod function_calling;
 pub mod job_service;
+pub mod knowledge;
+pub mod knowledge_service;
 pub mod llm_provider;
 pub mod llm_providers;
 pub mod llm_service;
 pub mod llm_template_service;
+pub mod mcp_server_service;
+pub mod openai_gateway;
 pub mod pipeline_service;
+pub mod rate_limit_service;
 pub mod secure_vault_service;
+pub mod tool_permission_service;
+pub mod usage_service;
 pub mod user_service;
+pub mod user_tool_service;
 pub mod worker_service;
 pub mod job_scheduler;
+pub mod job_queue_service;
 pub mod reasoning_patterns;
+pub mod response_cache;
 
+pub use agent_run_service::AgentRunService;
+pub use agent_runtime::AgentRuntime;
 pub use agent_service::AgentService;
 pub use amber_store_service::AmberStoreService;
 pub use api_key_service::ApiKeyService;
@@ -31,13 +45,21 @@ pub use docker_file_service::DockerFileService;
 pub use fluentcli_service::FluentCLIService;
 pub use function_calling::*;
 pub use job_service::JobService;
+pub use knowledge_service::KnowledgeService;
 pub use llm_provider::LLMProviderService;
 pub use llm_providers::*;
 pub use llm_service::LLMService;
 pub use llm_template_service::LLMTemplateService;
+pub use mcp_server_service::McpServerService;
+pub use openai_gateway::OpenAIGatewayService;
 pub use pipeline_service::PipelineService;
+pub use rate_limit_service::RateLimitService;
 pub use secure_vault_service::SecureVaultService;
+pub use tool_permission_service::ToolPermissionService;
+pub use usage_service::UsageService;
 pub use user_service::UserService;
+pub use user_tool_service::UserToolService;
 pub use worker_service::WorkerService;
 pub use job_scheduler::JobScheduler;
+pub use job_queue_service::JobQueueService;
 pub use reasoning_patterns::*;
@@ -0,0 +1,599 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::llm_provider::LLMProvider;
+use crate::models::user_llm_config::UserLLMConfig;
+use crate::services::chat_service::ChatService;
+use crate::services::function_calling::mcp::tool::schema_to_parameters;
+use crate::services::function_calling::{ContentPart, MediaSource, Tool, ToolCall, ToolChoice};
+use crate::services::llm_service::{LLMChatMessage, LLMChatOutput, LLMToolOptions};
+use crate::services::usage_service::TokenUsage;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Body of `POST /v1/chat/completions`; fields we don't support are ignored
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatCompletionMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub tools: Vec<ChatCompletionTool>,
+    pub tool_choice: Option<Value>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub max_completion_tokens: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionMessage {
+    pub role: String,
+    /// A string or an array of content parts
+    #[serde(default)]
+    pub content: Option<Value>,
+    #[serde(default)]
+    pub tool_calls: Vec<ChatCompletionToolCall>,
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatCompletionToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: ChatCompletionFunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatCompletionFunctionCall {
+    pub name: String,
+    /// JSON-encoded arguments, as OpenAI sends them
+    pub arguments: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionTool {
+    pub function: ChatCompletionFunction,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionFunction {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Value,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+impl From<&ToolCall> for ChatCompletionToolCall {
+    fn from(call: &ToolCall) -> Self {
+        Self {
+            id: call.id.clone(),
+            kind: function_type(),
+            function: ChatCompletionFunctionCall {
+                name: call.name.clone(),
+                arguments: call.arguments.to_string(),
+            },
+        }
+    }
+}
+
+/// One of the caller's LLM configs, exposed as a model
+#[derive(Debug, Clone)]
+pub struct GatewayModel {
+    /// `{provider name}/{model}`, or just the provider name when it has no model configured
+    pub id: String,
+    pub provider: LLMProvider,
+    pub user_config: UserLLMConfig,
+}
+
+impl GatewayModel {
+    fn new(provider: LLMProvider, user_config: UserLLMConfig) -> Self {
+        let id = match provider.configuration["model"].as_str() {
+            Some(model) => format!("{}/{}", provider.name, model),
+            None => provider.name.clone(),
+        };
+        Self {
+            id,
+            provider,
+            user_config,
+        }
+    }
+
+    fn configured_model(&self) -> Option<&str> {
+        self.provider.configuration["model"].as_str()
+    }
+}
+
+pub struct OpenAIGatewayService;
+
+impl OpenAIGatewayService {
+    /// Lists the models the user can call through the gateway
+    pub fn list_models(pool: &DbPool, user_id: Uuid) -> Result<Vec<GatewayModel>, AppError> {
+        ChatService::list_user_llm_configs(pool, user_id)?
+            .into_iter()
+            .map(|config| {
+                let provider = ChatService::get_llm_provider(pool, config.provider_id)?;
+                Ok(GatewayModel::new(provider, config))
+            })
+            .collect()
+    }
+
+    /// Finds the config a request's `model` refers to. Accepts a config id, a listed model
+    /// id, a configured model name, a provider name, or `{provider name}/{any model}`.
+    /// The returned provider has its configured model replaced by the requested one.
+    pub fn resolve_model(pool: &DbPool, user_id: Uuid, model: &str) -> Result<GatewayModel, AppError> {
+        let models = Self::list_models(pool, user_id)?;
+        resolve(models, model).ok_or_else(|| {
+            AppError::NotFoundError(format!("The model '{}' does not exist", model))
+        })
+    }
+}
+
+fn resolve(models: Vec<GatewayModel>, model: &str) -> Option<GatewayModel> {
+    if let Ok(id) = Uuid::parse_str(model) {
+        return models.into_iter().find(|m| m.user_config.id == id);
+    }
+
+    let index = models
+        .iter()
+        .position(|m| m.id == model)
+        .or_else(|| models.iter().position(|m| m.configured_model() == Some(model)))
+        .or_else(|| models.iter().position(|m| m.provider.name == model));
+    if let Some(index) = index {
+        return models.into_iter().nth(index);
+    }
+
+    // `{provider name}/{model}` picks a model the provider wasn't configured with
+    let (provider_name, model_name) = model.split_once('/')?;
+    let mut found = models.into_iter().find(|m| m.provider.name == provider_name)?;
+    debug!("Overriding model of provider {} with {}", provider_name, model_name);
+    found.provider.configuration["model"] = json!(model_name);
+    Some(found)
+}
+
+/// Copies sampling options from the request into the provider configuration
+pub fn apply_request_options(configuration: &mut Value, request: &ChatCompletionRequest) {
+    if !configuration.is_object() {
+        *configuration = json!({});
+    }
+    if let Some(temperature) = request.temperature {
+        configuration["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        configuration["top_p"] = json!(top_p);
+    }
+    if let Some(max_tokens) = request.max_completion_tokens.or(request.max_tokens) {
+        configuration["max_tokens"] = json!(max_tokens);
+    }
+}
+
+/// Converts OpenAI messages into the provider-neutral chat messages
+pub fn to_llm_messages(messages: &[ChatCompletionMessage]) -> Result<Vec<LLMChatMessage>, AppError> {
+    messages
+        .iter()
+        .map(|message| {
+            let role = match message.role.as_str() {
+                "developer" => "system",
+                role @ ("system" | "user" | "assistant" | "tool") => role,
+                other => {
+                    return Err(AppError::BadRequest(format!("Unsupported message role: {}", other)))
+                }
+            };
+
+            let tool_calls = message
+                .tool_calls
+                .iter()
+                .map(|call| ToolCall {
+                    id: call.id.clone(),
+                    name: call.function.name.clone(),
+                    arguments: serde_json::from_str(&call.function.arguments)
+                        .unwrap_or_else(|_| Value::String(call.function.arguments.clone())),
+                })
+                .collect();
+
+            let (content, parts) = message_content(message.content.as_ref())?;
+            Ok(LLMChatMessage {
+                role: role.to_string(),
+                content,
+                parts,
+                tool_calls,
+                tool_call_id: message.tool_call_id.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Splits message content into text and, when images or files are given, content parts
+fn message_content(content: Option<&Value>) -> Result<(String, Vec<ContentPart>), AppError> {
+    let parts = match content {
+        None | Some(Value::Null) => return Ok((String::new(), Vec::new())),
+        Some(Value::String(text)) => return Ok((text.clone(), Vec::new())),
+        Some(Value::Array(parts)) => parts,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!("Invalid message content: {}", other)))
+        }
+    };
+
+    let parts = parts.iter().map(content_part).collect::<Result<Vec<_>, _>>()?;
+    // Text-only content stays plain text so every provider can take it
+    if parts.iter().all(|part| matches!(part, ContentPart::Text { .. })) {
+        let texts: Vec<String> = parts
+            .into_iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text),
+                _ => None,
+            })
+            .collect();
+        return Ok((texts.join("\n"), Vec::new()));
+    }
+    Ok((String::new(), parts))
+}
+
+fn content_part(part: &Value) -> Result<ContentPart, AppError> {
+    match part["type"].as_str() {
+        Some("text") => part["text"]
+            .as_str()
+            .map(|text| ContentPart::Text { text: text.to_string() })
+            .ok_or_else(|| AppError::BadRequest("Text content part without text".to_string())),
+        Some("image_url") => {
+            let url = part["image_url"]["url"]
+                .as_str()
+                .or_else(|| part["image_url"].as_str())
+                .ok_or_else(|| AppError::BadRequest("image_url content part without url".to_string()))?;
+            Ok(ContentPart::Image { source: media_source(url) })
+        }
+        Some("file") => {
+            let data = part["file"]["file_data"].as_str().ok_or_else(|| {
+                AppError::BadRequest("Only file content parts with file_data are supported".to_string())
+            })?;
+            Ok(ContentPart::Document {
+                source: media_source(data),
+                name: part["file"]["filename"].as_str().map(str::to_string),
+            })
+        }
+        kind => Err(AppError::BadRequest(format!(
+            "Unsupported content part type: {}",
+            kind.unwrap_or("unknown")
+        ))),
+    }
+}
+
+/// Splits a `data:` URL into its media type and base64 data; other URLs are passed on
+fn media_source(url: &str) -> MediaSource {
+    url.strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .map(|(media_type, data)| MediaSource::Base64 {
+            media_type: media_type.to_string(),
+            data: data.to_string(),
+        })
+        .unwrap_or_else(|| MediaSource::Url { url: url.to_string() })
+}
+
+/// Converts OpenAI `tools`/`tool_choice` into tool options, if any tools were given
+pub fn to_tool_options(
+    tools: &[ChatCompletionTool],
+    tool_choice: Option<&Value>,
+) -> Result<Option<LLMToolOptions>, AppError> {
+    if tools.is_empty() {
+        return Ok(None);
+    }
+
+    let choice = match tool_choice {
+        None | Some(Value::Null) => ToolChoice::Auto,
+        Some(Value::String(choice)) => match choice.as_str() {
+            "auto" => ToolChoice::Auto,
+            "required" => ToolChoice::Required,
+            "none" => ToolChoice::None,
+            other => return Err(AppError::BadRequest(format!("Invalid tool_choice: {}", other))),
+        },
+        Some(choice) => match choice["function"]["name"].as_str() {
+            Some(name) => ToolChoice::Specific(name.to_string()),
+            None => return Err(AppError::BadRequest(format!("Invalid tool_choice: {}", choice))),
+        },
+    };
+
+    Ok(Some(LLMToolOptions {
+        tools: tools
+            .iter()
+            .map(|tool| {
+                Tool::new(
+                    &tool.function.name,
+                    &tool.function.description,
+                    schema_to_parameters(&tool.function.parameters),
+                )
+            })
+            .collect(),
+        choice,
+    }))
+}
+
+/// Maps provider usage reports onto OpenAI's `usage` object
+pub fn normalize_usage(usage: &Value) -> Option<Value> {
+    let tokens = TokenUsage::from_provider(usage)?;
+    let mut usage = json!({
+        "prompt_tokens": tokens.prompt_tokens,
+        "completion_tokens": tokens.completion_tokens,
+        "total_tokens": tokens.total_tokens,
+    });
+    if tokens.cached_tokens > 0 {
+        usage["prompt_tokens_details"] = json!({ "cached_tokens": tokens.cached_tokens });
+    }
+    Some(usage)
+}
+
+/// Builds a `chat.completion` response body
+pub fn completion_response(id: &str, model: &str, created: i64, output: &LLMChatOutput) -> Value {
+    let tool_calls: Vec<ChatCompletionToolCall> = output.tool_calls.iter().map(Into::into).collect();
+    let mut message = json!({
+        "role": "assistant",
+        "content": if output.content.is_empty() && !tool_calls.is_empty() {
+            Value::Null
+        } else {
+            json!(output.content)
+        },
+    });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = json!(tool_calls);
+    }
+
+    let mut response = json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": created,
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": finish_reason(!tool_calls.is_empty()),
+        }],
+    });
+    if let Some(usage) = output.usage.as_ref().and_then(normalize_usage) {
+        response["usage"] = usage;
+    }
+    response
+}
+
+/// Builds a `chat.completion.chunk` carrying `delta`
+pub fn completion_chunk(
+    id: &str,
+    model: &str,
+    created: i64,
+    delta: Value,
+    finish_reason: Option<&str>,
+) -> Value {
+    json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": created,
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "delta": delta,
+            "finish_reason": finish_reason,
+        }],
+    })
+}
+
+pub fn finish_reason(has_tool_calls: bool) -> &'static str {
+    if has_tool_calls {
+        "tool_calls"
+    } else {
+        "stop"
+    }
+}
+
+/// Builds the `/v1/models` response body
+pub fn models_response(models: &[GatewayModel]) -> Value {
+    json!({
+        "object": "list",
+        "data": models.iter().map(|model| json!({
+            "id": model.id,
+            "object": "model",
+            "created": model.user_config.created_at.and_utc().timestamp(),
+            "owned_by": model.provider.provider_type,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::llm_providers::ProviderRegistry;
+
+    #[test]
+    fn messages_and_tools_are_translated() {
+        let request: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "work/gpt-4o",
+            "messages": [
+                { "role": "developer", "content": "Be brief." },
+                { "role": "user", "content": [{ "type": "text", "text": "Weather in Paris?" }] },
+                { "role": "assistant", "content": null, "tool_calls": [{
+                    "id": "call_1", "type": "function",
+                    "function": { "name": "get_weather", "arguments": "{\"location\":\"Paris\"}" }
+                }]},
+                { "role": "tool", "tool_call_id": "call_1", "content": "{\"temp\":21}" }
+            ],
+            "tools": [{ "type": "function", "function": {
+                "name": "get_weather",
+                "parameters": { "type": "object", "properties": { "location": { "type": "string" } }, "required": ["location"] }
+            }}],
+            "tool_choice": { "type": "function", "function": { "name": "get_weather" } },
+            "stream": true
+        }))
+        .unwrap();
+
+        let messages = to_llm_messages(&request.messages).unwrap();
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[1].content, "Weather in Paris?");
+        assert_eq!(messages[2].tool_calls[0].arguments["location"], "Paris");
+        assert_eq!(messages[3].tool_call_id.as_deref(), Some("call_1"));
+
+        let options = to_tool_options(&request.tools, request.tool_choice.as_ref()).unwrap().unwrap();
+        assert_eq!(options.tools[0].parameters[0].name, "location");
+        assert!(options.tools[0].parameters[0].required);
+        assert!(matches!(options.choice, ToolChoice::Specific(ref name) if name == "get_weather"));
+    }
+
+    #[test]
+    fn image_and_file_parts_become_content_parts() {
+        let request: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "work/gpt-4o",
+            "messages": [
+                { "role": "user", "content": [{ "type": "text", "text": "a" }, { "type": "text", "text": "b" }] },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "What is this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } },
+                    { "type": "file", "file": { "filename": "spec.pdf", "file_data": "https://example.com/spec.pdf" } }
+                ]}
+            ]
+        }))
+        .unwrap();
+
+        let messages = to_llm_messages(&request.messages).unwrap();
+        assert_eq!(messages[0].content, "a\nb");
+        assert!(messages[0].parts.is_empty());
+        assert_eq!(
+            messages[1].parts,
+            vec![
+                ContentPart::Text { text: "What is this?".to_string() },
+                ContentPart::Image {
+                    source: MediaSource::Base64 {
+                        media_type: "image/png".to_string(),
+                        data: "iVBORw0KGgo=".to_string(),
+                    },
+                },
+                ContentPart::Document {
+                    source: MediaSource::Url { url: "https://example.com/spec.pdf".to_string() },
+                    name: Some("spec.pdf".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn completion_response_uses_openai_shape() {
+        let output = LLMChatOutput {
+            content: String::new(),
+            tool_calls: vec![ToolCall {
+                id: "call_1".to_string(),
+                name: "get_weather".to_string(),
+                arguments: json!({ "location": "Paris" }),
+            }],
+            usage: Some(json!({ "input_tokens": 12, "output_tokens": 3 })),
+            ..Default::default()
+        };
+
+        let response = completion_response("chatcmpl-1", "work/claude", 1700000000, &output);
+        let choice = &response["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert!(choice["message"]["content"].is_null());
+        assert_eq!(choice["message"]["tool_calls"][0]["function"]["arguments"], "{\"location\":\"Paris\"}");
+        assert_eq!(response["usage"]["total_tokens"], 15);
+    }
+
+    fn model(name: &str, model: &str) -> GatewayModel {
+        let now = chrono::Utc::now().naive_utc();
+        let provider = LLMProvider {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            name: name.to_string(),
+            provider_type: "gpt".to_string(),
+            api_endpoint: String::new(),
+            supported_modalities: json!(["text"]),
+            configuration: json!({ "model": model }),
+            created_at: now,
+            updated_at: now,
+        };
+        let user_config = UserLLMConfig {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            provider_id: provider.id,
+            api_key_id: Uuid::new_v4(),
+            description: None,
+            created_at: now,
+            updated_at: now,
+            fallbacks: json!([]),
+        };
+        GatewayModel::new(provider, user_config)
+    }
+
+    #[test]
+    fn models_resolve_by_id_model_or_provider() {
+        let models = vec![model("work", "gpt-4o"), model("personal", "claude-3-5-sonnet")];
+        let config_id = models[1].user_config.id;
+
+        assert_eq!(resolve(models.clone(), "work/gpt-4o").unwrap().provider.name, "work");
+        assert_eq!(resolve(models.clone(), "claude-3-5-sonnet").unwrap().provider.name, "personal");
+        assert_eq!(resolve(models.clone(), &config_id.to_string()).unwrap().provider.name, "personal");
+
+        let overridden = resolve(models.clone(), "work/gpt-4o-mini").unwrap();
+        assert_eq!(overridden.provider.configuration["model"], "gpt-4o-mini");
+
+        assert!(resolve(models, "unknown").is_none());
+    }
+
+    #[test]
+    fn sampling_options_reach_the_provider_request() {
+        let request: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "work/some-model",
+            "messages": [{ "role": "user", "content": "Hi" }],
+            "temperature": 0.2,
+            "top_p": 0.5,
+            "max_tokens": 64
+        }))
+        .unwrap();
+        let messages = to_llm_messages(&request.messages).unwrap();
+        let tools = LLMToolOptions {
+            tools: Vec::new(),
+            choice: ToolChoice::Auto,
+        };
+        let mut configuration = json!({ "model": "some-model" });
+        apply_request_options(&mut configuration, &request);
+
+        // Where each provider expects temperature, top_p and max_tokens
+        let cases = [
+            ("gpt", ["/temperature", "/top_p", "/max_completion_tokens"]),
+            ("claude", ["/temperature", "/top_p", "/max_tokens"]),
+            ("mistral", ["/temperature", "/top_p", "/max_tokens"]),
+            ("command", ["/temperature", "/p", "/max_tokens"]),
+            ("grok", ["/temperature", "/top_p", "/max_tokens"]),
+            (
+                "gemini",
+                ["/generationConfig/temperature", "/generationConfig/topP", "/generationConfig/maxOutputTokens"],
+            ),
+        ];
+        for (provider_type, [temperature, top_p, max_tokens]) in cases {
+            let provider = ProviderRegistry::global().create(provider_type, "").unwrap();
+            for stream in [false, true] {
+                let outgoing = provider
+                    .prepare_tool_request(&messages, &configuration, "key", &tools, stream)
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                let body: Value =
+                    serde_json::from_slice(outgoing.body().unwrap().as_bytes().unwrap()).unwrap();
+                assert_eq!(body.pointer(temperature), Some(&json!(0.2)), "{} temperature", provider_type);
+                assert_eq!(body.pointer(top_p), Some(&json!(0.5)), "{} top_p", provider_type);
+                assert_eq!(body.pointer(max_tokens), Some(&json!(64)), "{} max_tokens", provider_type);
+            }
+        }
+    }
+
+    #[test]
+    fn usage_from_gemini_is_normalized() {
+        let usage = json!({ "promptTokenCount": 4, "candidatesTokenCount": 6, "totalTokenCount": 10 });
+        assert_eq!(
+            normalize_usage(&usage).unwrap(),
+            json!({ "prompt_tokens": 4, "completion_tokens": 6, "total_tokens": 10 })
+        );
+        assert!(normalize_usage(&json!({ "model": "x" })).is_none());
+    }
+}
@@ -0,0 +1,458 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::rate_limit::{
+    Limits, NewRateLimit, RateLimit, RateLimitsResponse, SetRateLimitRequest,
+};
+use crate::schema::rate_limits;
+use crate::services::api_key_service::ApiKeyService;
+use chrono::Utc;
+use diesel::prelude::*;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const MINUTE: Duration = Duration::from_secs(60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// Suggested wait when all of a subject's streams are busy
+const STREAM_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref GLOBAL_LIMITER: Arc<RateLimiter> = Arc::new(RateLimiter::new());
+}
+
+/// Whose calls a counter tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitSubject {
+    User(Uuid),
+    /// A stored provider API key, which may be shared by several configs
+    ApiKey(Uuid),
+}
+
+impl LimitSubject {
+    fn describe(&self) -> String {
+        match self {
+            LimitSubject::User(_) => "your account".to_string(),
+            LimitSubject::ApiKey(id) => format!("API key {}", id),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    requests: VecDeque<Instant>,
+    tokens: VecDeque<(Instant, u64)>,
+    streams: u64,
+}
+
+impl Counters {
+    fn prune(&mut self, now: Instant) {
+        while self.requests.front().is_some_and(|at| now.duration_since(*at) >= MINUTE) {
+            self.requests.pop_front();
+        }
+        while self.tokens.front().is_some_and(|(at, _)| now.duration_since(*at) >= DAY) {
+            self.tokens.pop_front();
+        }
+    }
+
+    fn tokens_since(&self, now: Instant, window: Duration) -> u64 {
+        self.tokens
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) < window)
+            .map(|(_, tokens)| tokens)
+            .sum()
+    }
+
+    /// How long until the tokens used within `window` drop below `limit`
+    fn tokens_retry_after(&self, now: Instant, window: Duration, limit: u64) -> Duration {
+        let mut used = self.tokens_since(now, window);
+        for (at, tokens) in self.tokens.iter().filter(|(at, _)| now.duration_since(*at) < window) {
+            used -= tokens;
+            if used < limit {
+                return window - now.duration_since(*at);
+            }
+        }
+        window
+    }
+
+    /// Returns why and for how long a new call must wait, if it must
+    fn check(&self, now: Instant, limits: &Limits, stream: bool) -> Option<(String, Duration)> {
+        if let Some(limit) = limits.requests_per_minute {
+            if self.requests.len() as u64 >= limit {
+                let oldest = self.requests.front().copied().unwrap_or(now);
+                let wait = MINUTE.saturating_sub(now.duration_since(oldest));
+                return Some((format!("{} requests per minute", limit), wait));
+            }
+        }
+        if let Some(limit) = limits.concurrent_streams.filter(|_| stream) {
+            if self.streams >= limit {
+                return Some((format!("{} concurrent streams", limit), STREAM_RETRY_AFTER));
+            }
+        }
+        if let Some(limit) = limits.tokens_per_minute {
+            if self.tokens_since(now, MINUTE) >= limit {
+                let wait = self.tokens_retry_after(now, MINUTE, limit);
+                return Some((format!("{} tokens per minute", limit), wait));
+            }
+        }
+        if let Some(limit) = limits.tokens_per_day {
+            if self.tokens_since(now, DAY) >= limit {
+                let wait = self.tokens_retry_after(now, DAY, limit);
+                return Some((format!("{} tokens per day", limit), wait));
+            }
+        }
+        None
+    }
+}
+
+/// In-process counters for requests, concurrent streams and tokens per subject
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    counters: Mutex<HashMap<LimitSubject, Counters>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The limiter shared by all requests to this server
+    pub fn global() -> Arc<RateLimiter> {
+        Arc::clone(&GLOBAL_LIMITER)
+    }
+
+    /// Admits a call if every subject is within its limits, counting it against all of them
+    pub fn acquire(
+        self: &Arc<Self>,
+        subjects: &[(LimitSubject, Limits)],
+        stream: bool,
+    ) -> Result<RateLimitPermit, AppError> {
+        self.acquire_at(subjects, stream, Instant::now())
+    }
+
+    fn acquire_at(
+        self: &Arc<Self>,
+        subjects: &[(LimitSubject, Limits)],
+        stream: bool,
+        now: Instant,
+    ) -> Result<RateLimitPermit, AppError> {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+
+        for (subject, limits) in subjects {
+            let entry = counters.entry(*subject).or_default();
+            entry.prune(now);
+            if let Some((limit, wait)) = entry.check(now, limits, stream) {
+                debug!("Rate limited {:?}: {}", subject, limit);
+                return Err(AppError::RateLimited {
+                    message: format!("Limit of {} reached for {}", limit, subject.describe()),
+                    // Rounded up so clients never retry early
+                    retry_after: wait.as_secs() + u64::from(wait.subsec_nanos() > 0),
+                });
+            }
+        }
+
+        for (subject, _) in subjects {
+            let entry = counters.entry(*subject).or_default();
+            entry.requests.push_back(now);
+            if stream {
+                entry.streams += 1;
+            }
+        }
+
+        Ok(RateLimitPermit {
+            limiter: Arc::clone(self),
+            subjects: subjects.iter().map(|(subject, _)| *subject).collect(),
+            stream,
+        })
+    }
+
+    fn record_tokens_at(&self, subjects: &[LimitSubject], tokens: u64, now: Instant) {
+        if tokens == 0 {
+            return;
+        }
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        for subject in subjects {
+            counters.entry(*subject).or_default().tokens.push_back((now, tokens));
+        }
+    }
+
+    fn release_stream(&self, subjects: &[LimitSubject]) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        for subject in subjects {
+            if let Some(entry) = counters.get_mut(subject) {
+                entry.streams = entry.streams.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// An admitted call; a stream's slot is released when the permit is dropped
+#[derive(Debug)]
+pub struct RateLimitPermit {
+    limiter: Arc<RateLimiter>,
+    subjects: Vec<LimitSubject>,
+    stream: bool,
+}
+
+impl RateLimitPermit {
+    /// Counts the tokens the call used towards the token limits
+    pub fn record_tokens(&self, tokens: u64) {
+        self.limiter.record_tokens_at(&self.subjects, tokens, Instant::now());
+    }
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        if self.stream {
+            self.limiter.release_stream(&self.subjects);
+        }
+    }
+}
+
+fn env_limit(name: &str) -> Option<u64> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(limit) => Some(limit),
+        Err(_) => {
+            warn!("Ignoring invalid {}={}", name, value);
+            None
+        }
+    }
+}
+
+pub struct RateLimitService;
+
+impl RateLimitService {
+    /// Per-user limits from `RATE_LIMIT_*` variables, unset meaning unlimited
+    pub fn default_limits() -> Limits {
+        Limits {
+            requests_per_minute: env_limit("RATE_LIMIT_REQUESTS_PER_MINUTE"),
+            concurrent_streams: env_limit("RATE_LIMIT_CONCURRENT_STREAMS"),
+            tokens_per_minute: env_limit("RATE_LIMIT_TOKENS_PER_MINUTE"),
+            tokens_per_day: env_limit("RATE_LIMIT_TOKENS_PER_DAY"),
+        }
+    }
+
+    /// The subjects a call by `user_id` with `api_key_id` counts against, with their limits
+    pub fn subjects(
+        pool: &DbPool,
+        user_id: Uuid,
+        api_key_id: Option<Uuid>,
+    ) -> Result<Vec<(LimitSubject, Limits)>, AppError> {
+        let conn = &mut pool.get()?;
+
+        let user_limit = rate_limits::table
+            .filter(rate_limits::user_id.eq(user_id))
+            .filter(rate_limits::api_key_id.is_null())
+            .first::<RateLimit>(conn)
+            .optional()?;
+        let user_limits = user_limit
+            .as_ref()
+            .map(Limits::from)
+            .unwrap_or_default()
+            .tighten(Self::default_limits());
+
+        let mut subjects = vec![(LimitSubject::User(user_id), user_limits)];
+        if let Some(api_key_id) = api_key_id {
+            let key_limit = rate_limits::table
+                .filter(rate_limits::api_key_id.eq(api_key_id))
+                .first::<RateLimit>(conn)
+                .optional()?;
+            if let Some(limits) = key_limit.as_ref().map(Limits::from) {
+                subjects.push((LimitSubject::ApiKey(api_key_id), limits));
+            }
+        }
+        Ok(subjects)
+    }
+
+    /// Admits an LLM call or tool execution, or fails with `AppError::RateLimited`
+    pub fn acquire(
+        pool: &DbPool,
+        user_id: Uuid,
+        api_key_id: Option<Uuid>,
+        stream: bool,
+    ) -> Result<RateLimitPermit, AppError> {
+        let subjects = Self::subjects(pool, user_id, api_key_id)?;
+        RateLimiter::global().acquire(&subjects, stream)
+    }
+
+    pub fn get_limits(pool: &DbPool, user_id: Uuid) -> Result<RateLimitsResponse, AppError> {
+        let conn = &mut pool.get()?;
+        let limits = rate_limits::table
+            .filter(rate_limits::user_id.eq(user_id))
+            .order(rate_limits::created_at.asc())
+            .load::<RateLimit>(conn)?;
+
+        let (user, api_keys): (Vec<RateLimit>, Vec<RateLimit>) =
+            limits.into_iter().partition(|limit| limit.api_key_id.is_none());
+        let user = user.into_iter().next();
+        let defaults = Self::default_limits();
+        Ok(RateLimitsResponse {
+            defaults,
+            effective: user.as_ref().map(Limits::from).unwrap_or_default().tighten(defaults),
+            user,
+            api_keys,
+        })
+    }
+
+    /// Creates or replaces the user's own limits, or those of one of their API keys
+    pub fn set_limits(
+        pool: &DbPool,
+        user_id: Uuid,
+        api_key_id: Option<Uuid>,
+        request: SetRateLimitRequest,
+    ) -> Result<RateLimit, AppError> {
+        Self::validate(&request)?;
+        if let Some(api_key_id) = api_key_id {
+            Self::check_api_key_owner(pool, user_id, api_key_id)?;
+        }
+
+        let conn = &mut pool.get()?;
+        let existing = Self::find(conn, user_id, api_key_id)?;
+        let limit = match existing {
+            Some(existing) => diesel::update(rate_limits::table.find(existing.id))
+                .set((
+                    rate_limits::requests_per_minute.eq(request.requests_per_minute),
+                    rate_limits::concurrent_streams.eq(request.concurrent_streams),
+                    rate_limits::tokens_per_minute.eq(request.tokens_per_minute),
+                    rate_limits::tokens_per_day.eq(request.tokens_per_day),
+                    rate_limits::updated_at.eq(Utc::now()),
+                ))
+                .get_result::<RateLimit>(conn)?,
+            None => diesel::insert_into(rate_limits::table)
+                .values(&NewRateLimit {
+                    id: Uuid::new_v4(),
+                    user_id,
+                    api_key_id,
+                    requests_per_minute: request.requests_per_minute,
+                    concurrent_streams: request.concurrent_streams,
+                    tokens_per_minute: request.tokens_per_minute,
+                    tokens_per_day: request.tokens_per_day,
+                })
+                .get_result::<RateLimit>(conn)?,
+        };
+
+        info!("Set rate limits {} for user {}", limit.id, user_id);
+        Ok(limit)
+    }
+
+    pub fn delete_limits(
+        pool: &DbPool,
+        user_id: Uuid,
+        api_key_id: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        let conn = &mut pool.get()?;
+        let existing = Self::find(conn, user_id, api_key_id)?
+            .ok_or_else(|| AppError::NotFoundError("Rate limits not found".to_string()))?;
+        diesel::delete(rate_limits::table.find(existing.id)).execute(conn)?;
+        Ok(())
+    }
+
+    fn find(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        api_key_id: Option<Uuid>,
+    ) -> Result<Option<RateLimit>, AppError> {
+        let query = rate_limits::table.filter(rate_limits::user_id.eq(user_id)).into_boxed();
+        let query = match api_key_id {
+            Some(api_key_id) => query.filter(rate_limits::api_key_id.eq(api_key_id)),
+            None => query.filter(rate_limits::api_key_id.is_null()),
+        };
+        Ok(query.first::<RateLimit>(conn).optional()?)
+    }
+
+    fn check_api_key_owner(pool: &DbPool, user_id: Uuid, api_key_id: Uuid) -> Result<(), AppError> {
+        match ApiKeyService::get_api_key_by_id(pool, api_key_id)? {
+            Some(api_key) if api_key.user_id == user_id => Ok(()),
+            _ => Err(AppError::NotFoundError("API key not found".to_string())),
+        }
+    }
+
+    fn validate(request: &SetRateLimitRequest) -> Result<(), AppError> {
+        let negative = request.requests_per_minute.is_some_and(|limit| limit < 0)
+            || request.concurrent_streams.is_some_and(|limit| limit < 0)
+            || request.tokens_per_minute.is_some_and(|limit| limit < 0)
+            || request.tokens_per_day.is_some_and(|limit| limit < 0);
+        if negative {
+            return Err(AppError::BadRequest("Limits must not be negative".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_limits(limits: Limits) -> Vec<(LimitSubject, Limits)> {
+        vec![(LimitSubject::User(Uuid::nil()), limits)]
+    }
+
+    fn retry_after(result: Result<RateLimitPermit, AppError>) -> u64 {
+        match result {
+            Err(AppError::RateLimited { retry_after, .. }) => retry_after,
+            other => panic!("expected a rate limit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn requests_per_minute_expire_after_a_minute() {
+        let limiter = Arc::new(RateLimiter::new());
+        let subjects = user_limits(Limits { requests_per_minute: Some(2), ..Default::default() });
+        let start = Instant::now();
+
+        limiter.acquire_at(&subjects, false, start).unwrap();
+        limiter.acquire_at(&subjects, false, start + Duration::from_secs(10)).unwrap();
+        let wait = retry_after(limiter.acquire_at(&subjects, false, start + Duration::from_secs(20)));
+        assert_eq!(wait, 40);
+
+        assert!(limiter.acquire_at(&subjects, false, start + MINUTE).is_ok());
+    }
+
+    #[test]
+    fn stream_slots_are_released_on_drop() {
+        let limiter = Arc::new(RateLimiter::new());
+        let subjects = user_limits(Limits { concurrent_streams: Some(1), ..Default::default() });
+        let now = Instant::now();
+
+        let permit = limiter.acquire_at(&subjects, true, now).unwrap();
+        assert_eq!(retry_after(limiter.acquire_at(&subjects, true, now)), 5);
+        // Non-streaming calls don't take a slot
+        assert!(limiter.acquire_at(&subjects, false, now).is_ok());
+
+        drop(permit);
+        assert!(limiter.acquire_at(&subjects, true, now).is_ok());
+    }
+
+    #[test]
+    fn token_limits_apply_to_every_subject() {
+        let limiter = Arc::new(RateLimiter::new());
+        let api_key = LimitSubject::ApiKey(Uuid::new_v4());
+        let subjects = vec![
+            (LimitSubject::User(Uuid::nil()), Limits::default()),
+            (api_key, Limits { tokens_per_day: Some(1000), ..Default::default() }),
+        ];
+        let start = Instant::now();
+
+        let permit = limiter.acquire_at(&subjects, false, start).unwrap();
+        limiter.record_tokens_at(&permit.subjects, 1200, start);
+
+        let wait = retry_after(limiter.acquire_at(&subjects, false, start + Duration::from_secs(60 * 60)));
+        assert_eq!(wait, 23 * 60 * 60);
+
+        // Another user on the same key is limited too
+        let other_user = vec![(LimitSubject::User(Uuid::new_v4()), Limits::default()), subjects[1]];
+        assert!(limiter.acquire_at(&other_user, false, start).is_err());
+    }
+
+    #[test]
+    fn stored_limits_only_tighten_defaults() {
+        let defaults = Limits { requests_per_minute: Some(60), ..Default::default() };
+        let stored = Limits { requests_per_minute: Some(600), tokens_per_day: Some(10_000), ..Default::default() };
+        let effective = stored.tighten(defaults);
+        assert_eq!(effective.requests_per_minute, Some(60));
+        assert_eq!(effective.tokens_per_day, Some(10_000));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::services::function_calling::types::Tool;
+use std::str::FromStr;
 
 /// Available reasoning patterns for agents
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -13,6 +14,20 @@ pub enum ReasoningPattern {
     ToolUse,
 }
 
+impl FromStr for ReasoningPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace(['_', '-', ' '], "").as_str() {
+            "planning" => Ok(ReasoningPattern::Planning),
+            "react" => Ok(ReasoningPattern::ReAct),
+            "reflection" => Ok(ReasoningPattern::Reflection),
+            "tooluse" => Ok(ReasoningPattern::ToolUse),
+            _ => Err(format!("Unknown reasoning pattern: {}", s)),
+        }
+    }
+}
+
 /// Utility for composing multiple reasoning patterns together
 #[derive(Debug, Clone)]
 pub struct PatternComposer {
@@ -92,5 +107,13 @@ mod tests {
         assert!(prompt.contains("appropriate tool to gather"));
         assert!(prompt.contains("demo tool"));
     }
+
+    #[test]
+    fn parse_pattern_names() {
+        assert_eq!("Planning".parse::<ReasoningPattern>(), Ok(ReasoningPattern::Planning));
+        assert_eq!("ReAct".parse::<ReasoningPattern>(), Ok(ReasoningPattern::ReAct));
+        assert_eq!("tool_use".parse::<ReasoningPattern>(), Ok(ReasoningPattern::ToolUse));
+        assert!("unknown".parse::<ReasoningPattern>().is_err());
+    }
 }
 
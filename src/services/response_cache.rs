@@ -0,0 +1,275 @@
+use crate::handlers::metrics::record_llm_cache_lookup;
+use crate::models::llm_provider::LLMProvider;
+use crate::services::llm_service::{LLMChatMessage, LLMChatOutput, LLMToolOptions};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+/// Configuration keys that don't change what the model answers
+const IGNORED_CONFIG_KEYS: &[&str] = &["cache", "pricing", "headers"];
+
+lazy_static! {
+    static ref GLOBAL_CACHE: ResponseCache = ResponseCache::new(
+        env_setting("LLM_CACHE_TTL_SECS").map(Duration::from_secs).unwrap_or(DEFAULT_TTL),
+        env_setting("LLM_CACHE_MAX_ENTRIES").map(|n| n as usize).unwrap_or(DEFAULT_MAX_ENTRIES),
+    );
+}
+
+fn env_setting(name: &str) -> Option<u64> {
+    let value = std::env::var(name).ok()?;
+    value
+        .trim()
+        .parse()
+        .map_err(|_| warn!("Ignoring invalid {}={}", name, value))
+        .ok()
+}
+
+/// Whether a chat completion may be answered from, and stored in, the response cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Cache when the provider is configured with `temperature: 0` or `cache: true`
+    #[default]
+    Auto,
+    /// Cache regardless of the configuration
+    Force,
+    /// Never read or write the cache, e.g. for `Cache-Control: no-cache`
+    Bypass,
+}
+
+impl CachePolicy {
+    /// Reads `Cache-Control: no-cache` or `no-store` from a request, else `default`
+    pub fn from_request(req: &actix_web::HttpRequest, default: CachePolicy) -> CachePolicy {
+        let bypass = req
+            .headers()
+            .get_all(actix_web::http::header::CACHE_CONTROL)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|directive| matches!(directive.trim(), "no-cache" | "no-store"));
+        if bypass {
+            CachePolicy::Bypass
+        } else {
+            default
+        }
+    }
+
+    pub fn applies_to(&self, provider: &LLMProvider) -> bool {
+        let config = &provider.configuration;
+        match self {
+            CachePolicy::Bypass => false,
+            CachePolicy::Force => true,
+            CachePolicy::Auto => {
+                config["cache"].as_bool().unwrap_or(false)
+                    || config["temperature"].as_f64() == Some(0.0)
+            }
+        }
+    }
+}
+
+struct CacheEntry {
+    /// Full key, compared on lookup so hash collisions can't return another prompt's answer
+    key: String,
+    output: LLMChatOutput,
+    stored_at: Instant,
+}
+
+/// In-process cache of chat completions with a TTL and an entry limit
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<u64, CacheEntry>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        ResponseCache {
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn global() -> &'static ResponseCache {
+        &GLOBAL_CACHE
+    }
+
+    /// The provider, model, messages, tools and answer-relevant configuration, scoped to the user
+    pub fn key(
+        user_id: Uuid,
+        provider: &LLMProvider,
+        messages: &[LLMChatMessage],
+        tools: Option<&LLMToolOptions>,
+    ) -> String {
+        let mut config = provider.configuration.clone();
+        if let Some(config) = config.as_object_mut() {
+            config.retain(|key, _| !IGNORED_CONFIG_KEYS.contains(&key.as_str()));
+        }
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|message| {
+                json!({
+                    "role": message.role,
+                    "content": message.content.trim(),
+                    "parts": message.parts,
+                    "tool_calls": message.tool_calls,
+                    "tool_call_id": message.tool_call_id,
+                })
+            })
+            .collect();
+
+        json!({
+            "user_id": user_id,
+            "provider_type": provider.provider_type,
+            "api_endpoint": provider.api_endpoint,
+            "model": provider.configuration["model"],
+            "config": config,
+            "messages": messages,
+            "tools": tools.map(|tools| json!({ "tools": tools.tools, "choice": tools.choice })),
+        })
+        .to_string()
+    }
+
+    fn hash(key: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn get(&self, key: &str) -> Option<LLMChatOutput> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, now: Instant) -> Option<LLMChatOutput> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let hash = Self::hash(key);
+        let output = match entries.get(&hash) {
+            Some(entry) if now.duration_since(entry.stored_at) >= self.ttl => {
+                entries.remove(&hash);
+                None
+            }
+            Some(entry) if entry.key == key => Some(entry.output.clone()),
+            _ => None,
+        };
+        record_llm_cache_lookup(output.is_some());
+        output
+    }
+
+    pub fn insert(&self, key: String, output: &LLMChatOutput) {
+        self.insert_at(key, output, Instant::now());
+    }
+
+    fn insert_at(&self, key: String, output: &LLMChatOutput, now: Instant) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let hash = Self::hash(&key);
+        if !entries.contains_key(&hash) && entries.len() >= self.max_entries {
+            entries.retain(|_, entry| now.duration_since(entry.stored_at) < self.ttl);
+        }
+        if !entries.contains_key(&hash) && entries.len() >= self.max_entries {
+            // Still full of live entries, so make room by evicting the oldest
+            if let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(hash, _)| *hash)
+            {
+                entries.remove(&oldest);
+            }
+        }
+
+        debug!("Caching LLM response ({} entries)", entries.len() + 1);
+        entries.insert(
+            hash,
+            CacheEntry {
+                key,
+                output: output.clone(),
+                stored_at: now,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(configuration: Value) -> LLMProvider {
+        let now = chrono::Utc::now().naive_utc();
+        LLMProvider {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            name: "work".to_string(),
+            provider_type: "gpt".to_string(),
+            api_endpoint: String::new(),
+            supported_modalities: json!(["text"]),
+            configuration,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn message(content: &str) -> LLMChatMessage {
+        LLMChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+            parts: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    fn output(content: &str) -> LLMChatOutput {
+        LLMChatOutput {
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_deterministic_or_opted_in_requests_are_cached() {
+        assert!(CachePolicy::Auto.applies_to(&provider(json!({ "model": "gpt-4o", "temperature": 0 }))));
+        assert!(CachePolicy::Auto.applies_to(&provider(json!({ "model": "gpt-4o", "cache": true }))));
+        assert!(!CachePolicy::Auto.applies_to(&provider(json!({ "model": "gpt-4o", "temperature": 0.7 }))));
+        assert!(CachePolicy::Force.applies_to(&provider(json!({ "model": "gpt-4o" }))));
+        assert!(!CachePolicy::Bypass.applies_to(&provider(json!({ "model": "gpt-4o", "temperature": 0 }))));
+    }
+
+    #[test]
+    fn key_ignores_billing_settings_but_not_model_options() {
+        let user = Uuid::new_v4();
+        let messages = [message("Classify: great product")];
+        let base = ResponseCache::key(user, &provider(json!({ "model": "gpt-4o", "temperature": 0 })), &messages, None);
+
+        let priced = provider(json!({ "model": "gpt-4o", "temperature": 0, "pricing": { "input": 1.0, "output": 2.0 } }));
+        assert_eq!(ResponseCache::key(user, &priced, &messages, None), base);
+
+        let other_model = provider(json!({ "model": "gpt-4o-mini", "temperature": 0 }));
+        assert_ne!(ResponseCache::key(user, &other_model, &messages, None), base);
+        let other_user = provider(json!({ "model": "gpt-4o", "temperature": 0 }));
+        assert_ne!(ResponseCache::key(Uuid::new_v4(), &other_user, &messages, None), base);
+    }
+
+    #[test]
+    fn entries_expire_and_oldest_is_evicted() {
+        let cache = ResponseCache::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+
+        cache.insert_at("a".to_string(), &output("A"), start);
+        cache.insert_at("b".to_string(), &output("B"), start + Duration::from_secs(1));
+        assert_eq!(cache.get_at("a", start).unwrap().content, "A");
+
+        cache.insert_at("c".to_string(), &output("C"), start + Duration::from_secs(2));
+        assert!(cache.get_at("a", start + Duration::from_secs(2)).is_none());
+        assert_eq!(cache.get_at("c", start + Duration::from_secs(2)).unwrap().content, "C");
+
+        assert!(cache.get_at("b", start + Duration::from_secs(61)).is_none());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::tool_permission::{NewToolPermission, ToolPermission};
+use crate::schema::tool_permissions;
+use crate::services::function_calling::ToolRegistry;
+use chrono::Utc;
+use diesel::prelude::*;
+use log::{debug, info};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Comma-separated tool names that are dangerous for every user
+const DANGEROUS_TOOLS_ENV: &str = "DANGEROUS_TOOLS";
+
+pub struct ToolPermissionService;
+
+impl ToolPermissionService {
+    /// Creates or replaces the user's permission entry for a tool. Users can only add the
+    /// flag to tools the operator marked in `DANGEROUS_TOOLS`, never clear it.
+    pub fn set_permission(
+        pool: &DbPool,
+        user_id: Uuid,
+        tool_name: &str,
+        dangerous: bool,
+    ) -> Result<ToolPermission, AppError> {
+        check_user_setting(tool_name, dangerous, &global_dangerous_tools())?;
+        let conn = &mut pool.get()?;
+        let new_permission = NewToolPermission {
+            id: Uuid::new_v4(),
+            user_id,
+            tool_name: tool_name.to_string(),
+            dangerous,
+        };
+
+        let permission = diesel::insert_into(tool_permissions::table)
+            .values(&new_permission)
+            .on_conflict((tool_permissions::user_id, tool_permissions::tool_name))
+            .do_update()
+            .set((
+                tool_permissions::dangerous.eq(dangerous),
+                tool_permissions::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<ToolPermission>(conn)?;
+
+        info!("Set dangerous={} on tool {} for user {}", dangerous, tool_name, user_id);
+        Ok(permission)
+    }
+
+    pub fn list_permissions(pool: &DbPool, user_id: Uuid) -> Result<Vec<ToolPermission>, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Listing tool permissions for user {}", user_id);
+
+        tool_permissions::table
+            .filter(tool_permissions::user_id.eq(user_id))
+            .order(tool_permissions::tool_name.asc())
+            .load::<ToolPermission>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn delete_permission(pool: &DbPool, user_id: Uuid, tool_name: &str) -> Result<(), AppError> {
+        let conn = &mut pool.get()?;
+
+        let count = diesel::delete(tool_permissions::table)
+            .filter(tool_permissions::user_id.eq(user_id))
+            .filter(tool_permissions::tool_name.eq(tool_name))
+            .execute(conn)?;
+
+        if count == 0 {
+            return Err(AppError::NotFoundError(format!(
+                "No permission set for tool: {}",
+                tool_name
+            )));
+        }
+
+        info!("Cleared permission on tool {} for user {}", tool_name, user_id);
+        Ok(())
+    }
+
+    /// Names of the tools that need confirmation for this user: the
+    /// deployment-wide `DANGEROUS_TOOLS` plus the ones the user marked
+    pub fn dangerous_tools(pool: &DbPool, user_id: Uuid) -> Result<HashSet<String>, AppError> {
+        let mut names = global_dangerous_tools();
+        names.extend(
+            Self::list_permissions(pool, user_id)?
+                .into_iter()
+                .filter(|permission| permission.dangerous)
+                .map(|permission| permission.tool_name),
+        );
+        Ok(names)
+    }
+
+    /// Marks the given tools as dangerous in the registry
+    pub async fn apply(names: &HashSet<String>, registry: &ToolRegistry) {
+        for name in names {
+            registry.set_dangerous(name, true).await;
+        }
+    }
+}
+
+/// Tools the operator marked as dangerous for everyone; users can't unmark these
+pub fn global_dangerous_tools() -> HashSet<String> {
+    std::env::var(DANGEROUS_TOOLS_ENV)
+        .map(|value| parse_tool_list(&value))
+        .unwrap_or_default()
+}
+
+/// Refuses to clear the flag on a tool the operator marked as dangerous
+fn check_user_setting(
+    tool_name: &str,
+    dangerous: bool,
+    operator_tools: &HashSet<String>,
+) -> Result<(), AppError> {
+    if !dangerous && operator_tools.contains(tool_name) {
+        return Err(AppError::Forbidden(format!(
+            "Tool {} is marked as dangerous by the operator",
+            tool_name
+        )));
+    }
+    Ok(())
+}
+
+fn parse_tool_list(value: &str) -> HashSet<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tool_list_ignores_blanks() {
+        let names = parse_tool_list(" files__delete_file, ,send_email,");
+        assert_eq!(names.len(), 2);
+        assert!(names.contains("files__delete_file"));
+        assert!(names.contains("send_email"));
+    }
+
+    #[test]
+    fn users_cannot_clear_operator_flags() {
+        let operator_tools = parse_tool_list("send_email");
+        assert!(matches!(
+            check_user_setting("send_email", false, &operator_tools),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(check_user_setting("send_email", true, &operator_tools).is_ok());
+        assert!(check_user_setting("get_weather", false, &operator_tools).is_ok());
+    }
+}
@@ -0,0 +1,472 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::llm_provider::LLMProvider;
+use crate::models::usage::{
+    NewUsageRecord, UsageGroupBy, UsageRecord, UsageReport, UsageReportQuery, UsageReportRow,
+    UsageSource, UsageTotals,
+};
+use crate::schema::usage_records;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Double, Nullable, Text, Timestamptz, Uuid as SqlUuid};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Token counts of one completion, normalized across providers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Every input token, including cached ones
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Input tokens served from the provider's prompt cache
+    pub cached_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Reads a usage report in OpenAI, Anthropic, Gemini or Cohere format
+    pub fn from_provider(usage: &Value) -> Option<TokenUsage> {
+        // Cohere nests its counts, preferring `tokens` over `billed_units`
+        for key in ["tokens", "billed_units"] {
+            if usage[key].is_object() {
+                if let Some(tokens) = Self::from_provider(&usage[key]) {
+                    return Some(tokens);
+                }
+            }
+        }
+
+        let count = |keys: &[&str]| keys.iter().find_map(|key| usage[*key].as_u64());
+
+        let prompt = count(&["prompt_tokens", "input_tokens", "promptTokenCount"]);
+        let completion = count(&["completion_tokens", "output_tokens", "candidatesTokenCount"]);
+        if prompt.is_none() && completion.is_none() {
+            warn!("Unrecognized usage report: {}", usage);
+            return None;
+        }
+
+        // Anthropic counts cache reads and writes separately from input_tokens
+        let cache_read = count(&["cache_read_input_tokens"]).unwrap_or(0);
+        let cache_write = count(&["cache_creation_input_tokens"]).unwrap_or(0);
+        let cached = usage["prompt_tokens_details"]["cached_tokens"]
+            .as_u64()
+            .or_else(|| usage["cachedContentTokenCount"].as_u64())
+            .unwrap_or(cache_read);
+
+        let prompt_tokens = prompt.unwrap_or(0) + cache_read + cache_write;
+        let completion_tokens = completion.unwrap_or(0);
+        Some(TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            cached_tokens: cached,
+            total_tokens: count(&["total_tokens", "totalTokenCount"])
+                .unwrap_or(prompt_tokens + completion_tokens),
+        })
+    }
+
+    /// Adds up the reports of separate requests, e.g. the batches of one embeddings call
+    pub fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            cached_tokens: self.cached_tokens + other.cached_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+
+    /// Combines partial reports from one stream, e.g. Anthropic's input and output events
+    pub fn merge(self, other: TokenUsage) -> TokenUsage {
+        let prompt_tokens = self.prompt_tokens.max(other.prompt_tokens);
+        let completion_tokens = self.completion_tokens.max(other.completion_tokens);
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            cached_tokens: self.cached_tokens.max(other.cached_tokens),
+            total_tokens: self
+                .total_tokens
+                .max(other.total_tokens)
+                .max(prompt_tokens + completion_tokens),
+        }
+    }
+}
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Price of cached input tokens, `input` when unset
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+}
+
+const fn price(input: f64, output: f64, cached_input: Option<f64>) -> ModelPrice {
+    ModelPrice { input, output, cached_input }
+}
+
+/// List prices matched against the configured model by longest prefix
+const MODEL_PRICES: &[(&str, ModelPrice)] = &[
+    ("gpt-4o-mini", price(0.15, 0.60, Some(0.075))),
+    ("gpt-4o", price(2.50, 10.00, Some(1.25))),
+    ("gpt-4.1-nano", price(0.10, 0.40, Some(0.025))),
+    ("gpt-4.1-mini", price(0.40, 1.60, Some(0.10))),
+    ("gpt-4.1", price(2.00, 8.00, Some(0.50))),
+    ("gpt-4-turbo", price(10.00, 30.00, None)),
+    ("gpt-4", price(30.00, 60.00, None)),
+    ("gpt-3.5-turbo", price(0.50, 1.50, None)),
+    ("o1-mini", price(1.10, 4.40, Some(0.55))),
+    ("o1", price(15.00, 60.00, Some(7.50))),
+    ("o3-mini", price(1.10, 4.40, Some(0.55))),
+    ("claude-3-5-haiku", price(0.80, 4.00, Some(0.08))),
+    ("claude-3-haiku", price(0.25, 1.25, Some(0.03))),
+    ("claude-3-5-sonnet", price(3.00, 15.00, Some(0.30))),
+    ("claude-3-7-sonnet", price(3.00, 15.00, Some(0.30))),
+    ("claude-sonnet-4", price(3.00, 15.00, Some(0.30))),
+    ("claude-3-opus", price(15.00, 75.00, Some(1.50))),
+    ("claude-opus-4", price(15.00, 75.00, Some(1.50))),
+    ("gemini-1.5-flash", price(0.075, 0.30, Some(0.01875))),
+    ("gemini-1.5-pro", price(1.25, 5.00, Some(0.3125))),
+    ("gemini-2.0-flash", price(0.10, 0.40, Some(0.025))),
+    ("command-r-plus", price(2.50, 10.00, None)),
+    ("command-r", price(0.15, 0.60, None)),
+    ("mistral-large", price(2.00, 6.00, None)),
+    ("mistral-small", price(0.20, 0.60, None)),
+    ("codestral", price(0.30, 0.90, None)),
+    ("grok-2", price(2.00, 10.00, None)),
+    ("grok-beta", price(5.00, 15.00, None)),
+    ("text-embedding-3-small", price(0.02, 0.0, None)),
+    ("text-embedding-3-large", price(0.13, 0.0, None)),
+    ("text-embedding-ada-002", price(0.10, 0.0, None)),
+    ("embed-english-v3.0", price(0.10, 0.0, None)),
+    ("embed-multilingual-v3.0", price(0.10, 0.0, None)),
+    ("mistral-embed", price(0.10, 0.0, None)),
+];
+
+/// The provider's `configuration.pricing`, else the built-in price of its model
+pub fn model_price(provider: &LLMProvider, model: &str) -> Option<ModelPrice> {
+    if let Some(pricing) = provider.configuration.get("pricing").filter(|p| !p.is_null()) {
+        match serde_json::from_value(pricing.clone()) {
+            Ok(price) => return Some(price),
+            Err(e) => warn!("Ignoring invalid pricing on provider {}: {}", provider.id, e),
+        }
+    }
+
+    let model = model.to_lowercase();
+    MODEL_PRICES
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| *price)
+}
+
+/// Cost in USD, with cached input billed at its own rate
+pub fn cost_usd(tokens: &TokenUsage, price: &ModelPrice) -> f64 {
+    let cached = tokens.cached_tokens.min(tokens.prompt_tokens);
+    let uncached = tokens.prompt_tokens - cached;
+    (uncached as f64 * price.input
+        + cached as f64 * price.cached_input.unwrap_or(price.input)
+        + tokens.completion_tokens as f64 * price.output)
+        / 1_000_000.0
+}
+
+/// What a message stores in `usage_stats`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageStats {
+    #[serde(flatten)]
+    pub tokens: TokenUsage,
+    /// Unset when the model has no known price
+    pub cost_usd: Option<f64>,
+    pub provider_id: Uuid,
+    pub provider: String,
+    pub provider_type: String,
+    pub model: String,
+}
+
+impl UsageStats {
+    pub fn new(provider: &LLMProvider, tokens: TokenUsage) -> Self {
+        let model = provider.configuration["model"]
+            .as_str()
+            .unwrap_or(&provider.provider_type)
+            .to_string();
+        UsageStats {
+            cost_usd: model_price(provider, &model).map(|price| cost_usd(&tokens, &price)),
+            tokens,
+            provider_id: provider.id,
+            provider: provider.name.clone(),
+            provider_type: provider.provider_type.clone(),
+            model,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+pub struct UsageService;
+
+impl UsageService {
+    /// Stores one completion's usage for reporting
+    pub fn record(
+        pool: &DbPool,
+        user_id: Uuid,
+        stats: &UsageStats,
+        source: UsageSource,
+        conversation_id: Option<Uuid>,
+        message_id: Option<Uuid>,
+    ) -> Result<UsageRecord, AppError> {
+        let conn = &mut pool.get()?;
+        let new_record = NewUsageRecord {
+            id: Uuid::new_v4(),
+            user_id,
+            conversation_id,
+            message_id,
+            provider_id: Some(stats.provider_id),
+            provider_name: stats.provider.clone(),
+            provider_type: stats.provider_type.clone(),
+            model: stats.model.clone(),
+            source: source.as_str().to_string(),
+            prompt_tokens: stats.tokens.prompt_tokens as i64,
+            completion_tokens: stats.tokens.completion_tokens as i64,
+            cached_tokens: stats.tokens.cached_tokens as i64,
+            total_tokens: stats.tokens.total_tokens as i64,
+            cost_usd: stats.cost_usd,
+        };
+
+        diesel::insert_into(usage_records::table)
+            .values(&new_record)
+            .get_result::<UsageRecord>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    /// Records usage without failing the request that produced it
+    pub fn record_quietly(
+        pool: &DbPool,
+        user_id: Uuid,
+        stats: &UsageStats,
+        source: UsageSource,
+        conversation_id: Option<Uuid>,
+        message_id: Option<Uuid>,
+    ) {
+        if let Err(e) = Self::record(pool, user_id, stats, source, conversation_id, message_id) {
+            warn!("Failed to record usage for user {}: {}", user_id, e);
+        }
+    }
+
+    /// Sums the user's usage per group in the database, so the report costs one row per group
+    pub fn report(
+        pool: &DbPool,
+        user_id: Uuid,
+        query: &UsageReportQuery,
+    ) -> Result<UsageReport, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Building {:?} usage report for user {}", query.group_by, user_id);
+
+        let from = query.from.map(start_of);
+        let to = query.to.map(|to| start_of(to.succ_opt().unwrap_or(to)));
+        let rows: Vec<UsageReportRow> = diesel::sql_query(report_sql(query.group_by))
+            .bind::<SqlUuid, _>(user_id)
+            .bind::<Nullable<Timestamptz>, _>(from)
+            .bind::<Nullable<Timestamptz>, _>(to)
+            .load::<GroupTotals>(conn)?
+            .into_iter()
+            .map(UsageReportRow::from)
+            .collect();
+
+        let total = total(&rows);
+        Ok(UsageReport {
+            group_by: query.group_by,
+            from: query.from,
+            to: query.to,
+            rows,
+            total,
+        })
+    }
+}
+
+/// One group of a usage report, as summed by `report_sql`
+#[derive(QueryableByName)]
+struct GroupTotals {
+    #[diesel(sql_type = Text)]
+    key: String,
+    #[diesel(sql_type = BigInt)]
+    requests: i64,
+    #[diesel(sql_type = BigInt)]
+    prompt_tokens: i64,
+    #[diesel(sql_type = BigInt)]
+    completion_tokens: i64,
+    #[diesel(sql_type = BigInt)]
+    cached_tokens: i64,
+    #[diesel(sql_type = BigInt)]
+    total_tokens: i64,
+    #[diesel(sql_type = Double)]
+    cost_usd: f64,
+}
+
+impl From<GroupTotals> for UsageReportRow {
+    fn from(group: GroupTotals) -> Self {
+        UsageReportRow {
+            key: group.key,
+            totals: UsageTotals {
+                requests: group.requests,
+                prompt_tokens: group.prompt_tokens,
+                completion_tokens: group.completion_tokens,
+                cached_tokens: group.cached_tokens,
+                total_tokens: group.total_tokens,
+                cost_usd: group.cost_usd,
+            },
+        }
+    }
+}
+
+fn start_of(day: NaiveDate) -> chrono::DateTime<chrono::Utc> {
+    day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+/// The SQL expression rows are grouped by. Days are UTC dates, like the `from`/`to` filter.
+fn group_key(group_by: UsageGroupBy) -> &'static str {
+    match group_by {
+        UsageGroupBy::Day => "to_char(date_trunc('day', created_at AT TIME ZONE 'UTC'), 'YYYY-MM-DD')",
+        UsageGroupBy::Provider => "provider_name",
+        UsageGroupBy::Model => "model",
+        UsageGroupBy::Conversation => "COALESCE(conversation_id::TEXT, 'none')",
+    }
+}
+
+/// Sums a user's records per group key between two optional instants, binding `$1` to the user
+/// and `$2`/`$3` to the bounds. Groups are ordered by key, compared byte by byte.
+fn report_sql(group_by: UsageGroupBy) -> String {
+    let key = group_key(group_by);
+    format!(
+        r#"SELECT {key} AS key,
+       COUNT(*) AS requests,
+       COALESCE(SUM(prompt_tokens), 0)::BIGINT AS prompt_tokens,
+       COALESCE(SUM(completion_tokens), 0)::BIGINT AS completion_tokens,
+       COALESCE(SUM(cached_tokens), 0)::BIGINT AS cached_tokens,
+       COALESCE(SUM(total_tokens), 0)::BIGINT AS total_tokens,
+       COALESCE(SUM(cost_usd), 0)::DOUBLE PRECISION AS cost_usd
+FROM usage_records
+WHERE user_id = $1
+  AND ($2::TIMESTAMPTZ IS NULL OR created_at >= $2)
+  AND ($3::TIMESTAMPTZ IS NULL OR created_at < $3)
+GROUP BY 1
+ORDER BY {key} COLLATE "C""#
+    )
+}
+
+/// Adds up the groups of a report
+pub fn total(rows: &[UsageReportRow]) -> UsageTotals {
+    let mut total = UsageTotals::default();
+    for row in rows {
+        total.requests += row.totals.requests;
+        total.prompt_tokens += row.totals.prompt_tokens;
+        total.completion_tokens += row.totals.completion_tokens;
+        total.cached_tokens += row.totals.cached_tokens;
+        total.total_tokens += row.totals.total_tokens;
+        total.cost_usd += row.totals.cost_usd;
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn provider(configuration: Value) -> LLMProvider {
+        let now = Utc::now().naive_utc();
+        LLMProvider {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            name: "work".to_string(),
+            provider_type: "gpt".to_string(),
+            api_endpoint: String::new(),
+            supported_modalities: json!(["text"]),
+            configuration,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn provider_usage_formats_are_normalized() {
+        let openai = json!({
+            "prompt_tokens": 100, "completion_tokens": 20, "total_tokens": 120,
+            "prompt_tokens_details": { "cached_tokens": 60 }
+        });
+        assert_eq!(
+            TokenUsage::from_provider(&openai),
+            Some(TokenUsage { prompt_tokens: 100, completion_tokens: 20, cached_tokens: 60, total_tokens: 120 })
+        );
+
+        let anthropic = json!({ "input_tokens": 10, "cache_read_input_tokens": 90, "output_tokens": 5 });
+        assert_eq!(
+            TokenUsage::from_provider(&anthropic),
+            Some(TokenUsage { prompt_tokens: 100, completion_tokens: 5, cached_tokens: 90, total_tokens: 105 })
+        );
+
+        let cohere = json!({ "billed_units": { "input_tokens": 7, "output_tokens": 3 } });
+        assert_eq!(TokenUsage::from_provider(&cohere).unwrap().total_tokens, 10);
+        assert!(TokenUsage::from_provider(&json!({ "model": "x" })).is_none());
+    }
+
+    #[test]
+    fn partial_stream_reports_are_merged() {
+        // Anthropic's message_start and message_delta events
+        let start = TokenUsage::from_provider(&json!({ "input_tokens": 12, "output_tokens": 1 })).unwrap();
+        let delta = TokenUsage::from_provider(&json!({ "output_tokens": 30 })).unwrap();
+        let merged = start.merge(delta);
+        assert_eq!(merged.prompt_tokens, 12);
+        assert_eq!(merged.completion_tokens, 30);
+        assert_eq!(merged.total_tokens, 42);
+    }
+
+    #[test]
+    fn cost_uses_longest_prefix_and_pricing_override() {
+        let tokens = TokenUsage { prompt_tokens: 1_000_000, completion_tokens: 1_000_000, cached_tokens: 0, total_tokens: 2_000_000 };
+
+        let mini = UsageStats::new(&provider(json!({ "model": "gpt-4o-mini-2024-07-18" })), tokens);
+        assert!((mini.cost_usd.unwrap() - 0.75).abs() < 1e-9);
+
+        let cached = TokenUsage { cached_tokens: 1_000_000, ..tokens };
+        let stats = UsageStats::new(&provider(json!({ "model": "gpt-4o" })), cached);
+        assert_eq!(stats.cost_usd, Some(11.25));
+
+        let local = provider(json!({ "model": "llama3.1", "pricing": { "input": 0.0, "output": 0.0 } }));
+        assert_eq!(UsageStats::new(&local, tokens).cost_usd, Some(0.0));
+        assert_eq!(UsageStats::new(&provider(json!({ "model": "llama3.1" })), tokens).cost_usd, None);
+    }
+
+    #[test]
+    fn report_groups_by_the_requested_key() {
+        let sql = report_sql(UsageGroupBy::Day);
+        assert!(sql.contains("GROUP BY 1"));
+        assert!(sql.contains("date_trunc('day', created_at AT TIME ZONE 'UTC')"));
+        assert!(report_sql(UsageGroupBy::Model).starts_with("SELECT model AS key"));
+        assert!(report_sql(UsageGroupBy::Conversation).contains("'none'"));
+    }
+
+    #[test]
+    fn report_total_adds_up_the_groups() {
+        let row = |key: &str, requests: i64, tokens: i64, cost: f64| UsageReportRow {
+            key: key.to_string(),
+            totals: UsageTotals {
+                requests,
+                prompt_tokens: tokens,
+                total_tokens: tokens,
+                cost_usd: cost,
+                ..Default::default()
+            },
+        };
+        let rows = vec![row("2025-05-01", 2, 15, 0.5), row("2025-05-02", 1, 20, 1.0)];
+
+        let sum = total(&rows);
+        assert_eq!(sum.requests, 3);
+        assert_eq!(sum.total_tokens, 35);
+        assert_eq!(sum.cost_usd, 1.5);
+        assert_eq!(total(&[]), UsageTotals::default());
+    }
+}
@@ -0,0 +1,345 @@
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::models::user_tool::{
+    CreateUserToolRequest, NewUserTool, UpdateUserToolRequest, UserTool,
+};
+use crate::schema::{secure_vaults, user_tools};
+use crate::services::function_calling::tool::executor::ToolExecutorBase;
+use crate::services::function_calling::tool::webhook::WebhookTool;
+use crate::services::function_calling::{ToolParameter, ToolRegistry};
+use crate::utils::encryption::decrypt_data;
+use crate::utils::{json_path, url_guard};
+use chrono::Utc;
+use diesel::prelude::*;
+use log::{debug, error, info, warn};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const SUPPORTED_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+/// A webhook tool ready to register, with its secrets resolved
+pub struct LoadedUserTool {
+    pub executor: WebhookTool,
+    pub parameters: Vec<ToolParameter>,
+}
+
+pub struct UserToolService;
+
+impl UserToolService {
+    pub fn create_tool(
+        pool: &DbPool,
+        user_id: Uuid,
+        request: CreateUserToolRequest,
+    ) -> Result<UserTool, AppError> {
+        Self::validate(
+            &request.name,
+            &request.method,
+            &request.url_template,
+            request.response_path.as_deref(),
+        )?;
+
+        let conn = &mut pool.get()?;
+        let new_tool = NewUserTool::from_request(user_id, request);
+
+        let tool = diesel::insert_into(user_tools::table)
+            .values(&new_tool)
+            .get_result::<UserTool>(conn)?;
+
+        info!("Created tool {} ({}) for user {}", tool.name, tool.id, user_id);
+        Ok(tool)
+    }
+
+    /// Looks a tool up by id, or by name since names are unique per user
+    pub fn get_tool(pool: &DbPool, user_id: Uuid, id_or_name: &str) -> Result<UserTool, AppError> {
+        let conn = &mut pool.get()?;
+
+        let query = user_tools::table
+            .filter(user_tools::user_id.eq(user_id))
+            .into_boxed();
+        let query = match Uuid::parse_str(id_or_name) {
+            Ok(id) => query.filter(user_tools::id.eq(id)),
+            Err(_) => query.filter(user_tools::name.eq(id_or_name.to_string())),
+        };
+
+        query.first::<UserTool>(conn).map_err(|e| {
+            if let diesel::result::Error::NotFound = e {
+                AppError::NotFoundError(format!("Tool not found: {}", id_or_name))
+            } else {
+                AppError::DatabaseError(e)
+            }
+        })
+    }
+
+    pub fn list_tools(pool: &DbPool, user_id: Uuid) -> Result<Vec<UserTool>, AppError> {
+        let conn = &mut pool.get()?;
+
+        debug!("Listing tools for user {}", user_id);
+
+        user_tools::table
+            .filter(user_tools::user_id.eq(user_id))
+            .order(user_tools::name.asc())
+            .load::<UserTool>(conn)
+            .map_err(AppError::DatabaseError)
+    }
+
+    pub fn update_tool(
+        pool: &DbPool,
+        user_id: Uuid,
+        id_or_name: &str,
+        request: UpdateUserToolRequest,
+    ) -> Result<UserTool, AppError> {
+        let existing = Self::get_tool(pool, user_id, id_or_name)?;
+
+        let name = request.name.unwrap_or(existing.name);
+        let method = request.method.map(|m| m.to_uppercase()).unwrap_or(existing.method);
+        let url_template = request.url_template.unwrap_or(existing.url_template);
+        let response_path = request.response_path.or(existing.response_path);
+        Self::validate(&name, &method, &url_template, response_path.as_deref())?;
+
+        let conn = &mut pool.get()?;
+        let tool = diesel::update(user_tools::table.find(existing.id))
+            .set((
+                user_tools::name.eq(name),
+                user_tools::description.eq(request.description.unwrap_or(existing.description)),
+                user_tools::parameters.eq(request.parameters.map(|p| json!(p)).unwrap_or(existing.parameters)),
+                user_tools::method.eq(method),
+                user_tools::url_template.eq(url_template),
+                user_tools::headers.eq(request.headers.map(|h| json!(h)).unwrap_or(existing.headers)),
+                user_tools::response_path.eq(response_path),
+                user_tools::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<UserTool>(conn)?;
+
+        info!("Updated tool {}", tool.id);
+        Ok(tool)
+    }
+
+    pub fn delete_tool(pool: &DbPool, user_id: Uuid, id_or_name: &str) -> Result<(), AppError> {
+        let tool = Self::get_tool(pool, user_id, id_or_name)?;
+        let conn = &mut pool.get()?;
+
+        let count = diesel::delete(user_tools::table)
+            .filter(user_tools::id.eq(tool.id))
+            .filter(user_tools::user_id.eq(user_id))
+            .execute(conn)?;
+
+        if count == 0 {
+            error!("Tool not found for deletion: {}", id_or_name);
+            return Err(AppError::NotFoundError(format!("Tool not found: {}", id_or_name)));
+        }
+
+        info!("Deleted tool {}", tool.id);
+        Ok(())
+    }
+
+    fn validate(
+        name: &str,
+        method: &str,
+        url_template: &str,
+        response_path: Option<&str>,
+    ) -> Result<(), AppError> {
+        // `__` is reserved for tools from MCP servers
+        if name.is_empty()
+            || name.contains("__")
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(AppError::BadRequest(
+                "Tool name may only contain letters, digits, '_' and '-', and may not contain '__'".to_string(),
+            ));
+        }
+
+        if !SUPPORTED_METHODS.contains(&method.to_uppercase().as_str()) {
+            return Err(AppError::BadRequest(format!("Unsupported HTTP method: {}", method)));
+        }
+
+        Self::validate_url_template(url_template)?;
+
+        if let Some(path) = response_path {
+            json_path::validate(path)
+                .map_err(|e| AppError::BadRequest(format!("Invalid response_path: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// The host has to be fixed and public; arguments may only fill the path and query. Calls are
+    /// checked again when they're made, since the host may resolve differently by then.
+    fn validate_url_template(url_template: &str) -> Result<(), AppError> {
+        let authority = url_template
+            .split_once("://")
+            .map(|(_, rest)| rest.split(['/', '?', '#']).next().unwrap_or_default())
+            .unwrap_or_default();
+        if authority.contains("{{") {
+            return Err(AppError::BadRequest(
+                "The host in url_template can't contain placeholders".to_string(),
+            ));
+        }
+
+        url_guard::parse_public_url(&url_template.replace("{{", "").replace("}}", ""))
+            .and_then(|url| url_guard::check_resolved(&url))
+            .map_err(|e| AppError::BadRequest(format!("Invalid url_template: {}", e)))
+    }
+
+    /// Loads the user's tools as executors, resolving `{{secret:name}}` header references
+    pub fn load_tools(pool: &DbPool, user_id: Uuid) -> Result<Vec<LoadedUserTool>, AppError> {
+        let tools = Self::list_tools(pool, user_id)?;
+        if tools.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = &mut pool.get()?;
+        let vaults: HashMap<String, String> = secure_vaults::table
+            .filter(secure_vaults::user_id.eq(user_id))
+            .select((secure_vaults::name, secure_vaults::encrypted_data))
+            .load::<(String, String)>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut loaded = Vec::new();
+        for tool in tools {
+            let headers = match Self::resolve_headers(&tool.header_map(), &vaults) {
+                Ok(headers) => headers,
+                Err(e) => {
+                    warn!("Skipping tool {}: {}", tool.name, e);
+                    continue;
+                }
+            };
+
+            match WebhookTool::new(
+                &tool.name,
+                &tool.description,
+                &tool.method,
+                &tool.url_template,
+                headers,
+                tool.response_path.clone(),
+            ) {
+                Ok(executor) => loaded.push(LoadedUserTool {
+                    executor,
+                    parameters: tool.parameter_list(),
+                }),
+                Err(e) => warn!("Skipping tool {}: {}", tool.name, e),
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// Registers loaded tools with the registry
+    pub async fn register_tools(tools: Vec<LoadedUserTool>, registry: &ToolRegistry) {
+        for tool in tools {
+            let name = tool.executor.name().to_string();
+            registry.register(tool.executor).await;
+            registry.update_tool_parameters(&name, tool.parameters).await;
+        }
+    }
+
+    fn resolve_headers(
+        headers: &HashMap<String, String>,
+        vaults: &HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, String> {
+        headers
+            .iter()
+            .map(|(name, template)| Ok((name.clone(), resolve_secrets(template, vaults)?)))
+            .collect()
+    }
+}
+
+/// Replaces `{{secret:vault}}` or `{{secret:vault.field}}` with the decrypted vault contents
+fn resolve_secrets(template: &str, vaults: &HashMap<String, String>) -> Result<String, String> {
+    let mut resolved = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{secret:") {
+        let end = rest[start..]
+            .find("}}")
+            .map(|offset| start + offset)
+            .ok_or_else(|| "unclosed secret reference".to_string())?;
+        let reference = rest[start + "{{secret:".len()..end].trim();
+        let (vault, field) = match reference.split_once('.') {
+            Some((vault, field)) => (vault, Some(field)),
+            None => (reference, None),
+        };
+
+        let encrypted = vaults
+            .get(vault)
+            .ok_or_else(|| format!("secret '{}' not found", vault))?;
+        let secret = decrypt_data(encrypted);
+        let value = match field {
+            Some(field) => serde_json::from_str::<Value>(&secret)
+                .ok()
+                .and_then(|data| data.get(field).cloned())
+                .map(|value| match value {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                })
+                .ok_or_else(|| format!("secret '{}' has no field '{}'", vault, field))?,
+            None => secret,
+        };
+
+        resolved.push_str(&rest[..start]);
+        resolved.push_str(&value);
+        rest = &rest[end + 2..];
+    }
+    resolved.push_str(rest);
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::encryption::encrypt_data;
+
+    #[test]
+    fn resolve_secrets_substitutes_vault_values() {
+        std::env::set_var(
+            "ENCRYPTION_KEY",
+            "00000000000000000000000000000000ffffffffffffffffffffffffffffffff",
+        );
+        let vaults: HashMap<String, String> = [
+            ("crm_token".to_string(), encrypt_data("abc123")),
+            ("crm".to_string(), encrypt_data(r#"{"key": "k-1", "region": "eu"}"#)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            resolve_secrets("Bearer {{secret:crm_token}}", &vaults).unwrap(),
+            "Bearer abc123"
+        );
+        assert_eq!(
+            resolve_secrets("{{secret:crm.key}}/{{secret:crm.region}}", &vaults).unwrap(),
+            "k-1/eu"
+        );
+        assert!(resolve_secrets("{{secret:missing}}", &vaults).is_err());
+        assert_eq!(resolve_secrets("no secrets", &vaults).unwrap(), "no secrets");
+    }
+
+    #[test]
+    fn url_template_must_have_a_fixed_public_host() {
+        assert!(UserToolService::validate_url_template(
+            "https://93.184.216.34/orders/{{order_id}}?region={{region}}"
+        )
+        .is_ok());
+
+        for template in [
+            "ftp://93.184.216.34/{{path}}",
+            "http://169.254.169.254/latest/meta-data/{{path}}",
+            "http://localhost:8080/admin",
+            "http://10.1.2.3/{{path}}",
+            "http://[::1]/",
+            "https://{{host}}/orders",
+            "https://api.{{region}}.example.com/orders",
+        ] {
+            assert!(
+                matches!(
+                    UserToolService::validate_url_template(template),
+                    Err(AppError::BadRequest(_))
+                ),
+                "{} was accepted",
+                template
+            );
+        }
+    }
+}
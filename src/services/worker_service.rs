@@ -2,8 +2,13 @@ use crate::db::DbPool;
 use crate::error::AppError;
 use crate::models::worker::{Worker, NewWorker, UpdateWorker};
 use diesel::prelude::*;
+use rand::Rng;
 use uuid::Uuid;
 
+/// Worker tokens are random, so a low cost is enough, and workers present theirs several times a
+/// second while they run a job
+const TOKEN_HASH_COST: u32 = 6;
+
 pub struct WorkerService;
 
 impl WorkerService {
@@ -72,4 +77,46 @@ impl WorkerService {
             .get_result(conn)
             .map_err(AppError::DatabaseError)
     }
-}
\ No newline at end of file
+
+    /// Makes a new worker secret. Returns it along with the hash that is stored.
+    pub fn generate_token() -> Result<(String, String), AppError> {
+        let token = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+        let hash = bcrypt::hash(&token, TOKEN_HASH_COST).map_err(|_| AppError::InternalServerError)?;
+        Ok((token, hash))
+    }
+
+    /// Replaces a worker's secret, so containers running with the old one are turned away
+    pub fn rotate_token(pool: &DbPool, worker_id: Uuid, owner_id: Uuid) -> Result<(Worker, String), AppError> {
+        use crate::schema::workers::dsl::*;
+        let (token, hash) = Self::generate_token()?;
+        let conn = &mut pool.get()?;
+        let worker = diesel::update(workers.filter(id.eq(worker_id).and(user_id.eq(owner_id))))
+            .set(token_hash.eq(hash))
+            .get_result(conn)
+            .map_err(AppError::DatabaseError)?;
+        Ok((worker, token))
+    }
+
+    /// Identifies a worker calling the queue by its own secret, and records that it was seen
+    pub fn authenticate(pool: &DbPool, worker_id: Uuid, token: &str) -> Result<Worker, AppError> {
+        use crate::schema::workers::dsl::*;
+        let conn = &mut pool.get()?;
+        let hash = workers
+            .find(worker_id)
+            .select(token_hash)
+            .first::<Option<String>>(conn)
+            .optional()?
+            .flatten()
+            .ok_or(AppError::Unauthorized)?;
+        if !bcrypt::verify(token, &hash).unwrap_or(false) {
+            return Err(AppError::Unauthorized);
+        }
+        let worker = diesel::update(workers.find(worker_id))
+            .set(last_seen_at.eq(diesel::dsl::now))
+            .get_result::<Worker>(conn)?;
+        if !worker.active {
+            return Err(AppError::Forbidden(format!("Worker {} is not active", worker_id)));
+        }
+        Ok(worker)
+    }
+}
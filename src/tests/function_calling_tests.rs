@@ -189,3 +189,418 @@ async fn test_function_calling_manager() {
     assert_eq!(tool_results[0].result["location"], "Paris, France");
     assert_eq!(tool_results[0].result["units"], "celsius");
 }
+
+#[test]
+fn test_adapters_format_tool_exchange() {
+    use crate::services::function_calling::provider::anthropic::AnthropicAdapter;
+    use crate::services::function_calling::{Message, ProviderAdapter, ToolCall};
+
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "get_weather".to_string(),
+        arguments: json!({ "location": "Paris, France" }),
+    };
+    let messages = vec![
+        Message::system("You are helpful."),
+        Message::user("Weather in Paris?"),
+        Message::assistant_with_tool_calls("", vec![call]),
+        Message::tool_result("call_1", &json!({ "temperature": 22.5 })),
+    ];
+
+    let mut openai_request = json!({ "model": "gpt-4" });
+    OpenAIAdapter.format_messages(&mut openai_request, &messages);
+    let openai_messages = openai_request["messages"].as_array().unwrap();
+    assert_eq!(openai_messages.len(), 4);
+    assert_eq!(openai_messages[2]["tool_calls"][0]["function"]["name"], "get_weather");
+    assert_eq!(openai_messages[3]["role"], "tool");
+    assert_eq!(openai_messages[3]["tool_call_id"], "call_1");
+
+    let mut anthropic_request = json!({ "model": "claude-3" });
+    AnthropicAdapter.format_messages(&mut anthropic_request, &messages);
+    let anthropic_messages = anthropic_request["messages"].as_array().unwrap();
+    assert_eq!(anthropic_request["system"], "You are helpful.");
+    assert_eq!(anthropic_messages.len(), 3);
+    assert_eq!(anthropic_messages[1]["content"][0]["type"], "tool_use");
+    assert_eq!(anthropic_messages[2]["content"][0]["type"], "tool_result");
+    assert_eq!(anthropic_messages[2]["content"][0]["tool_use_id"], "call_1");
+}
+
+#[test]
+fn test_adapters_format_image_and_document_parts() {
+    use crate::services::function_calling::provider::anthropic::AnthropicAdapter;
+    use crate::services::function_calling::provider::gemini::GeminiAdapter;
+    use crate::services::function_calling::{ContentPart, MediaSource, Message, ProviderAdapter};
+
+    let messages = vec![Message::user_with_parts(
+        "Summarize these.",
+        vec![
+            ContentPart::Image {
+                source: MediaSource::Url { url: "https://example.com/chart.png".to_string() },
+            },
+            ContentPart::Document {
+                source: MediaSource::Base64 {
+                    media_type: "application/pdf".to_string(),
+                    data: "JVBERi0=".to_string(),
+                },
+                name: Some("report.pdf".to_string()),
+            },
+        ],
+    )];
+
+    let mut openai_request = json!({});
+    OpenAIAdapter.format_messages(&mut openai_request, &messages);
+    let content = &openai_request["messages"][0]["content"];
+    assert_eq!(content[0]["text"], "Summarize these.");
+    assert_eq!(content[1]["image_url"]["url"], "https://example.com/chart.png");
+    assert_eq!(content[2]["file"]["file_data"], "data:application/pdf;base64,JVBERi0=");
+
+    let mut anthropic_request = json!({});
+    AnthropicAdapter.format_messages(&mut anthropic_request, &messages);
+    let content = &anthropic_request["messages"][0]["content"];
+    assert_eq!(content[0]["source"]["type"], "url");
+    assert_eq!(content[1]["type"], "document");
+    assert_eq!(content[1]["source"]["media_type"], "application/pdf");
+    assert_eq!(content[2]["text"], "Summarize these.");
+
+    let mut gemini_request = json!({});
+    GeminiAdapter.format_messages(&mut gemini_request, &messages);
+    let parts = &gemini_request["contents"][0]["parts"];
+    assert_eq!(parts[1]["fileData"]["mimeType"], "image/png");
+    assert_eq!(parts[2]["inlineData"]["data"], "JVBERi0=");
+}
+
+#[tokio::test]
+async fn test_execute_tool_calls_reports_errors() {
+    use crate::services::function_calling::ToolCall;
+
+    let tool_registry = Arc::new(ToolRegistry::new());
+    tool_registry.register(WeatherTool::new()).await;
+    let manager = FunctionCallingManager::new(Arc::new(OpenAIAdapter), tool_registry);
+
+    let calls = vec![
+        ToolCall {
+            id: "call_ok".to_string(),
+            name: "get_weather".to_string(),
+            arguments: json!({ "location": "Paris, France" }),
+        },
+        ToolCall {
+            id: "call_missing".to_string(),
+            name: "get_weather".to_string(),
+            arguments: json!({}),
+        },
+    ];
+
+    let results = manager.execute_tool_calls(&calls).await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].result["location"], "Paris, France");
+    assert!(results[1].result["error"].as_str().unwrap().contains("location"));
+}
+
+#[tokio::test]
+async fn test_gemini_adapter_round_trip() {
+    use crate::services::function_calling::provider::gemini::GeminiAdapter;
+    use crate::services::function_calling::{Message, ProviderAdapter};
+
+    let adapter = GeminiAdapter;
+    let response = json!({
+        "candidates": [{
+            "content": {
+                "role": "model",
+                "parts": [
+                    { "text": "Checking the weather." },
+                    { "functionCall": { "name": "get_weather", "args": { "location": "Paris" } } }
+                ]
+            }
+        }]
+    });
+
+    let calls = adapter.parse_tool_calls(&response).await.unwrap();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].name, "get_weather");
+    assert_eq!(calls[0].arguments["location"], "Paris");
+    assert_eq!(adapter.parse_content(&response).as_deref(), Some("Checking the weather."));
+
+    let mut request = json!({});
+    adapter.format_messages(&mut request, &[
+        Message::system("Be brief."),
+        Message::user("Weather in Paris?"),
+        Message::assistant_with_tool_calls("", calls.clone()),
+        Message::tool_result(&calls[0].id, &json!({ "temperature": 22.5 })),
+    ]);
+    assert_eq!(request["systemInstruction"]["parts"][0]["text"], "Be brief.");
+    let contents = request["contents"].as_array().unwrap();
+    assert_eq!(contents.len(), 3);
+    assert_eq!(contents[2]["parts"][0]["functionResponse"]["name"], "get_weather");
+}
+
+#[test]
+fn test_llm_chat_message_conversion_preserves_tool_data() {
+    use crate::services::function_calling::{Message, ToolCall};
+    use crate::services::llm_service::LLMChatMessage;
+
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "get_weather".to_string(),
+        arguments: json!({ "location": "Paris" }),
+    };
+    let original = vec![
+        Message::assistant_with_tool_calls("", vec![call]),
+        Message::tool_result("call_1", &json!({ "temperature": 22.5 })),
+    ];
+
+    let converted: Vec<LLMChatMessage> = original.iter().map(LLMChatMessage::from).collect();
+    assert_eq!(converted[0].tool_calls.len(), 1);
+    assert_eq!(converted[1].role, "tool");
+    assert_eq!(converted[1].tool_call_id.as_deref(), Some("call_1"));
+
+    match Message::from(&converted[1]) {
+        Message::ToolResult { tool_call_id, content } => {
+            assert_eq!(tool_call_id, "call_1");
+            assert_eq!(content["temperature"], 22.5);
+        }
+        other => panic!("unexpected message: {:?}", other),
+    }
+}
+
+#[test]
+fn test_openai_stream_assembler_joins_argument_fragments() {
+    use crate::services::function_calling::provider::{OpenAIToolCallAssembler, StreamDelta, ToolCallAssembler};
+
+    let mut assembler = OpenAIToolCallAssembler::new();
+    let events = vec![
+        json!({ "choices": [{ "delta": { "content": "Let me check." }, "finish_reason": null }] }),
+        json!({ "choices": [{ "delta": { "tool_calls": [
+            { "index": 0, "id": "call_1", "type": "function", "function": { "name": "get_weather", "arguments": "" } }
+        ] }, "finish_reason": null }] }),
+        json!({ "choices": [{ "delta": { "tool_calls": [
+            { "index": 0, "function": { "arguments": "{\"locat" } }
+        ] }, "finish_reason": null }] }),
+        json!({ "choices": [{ "delta": { "tool_calls": [
+            { "index": 0, "function": { "arguments": "ion\": \"Paris\"}" } }
+        ] }, "finish_reason": null }] }),
+    ];
+
+    let mut deltas = Vec::new();
+    for event in &events {
+        deltas.extend(assembler.push_event(event).unwrap());
+    }
+    assert_eq!(deltas, vec![StreamDelta::Text("Let me check.".to_string())]);
+
+    let finished = assembler
+        .push_event(&json!({ "choices": [{ "delta": {}, "finish_reason": "tool_calls" }] }))
+        .unwrap();
+    match finished.as_slice() {
+        [StreamDelta::ToolCall(call)] => {
+            assert_eq!(call.id, "call_1");
+            assert_eq!(call.name, "get_weather");
+            assert_eq!(call.arguments, json!({ "location": "Paris" }));
+        }
+        other => panic!("unexpected deltas: {:?}", other),
+    }
+    assert!(assembler.finish().unwrap().is_empty());
+}
+
+#[test]
+fn test_anthropic_stream_assembler_joins_input_json_deltas() {
+    use crate::services::function_calling::provider::{AnthropicToolCallAssembler, StreamDelta, ToolCallAssembler};
+
+    let mut assembler = AnthropicToolCallAssembler::new();
+    let events = vec![
+        json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "text", "text": "" } }),
+        json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "Checking." } }),
+        json!({ "type": "content_block_stop", "index": 0 }),
+        json!({ "type": "content_block_start", "index": 1, "content_block": {
+            "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {}
+        } }),
+        json!({ "type": "content_block_delta", "index": 1, "delta": { "type": "input_json_delta", "partial_json": "{\"location\":" } }),
+        json!({ "type": "content_block_delta", "index": 1, "delta": { "type": "input_json_delta", "partial_json": " \"Paris\"}" } }),
+        json!({ "type": "content_block_stop", "index": 1 }),
+        json!({ "type": "message_stop" }),
+    ];
+
+    let mut deltas = Vec::new();
+    for event in &events {
+        deltas.extend(assembler.push_event(event).unwrap());
+    }
+
+    assert_eq!(deltas.len(), 2);
+    assert_eq!(deltas[0], StreamDelta::Text("Checking.".to_string()));
+    match &deltas[1] {
+        StreamDelta::ToolCall(call) => {
+            assert_eq!(call.id, "toolu_1");
+            assert_eq!(call.arguments, json!({ "location": "Paris" }));
+        }
+        other => panic!("unexpected delta: {:?}", other),
+    }
+
+    let error = assembler.push_event(&json!({
+        "type": "error",
+        "error": { "type": "overloaded_error", "message": "Overloaded" }
+    }));
+    assert!(error.is_err());
+}
+
+#[test]
+fn test_gemini_stream_assembler_reports_calls_and_usage() {
+    use crate::services::function_calling::provider::{GeminiStreamAssembler, StreamDelta, ToolCallAssembler};
+
+    let mut assembler = GeminiStreamAssembler::new();
+    let text = assembler.push_event(&json!({
+        "candidates": [{ "content": { "parts": [{ "text": "Checking." }] } }]
+    })).unwrap();
+    assert_eq!(text, vec![StreamDelta::Text("Checking.".to_string())]);
+
+    let last = assembler.push_event(&json!({
+        "candidates": [{ "content": { "parts": [{
+            "functionCall": { "name": "get_weather", "args": { "location": "Paris" } }
+        }] } }],
+        "usageMetadata": { "promptTokenCount": 8, "candidatesTokenCount": 4, "totalTokenCount": 12 }
+    })).unwrap();
+    assert_eq!(last.len(), 2);
+    match &last[0] {
+        StreamDelta::ToolCall(call) => {
+            assert_eq!(call.id, "get_weather-0");
+            assert_eq!(call.arguments, json!({ "location": "Paris" }));
+        }
+        other => panic!("unexpected delta: {:?}", other),
+    }
+    assert!(matches!(&last[1], StreamDelta::Usage(usage) if usage["totalTokenCount"] == 12));
+}
+
+#[test]
+fn test_cohere_stream_assembler_joins_tool_call_deltas() {
+    use crate::services::function_calling::provider::{CohereStreamAssembler, StreamDelta, ToolCallAssembler};
+
+    let mut assembler = CohereStreamAssembler::new();
+    let events = vec![
+        json!({ "type": "content-delta", "index": 0, "delta": { "message": { "content": { "text": "Checking." } } } }),
+        json!({ "type": "tool-call-start", "index": 0, "delta": { "message": { "tool_calls": {
+            "id": "call_1", "type": "function", "function": { "name": "get_weather", "arguments": "" }
+        } } } }),
+        json!({ "type": "tool-call-delta", "index": 0, "delta": { "message": { "tool_calls": {
+            "function": { "arguments": "{\"location\": \"Paris\"}" }
+        } } } }),
+        json!({ "type": "tool-call-end", "index": 0 }),
+        json!({ "type": "message-end", "delta": { "finish_reason": "TOOL_CALL", "usage": {
+            "billed_units": { "input_tokens": 7, "output_tokens": 3 }
+        } } }),
+    ];
+
+    let mut deltas = Vec::new();
+    for event in &events {
+        deltas.extend(assembler.push_event(event).unwrap());
+    }
+
+    assert_eq!(deltas.len(), 3);
+    assert_eq!(deltas[0], StreamDelta::Text("Checking.".to_string()));
+    match &deltas[1] {
+        StreamDelta::ToolCall(call) => {
+            assert_eq!(call.id, "call_1");
+            assert_eq!(call.arguments, json!({ "location": "Paris" }));
+        }
+        other => panic!("unexpected delta: {:?}", other),
+    }
+    assert!(matches!(&deltas[2], StreamDelta::Usage(usage) if usage["billed_units"]["input_tokens"] == 7));
+    assert!(assembler.finish().unwrap().is_empty());
+}
+
+#[test]
+fn test_sse_buffer_handles_split_lines() {
+    use crate::services::function_calling::provider::SseBuffer;
+
+    let mut buffer = SseBuffer::new();
+    assert!(buffer.push(b"event: message\ndata: {\"a\":").is_empty());
+    assert_eq!(buffer.push(b"1}\r\n\ndata: [DONE]\n"), vec!["{\"a\":1}", "[DONE]"]);
+}
+
+#[tokio::test]
+async fn test_registry_validates_arguments_against_schema() {
+    use crate::services::function_calling::ToolError;
+
+    let registry = ToolRegistry::new();
+    registry.register(WeatherTool::new()).await;
+    registry.update_tool_parameters("get_weather", vec![
+        ToolParameter::new("location", ParameterType::String { format: None, enum_values: None }, true),
+        ToolParameter::new("units", ParameterType::String {
+            format: None,
+            enum_values: Some(vec!["celsius".to_string(), "fahrenheit".to_string()]),
+        }, false),
+    ]).await;
+
+    let result = registry.execute("get_weather", json!({ "units": "kelvin" })).await;
+    match result {
+        Err(ToolError::InvalidArgument(message)) => {
+            assert!(message.contains("$.location: is required"));
+            assert!(message.contains("$.units: must be one of"));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let result = registry.execute("get_weather", json!({ "location": "Paris" })).await.unwrap();
+    assert_eq!(result["location"], "Paris");
+}
+
+#[tokio::test]
+async fn test_registry_requires_confirmation_for_dangerous_tools() {
+    use crate::services::function_calling::ToolError;
+
+    let user_id = uuid::Uuid::new_v4();
+    let registry = ToolRegistry::for_user(user_id);
+    registry.register(WeatherTool::new()).await;
+    assert_eq!(registry.owner(), Some(user_id));
+
+    registry.set_dangerous("get_weather", true).await;
+    assert!(registry.is_dangerous("get_weather").await);
+    match registry.execute("get_weather", json!({ "location": "Paris" })).await {
+        Err(ToolError::ConfirmationRequired(name)) => assert_eq!(name, "get_weather"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let result = registry.execute_confirmed("get_weather", json!({ "location": "Paris" })).await.unwrap();
+    assert_eq!(result["location"], "Paris");
+
+    registry.set_dangerous("get_weather", false).await;
+    assert!(registry.execute("get_weather", json!({ "location": "Paris" })).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_handle_response_holds_dangerous_calls_for_approval() {
+    use crate::services::function_calling::{ApprovalMode, FunctionCallingError};
+
+    let tool_registry = Arc::new(ToolRegistry::new());
+    tool_registry.register(WeatherTool::new()).await;
+    tool_registry.set_dangerous("get_weather", true).await;
+    let manager = FunctionCallingManager::new(Arc::new(OpenAIAdapter), tool_registry)
+        .with_approval_mode(ApprovalMode::Dangerous);
+
+    let response = json!({
+        "choices": [{
+            "message": {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {
+                        "name": "get_weather",
+                        "arguments": "{\"location\":\"Paris, France\"}"
+                    }
+                }]
+            },
+            "finish_reason": "tool_calls"
+        }]
+    });
+
+    let handled = manager.handle_response_with_approval(&response).await.unwrap();
+    assert!(handled.results.is_empty());
+    assert_eq!(handled.pending_approval.len(), 1);
+    assert_eq!(handled.pending_approval[0].id, "call_1");
+
+    match manager.handle_response(&response).await {
+        Err(FunctionCallingError::ApprovalRequired(calls)) => assert_eq!(calls[0].name, "get_weather"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let approved = manager.execute_approved_tool_call(&handled.pending_approval[0]).await.unwrap();
+    assert_eq!(approved["location"], "Paris, France");
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde_json::json;
+use crate::services::function_calling::mcp::{register_mcp_tools, McpClient};
+use crate::services::function_calling::{ParameterType, ToolError, ToolRegistry};
+
+async fn connect_stub() -> McpClient {
+    let script = format!("{}/tests/fixtures/mcp_stub_server.sh", env!("CARGO_MANIFEST_DIR"));
+    McpClient::connect_stdio("sh", &[script], &HashMap::new())
+        .await
+        .expect("stub MCP server should start")
+}
+
+#[tokio::test]
+async fn test_mcp_client_discovers_and_calls_tools() {
+    let client = connect_stub().await;
+
+    let tools = client.list_tools().await.unwrap();
+    assert_eq!(tools.len(), 1);
+    assert_eq!(tools[0].name, "echo");
+    assert_eq!(tools[0].input_schema["required"], json!(["message"]));
+
+    let result = client.call_tool("echo", json!({ "message": "hello" })).await.unwrap();
+    assert_eq!(result, json!({ "echo": "hello" }));
+
+    match client.call_tool("fail", json!({})).await {
+        Err(ToolError::RemoteToolError(message)) => assert_eq!(message, "boom"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_mcp_tools_register_with_mapped_schema() {
+    let client = Arc::new(connect_stub().await);
+    let definitions = client.list_tools().await.unwrap();
+
+    let registry = ToolRegistry::new();
+    let names = register_mcp_tools(&registry, client, "stub", &definitions).await;
+    assert_eq!(names, vec!["stub__echo".to_string()]);
+
+    let tool = registry.get_tool("stub__echo").await.unwrap();
+    assert_eq!(tool.description, "Echo a message back");
+    let times = tool.parameters.iter().find(|p| p.name == "times").unwrap();
+    assert!(!times.required);
+    assert!(matches!(times.parameter_type, ParameterType::Number { minimum: Some(min), .. } if min == 1.0));
+
+    let result = registry.execute("stub__echo", json!({ "message": "hi" })).await.unwrap();
+    assert_eq!(result["echo"], "hi");
+
+    // Arguments are checked against the discovered schema before reaching the server
+    match registry.execute("stub__echo", json!({ "times": 0 })).await {
+        Err(ToolError::InvalidArgument(message)) => {
+            assert!(message.contains("$.message: is required"));
+            assert!(message.contains("$.times: must be >= 1"));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -1,5 +1,8 @@
 use actix_web::{test, web, App};
-use crate::handlers::metrics::{metrics, init_metrics, set_scheduled_jobs, SCHEDULED_JOBS_GAUGE};
+use crate::handlers::metrics::{
+    metrics, init_metrics, record_llm_cache_lookup, set_scheduled_jobs, LLM_CACHE_COUNTER,
+    SCHEDULED_JOBS_GAUGE,
+};
 
 #[actix_web::test]
 async fn test_metrics_endpoint() {
@@ -11,3 +14,17 @@ async fn test_metrics_endpoint() {
     assert!(resp.status().is_success());
     assert_eq!(SCHEDULED_JOBS_GAUGE.get(), 5);
 }
+
+#[test]
+fn test_llm_cache_counter_tracks_hits_and_misses() {
+    init_metrics();
+    let hits = LLM_CACHE_COUNTER.with_label_values(&["hit"]).get();
+    let misses = LLM_CACHE_COUNTER.with_label_values(&["miss"]).get();
+
+    record_llm_cache_lookup(true);
+    record_llm_cache_lookup(false);
+    record_llm_cache_lookup(false);
+
+    assert_eq!(LLM_CACHE_COUNTER.with_label_values(&["hit"]).get(), hits + 1.0);
+    assert_eq!(LLM_CACHE_COUNTER.with_label_values(&["miss"]).get(), misses + 2.0);
+}
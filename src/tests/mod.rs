@@ -4,3 +4,4 @@ mod metrics_tests;
 mod config_tests;
 mod function_calling_tests;
 
+mod mcp_tests;
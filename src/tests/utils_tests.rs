@@ -38,3 +38,30 @@ fn test_jwt_token_generation_and_validation() {
     let new_version = increment_token_version(&user_id);
     assert_eq!(new_version, version + 1);
 }
+
+#[test]
+fn test_json_path_select() {
+    use crate::utils::json_path::{select, validate};
+    use serde_json::json;
+
+    let data = json!({
+        "data": {
+            "items": [
+                { "id": 1, "tags": ["a", "b"] },
+                { "id": 2, "tags": ["c"] }
+            ],
+            "total": 2
+        }
+    });
+
+    assert_eq!(select(&data, "$.data.total").unwrap(), vec![&json!(2)]);
+    assert_eq!(select(&data, "$.data.items[1].id").unwrap(), vec![&json!(2)]);
+    assert_eq!(select(&data, "$.data.items[-1].tags[0]").unwrap(), vec![&json!("c")]);
+    assert_eq!(select(&data, "$['data']['items'][*].id").unwrap(), vec![&json!(1), &json!(2)]);
+    assert_eq!(select(&data, "data.total").unwrap(), vec![&json!(2)]);
+    assert!(select(&data, "$.data.missing").unwrap().is_empty());
+    assert_eq!(select(&data, "$").unwrap(), vec![&data]);
+
+    assert!(validate("$.data[").is_err());
+    assert!(validate("$..items").is_err());
+}
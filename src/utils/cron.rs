@@ -0,0 +1,283 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use std::str::FromStr;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How many years ahead to look for a match before deciding an expression never fires,
+/// long enough to reach the next 29 February across a skipped leap year
+const SEARCH_YEARS: i32 = 9;
+
+/// A five-field cron expression: minute, hour, day of month, month and day of week.
+///
+/// Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps (`*/10`, `8-18/2`).
+/// Months and weekdays may be names (`JAN`, `MON`), and Sunday is `0` or `7`. As in classic
+/// cron, when both day fields are restricted a day matches if either of them does.
+/// `@hourly`, `@daily`, `@midnight`, `@weekly`, `@monthly`, `@yearly` and `@annually` are
+/// shorthands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl FromStr for CronExpression {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = match expression.trim().to_ascii_lowercase().as_str() {
+            "@hourly" => "0 * * * *".to_string(),
+            "@daily" | "@midnight" => "0 0 * * *".to_string(),
+            "@weekly" => "0 0 * * 0".to_string(),
+            "@monthly" => "0 0 1 * *".to_string(),
+            "@yearly" | "@annually" => "0 0 1 1 *".to_string(),
+            other => other.to_string(),
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Expected 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            ));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7, WEEKDAY_NAMES, 0)
+            .map_err(|e| format!("day of week: {}", e))?;
+        // 7 is another name for Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59, &[], 0).map_err(|e| format!("minute: {}", e))?,
+            hours: parse_field(fields[1], 0, 23, &[], 0).map_err(|e| format!("hour: {}", e))?,
+            days: parse_field(fields[2], 1, 31, &[], 0)
+                .map_err(|e| format!("day of month: {}", e))?,
+            months: parse_field(fields[3], 1, 12, MONTH_NAMES, 1)
+                .map_err(|e| format!("month: {}", e))?,
+            weekdays,
+            days_restricted: !fields[2].starts_with('*'),
+            weekdays_restricted: !fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl CronExpression {
+    /// The first matching minute strictly after `after`, or `None` if there is none in the
+    /// next few years (e.g. `0 0 30 2 *`)
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let last_year = after.year() + SEARCH_YEARS;
+
+        while time.year() <= last_year {
+            if !has(self.months, time.month()) {
+                let (year, month) = if time.month() == 12 {
+                    (time.year() + 1, 1)
+                } else {
+                    (time.year(), time.month() + 1)
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.day_matches(time.date()) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !has(self.hours, time.hour()) {
+                time = time.date().and_hms_opt(time.hour(), 0, 0)? + Duration::hours(1);
+                continue;
+            }
+            if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parses one field into a bit set of the values it allows
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    first_name: u32,
+) -> Result<u64, String> {
+    let mut set = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step '{}'", step))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                value(start, names, first_name)?,
+                value(end, names, first_name)?,
+            )
+        } else {
+            let start = value(range, names, first_name)?;
+            // `5/15` runs from 5 to the end of the range
+            (start, if step.is_some() { max } else { start })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("'{}' is outside {}-{}", range, min, max));
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+fn value(text: &str, names: &[&str], first_name: u32) -> Result<u32, String> {
+    if let Ok(number) = text.parse() {
+        return Ok(number);
+    }
+    names
+        .iter()
+        .position(|name| *name == text)
+        .map(|index| index as u32 + first_name)
+        .ok_or_else(|| format!("invalid value '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> Option<NaiveDateTime> {
+        expression
+            .parse::<CronExpression>()
+            .unwrap()
+            .next_after(at(after))
+    }
+
+    #[test]
+    fn finds_the_next_matching_minute() {
+        assert_eq!(
+            next("0 2 * * *", "2025-06-01 01:30"),
+            Some(at("2025-06-01 02:00"))
+        );
+        assert_eq!(
+            next("0 2 * * *", "2025-06-01 02:00"),
+            Some(at("2025-06-02 02:00"))
+        );
+        assert_eq!(
+            next("*/15 * * * *", "2025-06-01 10:07"),
+            Some(at("2025-06-01 10:15"))
+        );
+        assert_eq!(
+            next("30 8-18/2 * * *", "2025-06-01 09:00"),
+            Some(at("2025-06-01 10:30"))
+        );
+    }
+
+    #[test]
+    fn rolls_over_months_and_years() {
+        assert_eq!(
+            next("0 0 1 * *", "2025-12-15 00:00"),
+            Some(at("2026-01-01 00:00"))
+        );
+        assert_eq!(
+            next("@yearly", "2025-01-01 00:00"),
+            Some(at("2026-01-01 00:00"))
+        );
+        assert_eq!(
+            next("0 12 29 feb *", "2025-03-01 00:00"),
+            Some(at("2028-02-29 12:00"))
+        );
+    }
+
+    #[test]
+    fn weekdays_accept_names_and_sunday_as_seven() {
+        // 2025-06-01 is a Sunday
+        assert_eq!(
+            next("0 9 * * mon-fri", "2025-05-31 12:00"),
+            Some(at("2025-06-02 09:00"))
+        );
+        assert_eq!(
+            next("0 9 * * 7", "2025-05-31 12:00"),
+            Some(at("2025-06-01 09:00"))
+        );
+        assert_eq!(
+            next("0 0 * JAN,jul *", "2025-02-01 00:00"),
+            Some(at("2025-07-01 00:00"))
+        );
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 13th, or any Friday
+        assert_eq!(
+            next("0 0 13 * 5", "2025-06-01 00:00"),
+            Some(at("2025-06-06 00:00"))
+        );
+        assert_eq!(
+            next("0 0 13 * 5", "2025-06-10 00:00"),
+            Some(at("2025-06-13 00:00"))
+        );
+    }
+
+    #[test]
+    fn impossible_dates_never_fire() {
+        assert_eq!(next("0 0 30 2 *", "2025-01-01 00:00"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in [
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "0 0 0 * *",
+            "*/0 * * * *",
+            "0 0 * foo *",
+            "5-1 * * * *",
+        ] {
+            assert!(
+                expression.parse::<CronExpression>().is_err(),
+                "{} parsed",
+                expression
+            );
+        }
+    }
+}
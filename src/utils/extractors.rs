@@ -1,6 +1,9 @@
+use crate::db::DbPool;
+use crate::models::worker::Worker;
+use crate::services::worker_service::WorkerService;
 use crate::utils::jwt::validate_token;
-use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
-use futures::future::{ready, Ready};
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpRequest};
+use futures::future::{ready, LocalBoxFuture, Ready};
 use uuid::Uuid;
 
 pub struct AuthenticatedUser(pub Uuid);
@@ -34,3 +37,48 @@ impl FromRequest for AuthenticatedUser {
         }
     }
 }
+
+/// A registered worker calling the job queue. It sends its id in `X-Worker-Id` and the token
+/// issued for it as a bearer token.
+pub struct QueueWorker(pub Worker);
+
+impl FromRequest for QueueWorker {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let worker_id = req
+            .headers()
+            .get("X-Worker-Id")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| Uuid::parse_str(v).ok());
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let (worker_id, token) = match (worker_id, token) {
+            (Some(worker_id), Some(token)) => (worker_id, token.to_string()),
+            _ => {
+                return Box::pin(ready(Err(actix_web::error::ErrorUnauthorized(
+                    "Missing worker id or token",
+                ))))
+            }
+        };
+        let pool = match req.app_data::<web::Data<DbPool>>() {
+            Some(pool) => pool.clone(),
+            None => {
+                return Box::pin(ready(Err(actix_web::error::ErrorInternalServerError(
+                    "Database pool not configured",
+                ))))
+            }
+        };
+
+        // Checking the token hash and recording the visit would block the worker thread
+        Box::pin(async move {
+            let worker =
+                web::block(move || WorkerService::authenticate(&pool, worker_id, &token)).await??;
+            Ok(QueueWorker(worker))
+        })
+    }
+}
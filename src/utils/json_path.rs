@@ -0,0 +1,96 @@
+use serde_json::Value;
+
+/// One step of a parsed JSONPath expression
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(i64),
+    Wildcard,
+}
+
+/// Parses the JSONPath subset we support: `$`, `.field`, `['field']`, `[0]`, `[-1]`, `[*]` and `.*`
+fn parse(path: &str) -> Result<Vec<Segment>, String> {
+    let path = path.trim();
+    let rest = path.strip_prefix('$').unwrap_or(path);
+    let chars: Vec<char> = rest.chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && chars[end] != '.' && chars[end] != '[' {
+                    end += 1;
+                }
+                let name: String = chars[start..end].iter().collect();
+                match name.as_str() {
+                    "" => return Err(format!("Empty field name at position {} in '{}'", start, path)),
+                    "*" => segments.push(Segment::Wildcard),
+                    _ => segments.push(Segment::Field(name)),
+                }
+                i = end;
+            }
+            '[' => {
+                let close = chars[i..].iter().position(|c| *c == ']')
+                    .map(|offset| i + offset)
+                    .ok_or_else(|| format!("Unclosed '[' in '{}'", path))?;
+                let inner: String = chars[i + 1..close].iter().collect();
+                let inner = inner.trim();
+
+                if inner == "*" {
+                    segments.push(Segment::Wildcard);
+                } else if let Some(quoted) = inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+                {
+                    segments.push(Segment::Field(quoted.to_string()));
+                } else {
+                    let index = inner.parse::<i64>()
+                        .map_err(|_| format!("Invalid index '{}' in '{}'", inner, path))?;
+                    segments.push(Segment::Index(index));
+                }
+                i = close + 1;
+            }
+            // Allow a leading bare field name, e.g. `data.items`
+            _ if segments.is_empty() && i == 0 => {
+                let mut end = i;
+                while end < chars.len() && chars[end] != '.' && chars[end] != '[' {
+                    end += 1;
+                }
+                segments.push(Segment::Field(chars[i..end].iter().collect()));
+                i = end;
+            }
+            c => return Err(format!("Unexpected '{}' at position {} in '{}'", c, i, path)),
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Returns every value in `value` matched by `path`
+pub fn select<'a>(value: &'a Value, path: &str) -> Result<Vec<&'a Value>, String> {
+    let mut current = vec![value];
+
+    for segment in parse(path)? {
+        current = current.into_iter().flat_map(|value| -> Vec<&Value> {
+            match (&segment, value) {
+                (Segment::Field(name), Value::Object(map)) => map.get(name).into_iter().collect(),
+                (Segment::Index(index), Value::Array(items)) => {
+                    let index = if *index < 0 { items.len() as i64 + index } else { *index };
+                    usize::try_from(index).ok().and_then(|i| items.get(i)).into_iter().collect()
+                }
+                (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                (Segment::Wildcard, Value::Object(map)) => map.values().collect(),
+                _ => Vec::new(),
+            }
+        }).collect();
+    }
+
+    Ok(current)
+}
+
+/// Checks that a path is in the supported subset
+pub fn validate(path: &str) -> Result<(), String> {
+    parse(path).map(|_| ())
+}
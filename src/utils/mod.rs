@@ -1,4 +1,7 @@
 pub mod auth;
+pub mod cron;
 pub mod encryption;
 pub mod extractors;
+pub mod json_path;
 pub mod jwt;
+pub mod url_guard;
@@ -0,0 +1,168 @@
+use reqwest::Url;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+
+/// Parses a URL users want the server to request, allowing only http(s) and rejecting hosts
+/// that are internal addresses as written, e.g. `localhost` or `169.254.169.254`
+pub fn parse_public_url(url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(format!("Only http and https URLs are allowed, got '{}'", parsed.scheme()));
+    }
+
+    let host = parsed.host_str().ok_or_else(|| format!("URL '{}' has no host", url))?;
+    // IP hosts come normalized from the parser, e.g. `0x7f.1` as `127.0.0.1`
+    if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        check_ip(ip)?;
+    } else {
+        let domain = host.trim_end_matches('.').to_ascii_lowercase();
+        if domain == "localhost" || domain.ends_with(".localhost") {
+            return Err(format!("URL '{}' points to a local address", url));
+        }
+    }
+    Ok(parsed)
+}
+
+/// Resolves the URL's host and returns its addresses, failing if any of them is internal. The
+/// request should then be sent to these addresses, so the name can't resolve differently by the
+/// time it's made.
+pub async fn resolve_public(url: &Url) -> Result<Vec<SocketAddr>, String> {
+    let host = url.host_str().ok_or_else(|| format!("URL '{}' has no host", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    // IPv6 literals come bracketed from `host_str`
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Couldn't resolve '{}': {}", host, e))?
+        .collect();
+    check_addrs(host, &addrs)?;
+    Ok(addrs)
+}
+
+/// Like `resolve_public`, but blocking, for checks made outside the async runtime. A host that
+/// can't be resolved right now is let through, since it's resolved again for every request.
+pub fn check_resolved(url: &Url) -> Result<(), String> {
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return Err(format!("URL '{}' has no host", url)),
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+    match (host, port).to_socket_addrs() {
+        Ok(addrs) => check_addrs(host, &addrs.collect::<Vec<_>>()),
+        Err(_) => Ok(()),
+    }
+}
+
+fn check_addrs(host: &str, addrs: &[SocketAddr]) -> Result<(), String> {
+    if addrs.is_empty() {
+        return Err(format!("'{}' didn't resolve to any address", host));
+    }
+    for addr in addrs {
+        check_ip(addr.ip()).map_err(|_| format!("'{}' resolves to a non-public address", host))?;
+    }
+    Ok(())
+}
+
+fn check_ip(ip: IpAddr) -> Result<(), String> {
+    if is_public(ip) {
+        Ok(())
+    } else {
+        Err(format!("{} is not a public address", ip))
+    }
+}
+
+/// Whether `ip` is on the public internet. Loopback, private, link-local (which covers the cloud
+/// metadata service at 169.254.169.254), shared, multicast and reserved ranges are not.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            // IPv4-mapped and NAT64 addresses reach the IPv4 address they embed
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_v4(v4);
+            }
+            let segments = ip.segments();
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return is_public_v4(Ipv4Addr::new(a, b, c, d));
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7, which includes AWS's fd00:ec2::254 metadata address
+                || (segments[0] & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (segments[0] & 0xffc0) == 0xfe80
+                // Documentation, 2001:db8::/32
+                || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+        }
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, _, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network", 0.0.0.0/8
+        || a == 0
+        // Shared address space, 100.64.0.0/10, used for e.g. Alibaba Cloud's metadata service
+        || (a == 100 && (64..128).contains(&b))
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b == 18 || b == 19))
+        // Reserved, 240.0.0.0/4
+        || a >= 240)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_other_schemes() {
+        assert!(parse_public_url("https://api.example.com/v1").is_ok());
+        assert!(parse_public_url("http://api.example.com:8080/v1").is_ok());
+        assert!(parse_public_url("file:///etc/passwd").is_err());
+        assert!(parse_public_url("gopher://api.example.com").is_err());
+        assert!(parse_public_url("not a url").is_err());
+    }
+
+    #[test]
+    fn rejects_internal_hosts_as_written() {
+        for url in [
+            "http://localhost:8000/admin",
+            "http://api.localhost./",
+            "http://127.0.0.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.0.0.5/",
+            "http://192.168.1.1/",
+            "http://172.16.0.1/",
+            "http://100.100.100.200/",
+            "http://0.0.0.0:8000/",
+            "http://[::1]/",
+            "http://[fd00:ec2::254]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[64:ff9b::a9fe:a9fe]/",
+        ] {
+            assert!(parse_public_url(url).is_err(), "{} was accepted", url);
+        }
+        assert!(parse_public_url("http://93.184.216.34/").is_ok());
+        assert!(parse_public_url("http://[2606:2800:220:1::]/").is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_names_resolving_to_internal_addresses() {
+        let url = Url::parse("http://localhost:9/").unwrap();
+        assert!(resolve_public(&url).await.is_err());
+        assert!(check_resolved(&url).is_err());
+
+        let url = Url::parse("http://127.0.0.1:9/").unwrap();
+        assert!(resolve_public(&url).await.is_err());
+    }
+}
@@ -0,0 +1,24 @@
+#!/bin/sh
+# Minimal MCP server speaking newline-delimited JSON-RPC over stdio, used by the MCP client tests.
+# It offers one tool, "echo", and answers calls to "fail" with a tool error.
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"stub","version":"0.1.0"}}}\n' "$id"
+      ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echo a message back","inputSchema":{"type":"object","properties":{"message":{"type":"string","description":"Text to echo"},"times":{"type":"integer","minimum":1}},"required":["message"]}}]}}\n' "$id"
+      ;;
+    *'"method":"tools/call"'*'"name":"fail"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"boom"}],"isError":true}}\n' "$id"
+      ;;
+    *'"method":"tools/call"'*)
+      message=$(printf '%s' "$line" | sed -n 's/.*"message":"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"{\\"echo\\":\\"%s\\"}"}]}}\n' "$id" "$message"
+      ;;
+    *'"id":'*)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"Method not found"}}\n' "$id"
+      ;;
+  esac
+done
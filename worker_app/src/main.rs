@@ -1,66 +1,237 @@
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::collections::HashMap;
+use std::io;
+use std::process::{ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
 
-#[derive(Debug, Serialize, Deserialize)]
+mod queue;
+
+/// How long a stopped command gets to exit after SIGTERM before it's killed
+const DEFAULT_STOP_TIMEOUT_SECONDS: u64 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandRequest {
     pub command: String,
     pub args: Vec<String>,
+    /// Lets the command be stopped through `/stop`
+    pub run_id: Option<String>,
 }
 
+/// One line of the response to `/execute`: output lines as the command produces them, then its
+/// exit status. Queued runs report the same events to the backend.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct CommandResult {
-    pub output: String,
-    pub error: Option<String>,
-    pub exit_code: i32,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerEvent {
+    Log {
+        stream: String,
+        line: String,
+    },
+    Exit {
+        exit_code: i32,
+        error: Option<String>,
+        /// Whether the command ended because of a stop request
+        stopped: bool,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StopRequest {
     pub run_id: String,
+    pub timeout_seconds: Option<u64>,
 }
 
-async fn execute_command(command_request: web::Json<CommandRequest>) -> impl Responder {
+type EventSender = mpsc::Sender<WorkerEvent>;
+
+/// Commands that are still running, by run id. Each one waits on its channel for a stop request
+/// carrying the grace period before SIGKILL.
+#[derive(Default)]
+struct Runs(Mutex<HashMap<String, mpsc::Sender<Duration>>>);
+
+async fn execute_command(
+    runs: web::Data<Runs>,
+    command_request: web::Json<CommandRequest>,
+) -> impl Responder {
     println!("Received command request: {:?}", command_request);
 
-    let mut command = Command::new("fluent");
-    command.arg(&command_request.command);
-    command.args(&command_request.args);
-
-    let output = command.output();
-
-    println!("Command executed, output: {:?}", output);
-
-    match output {
-        Ok(output) => {
-            let result = CommandResult {
-                output: String::from_utf8_lossy(&output.stdout).to_string(),
-                error: if output.status.success() {
-                    None
-                } else {
-                    Some(String::from_utf8_lossy(&output.stderr).to_string())
-                },
-                exit_code: output.status.code().unwrap_or(-1),
-            };
-            println!("Sending response: {:?}", result);
-            HttpResponse::Ok().json(result)
-        }
+    let child = match spawn_fluent(&command_request) {
+        Ok(child) => child,
         Err(e) => {
             println!("Error executing command: {:?}", e);
-            HttpResponse::InternalServerError().body(format!("Failed to execute command: {}", e))
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to execute command: {}", e));
         }
+    };
+
+    let (tx, mut rx) = mpsc::channel(100);
+    let (stop_tx, stop_rx) = mpsc::channel(1);
+    let run_id = command_request.run_id.clone();
+    if let Some(run_id) = &run_id {
+        runs.0.lock().unwrap().insert(run_id.clone(), stop_tx);
     }
+
+    actix_web::rt::spawn(async move {
+        run_command(child, tx, stop_rx).await;
+        if let Some(run_id) = &run_id {
+            runs.0.lock().unwrap().remove(run_id);
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(futures::stream::poll_fn(move |cx| {
+            rx.poll_recv(cx)
+                .map(|event| event.map(|event| Ok::<_, actix_web::Error>(to_ndjson(&event))))
+        }))
+}
+
+/// Starts `fluent` with the request's arguments, in its own process group so stopping it reaches
+/// the processes it starts too
+fn spawn_fluent(request: &CommandRequest) -> io::Result<Child> {
+    let mut command = Command::new("fluent");
+    command.arg(&request.command);
+    command.args(&request.args);
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    command.process_group(0);
+    command.spawn()
 }
 
-async fn stop_command(_req: web::Json<StopRequest>) -> impl Responder {
-    println!("Received stop request: {:?}", _req);
-    HttpResponse::Ok().finish()
+/// Sends the command's output lines to `events` as they're produced, then its exit status
+async fn run_command(mut child: Child, events: EventSender, stop: mpsc::Receiver<Duration>) {
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let (_, _, (status, stopped)) = tokio::join!(
+        forward_lines(stdout, "stdout", events.clone()),
+        forward_lines(stderr, "stderr", events.clone()),
+        wait_or_stop(&mut child, stop),
+    );
+
+    let exit = match status {
+        Ok(status) => WorkerEvent::Exit {
+            exit_code: status.code().unwrap_or(-1),
+            error: None,
+            stopped,
+        },
+        Err(e) => WorkerEvent::Exit {
+            exit_code: -1,
+            error: Some(format!("Failed to wait for command: {}", e)),
+            stopped,
+        },
+    };
+    println!("Command finished: {:?}", exit);
+    let _ = events.send(exit).await;
+}
+
+/// Waits for the command to exit. A stop request sends SIGTERM, then SIGKILL if the command is
+/// still running once the request's grace period is over. Also returns whether it was stopped.
+async fn wait_or_stop(
+    child: &mut Child,
+    mut stop: mpsc::Receiver<Duration>,
+) -> (io::Result<ExitStatus>, bool) {
+    let grace = tokio::select! {
+        status = child.wait() => return (status, false),
+        Some(grace) = stop.recv() => grace,
+    };
+
+    println!("Stopping command {:?}", child.id());
+    signal_group(child, libc::SIGTERM);
+    match tokio::time::timeout(grace, child.wait()).await {
+        Ok(status) => (status, true),
+        Err(_) => {
+            println!(
+                "Command didn't exit within {:?} of SIGTERM, killing it",
+                grace
+            );
+            signal_group(child, libc::SIGKILL);
+            (child.wait().await, true)
+        }
+    }
+}
+
+fn signal_group(child: &Child, signal: libc::c_int) {
+    if let Some(pid) = child.id() {
+        // SAFETY: the child leads its own process group and hasn't been reaped, so the group id
+        // can't have been reused
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), signal);
+        }
+    }
+}
+
+/// Sends each line of `reader` as it arrives. Reading carries on if nothing is listening anymore,
+/// so the command never blocks on a full pipe.
+async fn forward_lines<R: AsyncRead + Unpin>(reader: Option<R>, stream: &str, tx: EventSender) {
+    let mut reader = match reader {
+        Some(reader) => BufReader::new(reader),
+        None => return,
+    };
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        match reader.read_until(b'\n', &mut buffer).await {
+            Ok(0) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buffer);
+                let line = line.trim_end_matches(['\n', '\r']).to_string();
+                let _ = tx
+                    .send(WorkerEvent::Log {
+                        stream: stream.to_string(),
+                        line,
+                    })
+                    .await;
+            }
+            Err(e) => {
+                println!("Error reading {}: {:?}", stream, e);
+                break;
+            }
+        }
+    }
+}
+
+fn to_ndjson(event: &WorkerEvent) -> web::Bytes {
+    let mut json = serde_json::to_string(event).unwrap_or_default();
+    json.push('\n');
+    web::Bytes::from(json)
+}
+
+/// Starts stopping a running command. Its final state is reported on the `/execute` stream.
+async fn stop_command(runs: web::Data<Runs>, req: web::Json<StopRequest>) -> impl Responder {
+    println!("Received stop request: {:?}", req);
+
+    let grace = Duration::from_secs(req.timeout_seconds.unwrap_or(DEFAULT_STOP_TIMEOUT_SECONDS));
+    let stop = runs.0.lock().unwrap().get(&req.run_id).cloned();
+    match stop {
+        Some(stop) => {
+            // A full channel means the command is already being stopped
+            let _ = stop.try_send(grace);
+            HttpResponse::Accepted().json(serde_json::json!({
+                "run_id": req.run_id,
+                "status": "stopping",
+            }))
+        }
+        None => HttpResponse::NotFound().body(format!("No running command for run {}", req.run_id)),
+    }
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
+    let runs = web::Data::new(Runs::default());
+
+    // Runs queued on the backend are pulled from it; `/execute` still serves one-off commands
+    match queue::QueueClient::from_env() {
+        Some(client) => queue::start(client),
+        None => {
+            println!("BACKEND_URL, WORKER_ID or WORKER_TOKEN isn't set, not pulling queued runs")
+        }
+    }
+
+    HttpServer::new(move || {
         App::new()
+            .app_data(runs.clone())
             .service(web::resource("/execute").route(web::post().to(execute_command)))
             .service(web::resource("/stop").route(web::post().to(stop_command)))
     })
@@ -68,3 +239,126 @@ async fn main() -> std::io::Result<()> {
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::time::Instant;
+    use tokio::io::AsyncWriteExt;
+
+    /// Starts `script` in its own process group, like `spawn_fluent`, once it has printed `ready`
+    async fn spawn_script(script: &str) -> Child {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "ready\n");
+        child
+    }
+
+    #[tokio::test]
+    async fn wait_or_stop_waits_for_a_command_that_exits() {
+        let mut child = spawn_script("echo ready; exit 3").await;
+        let (_stop_tx, stop) = mpsc::channel(1);
+
+        let (status, stopped) = wait_or_stop(&mut child, stop).await;
+        assert_eq!(status.unwrap().code(), Some(3));
+        assert!(!stopped);
+    }
+
+    #[tokio::test]
+    async fn wait_or_stop_terminates_a_running_command() {
+        let mut child = spawn_script("echo ready; sleep 30").await;
+        let (stop_tx, stop) = mpsc::channel(1);
+        let started = Instant::now();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            stop_tx.send(Duration::from_secs(10)).await.unwrap();
+        });
+
+        let (status, stopped) = wait_or_stop(&mut child, stop).await;
+        assert_eq!(status.unwrap().signal(), Some(libc::SIGTERM));
+        assert!(stopped);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn wait_or_stop_kills_a_command_that_ignores_sigterm() {
+        // Ignored signals stay ignored in the commands the shell starts
+        let mut child = spawn_script("trap '' TERM; echo ready; while :; do sleep 1; done").await;
+        let (stop_tx, stop) = mpsc::channel(1);
+        let grace = Duration::from_millis(300);
+        stop_tx.send(grace).await.unwrap();
+        let started = Instant::now();
+
+        let (status, stopped) = wait_or_stop(&mut child, stop).await;
+        assert_eq!(status.unwrap().signal(), Some(libc::SIGKILL));
+        assert!(stopped);
+        assert!(started.elapsed() >= grace);
+    }
+
+    async fn next_log(events: &mut mpsc::Receiver<WorkerEvent>) -> (String, String) {
+        match events.recv().await {
+            Some(WorkerEvent::Log { stream, line }) => (stream, line),
+            event => panic!("expected a log line, got {:?}", event),
+        }
+    }
+
+    fn log(stream: &str, line: &str) -> (String, String) {
+        (stream.to_string(), line.to_string())
+    }
+
+    #[tokio::test]
+    async fn forward_lines_keeps_interleaved_streams_apart() {
+        let (mut stdout, stdout_reader) = tokio::io::duplex(64);
+        let (mut stderr, stderr_reader) = tokio::io::duplex(64);
+        let (tx, mut events) = mpsc::channel(10);
+        let forwarding = tokio::spawn(async move {
+            tokio::join!(
+                forward_lines(Some(stdout_reader), "stdout", tx.clone()),
+                forward_lines(Some(stderr_reader), "stderr", tx),
+            );
+        });
+
+        stdout.write_all(b"step 1\n").await.unwrap();
+        assert_eq!(next_log(&mut events).await, log("stdout", "step 1"));
+        stderr.write_all(b"warning: slow\r\n").await.unwrap();
+        assert_eq!(next_log(&mut events).await, log("stderr", "warning: slow"));
+        // A line written in pieces is sent once it's complete, while the other stream goes on
+        stdout.write_all(b"step ").await.unwrap();
+        stderr.write_all(b"error: \xff\n").await.unwrap();
+        assert_eq!(next_log(&mut events).await, log("stderr", "error: \u{fffd}"));
+        stdout.write_all(b"2\n").await.unwrap();
+        assert_eq!(next_log(&mut events).await, log("stdout", "step 2"));
+        // The last line is sent even without a newline
+        stdout.write_all(b"done").await.unwrap();
+        drop(stdout);
+        assert_eq!(next_log(&mut events).await, log("stdout", "done"));
+
+        drop(stderr);
+        forwarding.await.unwrap();
+        assert!(events.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn forward_lines_keeps_reading_when_nobody_listens() {
+        let (mut stdout, stdout_reader) = tokio::io::duplex(64);
+        let (tx, events) = mpsc::channel(1);
+        drop(events);
+        let forwarding = tokio::spawn(forward_lines(Some(stdout_reader), "stdout", tx));
+
+        // Far more than the pipe holds, so writing only finishes if it's being drained
+        for _ in 0..1000 {
+            stdout.write_all(b"line\n").await.unwrap();
+        }
+        drop(stdout);
+        forwarding.await.unwrap();
+    }
+}
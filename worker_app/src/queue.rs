@@ -0,0 +1,582 @@
+//! Pulls queued runs from the backend. Each run is held under a lease that is renewed while the
+//! command runs; if this worker goes away the lease expires and the backend hands the run to
+//! another worker.
+
+use crate::{run_command, spawn_fluent, CommandRequest, WorkerEvent, DEFAULT_STOP_TIMEOUT_SECONDS};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long to wait before asking for work again when there was none
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How often a running command's lease is renewed, which is also how soon a stop request is seen
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long the backend waits for a renewal before taking the run back
+const LEASE_SECONDS: u64 = 60;
+/// How often buffered output lines are sent to the backend
+const LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_LOG_BATCH: usize = 500;
+/// How many of the last stdout and stderr lines are kept for the result. Every line has already
+/// been sent with the logs.
+const RESULT_TAIL_LINES: usize = 100;
+/// How many times a result is sent while the backend can't be reached
+const REPORT_ATTEMPTS: u32 = 5;
+
+#[derive(Clone)]
+pub struct QueueClient {
+    http: reqwest::Client,
+    backend_url: String,
+    worker_id: String,
+    token: String,
+    concurrency: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JobLease {
+    run_id: String,
+    lease_token: String,
+    attempt: i32,
+    command: CommandRequest,
+    /// The file in `FLUENT_STATE_STORE` the pipeline leaves its state in
+    state_file: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaseStatus {
+    stop_requested: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct LogLine {
+    stream: String,
+    line: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CommandResult {
+    output: String,
+    error: Option<String>,
+    exit_code: i32,
+    stopped: bool,
+    state_file_content: Option<serde_json::Value>,
+}
+
+impl QueueClient {
+    /// Reads `BACKEND_URL`, `WORKER_ID` (the id of a worker registered through `/workers`) and
+    /// `WORKER_TOKEN`, the token issued for that worker. `WORKER_CONCURRENCY` sets how many runs
+    /// are executed at once, default 1.
+    pub fn from_env() -> Option<Self> {
+        let backend_url = std::env::var("BACKEND_URL").ok()?;
+        let worker_id = std::env::var("WORKER_ID").ok()?;
+        let token = std::env::var("WORKER_TOKEN").ok()?;
+        let concurrency = std::env::var("WORKER_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1)
+            .max(1);
+        Some(Self::new(&backend_url, worker_id, token, concurrency))
+    }
+
+    fn new(backend_url: &str, worker_id: String, token: String, concurrency: usize) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            backend_url: backend_url.trim_end_matches('/').to_string(),
+            worker_id,
+            token,
+            concurrency,
+        }
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .post(format!("{}/queue{}", self.backend_url, path))
+            .header("X-Worker-Id", &self.worker_id)
+            .bearer_auth(&self.token)
+    }
+
+    async fn lease(&self) -> Result<Option<JobLease>, String> {
+        let response = self
+            .post("/lease")
+            .json(&serde_json::json!({ "lease_seconds": LEASE_SECONDS }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.status() {
+            reqwest::StatusCode::NO_CONTENT => Ok(None),
+            status if status.is_success() => response.json().await.map_err(|e| e.to_string()),
+            status => Err(format!(
+                "Backend returned {}: {}",
+                status,
+                response.text().await.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// `None` means the lease was lost
+    async fn renew(&self, lease: &JobLease) -> Result<Option<LeaseStatus>, String> {
+        let response = self
+            .post(&format!("/runs/{}/heartbeat", lease.run_id))
+            .json(&serde_json::json!({
+                "lease_token": lease.lease_token,
+                "lease_seconds": LEASE_SECONDS,
+            }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match checked(response).await? {
+            Some(response) => response.json().await.map(Some).map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns false if the lease was lost
+    async fn send_logs(&self, lease: &JobLease, lines: Vec<LogLine>) -> Result<bool, String> {
+        let response = self
+            .post(&format!("/runs/{}/logs", lease.run_id))
+            .json(&serde_json::json!({
+                "lease_token": lease.lease_token,
+                "lines": lines,
+            }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        checked(response).await.map(|response| response.is_some())
+    }
+
+    /// Returns false if the lease was lost
+    async fn complete(&self, lease: &JobLease, result: &CommandResult) -> Result<bool, String> {
+        let mut body = serde_json::to_value(result).map_err(|e| e.to_string())?;
+        body["lease_token"] = serde_json::json!(lease.lease_token);
+        let response = self
+            .post(&format!("/runs/{}/complete", lease.run_id))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        checked(response).await.map(|response| response.is_some())
+    }
+}
+
+/// Passes on a successful response, `None` for `409 Conflict`, which means the lease was lost,
+/// and an error for anything else
+async fn checked(response: reqwest::Response) -> Result<Option<reqwest::Response>, String> {
+    match response.status() {
+        reqwest::StatusCode::CONFLICT => Ok(None),
+        status if status.is_success() => Ok(Some(response)),
+        status => Err(format!(
+            "Backend returned {}: {}",
+            status,
+            response.text().await.unwrap_or_default()
+        )),
+    }
+}
+
+pub fn start(client: QueueClient) {
+    println!(
+        "Pulling queued runs from {} as worker {}, {} at a time",
+        client.backend_url, client.worker_id, client.concurrency
+    );
+    for _ in 0..client.concurrency {
+        tokio::spawn(pull(client.clone()));
+    }
+}
+
+async fn pull(client: QueueClient) {
+    loop {
+        match client.lease().await {
+            Ok(Some(lease)) => execute(&client, lease).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(e) => {
+                println!("Error asking for a queued run: {}", e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Runs a leased command, sending its output to the backend as it's produced and its result once
+/// it exits
+async fn execute(client: &QueueClient, lease: JobLease) {
+    println!(
+        "Leased run {} (attempt {}): {:?}",
+        lease.run_id, lease.attempt, lease.command
+    );
+
+    let child = match spawn_fluent(&lease.command) {
+        Ok(child) => child,
+        Err(e) => {
+            println!("Error executing command: {:?}", e);
+            let result = CommandResult {
+                output: String::new(),
+                error: Some(format!("Failed to execute command: {}", e)),
+                exit_code: -1,
+                stopped: false,
+                state_file_content: None,
+            };
+            report(client, &lease, &result).await;
+            return;
+        }
+    };
+
+    let (events_tx, mut events) = mpsc::channel(100);
+    let (stop_tx, stop_rx) = mpsc::channel(1);
+    tokio::spawn(run_command(child, events_tx, stop_rx));
+    let heartbeat = tokio::spawn(keep_lease(client.clone(), lease.clone(), stop_tx));
+
+    let mut stdout = Tail::default();
+    let mut stderr = Tail::default();
+    let mut batch = Vec::new();
+    let mut exit = None;
+    let mut flush = tokio::time::interval(LOG_FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(WorkerEvent::Log { stream, line }) => {
+                    let output = if stream == "stderr" { &mut stderr } else { &mut stdout };
+                    output.push(&line);
+                    batch.push(LogLine { stream, line });
+                    if batch.len() >= MAX_LOG_BATCH {
+                        flush_logs(client, &lease, &mut batch).await;
+                    }
+                }
+                Some(WorkerEvent::Exit { exit_code, error, stopped }) => {
+                    exit = Some((exit_code, error, stopped));
+                }
+                None => break,
+            },
+            _ = flush.tick() => flush_logs(client, &lease, &mut batch).await,
+        }
+    }
+    flush_logs(client, &lease, &mut batch).await;
+    heartbeat.abort();
+
+    let (exit_code, error, stopped) = exit.unwrap_or((
+        -1,
+        Some("Command ended without an exit status".to_string()),
+        false,
+    ));
+    let result = CommandResult {
+        output: stdout.into_string(),
+        error: error.or_else(|| (exit_code != 0).then(|| stderr.into_string())),
+        exit_code,
+        stopped,
+        state_file_content: read_state_file(&lease).await,
+    };
+    report(client, &lease, &result).await;
+}
+
+/// The last lines of a stream
+#[derive(Default)]
+struct Tail(VecDeque<String>);
+
+impl Tail {
+    fn push(&mut self, line: &str) {
+        if self.0.len() == RESULT_TAIL_LINES {
+            self.0.pop_front();
+        }
+        self.0.push_back(line.to_string());
+    }
+
+    fn into_string(self) -> String {
+        self.0.into_iter().fold(String::new(), |mut output, line| {
+            output.push_str(&line);
+            output.push('\n');
+            output
+        })
+    }
+}
+
+/// Reads and removes the state file the pipeline left in `FLUENT_STATE_STORE`, if any
+async fn read_state_file(lease: &JobLease) -> Option<serde_json::Value> {
+    let store = std::env::var("FLUENT_STATE_STORE").ok()?;
+    let path = Path::new(&store).join(&lease.state_file);
+    let content = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) => {
+            println!("No state file for run {} at {:?}: {}", lease.run_id, path, e);
+            return None;
+        }
+    };
+    let _ = tokio::fs::remove_file(&path).await;
+    match serde_json::from_str(&content) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            println!("State file of run {} isn't JSON: {}", lease.run_id, e);
+            None
+        }
+    }
+}
+
+async fn flush_logs(client: &QueueClient, lease: &JobLease, batch: &mut Vec<LogLine>) {
+    if batch.is_empty() {
+        return;
+    }
+    match client.send_logs(lease, std::mem::take(batch)).await {
+        Ok(true) => {}
+        Ok(false) => println!(
+            "Lost the lease on run {}, dropping its output",
+            lease.run_id
+        ),
+        Err(e) => println!("Error sending the output of run {}: {}", lease.run_id, e),
+    }
+}
+
+/// Reports a run's result, retrying while the backend can't be reached so the run isn't taken
+/// back and run again just because of a brief outage
+async fn report(client: &QueueClient, lease: &JobLease, result: &CommandResult) {
+    for attempt in 1..=REPORT_ATTEMPTS {
+        match client.complete(lease, result).await {
+            Ok(true) => return,
+            Ok(false) => {
+                println!(
+                    "Lost the lease on run {}, its result is dropped",
+                    lease.run_id
+                );
+                return;
+            }
+            Err(e) => {
+                println!(
+                    "Error reporting the result of run {} (attempt {}): {}",
+                    lease.run_id, attempt, e
+                );
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Renews the lease until the command exits. Stops the command when the backend asks for it, or
+/// when the lease was lost because the run has been handed to another worker.
+async fn keep_lease(client: QueueClient, lease: JobLease, stop: mpsc::Sender<Duration>) {
+    let grace = Duration::from_secs(DEFAULT_STOP_TIMEOUT_SECONDS);
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    // The first tick completes immediately, and the lease was only just taken
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match client.renew(&lease).await {
+            Ok(Some(status)) => {
+                if status.stop_requested {
+                    // A full channel means the command is already being stopped
+                    let _ = stop.try_send(grace);
+                }
+            }
+            Ok(None) => {
+                println!("Lost the lease on run {}, stopping it", lease.run_id);
+                let _ = stop.try_send(grace);
+                return;
+            }
+            Err(e) => println!("Error renewing the lease on run {}: {}", lease.run_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[derive(Debug, Clone)]
+    struct Received {
+        path: String,
+        head: String,
+        body: serde_json::Value,
+    }
+
+    /// A backend on a local port that answers each request with `respond(path, n)`, where `n`
+    /// counts the earlier requests to the same path
+    async fn mock_backend(
+        respond: impl Fn(&str, usize) -> (u16, &'static str) + Send + Sync + 'static,
+    ) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::<Received>::new()));
+        let respond = Arc::new(respond);
+        let log = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                let (head, body) = loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|length| length.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break (head.to_string(), body.to_string());
+                        }
+                    }
+                };
+                let path = head.split_whitespace().nth(1).unwrap().to_string();
+                let (status, response) = {
+                    let mut log = log.lock().unwrap();
+                    let n = log.iter().filter(|r| r.path == path).count();
+                    log.push(Received {
+                        path: path.clone(),
+                        head,
+                        body: serde_json::from_str(&body).unwrap_or(serde_json::Value::Null),
+                    });
+                    respond(&path, n)
+                };
+                let reply = format!(
+                    "HTTP/1.1 {} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                );
+                socket.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn lease() -> JobLease {
+        JobLease {
+            run_id: "run-1".to_string(),
+            lease_token: "token-1".to_string(),
+            attempt: 1,
+            command: CommandRequest {
+                command: "openai".to_string(),
+                args: Vec::new(),
+                run_id: Some("run-1".to_string()),
+            },
+            state_file: "pipeline-run-1.json".to_string(),
+        }
+    }
+
+    fn client(url: &str) -> QueueClient {
+        QueueClient::new(url, "worker-1".to_string(), "secret".to_string(), 1)
+    }
+
+    fn result() -> CommandResult {
+        CommandResult {
+            output: "done\n".to_string(),
+            error: None,
+            exit_code: 0,
+            stopped: false,
+            state_file_content: Some(serde_json::json!({ "step": 3 })),
+        }
+    }
+
+    const RENEWED: &str = r#"{"lease_expires_at":"2025-06-02T08:01:00Z","stop_requested":false}"#;
+    const STOP: &str = r#"{"lease_expires_at":"2025-06-02T08:01:00Z","stop_requested":true}"#;
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeat_stops_the_command_when_the_backend_asks() {
+        let (url, received) = mock_backend(|_, n| (200, if n < 2 { RENEWED } else { STOP })).await;
+        let (stop_tx, mut stop_rx) = mpsc::channel(1);
+        let heartbeat = tokio::spawn(keep_lease(client(&url), lease(), stop_tx));
+
+        let grace = stop_rx.recv().await.unwrap();
+        assert_eq!(grace, Duration::from_secs(DEFAULT_STOP_TIMEOUT_SECONDS));
+        // The lease is still renewed while the command is being stopped
+        assert!(!heartbeat.is_finished());
+        heartbeat.abort();
+
+        let received = received.lock().unwrap();
+        assert!(received.len() >= 3);
+        for request in received.iter() {
+            assert_eq!(request.path, "/queue/runs/run-1/heartbeat");
+            assert!(request.head.contains("x-worker-id: worker-1"));
+            assert!(request.head.contains("authorization: Bearer secret"));
+            assert_eq!(request.body["lease_token"], "token-1");
+            assert_eq!(request.body["lease_seconds"], LEASE_SECONDS);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeat_stops_the_command_when_the_lease_is_lost() {
+        let (url, received) = mock_backend(|_, n| {
+            if n == 0 {
+                (200, RENEWED)
+            } else {
+                (409, r#"{"error":"lost"}"#)
+            }
+        })
+        .await;
+        let (stop_tx, mut stop_rx) = mpsc::channel(1);
+        keep_lease(client(&url), lease(), stop_tx).await;
+
+        assert!(stop_rx.try_recv().is_ok());
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeat_errors_dont_stop_the_command() {
+        let (url, received) = mock_backend(|_, n| {
+            if n < 2 {
+                (503, "unavailable")
+            } else {
+                (200, STOP)
+            }
+        })
+        .await;
+        let (stop_tx, mut stop_rx) = mpsc::channel(1);
+        let heartbeat = tokio::spawn(keep_lease(client(&url), lease(), stop_tx));
+
+        stop_rx.recv().await.unwrap();
+        heartbeat.abort();
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn report_retries_until_the_backend_answers() {
+        let (url, received) = mock_backend(|_, n| {
+            if n < 2 {
+                (502, "bad gateway")
+            } else {
+                (204, "")
+            }
+        })
+        .await;
+        report(&client(&url), &lease(), &result()).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        let body = &received[2].body;
+        assert_eq!(received[2].path, "/queue/runs/run-1/complete");
+        assert_eq!(body["lease_token"], "token-1");
+        assert_eq!(body["output"], "done\n");
+        assert_eq!(body["exit_code"], 0);
+        assert_eq!(body["state_file_content"]["step"], 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn report_gives_up_after_its_attempts() {
+        let (url, received) = mock_backend(|_, _| (500, "error")).await;
+        report(&client(&url), &lease(), &result()).await;
+        assert_eq!(received.lock().unwrap().len(), REPORT_ATTEMPTS as usize);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn report_stops_when_the_lease_is_lost() {
+        let (url, received) = mock_backend(|_, _| (409, r#"{"error":"lost"}"#)).await;
+        report(&client(&url), &lease(), &result()).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tail_keeps_the_last_lines() {
+        let mut tail = Tail::default();
+        for i in 0..RESULT_TAIL_LINES + 5 {
+            tail.push(&format!("line {}", i));
+        }
+        let output = tail.into_string();
+        assert_eq!(output.lines().count(), RESULT_TAIL_LINES);
+        assert!(output.starts_with("line 5\n"));
+        assert!(output.ends_with(&format!("line {}\n", RESULT_TAIL_LINES + 4)));
+    }
+}